@@ -0,0 +1,23 @@
+use std::process::Command;
+
+/// Bake the git commit into the binary for the startup banner and ks_build_info.
+/// Builds without a .git directory (e.g. Docker) can pass KS_GIT_COMMIT instead
+fn main() {
+    println!("cargo:rerun-if-env-changed=KS_GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+
+    let commit = std::env::var("KS_GIT_COMMIT")
+        .ok()
+        .filter(|c| !c.trim().is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|out| out.status.success())
+                .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=KS_GIT_COMMIT={}", commit.trim());
+}
@@ -0,0 +1,316 @@
+use crate::client_handler::ClientHandler;
+use crate::prom::record_maintenance_mode;
+use crate::stratum_context::DisconnectReason;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Shown to miners (client.show_message) when a maintenance drain starts
+const MAINTENANCE_NOTICE: &str = "Pool going down for maintenance, switch to your backup pool";
+
+static MAINTENANCE: AtomicBool = AtomicBool::new(false);
+
+/// Bumped on every maintenance change, so a drain scheduled by an earlier maintenance never fires
+static MAINTENANCE_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Client handlers of every running instance, so one admin socket can reach all miners
+static ADMIN_REGISTRY: Lazy<Mutex<Vec<Arc<ClientHandler>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Make an instance's miners visible to the admin socket
+pub fn register_admin_client_handler(client_handler: Arc<ClientHandler>) {
+    ADMIN_REGISTRY.lock().push(client_handler);
+}
+
+/// Disconnect every miner of every instance for `reason`; returns how many were connected
+pub fn disconnect_all_miners(reason: DisconnectReason) -> usize {
+    let handlers = ADMIN_REGISTRY.lock().clone();
+    handlers.iter().map(|h| h.disconnect_all(reason)).sum()
+}
+
+/// Whether new connections are being refused for maintenance (every instance)
+pub fn maintenance_mode() -> bool {
+    MAINTENANCE.load(Ordering::Acquire)
+}
+
+/// Refuse new miners on every instance. With `drain_after`, connected miners are told to fail over
+/// right away and disconnected once it has passed; without it they stay until they leave
+pub fn enter_maintenance(drain_after: Option<Duration>) -> String {
+    let epoch = MAINTENANCE_EPOCH.fetch_add(1, Ordering::AcqRel) + 1;
+    MAINTENANCE.store(true, Ordering::Release);
+    record_maintenance_mode(true);
+    let handlers = ADMIN_REGISTRY.lock().clone();
+    let connected: usize = handlers.iter().map(|h| h.client_count()).sum();
+
+    warn!("==================== MAINTENANCE MODE ====================");
+    let Some(grace) = drain_after else {
+        warn!("refusing new miners, {} connected miner(s) stay until they leave", connected);
+        return format!("maintenance on, {} miner(s) left connected", connected);
+    };
+    warn!("refusing new miners, disconnecting {} connected miner(s) in {:?}", connected, grace);
+    for handler in &handlers {
+        handler.show_message_to_all(MAINTENANCE_NOTICE);
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        // Maintenance was left, or entered again with its own grace period, in the meantime
+        if !maintenance_mode() || MAINTENANCE_EPOCH.load(Ordering::Acquire) != epoch {
+            return;
+        }
+        let drained = disconnect_all_miners(DisconnectReason::Maintenance);
+        warn!("maintenance: grace period over, disconnected {} miner(s)", drained);
+    });
+    format!("maintenance on, draining {} miner(s) in {}s", connected, grace.as_secs())
+}
+
+/// Accept miners again; a drain still waiting for its grace period is called off
+pub fn exit_maintenance() -> String {
+    MAINTENANCE_EPOCH.fetch_add(1, Ordering::AcqRel);
+    if !MAINTENANCE.swap(false, Ordering::AcqRel) {
+        return "not in maintenance".to_string();
+    }
+    record_maintenance_mode(false);
+    warn!("==================== MAINTENANCE OVER ====================");
+    warn!("accepting new miners again");
+    "maintenance off".to_string()
+}
+
+/// A command received on the admin socket (one per line)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    ListMiners,
+    Kick(String),
+    SetDiff(String, f64),
+    Show(String),
+    MaintenanceOn(Option<Duration>), // Drain connected miners after this long (None = leave them)
+    MaintenanceOff,
+    Pause,
+    Resume,
+}
+
+pub fn parse_admin_command(line: &str) -> Result<AdminCommand, String> {
+    let mut parts = line.split_whitespace();
+    let command = parts.next().ok_or("empty command")?;
+    let args: Vec<&str> = parts.collect();
+
+    match (command, args.as_slice()) {
+        ("list-miners", []) => Ok(AdminCommand::ListMiners),
+        ("kick", [worker]) => Ok(AdminCommand::Kick(worker.to_string())),
+        ("show", [worker]) => Ok(AdminCommand::Show(worker.to_string())),
+        ("setdiff", [worker, value]) => {
+            let diff: f64 = value.parse().map_err(|_| format!("invalid difficulty '{}'", value))?;
+            if !diff.is_finite() || diff < 1.0 {
+                return Err(format!("difficulty must be >= 1, got {}", value));
+            }
+            Ok(AdminCommand::SetDiff(worker.to_string(), diff))
+        }
+        ("maintenance", ["on"]) => Ok(AdminCommand::MaintenanceOn(None)),
+        ("maintenance", ["on", secs]) => {
+            let secs: u64 = secs.parse().map_err(|_| format!("invalid drain seconds '{}'", secs))?;
+            Ok(AdminCommand::MaintenanceOn(Some(Duration::from_secs(secs))))
+        }
+        ("maintenance", ["off"]) => Ok(AdminCommand::MaintenanceOff),
+        ("pause", []) => Ok(AdminCommand::Pause),
+        ("resume", []) => Ok(AdminCommand::Resume),
+        ("list-miners", _) => Err("usage: list-miners".to_string()),
+        ("kick", _) => Err("usage: kick <worker>".to_string()),
+        ("show", _) => Err("usage: show <worker>".to_string()),
+        ("setdiff", _) => Err("usage: setdiff <worker> <difficulty>".to_string()),
+        ("maintenance", _) => Err("usage: maintenance on [drain_secs] | maintenance off".to_string()),
+        ("pause", _) => Err("usage: pause".to_string()),
+        ("resume", _) => Err("usage: resume".to_string()),
+        (other, _) => Err(format!("unknown command '{}' (list-miners, show, kick, setdiff, maintenance, pause, resume)", other)),
+    }
+}
+
+/// Run a command against every registered instance and return the reply text
+pub fn execute_admin_command(command: &AdminCommand) -> String {
+    let handlers = ADMIN_REGISTRY.lock().clone();
+    match command {
+        AdminCommand::ListMiners => {
+            let lines: Vec<String> = handlers.iter().flat_map(|h| h.list_miners()).collect();
+            if lines.is_empty() {
+                "no miners connected".to_string()
+            } else {
+                lines.join("\n")
+            }
+        }
+        AdminCommand::Show(worker) => {
+            // One JSON array of connections on a single line, for jq
+            let connections: Vec<serde_json::Value> = handlers.iter().flat_map(|h| h.show_worker(worker)).collect();
+            serde_json::Value::Array(connections).to_string()
+        }
+        AdminCommand::Kick(worker) => {
+            let kicked: usize = handlers.iter().map(|h| h.kick_worker(worker)).sum();
+            info!("admin: kicked {} connection(s) for worker {}", kicked, worker);
+            format!("kicked {} connection(s)", kicked)
+        }
+        AdminCommand::SetDiff(worker, diff) => {
+            let updated: usize = handlers.iter().map(|h| h.set_worker_difficulty(worker, *diff)).sum();
+            info!("admin: set difficulty {} on {} connection(s) for worker {}", diff, updated, worker);
+            format!("set difficulty {} on {} connection(s)", diff, updated)
+        }
+        AdminCommand::MaintenanceOn(drain_after) => enter_maintenance(*drain_after),
+        AdminCommand::MaintenanceOff => exit_maintenance(),
+        AdminCommand::Pause | AdminCommand::Resume => {
+            let paused = *command == AdminCommand::Pause;
+            let changed = handlers.iter().filter(|h| h.set_paused(paused) != paused).count();
+            if paused {
+                warn!("admin: paused {} instance(s), no new jobs until resume", changed);
+                format!("paused {} instance(s)", changed)
+            } else {
+                warn!("admin: resumed {} instance(s)", changed);
+                format!("resumed {} instance(s)", changed)
+            }
+        }
+    }
+}
+
+/// Remove a socket a previous run left at `path`, which would make bind fail. A socket something still
+/// accepts on belongs to a running bridge and is refused with AddrInUse; anything else there (a regular
+/// file, a symlink) is refused rather than deleted
+#[cfg(unix)]
+pub fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("{} is in use by another process, is a bridge already running?", path.display()),
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => std::fs::remove_file(path),
+            Err(e) => Err(e),
+        },
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket, refusing to replace it", path.display()),
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Bind the admin socket at `path` with mode 0600. It is bound inside a fresh 0700 directory and moved
+/// into place once restricted, so no one else can connect in between
+#[cfg(unix)]
+fn bind_admin_socket(path: &str) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let path = std::path::Path::new(path);
+    remove_stale_socket(path)?;
+    let file_name = path.file_name().ok_or_else(|| std::io::Error::other(format!("{} is not a file path", path.display())))?;
+    let private_dir = path.with_file_name(format!(".{}.{}", file_name.to_string_lossy(), std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+    let staged = private_dir.join(file_name);
+    let bound = tokio::net::UnixListener::bind(&staged)
+        .and_then(|listener| std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600)).map(|_| listener))
+        .and_then(|listener| std::fs::rename(&staged, path).map(|_| listener));
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&private_dir);
+    bound
+}
+
+/// Serve admin commands on a Unix domain socket, readable only by the bridge's user
+/// There is deliberately no TCP variant: the socket's file permissions are the authentication
+#[cfg(unix)]
+pub async fn serve_admin_socket(path: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let listener = bind_admin_socket(&path)?;
+    info!("admin socket listening on {}", path);
+
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("admin socket accept failed: {}", e);
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            let (read_half, mut write_half) = stream.into_split();
+            let mut lines = BufReader::new(read_half).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let reply = match parse_admin_command(&line) {
+                    Ok(command) => execute_admin_command(&command),
+                    Err(e) => format!("error: {}", e),
+                };
+                if write_half.write_all(format!("{}\n", reply).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn serve_admin_socket(path: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    Err(format!("admin_socket {} requires Unix domain sockets, which this platform does not support", path).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_admin_command() {
+        assert_eq!(parse_admin_command("list-miners"), Ok(AdminCommand::ListMiners));
+        assert_eq!(parse_admin_command("  kick rig01 "), Ok(AdminCommand::Kick("rig01".to_string())));
+        assert_eq!(parse_admin_command("show rig01"), Ok(AdminCommand::Show("rig01".to_string())));
+        assert_eq!(parse_admin_command("setdiff rig01 4096"), Ok(AdminCommand::SetDiff("rig01".to_string(), 4096.0)));
+
+        assert!(parse_admin_command("").is_err());
+        assert!(parse_admin_command("kick").is_err());
+        assert!(parse_admin_command("show").is_err());
+        assert!(parse_admin_command("setdiff rig01").is_err());
+        assert!(parse_admin_command("setdiff rig01 lots").is_err());
+        assert!(parse_admin_command("setdiff rig01 0").is_err());
+        assert!(parse_admin_command("reboot").is_err());
+
+        assert_eq!(parse_admin_command("maintenance on"), Ok(AdminCommand::MaintenanceOn(None)));
+        assert_eq!(parse_admin_command("maintenance on 30"), Ok(AdminCommand::MaintenanceOn(Some(Duration::from_secs(30)))));
+        assert_eq!(parse_admin_command("maintenance off"), Ok(AdminCommand::MaintenanceOff));
+        assert!(parse_admin_command("maintenance").is_err());
+        assert!(parse_admin_command("maintenance on soon").is_err());
+
+        assert_eq!(parse_admin_command("pause"), Ok(AdminCommand::Pause));
+        assert_eq!(parse_admin_command("resume"), Ok(AdminCommand::Resume));
+        assert!(parse_admin_command("pause now").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_admin_socket_only_replaces_sockets() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ks_admin_socket_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("admin.sock");
+        let path_str = path.to_str().unwrap();
+
+        // Someone else's file at the configured path is left alone
+        std::fs::write(&path, "not a socket").unwrap();
+        assert_eq!(bind_admin_socket(path_str).unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+        std::fs::remove_file(&path).unwrap();
+
+        // A socket left behind by an earlier run is replaced, and the new one is private
+        let stale = bind_admin_socket(path_str).unwrap();
+        drop(stale);
+        let _listener = bind_admin_socket(path_str).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1, "staging directory cleaned up");
+
+        // A socket a running bridge is still listening on is not taken over
+        assert_eq!(bind_admin_socket(path_str).unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok(), "the live socket is still in place");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use crate::prom::{record_block_submit_queue_depth, record_block_submit_queue_full};
+use crate::share_handler::KaspaApiTrait;
+use kaspa_consensus_core::block::Block;
+use kaspa_rpc_core::SubmitBlockResponse;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+/// Default submit_queue_size: off, found blocks are submitted inline and concurrently
+pub const DEFAULT_SUBMIT_QUEUE_SIZE: usize = 0;
+
+type SubmitResult = Result<SubmitBlockResponse, Box<dyn std::error::Error + Send + Sync>>;
+
+/// What a found block does when the submit queue is full. Blocks are never dropped either way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubmitQueuePolicy {
+    /// The submitting connection waits for room, keeping memory bounded (default)
+    Wait,
+    /// The block skips the queue and is submitted straight away, as without a queue
+    Bypass,
+}
+
+impl std::str::FromStr for SubmitQueuePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "wait" => Ok(SubmitQueuePolicy::Wait),
+            "bypass" => Ok(SubmitQueuePolicy::Bypass),
+            other => Err(format!("unknown submit_queue_policy '{}', expected wait or bypass", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for SubmitQueuePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitQueuePolicy::Wait => write!(f, "wait"),
+            SubmitQueuePolicy::Bypass => write!(f, "bypass"),
+        }
+    }
+}
+
+struct QueuedSubmit {
+    kaspa_api: Arc<dyn KaspaApiTrait + Send + Sync>,
+    block: Block,
+    reply: oneshot::Sender<SubmitResult>,
+}
+
+/// A background submitter the instances sharing a kaspad hand their found blocks to
+pub struct BlockSubmitQueue {
+    queue: mpsc::Sender<QueuedSubmit>,
+    policy: SubmitQueuePolicy,
+}
+
+impl BlockSubmitQueue {
+    /// Start the background submitter. Found blocks are then handed to kaspad one at a time from a queue of
+    /// `size`, so validation never piles up unbounded submit calls while kaspad is slow
+    pub fn start(size: usize, policy: SubmitQueuePolicy) -> Arc<Self> {
+        let (queue, mut rx) = mpsc::channel::<QueuedSubmit>(size.max(1));
+        record_block_submit_queue_depth(0);
+        tokio::spawn(async move {
+            while let Some(submit) = rx.recv().await {
+                record_block_submit_queue_depth(rx.len());
+                let result = submit.kaspa_api.submit_block(submit.block).await;
+                // The share handler only goes away with its connection; the block was submitted regardless
+                let _ = submit.reply.send(result);
+            }
+        });
+        Arc::new(Self { queue, policy })
+    }
+}
+
+/// Submit a found block through `queue` when the instance has one, directly otherwise
+pub async fn submit_block_queued(
+    queue: Option<&BlockSubmitQueue>,
+    kaspa_api: Arc<dyn KaspaApiTrait + Send + Sync>,
+    block: Block,
+) -> SubmitResult {
+    let Some(BlockSubmitQueue { queue, policy }) = queue else {
+        return kaspa_api.submit_block(block).await;
+    };
+    let (reply, result) = oneshot::channel();
+    let submit = QueuedSubmit { kaspa_api: Arc::clone(&kaspa_api), block, reply };
+    let submit = match queue.try_send(submit) {
+        Ok(()) => None,
+        Err(mpsc::error::TrySendError::Full(submit)) => {
+            record_block_submit_queue_full(&policy.to_string());
+            Some(submit)
+        }
+        Err(mpsc::error::TrySendError::Closed(submit)) => {
+            tracing::warn!("block submit queue stopped, submitting directly");
+            return kaspa_api.submit_block(submit.block).await;
+        }
+    };
+    if let Some(submit) = submit {
+        match policy {
+            SubmitQueuePolicy::Wait => {
+                tracing::warn!("block submit queue full ({} waiting), block waits for room", queue.max_capacity());
+                if let Err(mpsc::error::SendError(submit)) = queue.send(submit).await {
+                    return kaspa_api.submit_block(submit.block).await;
+                }
+            }
+            SubmitQueuePolicy::Bypass => {
+                tracing::warn!("block submit queue full ({} waiting), submitting block directly", queue.max_capacity());
+                return kaspa_api.submit_block(submit.block).await;
+            }
+        }
+    }
+    record_block_submit_queue_depth(queue.max_capacity() - queue.capacity());
+    result.await.unwrap_or_else(|_| Err("block submitter stopped before answering".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kaspa_consensus_core::header::Header;
+    use kaspa_hashes::Hash;
+    use kaspa_rpc_core::SubmitBlockReport;
+    use parking_lot::Mutex;
+    use std::time::Duration;
+    use tokio::sync::Semaphore;
+
+    /// kaspad that answers a submit only once the test hands out a permit, recording the order submits start in
+    #[derive(Default)]
+    struct SlowKaspad {
+        gate: Semaphore,
+        started: Mutex<Vec<u64>>,
+    }
+
+    #[async_trait::async_trait]
+    impl KaspaApiTrait for SlowKaspad {
+        async fn get_block_template(&self, _: &str, _: &str, _: &str) -> Result<Block, Box<dyn std::error::Error + Send + Sync>> {
+            Err("no templates in tests".into())
+        }
+
+        async fn submit_block(&self, block: Block) -> SubmitResult {
+            self.started.lock().push(block.header.nonce);
+            self.gate.acquire().await.unwrap().forget();
+            Ok(SubmitBlockResponse { report: SubmitBlockReport::Success })
+        }
+
+        async fn get_balances_by_addresses(
+            &self,
+            _: &[String],
+        ) -> Result<Vec<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn block(nonce: u64) -> Block {
+        let mut header = Header::from_precomputed_hash(Hash::default(), vec![]);
+        header.nonce = nonce;
+        Block::new(header, vec![])
+    }
+
+    /// Submit blocks `nonces` through `queue` one after another, each given time to reach kaspad or the queue
+    async fn submit_all(
+        queue: &Arc<BlockSubmitQueue>,
+        kaspad: &Arc<SlowKaspad>,
+        nonces: &[u64],
+    ) -> Vec<tokio::task::JoinHandle<SubmitResult>> {
+        let mut submits = Vec::new();
+        for &nonce in nonces {
+            let (queue, kaspad) = (Arc::clone(queue), Arc::clone(kaspad));
+            submits.push(tokio::spawn(async move { submit_block_queued(Some(&queue), kaspad, block(nonce)).await }));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        submits
+    }
+
+    async fn all_accepted(submits: Vec<tokio::task::JoinHandle<SubmitResult>>) {
+        for submit in submits {
+            let response = submit.await.unwrap().expect("every block is submitted");
+            assert!(matches!(response.report, SubmitBlockReport::Success));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_submits_one_at_a_time_in_order() {
+        let kaspad = Arc::new(SlowKaspad::default());
+        let queue = BlockSubmitQueue::start(4, SubmitQueuePolicy::Wait);
+        let submits = submit_all(&queue, &kaspad, &[1, 2, 3]).await;
+        assert_eq!(*kaspad.started.lock(), vec![1], "the rest wait behind the block kaspad is on");
+
+        kaspad.gate.add_permits(3);
+        all_accepted(submits).await;
+        assert_eq!(*kaspad.started.lock(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_bypass_submits_directly() {
+        let kaspad = Arc::new(SlowKaspad::default());
+        let queue = BlockSubmitQueue::start(1, SubmitQueuePolicy::Bypass);
+        // 1 is with kaspad, 2 fills the queue, 3 finds it full and goes straight to kaspad
+        let submits = submit_all(&queue, &kaspad, &[1, 2, 3]).await;
+        assert_eq!(*kaspad.started.lock(), vec![1, 3]);
+
+        kaspad.gate.add_permits(3);
+        all_accepted(submits).await;
+        assert_eq!(*kaspad.started.lock(), vec![1, 3, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_wait_holds_the_block() {
+        let kaspad = Arc::new(SlowKaspad::default());
+        let queue = BlockSubmitQueue::start(1, SubmitQueuePolicy::Wait);
+        let submits = submit_all(&queue, &kaspad, &[1, 2, 3]).await;
+        assert_eq!(*kaspad.started.lock(), vec![1], "3 waits for room instead of skipping the queue");
+        assert!(!submits[2].is_finished());
+
+        kaspad.gate.add_permits(3);
+        all_accepted(submits).await;
+        assert_eq!(*kaspad.started.lock(), vec![1, 2, 3], "nothing is dropped and order holds");
+    }
+
+    #[tokio::test]
+    async fn test_no_queue_submits_directly() {
+        let kaspad = Arc::new(SlowKaspad::default());
+        kaspad.gate.add_permits(1);
+        let response = submit_block_queued(None, kaspad.clone(), block(7)).await.unwrap();
+        assert!(matches!(response.report, SubmitBlockReport::Success));
+        assert_eq!(*kaspad.started.lock(), vec![7]);
+    }
+
+    #[test]
+    fn test_submit_queue_policy_parse() {
+        assert_eq!("wait".parse::<SubmitQueuePolicy>(), Ok(SubmitQueuePolicy::Wait));
+        assert_eq!("Bypass".parse::<SubmitQueuePolicy>(), Ok(SubmitQueuePolicy::Bypass));
+        assert!("drop".parse::<SubmitQueuePolicy>().is_err());
+        assert_eq!(SubmitQueuePolicy::Bypass.to_string(), "bypass");
+    }
+}
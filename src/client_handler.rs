@@ -1,17 +1,21 @@
 use crate::{
-    hasher::{calculate_target, generate_iceriver_job_params, generate_job_header, generate_large_job_params, serialize_block_header},
+    default_client::AuthorizeConfig,
+    hasher::{
+        calculate_target, difficulty_to_wire, generate_iceriver_job_params, generate_job_header, generate_large_job_params,
+        serialize_block_header, DifficultyFormat,
+    },
     jsonrpc_event::JsonRpcEvent,
     mining_state::{GetMiningState, Job, MiningState},
     prom::*,
-    share_handler::{KaspaApiTrait, ShareHandler},
-    stratum_context::StratumContext,
+    share_handler::{KaspaApiTrait, ShareHandler, SuggestDifficultyPolicy},
+    stratum_context::{DisconnectReason, StratumContext},
 };
 use num_bigint::BigUint;
 use num_traits::Zero;
 use parking_lot::Mutex;
 use regex::Regex;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{error, warn};
@@ -22,37 +26,663 @@ static BIG_JOB_REGEX: once_cell::sync::Lazy<Regex> =
 const BALANCE_DELAY: Duration = Duration::from_secs(60);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(20);
 
+/// A connection counts as active in ks_connections_by_state while its last mining.submit is this recent
+const ACTIVE_SUBMIT_WINDOW: Duration = Duration::from_secs(60);
+
+static DEDUP_TEMPLATES: AtomicBool = AtomicBool::new(false);
+
+/// Widest extranonce prefix handed out, in bytes
+const MAX_EXTRANONCE_SIZE: usize = 3;
+
+/// Share of the prefix space in use at which auto_extranonce_grow widens new prefixes by a byte.
+/// Widening only has room while some prefixes of the current size are still free to subdivide
+const EXTRANONCE_GROW_UTILIZATION: f64 = 0.9;
+
+static AUTO_EXTRANONCE_GROW: AtomicBool = AtomicBool::new(false);
+
+/// Widen extranonce prefixes when their space fills up instead of refusing miners (process-wide)
+pub fn set_auto_extranonce_grow(enabled: bool) {
+    AUTO_EXTRANONCE_GROW.store(enabled, Ordering::Relaxed);
+}
+
+static EXTRANONCE_DETECTION: once_cell::sync::Lazy<Mutex<ExtranonceDetection>> = once_cell::sync::Lazy::new(Default::default);
+
+/// How subscribing miners' extranonce sizes are picked (process-wide)
+pub fn set_extranonce_detection(detection: ExtranonceDetection) {
+    *EXTRANONCE_DETECTION.lock() = detection;
+}
+
+/// How a subscribing miner's extranonce size is picked, in order: the size the miner advertises in
+/// mining.subscribe (honor_hints), 0 for Bitmain firmware, `preferred` for the other miners the bridge
+/// recognizes (IceRiver, BzMiner, Goldshell), extranonce_size for everything else. The result is
+/// clamped into min..=max, except Bitmain's 0, which its firmware needs. Other miners never get a
+/// hinted 0, which would leave them searching the same nonces as each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtranonceDetection {
+    pub preferred: Option<u8>, // None = extranonce_size
+    pub min: u8,
+    pub max: u8,
+    pub honor_hints: bool,
+}
+
+impl Default for ExtranonceDetection {
+    fn default() -> Self {
+        Self { preferred: None, min: 0, max: MAX_EXTRANONCE_SIZE as u8, honor_hints: false }
+    }
+}
+
+impl ExtranonceDetection {
+    /// Extranonce size for a miner and where it came from ("hint", "agent" or "fallback")
+    pub fn detect(&self, remote_app: &str, hint: Option<u8>, fallback: u8) -> (u8, &'static str) {
+        let remote_app = remote_app.to_lowercase();
+        let bitmain = ["godminer", "bitmain", "antminer"].iter().any(|a| remote_app.contains(a));
+        let (size, source) = match hint.filter(|_| self.honor_hints) {
+            Some(hint) if bitmain => (hint, "hint"),
+            Some(hint) => (hint.max(1), "hint"),
+            None if bitmain => (0, "agent"),
+            None if ["iceriver", "bzminer", "goldshell"].iter().any(|a| remote_app.contains(a)) => {
+                (self.preferred.unwrap_or(fallback), "agent")
+            }
+            None => (fallback, "fallback"),
+        };
+        if bitmain && size == 0 {
+            return (0, source);
+        }
+        (size.clamp(self.min, self.max.max(self.min)), source)
+    }
+}
+
+/// Broadcasts slower than this are warned about (ms, 0 = never)
+static NOTIFY_LATENCY_BUDGET_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Least time between two notify latency warnings; broadcasts over budget in between are only counted
+const NOTIFY_LATENCY_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+static NOTIFY_LATENCY_ALERT: once_cell::sync::Lazy<Mutex<NotifyLatencyAlert>> = once_cell::sync::Lazy::new(Default::default);
+
+/// Warn when a new template takes longer than `budget` to reach every miner as mining.notify (process-wide, zero = off)
+pub fn set_notify_latency_budget(budget: Duration) {
+    NOTIFY_LATENCY_BUDGET_MS.store(budget.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Throttle for the over-budget broadcast warning
+#[derive(Debug, Default)]
+struct NotifyLatencyAlert {
+    last_warned: Option<Instant>,
+    suppressed: u64,
+}
+
+impl NotifyLatencyAlert {
+    /// Some(broadcasts over budget since the last warning) when it is time to warn again, None while throttled
+    fn over_budget(&mut self, now: Instant) -> Option<u64> {
+        if self.last_warned.is_some_and(|at| now.saturating_duration_since(at) < NOTIFY_LATENCY_WARN_INTERVAL) {
+            self.suppressed += 1;
+            return None;
+        }
+        self.last_warned = Some(now);
+        Some(std::mem::take(&mut self.suppressed))
+    }
+}
+
+/// Layout of the job data in mining.notify, by miner firmware
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JobFormat {
+    IceRiver, // Single hex string built from Hash::to_string()
+    BigJob,   // Single big endian hex string (BzMiner)
+    Legacy,   // Array of four u64 plus the timestamp (Bitmain and others)
+}
+
+impl JobFormat {
+    fn for_miner(remote_app: &str, use_big_job: bool) -> Self {
+        let remote_app_lower = remote_app.to_lowercase();
+        if remote_app_lower.contains("iceriver") || remote_app_lower.contains("icemining") || remote_app_lower.contains("icm") {
+            JobFormat::IceRiver
+        } else if use_big_job {
+            JobFormat::BigJob
+        } else {
+            JobFormat::Legacy
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The mining.notify params after the job ID, already serialized as JSON (without the surrounding brackets)
+fn encode_job_data(format: JobFormat, pre_pow_hash: &kaspa_hashes::Hash, timestamp: u64) -> String {
+    match format {
+        JobFormat::IceRiver => serde_json::Value::String(generate_iceriver_job_params(pre_pow_hash, timestamp)).to_string(),
+        JobFormat::BigJob => serde_json::Value::String(generate_large_job_params(&pre_pow_hash.as_bytes(), timestamp)).to_string(),
+        JobFormat::Legacy => {
+            let job_header = generate_job_header(&pre_pow_hash.as_bytes());
+            format!("{},{}", serde_json::Value::from(job_header), timestamp)
+        }
+    }
+}
+
+/// One mining.notify line; byte for byte what serializing the equivalent JsonRpcEvent (or, minimal, the bare
+/// method + params notification) would give, but built around job data encoded once per broadcast
+fn notify_line(job_id: u64, job_data: &str, clean_jobs: Option<bool>, minimal: bool) -> String {
+    let clean_jobs = clean_jobs.map(|clean| format!(",{}", clean)).unwrap_or_default();
+    if minimal {
+        format!(r#"{{"method":"mining.notify","params":["{}",{}{}]}}"#, job_id, job_data, clean_jobs)
+    } else {
+        format!(r#"{{"id":{},"jsonrpc":"2.0","method":"mining.notify","params":["{}",{}{}]}}"#, job_id, job_id, job_data, clean_jobs)
+    }
+}
+
+/// A template as every client mining to one wallet receives it; the job data is encoded at most once per format
+struct SharedJob {
+    block: kaspa_consensus_core::block::Block,
+    pre_pow_hash: kaspa_hashes::Hash,
+    job_data: [std::sync::OnceLock<Arc<str>>; 3],
+}
+
+impl SharedJob {
+    /// Job data for a connection on timestamp lane `lane`; only the template's own time (lane 0) is shared
+    fn job_data(&self, format: JobFormat, lane: u64) -> Arc<str> {
+        let timestamp = self.block.header.timestamp;
+        if lane != 0 {
+            return encode_job_data(format, &self.pre_pow_hash, timestamp + lane).into();
+        }
+        Arc::clone(self.job_data[format.index()].get_or_init(|| encode_job_data(format, &self.pre_pow_hash, timestamp).into()))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SharedJobError {
+    Fetch(String),     // get_block_template failed
+    Serialize(String), // The template's header could not be serialized
+}
+
+type SharedJobCell = Arc<tokio::sync::OnceCell<Result<Arc<SharedJob>, SharedJobError>>>;
+
+/// Templates for one new_block_available, fetched and serialized once per (wallet, remote app, canxium address)
+/// however many connections share them
+#[derive(Default)]
+struct JobBroadcast {
+    templates: Mutex<HashMap<(String, String, String), SharedJobCell>>,
+}
+
+impl JobBroadcast {
+    async fn job<T: KaspaApiTrait + ?Sized>(
+        &self,
+        kaspa_api: &T,
+        wallet_addr: &str,
+        remote_app: &str,
+        canxium_addr: &str,
+    ) -> Result<Arc<SharedJob>, SharedJobError> {
+        let cell = Arc::clone(
+            self.templates.lock().entry((wallet_addr.to_string(), remote_app.to_string(), canxium_addr.to_string())).or_default(),
+        );
+        cell.get_or_init(|| async {
+            let block = kaspa_api
+                .get_block_template(wallet_addr, remote_app, canxium_addr)
+                .await
+                .map_err(|e| SharedJobError::Fetch(e.to_string()))?;
+            // Serialize header - now returns Hash type directly
+            // The "Odd number of digits" error typically indicates a malformed hex string
+            // in one of the hash fields. This can happen if the block data from the node
+            // contains an invalid hash representation.
+            let pre_pow_hash = match serialize_block_header(&block) {
+                Ok(h) => h,
+                Err(e) => {
+                    let error_msg = e.to_string();
+                    error!("failed to serialize block header: {}", error_msg);
+
+                    // Log block header details for debugging
+                    tracing::debug!("Block header version: {}", block.header.version);
+                    tracing::debug!("Block header timestamp: {}", block.header.timestamp);
+                    tracing::debug!("Block header bits: {}", block.header.bits);
+                    tracing::debug!("Block header daa_score: {}", block.header.daa_score);
+                    tracing::debug!("Block header blue_score: {}", block.header.blue_score);
+                    tracing::debug!("Block header parents_by_level expanded_len: {}", block.header.parents_by_level.expanded_len());
+
+                    // Skip this block - the next block template should work
+                    return Err(SharedJobError::Serialize(error_msg));
+                }
+            };
+            Ok(Arc::new(SharedJob { block, pre_pow_hash, job_data: Default::default() }))
+        })
+        .await
+        .clone()
+    }
+}
+
+/// Connections across every instance
+static CONNECTED_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Stratum connections currently open on all instances together
+pub fn connected_clients() -> usize {
+    CONNECTED_CLIENTS.load(Ordering::Relaxed)
+}
+
+/// Skip mining.notify for templates that carry the same work as the client's last job (process-wide)
+pub fn set_dedup_templates(enabled: bool) {
+    DEDUP_TEMPLATES.store(enabled, Ordering::Relaxed);
+}
+
+/// CleanJobsPolicy as u8
+static CLEAN_JOBS_POLICY: AtomicU8 = AtomicU8::new(CleanJobsPolicy::Always as u8);
+
+/// When mining.notify tells miners to drop the work they are on. Dropping on every refresh
+/// avoids stale shares but throws away in-flight nonces; keeping work across refreshes saves
+/// it but shares on superseded templates may come back stale or as duplicates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CleanJobsPolicy {
+    /// Every job replaces the previous ones (default). No flag goes on the wire, as before
+    Always,
+    /// Only jobs for a new block (DAA score or parents changed) are clean; refreshes are not
+    OnNewBlock,
+    /// No job asks miners to drop their work
+    Never,
+}
+
+impl CleanJobsPolicy {
+    /// clean_jobs for a job built from `new`, given the header of the client's previous job
+    pub fn clean_jobs(
+        self,
+        previous: Option<&kaspa_consensus_core::header::Header>,
+        new: &kaspa_consensus_core::header::Header,
+    ) -> bool {
+        match self {
+            CleanJobsPolicy::Always => true,
+            CleanJobsPolicy::OnNewBlock => previous.is_none_or(|old| crate::mining_state::is_new_block(old, new)),
+            CleanJobsPolicy::Never => false,
+        }
+    }
+
+    /// Trailing clean_jobs param for mining.notify. None under the default policy, so the notify params stay
+    /// exactly what firmware has always been sent; the flag only goes on the wire once a policy is configured
+    pub fn notify_flag(
+        self,
+        previous: Option<&kaspa_consensus_core::header::Header>,
+        new: &kaspa_consensus_core::header::Header,
+    ) -> Option<bool> {
+        (self != CleanJobsPolicy::Always).then(|| self.clean_jobs(previous, new))
+    }
+}
+
+impl std::str::FromStr for CleanJobsPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "always" => Ok(CleanJobsPolicy::Always),
+            "on_new_block" => Ok(CleanJobsPolicy::OnNewBlock),
+            "never" => Ok(CleanJobsPolicy::Never),
+            other => Err(format!("unknown clean_jobs '{}', expected always, on_new_block or never", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for CleanJobsPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CleanJobsPolicy::Always => write!(f, "always"),
+            CleanJobsPolicy::OnNewBlock => write!(f, "on_new_block"),
+            CleanJobsPolicy::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// Choose when mining.notify sets clean_jobs (process-wide)
+pub fn set_clean_jobs_policy(policy: CleanJobsPolicy) {
+    CLEAN_JOBS_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+fn clean_jobs_policy() -> CleanJobsPolicy {
+    match CLEAN_JOBS_POLICY.load(Ordering::Relaxed) {
+        p if p == CleanJobsPolicy::OnNewBlock as u8 => CleanJobsPolicy::OnNewBlock,
+        p if p == CleanJobsPolicy::Never as u8 => CleanJobsPolicy::Never,
+        _ => CleanJobsPolicy::Always,
+    }
+}
+
+/// DiffChangeStrategy as u8
+static DIFF_CHANGE_STRATEGY: AtomicU8 = AtomicU8::new(DiffChangeStrategy::SetDifficulty as u8);
+
+/// How a vardiff retarget reaches a connected miner, for firmware that mishandles mining.set_difficulty
+/// mid-stream. A connection's initial difficulty always goes out as mining.set_difficulty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffChangeStrategy {
+    /// mining.set_difficulty (default)
+    SetDifficulty,
+    /// client.reconnect; the miner comes back on a fresh connection that starts at the new difficulty
+    Reconnect,
+    /// mining.set_difficulty followed by mining.set_extranonce with the unchanged prefix, so firmware
+    /// that only reloads its difficulty on a new session restarts its work
+    SetExtranonce,
+}
+
+impl DiffChangeStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiffChangeStrategy::SetDifficulty => "set_difficulty",
+            DiffChangeStrategy::Reconnect => "reconnect",
+            DiffChangeStrategy::SetExtranonce => "set_extranonce",
+        }
+    }
+}
+
+impl std::str::FromStr for DiffChangeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "set_difficulty" => Ok(DiffChangeStrategy::SetDifficulty),
+            "reconnect" => Ok(DiffChangeStrategy::Reconnect),
+            "set_extranonce" => Ok(DiffChangeStrategy::SetExtranonce),
+            other => Err(format!("unknown diff_change_strategy '{}', expected set_difficulty, reconnect or set_extranonce", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for DiffChangeStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Choose how vardiff retargets are delivered (process-wide)
+pub fn set_diff_change_strategy(strategy: DiffChangeStrategy) {
+    DIFF_CHANGE_STRATEGY.store(strategy as u8, Ordering::Relaxed);
+}
+
+fn diff_change_strategy() -> DiffChangeStrategy {
+    match DIFF_CHANGE_STRATEGY.load(Ordering::Relaxed) {
+        s if s == DiffChangeStrategy::Reconnect as u8 => DiffChangeStrategy::Reconnect,
+        s if s == DiffChangeStrategy::SetExtranonce as u8 => DiffChangeStrategy::SetExtranonce,
+        _ => DiffChangeStrategy::SetDifficulty,
+    }
+}
+
+/// What happens when a wallet.worker authorizes while a connection with the same name is still open.
+/// Two devices sharing a name share per-worker stats and vardiff, which then fit neither
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicateWorkerPolicy {
+    /// Both stay connected under the same name (default); only logged and counted
+    #[default]
+    Allow,
+    /// The new connection is refused and closed
+    Reject,
+    /// The new connection is renamed worker_2 (worker_3, ...) so both keep their own stats
+    Suffix,
+    /// The old connection is closed, the new one takes the name over
+    KickOld,
+}
+
+impl DuplicateWorkerPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DuplicateWorkerPolicy::Allow => "allow",
+            DuplicateWorkerPolicy::Reject => "reject",
+            DuplicateWorkerPolicy::Suffix => "suffix",
+            DuplicateWorkerPolicy::KickOld => "kick_old",
+        }
+    }
+}
+
+impl std::str::FromStr for DuplicateWorkerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "allow" => Ok(DuplicateWorkerPolicy::Allow),
+            "reject" => Ok(DuplicateWorkerPolicy::Reject),
+            "suffix" => Ok(DuplicateWorkerPolicy::Suffix),
+            "kick_old" => Ok(DuplicateWorkerPolicy::KickOld),
+            other => Err(format!("unknown duplicate_worker_policy '{}', expected allow, reject, suffix or kick_old", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for DuplicateWorkerPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// First `worker_N` (N from 2) that `taken` says is free
+fn suffixed_worker_name(worker: &str, taken: impl Fn(&str) -> bool) -> String {
+    (2..).map(|n| format!("{}_{}", worker, n)).find(|name| !taken(name)).unwrap_or_else(|| worker.to_string())
+}
+
+/// How long a difficulty handed over with client.reconnect waits for the miner to come back
+const RECONNECT_DIFF_TTL: Duration = Duration::from_secs(120);
+
+/// Difficulty for miners sent client.reconnect, by (remote IP, worker name), and when it was handed over
+static RECONNECT_DIFFS: once_cell::sync::Lazy<Mutex<HashMap<(String, String), (f64, Instant)>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+fn hand_over_reconnect_diff(ip: &str, worker: &str, diff: f64, now: Instant) {
+    let mut diffs = RECONNECT_DIFFS.lock();
+    diffs.retain(|_, (_, at)| now.saturating_duration_since(*at) < RECONNECT_DIFF_TTL);
+    diffs.insert((ip.to_string(), worker.to_string()), (diff, now));
+}
+
+/// Difficulty a miner told to reconnect should resume at; taken once, by its next authorize
+pub fn take_reconnect_diff(ip: &str, worker: &str, now: Instant) -> Option<f64> {
+    let (diff, at) = RECONNECT_DIFFS.lock().remove(&(ip.to_string(), worker.to_string()))?;
+    (now.saturating_duration_since(at) < RECONNECT_DIFF_TTL).then_some(diff)
+}
+
+/// Extranonce prefixes freed by disconnected clients, handed out again only after
+/// they have been idle for reuse_delay (so a fast-reconnecting miner can't collide
+/// with its own just-freed prefix assigned to another client)
+struct ExtranonceReuseQueue<T> {
+    freed: VecDeque<(T, Instant)>,
+    reuse_delay: Duration,
+}
+
+impl<T: PartialEq> ExtranonceReuseQueue<T> {
+    fn new(reuse_delay: Duration) -> Self {
+        Self { freed: VecDeque::new(), reuse_delay }
+    }
+
+    fn release(&mut self, extranonce: T, now: Instant) {
+        if !self.freed.iter().any(|(v, _)| *v == extranonce) {
+            self.freed.push_back((extranonce, now));
+        }
+    }
+
+    /// Oldest freed prefix accepted by `fits` that has been idle long enough, if any
+    fn take_reusable(&mut self, now: Instant, fits: impl Fn(&T) -> bool) -> Option<T> {
+        let index = self
+            .freed
+            .iter()
+            .take_while(|(_, freed_at)| now.duration_since(*freed_at) >= self.reuse_delay)
+            .position(|(v, _)| fits(v))?;
+        self.freed.remove(index).map(|(v, _)| v)
+    }
+}
+
+/// An extranonce prefix: its width in bytes and value
+type ExtranoncePrefix = (usize, u32);
+
+/// Hands out extranonce prefixes whose nonce ranges never overlap a prefix still in use. A prefix
+/// owns every nonce that starts with its bytes, so prefixes of different widths (miners negotiating
+/// different sizes, or widened by auto_extranonce_grow) are only free when neither starts the other
+struct ExtranonceAllocator {
+    grown: usize,                         // Narrowest width handed out since auto_extranonce_grow widened (0 = never)
+    next: [u32; MAX_EXTRANONCE_SIZE + 1], // Where the search for a free prefix resumes, per width
+    reserved: HashSet<ExtranoncePrefix>,  // Held by a client or waiting out the reuse delay
+    covered: HashSet<ExtranoncePrefix>,   // Narrower prefixes that start a reserved one
+    reuse: ExtranonceReuseQueue<ExtranoncePrefix>,
+}
+
+impl ExtranonceAllocator {
+    fn new(reuse_delay: Duration) -> Self {
+        Self {
+            grown: 0,
+            next: [0; MAX_EXTRANONCE_SIZE + 1],
+            reserved: HashSet::new(),
+            covered: HashSet::new(),
+            reuse: ExtranonceReuseQueue::new(reuse_delay),
+        }
+    }
+
+    /// Fraction of the nonce space covered by reserved prefixes (narrower ones cover more)
+    fn utilization(&self) -> f64 {
+        self.reserved.iter().map(|(bytes, _)| 256f64.powi(-(*bytes as i32))).sum()
+    }
+
+    fn overlaps_reserved(&self, (width, value): ExtranoncePrefix) -> bool {
+        self.covered.contains(&(width, value))
+            || (1..=width).any(|bytes| self.reserved.contains(&(bytes, value >> (8 * (width - bytes)))))
+    }
+
+    fn reserve(&mut self, (width, value): ExtranoncePrefix) {
+        self.reserved.insert((width, value));
+        for bytes in 1..width {
+            self.covered.insert((bytes, value >> (8 * (width - bytes))));
+        }
+    }
+
+    /// A free prefix of `width` bytes (wider once auto_grow has widened), reserved until release. Freed
+    /// prefixes of that width past the reuse delay come first
+    fn allocate(&mut self, now: Instant, auto_grow: bool, width: usize) -> Result<ExtranoncePrefix, String> {
+        let mut width = width.clamp(1, MAX_EXTRANONCE_SIZE).max(self.grown);
+        if let Some(freed) = self.reuse.take_reusable(now, |(bytes, _)| *bytes == width) {
+            return Ok(freed); // Never left `reserved`, nothing could have been carved out of it
+        }
+        if auto_grow && width < MAX_EXTRANONCE_SIZE && self.utilization() >= EXTRANONCE_GROW_UTILIZATION {
+            width += 1;
+            self.grown = width;
+        }
+        let capacity = 1u32 << (8 * width);
+        for _ in 0..capacity {
+            let value = self.next[width];
+            self.next[width] = (value + 1) % capacity;
+            if !self.overlaps_reserved((width, value)) {
+                self.reserve((width, value));
+                return Ok((width, value));
+            }
+        }
+        Err(format!("all {} extranonce prefixes of {} byte(s) are in use", capacity, width))
+    }
+
+    /// Hand a prefix back; it stays reserved until the reuse delay has passed and it is reallocated
+    fn release(&mut self, prefix: ExtranoncePrefix, now: Instant) {
+        if self.reserved.contains(&prefix) {
+            self.reuse.release(prefix, now);
+        }
+    }
+}
+
+/// Timestamp lanes partition_nonce hands out; lane N mines the template N ms ahead of its own time,
+/// well inside how far into the future kaspad accepts a block
+const MAX_NONCE_LANES: u64 = 1000;
+
+static PARTITION_NONCE: AtomicBool = AtomicBool::new(false);
+
+/// Give connections that mine without an extranonce prefix a timestamp lane of their own (process-wide)
+pub fn set_partition_nonce(enabled: bool) {
+    PARTITION_NONCE.store(enabled, Ordering::Relaxed);
+}
+
+static NONCE_LANES: once_cell::sync::Lazy<Mutex<NonceLanes>> = once_cell::sync::Lazy::new(Default::default);
+
+/// Timestamp lanes held by connections without an extranonce prefix, across every instance. Such
+/// firmware searches the whole nonce range, so two of them on one template hash the same nonces;
+/// moving each one's header time apart gives every nonce a different PoW. Lane 0 is never handed
+/// out, it is where connections with an extranonce prefix mine
+#[derive(Default)]
+struct NonceLanes {
+    held: BTreeSet<u64>,
+}
+
+impl NonceLanes {
+    /// Lowest free lane, reserved until release; None once all MAX_NONCE_LANES are held
+    fn allocate(&mut self) -> Option<u64> {
+        let lane = (1..=MAX_NONCE_LANES).find(|lane| !self.held.contains(lane))?;
+        self.held.insert(lane);
+        Some(lane)
+    }
+
+    fn release(&mut self, lane: u64) {
+        self.held.remove(&lane);
+    }
+
+    fn len(&self) -> usize {
+        self.held.len()
+    }
+}
+
 pub struct ClientHandler {
     clients: Arc<Mutex<HashMap<i32, Arc<StratumContext>>>>,
     client_counter: AtomicI32,
-    min_share_diff: f64,
-    _extranonce_size: i8,       // Kept for backward compatibility, but now auto-detected per client (unused)
-    _max_extranonce: i32,       // Kept for backward compatibility (unused)
-    next_extranonce: AtomicI32, // Used for extranonce_size=2 (IceRiver/BzMiner/Goldshell)
+    start_diff: f64,      // Difficulty new connections start at (start_diff, or min_share_diff when unset)
+    extranonce_size: u8,  // Size for miners the extranonce detection has nothing better for
+    _max_extranonce: i32, // Kept for backward compatibility (unused)
+    extranonce: Mutex<ExtranonceAllocator>, // Prefixes of every negotiated size
     last_template_time: Arc<Mutex<Instant>>,
     last_balance_check: Arc<Mutex<Instant>>,
     share_handler: Arc<ShareHandler>,
-    instance_id: String, // Instance identifier for logging
+    instance_id: String,         // Instance identifier for logging
+    log_notify_submit_rtt: bool, // Time sampled jobs from mining.notify to mining.submit
+    difficulty_format: DifficultyFormat,
+    diff_policy: SuggestDifficultyPolicy, // Bounds and rounding for d= static difficulties
+    min_diff: f64,                        // Instance min_share_diff, the lowest static difficulty
+    authorize: AuthorizeConfig,           // Fixed wallet, overrides and network checked on authorize
 }
 
 impl ClientHandler {
-    pub fn new(share_handler: Arc<ShareHandler>, min_share_diff: f64, extranonce_size: i8, instance_id: String) -> Self {
+    pub fn new(
+        share_handler: Arc<ShareHandler>,
+        start_diff: f64,
+        extranonce_size: i8,
+        instance_id: String,
+        log_notify_submit_rtt: bool,
+        difficulty_format: DifficultyFormat,
+        extranonce_reuse_delay: Duration,
+        diff_policy: SuggestDifficultyPolicy,
+        min_diff: f64,
+        authorize: AuthorizeConfig,
+    ) -> Self {
         let max_extranonce = if extranonce_size > 0 { (2_f64.powi(8 * extranonce_size.min(3) as i32) - 1.0) as i32 } else { 0 };
 
         Self {
             clients: Arc::new(Mutex::new(HashMap::new())),
             client_counter: AtomicI32::new(0),
-            min_share_diff,
-            _extranonce_size: extranonce_size,
+            start_diff,
+            extranonce_size: extranonce_size.clamp(0, MAX_EXTRANONCE_SIZE as i8) as u8,
             _max_extranonce: max_extranonce,
-            next_extranonce: AtomicI32::new(0),
+            extranonce: Mutex::new(ExtranonceAllocator::new(extranonce_reuse_delay)),
             last_template_time: Arc::new(Mutex::new(Instant::now())),
             last_balance_check: Arc::new(Mutex::new(Instant::now())),
             share_handler,
             instance_id,
+            log_notify_submit_rtt,
+            difficulty_format,
+            diff_policy,
+            min_diff,
+            authorize,
         }
     }
 
+    /// Settings handle_authorize applies to this instance's miners
+    pub fn authorize_config(&self) -> &AuthorizeConfig {
+        &self.authorize
+    }
+
+    /// Difficulty a worker asking for a static `d=<diff>` is pinned at: clamped into var_diff_min/var_diff_max
+    /// and rounded by pow2_clamp like any starting difficulty, never below min_share_diff
+    pub fn static_diff(&self, requested: f64) -> f64 {
+        self.diff_policy.start_diff(requested, self.min_diff)
+    }
+
+    /// Number of connected clients
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().len()
+    }
+
+    /// Fraction of the extranonce prefix space in use (1.0 = new IceRiver/BzMiner/Goldshell miners are refused)
+    pub fn extranonce_utilization(&self) -> f64 {
+        self.extranonce.lock().utilization()
+    }
+
     pub fn on_connect(&self, ctx: Arc<StratumContext>) {
         let idx = self.client_counter.fetch_add(1, Ordering::Relaxed);
 
@@ -62,6 +692,8 @@ impl ClientHandler {
 
         ctx.set_id(idx);
         self.clients.lock().insert(idx, Arc::clone(&ctx));
+        CONNECTED_CLIENTS.fetch_add(1, Ordering::Relaxed);
+        crate::webhook::emit_lifecycle_event("connect", &ctx, serde_json::json!({}));
 
         tracing::debug!(
             "{} [CONNECTION] Client {} connected (ID: {}), extranonce will be assigned after miner type detection",
@@ -79,79 +711,413 @@ impl ClientHandler {
         });
     }
 
-    /// Assign extranonce to a client based on detected miner type
-    /// Called from handle_subscribe after miner type is detected
-    pub fn assign_extranonce_for_miner(&self, ctx: &StratumContext, remote_app: &str) {
-        use std::sync::atomic::Ordering;
-
-        // Detect miner type and determine required extranonce size
-        // Bitmain (GodMiner) requires extranonce_size = 0 (no extranonce)
-        // IceRiver, BzMiner, Goldshell require extranonce_size = 2
-        let remote_app_lower = remote_app.to_lowercase();
-        let is_bitmain =
-            remote_app_lower.contains("godminer") || remote_app_lower.contains("bitmain") || remote_app_lower.contains("antminer");
+    /// Assign extranonce to a client, sized by the extranonce detection from its user-agent and the
+    /// size it advertised in mining.subscribe (`size_hint`). Fails when every prefix is taken (and
+    /// auto_extranonce_grow is off or already at the widest size); the miner must then be refused
+    pub fn assign_extranonce_for_miner(&self, ctx: &StratumContext, remote_app: &str, size_hint: Option<u8>) -> Result<(), String> {
+        let (required_extranonce_size, source) = EXTRANONCE_DETECTION.lock().detect(remote_app, size_hint, self.extranonce_size);
 
-        let required_extranonce_size = if is_bitmain { 0 } else { 2 };
+        // A repeated subscribe must not leak the prefix (or lane) it was given the first time
+        self.release_extranonce(ctx);
+        self.release_nonce_lane(ctx);
 
         let extranonce = if required_extranonce_size > 0 {
-            // Calculate max extranonce for size 2
-            let max_extranonce = (2_f64.powi(16) - 1.0) as i32; // 2 bytes = 16 bits = 65535
-
-            let next = self.next_extranonce.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |val| {
-                if val < max_extranonce {
-                    Some(val + 1)
-                } else {
-                    Some(0)
+            // Prefers a freed prefix that has been idle for extranonce_reuse_delay_secs
+            let (extranonce_bytes, extranonce_val, grown) = {
+                let mut allocator = self.extranonce.lock();
+                let grown_before = allocator.grown;
+                let allocated = allocator.allocate(
+                    Instant::now(),
+                    AUTO_EXTRANONCE_GROW.load(Ordering::Relaxed),
+                    required_extranonce_size as usize,
+                );
+                match allocated {
+                    Ok((bytes, value)) => (bytes, value, allocator.grown > grown_before),
+                    Err(e) => {
+                        error!("{} extranonce space exhausted, refusing {}: {}", self.instance_id, ctx.remote_addr, e);
+                        return Err(e);
+                    }
                 }
-            });
-
-            if next.is_err() || next.unwrap() >= max_extranonce {
-                warn!("wrapped extranonce! new clients may be duplicating work...");
+            };
+            if grown {
+                warn!(
+                    "{} extranonce space nearly full, new miners get {}-byte prefixes (auto_extranonce_grow)",
+                    self.instance_id, extranonce_bytes
+                );
+            }
+            let extranonce_str = format!("{:0width$x}", extranonce_val, width = extranonce_bytes * 2);
+            // Independent of the allocator's bookkeeping: two miners on overlapping prefixes hash the same nonces
+            if let Some(holder) = self.extranonce_holder(&extranonce_str, ctx) {
+                error!(
+                    "{} extranonce {} for {} overlaps the prefix {} is still mining on, refusing the connection",
+                    self.instance_id, extranonce_str, ctx.remote_addr, holder
+                );
+                record_extranonce_collision();
+                // Stays reserved: releasing it would let the allocator hand out a prefix that is in use
+                return Err(format!("extranonce {} is already in use", extranonce_str));
             }
-
-            let extranonce_val = next.unwrap_or(0);
-            let extranonce_str = format!("{:0width$x}", extranonce_val, width = (required_extranonce_size * 2) as usize);
             tracing::debug!(
-                "[AUTO-EXTRANONCE] Assigned extranonce '{}' (value: {}, size: {} bytes) to {} miner '{}'",
+                "[AUTO-EXTRANONCE] Assigned extranonce '{}' (value: {}, size: {} bytes) to miner '{}'",
                 extranonce_str,
                 extranonce_val,
-                required_extranonce_size,
-                if is_bitmain { "Bitmain" } else { "IceRiver/BzMiner/Goldshell" },
+                extranonce_bytes,
                 remote_app
             );
             extranonce_str
         } else {
-            tracing::debug!("[AUTO-EXTRANONCE] Assigned empty extranonce (size: 0 bytes) to Bitmain miner '{}'", remote_app);
+            tracing::debug!("[AUTO-EXTRANONCE] Assigned empty extranonce (size: 0 bytes) to miner '{}'", remote_app);
+            self.assign_nonce_lane(ctx);
             String::new()
         };
 
         *ctx.extranonce.lock() = extranonce.clone();
+        GetMiningState(ctx).set_extranonce_source(source);
+        record_extranonce_negotiated(extranonce.len() / 2, source);
 
         tracing::debug!(
-            "[AUTO-EXTRANONCE] Client {} extranonce set to '{}' (detected miner: '{}', type: {})",
+            "[AUTO-EXTRANONCE] Client {} extranonce set to '{}' ({} bytes from {}, miner: '{}', hint: {:?})",
             ctx.remote_addr,
             extranonce,
+            extranonce.len() / 2,
+            source,
             remote_app,
-            if is_bitmain { "Bitmain" } else { "IceRiver/BzMiner/Goldshell" }
+            size_hint
         );
+        Ok(())
+    }
+
+    /// Another connected client whose extranonce starts with `extranonce` or is started by it
+    fn extranonce_holder(&self, extranonce: &str, ctx: &StratumContext) -> Option<String> {
+        self.clients.lock().values().find_map(|other| {
+            if std::ptr::eq(other.as_ref(), ctx) {
+                return None;
+            }
+            let held = other.extranonce.lock();
+            let overlaps = !held.is_empty() && (held.starts_with(extranonce) || extranonce.starts_with(held.as_str()));
+            overlaps.then(|| other.remote_endpoint())
+        })
+    }
+
+    /// Hand a client's extranonce prefix back to the allocator
+    fn release_extranonce(&self, ctx: &StratumContext) {
+        let extranonce = std::mem::take(&mut *ctx.extranonce.lock());
+        if !extranonce.is_empty() {
+            if let Ok(value) = u32::from_str_radix(&extranonce, 16) {
+                self.extranonce.lock().release((extranonce.len() / 2, value), Instant::now());
+            }
+        }
+    }
+
+    /// With partition_nonce, move a connection that has no extranonce prefix to a timestamp lane of its own.
+    /// Once every lane is held it mines the template as is and may repeat another such connection's work
+    fn assign_nonce_lane(&self, ctx: &StratumContext) {
+        if !PARTITION_NONCE.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut lanes = NONCE_LANES.lock();
+        match lanes.allocate() {
+            Some(lane) => {
+                ctx.state.set_nonce_lane(lane);
+                tracing::debug!(
+                    "{} [PARTITION] {} mines on timestamp lane {} (+{} ms)",
+                    self.instance_id,
+                    ctx.remote_addr,
+                    lane,
+                    lane
+                );
+            }
+            None => {
+                warn!(
+                    "{} all {} nonce lanes in use, {} shares the nonce space of its template (partition_nonce)",
+                    self.instance_id, MAX_NONCE_LANES, ctx.remote_addr
+                );
+                record_nonce_partition_fallback();
+            }
+        }
+        record_nonce_partition_lanes(lanes.len());
+    }
+
+    /// Hand a client's timestamp lane back, if it had one
+    fn release_nonce_lane(&self, ctx: &StratumContext) {
+        if let Some(lane) = ctx.state.take_nonce_lane() {
+            let mut lanes = NONCE_LANES.lock();
+            lanes.release(lane);
+            record_nonce_partition_lanes(lanes.len());
+        }
     }
 
     pub fn on_disconnect(&self, ctx: &StratumContext) {
         ctx.disconnect();
-        let mut clients = self.clients.lock();
-        if let Some(id) = ctx.id() {
-            tracing::debug!("removing client {}", id);
-            clients.remove(&id);
-            tracing::debug!("removed client {}", id);
-        }
         let wallet_addr = ctx.wallet_addr.lock().clone();
         let worker_name = ctx.worker_name.lock().clone();
+        // The wallet.worker series are shared by every connection with the same label (duplicate workers,
+        // a kick_old replacement, names the label mode folds together), so only the last one out removes them
+        let last_holder = {
+            let mut clients = self.clients.lock();
+            if let Some(id) = ctx.id() {
+                tracing::debug!("removing client {}", id);
+                if clients.remove(&id).is_some() {
+                    CONNECTED_CLIENTS.fetch_sub(1, Ordering::Relaxed);
+                }
+                tracing::debug!("removed client {}", id);
+            }
+            let label = current_worker_label(&worker_name);
+            !clients.values().any(|other| {
+                !std::ptr::eq(other.as_ref(), ctx)
+                    && *other.wallet_addr.lock() == wallet_addr
+                    && current_worker_label(&other.worker_name.lock()) == label
+            })
+        };
+
+        // Authorized but never produced a valid share: a likely firmware/settings mismatch
+        let first_share = ctx.state.first_share();
+        if first_share.authorized_at.is_some() && !first_share.accepted {
+            record_disconnect_without_share();
+        }
+
+        // Return the extranonce prefix to the reuse queue
+        self.release_extranonce(ctx);
+        self.release_nonce_lane(ctx);
+        crate::webhook::emit_lifecycle_event("disconnect", ctx, serde_json::json!({}));
         record_disconnect(&crate::prom::WorkerContext {
             worker_name: worker_name.clone(),
             miner: String::new(),
             wallet: wallet_addr.clone(),
-            ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
+            ip: ctx.remote_endpoint(),
+            port: ctx.metrics_port.clone(),
         });
+        if last_holder {
+            remove_worker_difficulty(&worker_name, &wallet_addr);
+            remove_worker_stale_share_pct(&worker_name, &wallet_addr);
+            if let Some(split) = ctx.payout_split.lock().as_ref() {
+                remove_payout_split(&worker_name, &wallet_addr, &split.address);
+            }
+        }
+        self.record_connection_states();
+    }
+
+    /// Other connections in `clients` authorized as `worker` of `wallet`
+    fn worker_holders(
+        clients: &HashMap<i32, Arc<StratumContext>>,
+        ctx: &StratumContext,
+        wallet: &str,
+        worker: &str,
+    ) -> Vec<Arc<StratumContext>> {
+        clients
+            .values()
+            .filter(|other| !std::ptr::eq(other.as_ref(), ctx))
+            .filter(|other| *other.worker_name.lock() == worker && *other.wallet_addr.lock() == wallet)
+            .cloned()
+            .collect()
+    }
+
+    /// Name an authorizing connection gets as `worker` of `wallet` under duplicate_worker_policy, closing
+    /// the connections it replaces (kick_old), and set it with the wallet on the connection. Both happen
+    /// under the client list's lock, so two connections authorizing at once can't take the same name.
+    /// Err when the policy refuses it
+    pub fn claim_worker_name(&self, ctx: &StratumContext, wallet: &str, worker: &str) -> Result<String, String> {
+        let clients = self.clients.lock();
+        let name = self.resolve_worker_name(&clients, ctx, wallet, worker)?;
+        *ctx.wallet_addr.lock() = wallet.to_string();
+        *ctx.worker_name.lock() = name.clone();
+        Ok(name)
+    }
+
+    /// Unnamed workers are never duplicates: their stats are already kept per connection
+    fn resolve_worker_name(
+        &self,
+        clients: &HashMap<i32, Arc<StratumContext>>,
+        ctx: &StratumContext,
+        wallet: &str,
+        worker: &str,
+    ) -> Result<String, String> {
+        if worker.is_empty() {
+            return Ok(String::new());
+        }
+        let holders = Self::worker_holders(clients, ctx, wallet, worker);
+        let Some(holder) = holders.first() else {
+            return Ok(worker.to_string());
+        };
+        let policy = self.authorize.duplicate_worker_policy;
+        record_duplicate_worker(policy.as_str());
+        warn!(
+            "{} worker {} of {} authorized from {} while already connected from {} (duplicate_worker_policy: {})",
+            self.instance_id,
+            worker,
+            wallet,
+            ctx.remote_endpoint(),
+            holder.remote_endpoint(),
+            policy
+        );
+        match policy {
+            DuplicateWorkerPolicy::Allow => Ok(worker.to_string()),
+            DuplicateWorkerPolicy::Reject => Err(format!("worker {} is already connected", worker)),
+            DuplicateWorkerPolicy::Suffix => {
+                let name = suffixed_worker_name(worker, |name| !Self::worker_holders(clients, ctx, wallet, name).is_empty());
+                warn!("{} {} mines as {} to keep its stats apart", self.instance_id, ctx.remote_endpoint(), name);
+                Ok(name)
+            }
+            DuplicateWorkerPolicy::KickOld => {
+                for old in &holders {
+                    old.disconnect_for(DisconnectReason::DuplicateWorker);
+                }
+                Ok(worker.to_string())
+            }
+        }
+    }
+
+    /// Connected clients whose worker name (or wallet.worker) matches, for admin commands
+    fn clients_for_worker(&self, worker: &str) -> Vec<Arc<StratumContext>> {
+        self.clients
+            .lock()
+            .values()
+            .filter(|ctx| {
+                let worker_name = ctx.worker_name.lock().clone();
+                worker_name == worker || format!("{}.{}", ctx.wallet_addr.lock(), worker_name) == worker
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// One line per connected client: instance, id, worker, wallet, address, app and difficulty
+    pub fn list_miners(&self) -> Vec<String> {
+        let mut clients: Vec<Arc<StratumContext>> = self.clients.lock().values().cloned().collect();
+        clients.sort_by_key(|ctx| ctx.id());
+        clients
+            .iter()
+            .map(|ctx| {
+                format!(
+                    "{} id={} worker={} wallet={} addr={} app={} diff={} extranonce_updates={}",
+                    self.instance_id,
+                    ctx.id().unwrap_or_default(),
+                    ctx.worker_name.lock(),
+                    ctx.wallet_addr.lock(),
+                    ctx.remote_endpoint(),
+                    ctx.remote_app.lock(),
+                    GetMiningState(ctx).stratum_diff().map(|d| d.diff_value).unwrap_or(0.0),
+                    ctx.supports_extranonce_updates()
+                )
+            })
+            .collect()
+    }
+
+    /// Everything the bridge tracks for each connection of a worker, for live triage. Only reads state:
+    /// no stats are created and nothing is sent to the miner. Share counts are the worker's (all its connections)
+    pub fn show_worker(&self, worker: &str) -> Vec<serde_json::Value> {
+        let now = std::time::SystemTime::now();
+        self.clients_for_worker(worker)
+            .iter()
+            .map(|ctx| {
+                let state = GetMiningState(ctx);
+                let stats = self.share_handler.peek_stats(ctx);
+                let idle = ctx.idle_for();
+                let unix_secs =
+                    |t: std::time::SystemTime| t.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+                serde_json::json!({
+                    "instance": self.instance_id,
+                    "id": ctx.id(),
+                    "worker": ctx.worker_name.lock().clone(),
+                    "wallet": ctx.wallet_addr.lock().clone(),
+                    "address": ctx.remote_endpoint(),
+                    "app": ctx.remote_app.lock().clone(),
+                    "connected": ctx.connected(),
+                    "connected_at": unix_secs(state.connect_time()),
+                    "last_seen": unix_secs(now.checked_sub(idle).unwrap_or(now)),
+                    "idle_secs": idle.as_secs_f64(),
+                    "difficulty": state.stratum_diff().map(|d| d.diff_value),
+                    "suggested_difficulty": state.suggested_diff(),
+                    "extranonce": ctx.extranonce.lock().clone(),
+                    "extranonce_size": ctx.extranonce.lock().len() / 2,
+                    "extranonce_source": state.extranonce_source(),
+                    "nonce_lane": state.nonce_lane(),
+                    "last_job_id": state.current_job_counter(),
+                    "big_job": state.use_big_job(),
+                    "extensions": { "subscribe-extranonce": ctx.supports_extranonce_updates() },
+                    "accepted": stats.as_ref().map(|s| *s.shares_found.lock()),
+                    "stale": stats.as_ref().map(|s| *s.stale_shares.lock()),
+                    "invalid": stats.as_ref().map(|s| *s.invalid_shares.lock()),
+                    "blocks": stats.as_ref().map(|s| *s.blocks_found.lock()),
+                    "last_share_secs_ago": stats.as_ref().map(|s| s.last_share.lock().elapsed().as_secs_f64()),
+                })
+            })
+            .collect()
+    }
+
+    /// Difficulty currently assigned to each connection that has been sent one
+    pub fn current_difficulties(&self) -> Vec<f64> {
+        self.clients.lock().values().filter_map(|ctx| GetMiningState(ctx).stratum_diff().map(|d| d.diff_value)).collect()
+    }
+
+    /// Close every connection of a worker; on_disconnect then cleans up its metrics
+    pub fn kick_worker(&self, worker: &str) -> usize {
+        let clients = self.clients_for_worker(worker);
+        for ctx in &clients {
+            warn!("{} admin: disconnecting {} ({})", self.instance_id, worker, ctx.remote_endpoint());
+            ctx.disconnect_for(DisconnectReason::Kicked);
+        }
+        clients.len()
+    }
+
+    /// Push a new difficulty to every connection of a worker (vardiff continues from there)
+    pub fn set_worker_difficulty(&self, worker: &str, diff: f64) -> usize {
+        let clients = self.clients_for_worker(worker);
+        for ctx in &clients {
+            let state = GetMiningState(ctx);
+            let Some(mut stratum_diff) = state.stratum_diff() else {
+                // No job sent yet, the first job will start at this difficulty
+                state.set_suggested_diff(diff);
+                continue;
+            };
+            let remote_app = ctx.remote_app.lock().clone();
+            stratum_diff.set_diff_value_for_miner(diff, &remote_app);
+            state.set_stratum_diff(stratum_diff);
+            send_client_diff(ctx, &state, diff, self.difficulty_format, &self.share_handler, false);
+            self.share_handler.set_client_vardiff(ctx, diff);
+        }
+        clients.len()
+    }
+
+    /// Pause or resume this instance: while paused no new jobs go out, and late shares on the jobs miners
+    /// already have are accounted only with account_shares_while_paused. Returns whether it was paused before
+    pub fn set_paused(&self, paused: bool) -> bool {
+        let was_paused = self.share_handler.is_paused();
+        self.share_handler.set_paused(paused);
+        was_paused
+    }
+
+    /// Send a client.show_message to every connection (maintenance notices)
+    pub fn show_message_to_all(&self, message: &str) {
+        let clients: Vec<Arc<StratumContext>> = self.clients.lock().values().cloned().collect();
+        for ctx in clients {
+            let message = message.to_string();
+            tokio::spawn(async move {
+                let _ = ctx.send_notification("client.show_message", vec![serde_json::Value::String(message)]).await;
+            });
+        }
+    }
+
+    /// Bring ks_connections_by_state up to date for this instance
+    pub fn record_connection_states(&self) {
+        let now = Instant::now();
+        let (mut subscribed, mut authorized, mut active) = (0, 0, 0);
+        for ctx in self.clients.lock().values() {
+            let states = ctx.state.connection_states(now, ACTIVE_SUBMIT_WINDOW);
+            subscribed += states.subscribed as usize;
+            authorized += states.authorized as usize;
+            active += states.active as usize;
+        }
+        record_connection_states(&self.instance_id, subscribed, authorized, active);
+    }
+
+    /// Disconnect every miner of this instance (maintenance drain, shutdown). Shares already being validated
+    /// finish normally
+    pub fn disconnect_all(&self, reason: DisconnectReason) -> usize {
+        let clients: Vec<Arc<StratumContext>> = self.clients.lock().values().cloned().collect();
+        for ctx in &clients {
+            tracing::info!("{} {}: disconnecting {}", self.instance_id, reason, ctx.remote_endpoint());
+            ctx.disconnect_for(reason);
+        }
+        clients.len()
     }
 
     /// Send an immediate job to a specific client (for use after authorization)
@@ -179,7 +1145,9 @@ impl ClientHandler {
         let client_clone = Arc::clone(&client);
         let kaspa_api_clone = Arc::clone(&kaspa_api);
         let share_handler = Arc::clone(&self.share_handler);
-        let min_diff = self.min_share_diff;
+        let min_diff = self.start_diff;
+        let log_notify_submit_rtt = self.log_notify_submit_rtt;
+        let difficulty_format = self.difficulty_format;
 
         tokio::spawn(async move {
             // Get per-client mining state from context
@@ -238,7 +1206,7 @@ impl ClientHandler {
                     if e.to_string().contains("Could not decode address") {
                         record_worker_error(&wallet_addr, crate::errors::ErrorShortCode::InvalidAddressFmt.as_str());
                         error!("send_immediate_job: failed fetching block template, malformed address: {}", e);
-                        client_clone.disconnect();
+                        client_clone.disconnect_for(DisconnectReason::BadWallet);
                     } else {
                         record_worker_error(&wallet_addr, crate::errors::ErrorShortCode::FailedBlockFetch.as_str());
                         error!("send_immediate_job: failed fetching block template: {}", e);
@@ -272,8 +1240,9 @@ impl ClientHandler {
                 }
             };
 
-            // Create Job struct with both block and pre_pow_hash
-            let job = Job { block: block.clone(), pre_pow_hash };
+            // Create Job struct with both block and pre_pow_hash, on the connection's timestamp lane if it has one
+            let job = Job::in_nonce_lane(&block, pre_pow_hash, state.nonce_lane());
+            let block = job.block.clone();
 
             // Add job
             let job_id = state.add_job(job);
@@ -287,11 +1256,15 @@ impl ClientHandler {
                 stored_ids
             );
 
+            // Start from the miner's mining.suggest_difficulty hint if one was accepted, then any worker_overrides entry
+            let min_diff = state.starting_diff(min_diff);
+
             // Initialize state if first time
             if !state.is_initialized() {
                 state.set_initialized(true);
                 let use_big_job = BIG_JOB_REGEX.is_match(&remote_app);
                 state.set_use_big_job(use_big_job);
+                share_handler.start_client_vardiff_warmup(&client_clone);
 
                 // Initialize stratum diff
                 use crate::hasher::KaspaDiff;
@@ -314,7 +1287,7 @@ impl ClientHandler {
             // Even if state is already initialized, we need to send difficulty to this specific client
             tracing::debug!("[DIFFICULTY] ===== SENDING DIFFICULTY TO {} =====", client_clone.remote_addr);
             tracing::debug!("[DIFFICULTY] Difficulty value: {}", min_diff);
-            send_client_diff(&client_clone, &state, min_diff);
+            send_client_diff(&client_clone, &state, min_diff, difficulty_format, &share_handler, false);
             share_handler.set_client_vardiff(&client_clone, min_diff);
             tracing::debug!("[DIFFICULTY] ===== DIFFICULTY SENT TO {} =====", client_clone.remote_addr);
 
@@ -365,6 +1338,11 @@ impl ClientHandler {
                 job_params.push(serde_json::Value::Number(block.header.timestamp.into()));
             }
 
+            // A client's first job always replaces whatever it had; the flag is only sent when configured
+            if clean_jobs_policy().notify_flag(None, &block.header).is_some() {
+                job_params.push(serde_json::Value::Bool(true));
+            }
+
             tracing::debug!("[JOB] ===== SENDING MINING.NOTIFY TO {} =====", client_clone.remote_addr);
             tracing::debug!("[JOB] Method: mining.notify");
             tracing::debug!("[JOB] Params count: {}", job_params.len());
@@ -405,6 +1383,10 @@ impl ClientHandler {
                 job_params.len()
             );
 
+            if log_notify_submit_rtt {
+                state.record_notify_sent(job_id);
+            }
+
             // IceRiver expects minimal notification format (method + params only, no id or jsonrpc)
             // Send job ID in mining.notify
             let send_result = if is_iceriver {
@@ -438,7 +1420,9 @@ impl ClientHandler {
                     miner: String::new(),
                     wallet: wallet_addr_str.clone(),
                     ip: format!("{}:{}", client_clone.remote_addr(), client_clone.remote_port()),
+                    port: client_clone.metrics_port.clone(),
                 });
+                record_job_notify(true);
                 tracing::debug!("[JOB] Successfully sent job ID {} to client {}", job_id, client_clone.remote_addr);
                 tracing::debug!("[JOB] ===== JOB SENT SUCCESSFULLY TO {} =====", client_clone.remote_addr);
             }
@@ -446,6 +1430,16 @@ impl ClientHandler {
     }
 
     pub async fn new_block_available<T: KaspaApiTrait + Send + Sync + 'static>(&self, kaspa_api: Arc<T>) {
+        // kaspad keeps rejecting our blocks; new work would only waste miner effort
+        if crate::kaspaapi::submit_breaker_pausing_jobs() {
+            tracing::debug!("[{}] kaspad degraded, holding back new jobs", self.instance_id);
+            return;
+        }
+        if self.share_handler.is_paused() {
+            tracing::debug!("[{}] paused, holding back new jobs", self.instance_id);
+            return;
+        }
+
         // Rate limit templates (250ms minimum between sends)
         {
             let mut last_time = self.last_template_time.lock();
@@ -454,6 +1448,13 @@ impl ClientHandler {
             }
             *last_time = Instant::now();
         }
+        // Idle connections drop out of the active count here, alongside the no-wallet timeout sweep below
+        self.record_connection_states();
+
+        // Template in hand from here; the broadcast is done once every client's mining.notify is written
+        let broadcast_start = Instant::now();
+        let mut sends = Vec::new();
+        let broadcast = Arc::new(JobBroadcast::default());
 
         let clients = {
             let clients_guard = self.clients.lock();
@@ -485,9 +1486,12 @@ impl ClientHandler {
             let client_clone = Arc::clone(&client);
             let kaspa_api_clone = Arc::clone(&kaspa_api);
             let share_handler = Arc::clone(&self.share_handler);
-            let min_diff = self.min_share_diff;
+            let min_diff = self.start_diff;
+            let log_notify_submit_rtt = self.log_notify_submit_rtt;
+            let difficulty_format = self.difficulty_format;
+            let broadcast = Arc::clone(&broadcast);
 
-            tokio::spawn(async move {
+            sends.push(tokio::spawn(async move {
                 // Get per-client mining state from context
                 let state = GetMiningState(&client_clone);
 
@@ -502,7 +1506,7 @@ impl ClientHandler {
                                 let wallet_str = wallet_addr.clone();
                                 record_worker_error(&wallet_str, crate::errors::ErrorShortCode::NoMinerAddress.as_str());
                                 drop(wallet_addr); // Drop before disconnect
-                                client_clone.disconnect();
+                                client_clone.disconnect_for(DisconnectReason::AuthTimeout);
                             }
                         }
                         tracing::debug!(
@@ -520,7 +1524,7 @@ impl ClientHandler {
                     wallet_addr_str
                 );
 
-                // Get block template
+                // Get block template, fetched once per broadcast for everyone mining to the same wallet
                 let (wallet_addr, remote_app, canxium_addr) = {
                     let wallet = client_clone.wallet_addr.lock().clone();
                     let app = client_clone.remote_app.lock().clone();
@@ -528,62 +1532,64 @@ impl ClientHandler {
                     (wallet, app, canx)
                 };
 
-                let template_result = kaspa_api_clone.get_block_template(&wallet_addr, &remote_app, &canxium_addr).await;
-
-                let block = match template_result {
-                    Ok(block) => {
+                let shared_job = match broadcast.job(kaspa_api_clone.as_ref(), &wallet_addr, &remote_app, &canxium_addr).await {
+                    Ok(shared_job) => {
                         tracing::debug!(
-                            "new_block_available: successfully fetched block template for client {}",
+                            "new_block_available: got block template for client {} (shared by its wallet)",
                             client_clone.remote_addr
                         );
-                        block
+                        shared_job
                     }
-                    Err(e) => {
-                        if e.to_string().contains("Could not decode address") {
+                    Err(SharedJobError::Fetch(e)) => {
+                        if e.contains("Could not decode address") {
                             record_worker_error(&wallet_addr, crate::errors::ErrorShortCode::InvalidAddressFmt.as_str());
                             error!("failed fetching new block template from kaspa, malformed address: {}", e);
-                            client_clone.disconnect();
+                            client_clone.disconnect_for(DisconnectReason::BadWallet);
                         } else {
                             record_worker_error(&wallet_addr, crate::errors::ErrorShortCode::FailedBlockFetch.as_str());
                             error!("failed fetching new block template from kaspa: {}", e);
                         }
                         return;
                     }
-                };
-
-                // Calculate target
-                let big_diff = calculate_target(block.header.bits as u64);
-                state.set_big_diff(big_diff);
-
-                // Serialize header - now returns Hash type directly
-                // The "Odd number of digits" error typically indicates a malformed hex string
-                // in one of the hash fields. This can happen if the block data from the node
-                // contains an invalid hash representation.
-                let pre_pow_hash = match serialize_block_header(&block) {
-                    Ok(h) => h,
-                    Err(e) => {
-                        let error_msg = e.to_string();
+                    Err(SharedJobError::Serialize(e)) => {
+                        // Logged with the header details once, when the template was fetched
                         record_worker_error(&wallet_addr, crate::errors::ErrorShortCode::BadDataFromMiner.as_str());
-                        error!("failed to serialize block header: {}", error_msg);
-
-                        // Log block header details for debugging
-                        tracing::debug!("Block header version: {}", block.header.version);
-                        tracing::debug!("Block header timestamp: {}", block.header.timestamp);
-                        tracing::debug!("Block header bits: {}", block.header.bits);
-                        tracing::debug!("Block header daa_score: {}", block.header.daa_score);
-                        tracing::debug!("Block header blue_score: {}", block.header.blue_score);
                         tracing::debug!(
-                            "Block header parents_by_level expanded_len: {}",
-                            block.header.parents_by_level.expanded_len()
+                            "new_block_available: skipping unserializable template for {}: {}",
+                            client_clone.remote_addr,
+                            e
                         );
-
-                        // Skip this block and continue - the next block template should work
                         return;
                     }
                 };
+                let block = &shared_job.block;
+
+                // Same DAA score, parents and transactions as the last job: nothing new to mine, unless the
+                // header time has moved on by a template refresh interval
+                let previous_header = state.get_last_header();
+                if DEDUP_TEMPLATES.load(Ordering::Relaxed) {
+                    if let Some(last) = &previous_header {
+                        if !crate::mining_state::template_changed(last, &block.header)
+                            && !crate::mining_state::timestamp_refresh_due(last, &block.header, crate::kaspaapi::template_refresh())
+                        {
+                            tracing::debug!(
+                                "new_block_available: unchanged template for client {}, skipping notify",
+                                client_clone.remote_addr
+                            );
+                            record_template_dedup_skip();
+                            return;
+                        }
+                    }
+                }
+                state.set_last_header((*block.header).clone());
 
-                // Create Job struct with both block and pre_pow_hash
-                let job = Job { block: block.clone(), pre_pow_hash };
+                // Calculate target
+                let big_diff = calculate_target(block.header.bits as u64);
+                state.set_big_diff(big_diff);
+
+                // Create Job struct with both block and pre_pow_hash, on the connection's timestamp lane if it has one
+                let nonce_lane = state.nonce_lane();
+                let job = Job::in_nonce_lane(block, shared_job.pre_pow_hash, nonce_lane);
 
                 // Add job
                 let job_id = state.add_job(job);
@@ -603,7 +1609,8 @@ impl ClientHandler {
                     let use_big_job = BIG_JOB_REGEX.is_match(&remote_app);
                     state.set_use_big_job(use_big_job);
 
-                    // Send initial difficulty
+                    // Send initial difficulty, preferring the miner's mining.suggest_difficulty hint, then worker_overrides
+                    let min_diff = state.starting_diff(min_diff);
                     use crate::hasher::KaspaDiff;
                     let mut stratum_diff = KaspaDiff::new();
                     // Use miner-specific calculation (IceRiver uses different formula)
@@ -619,108 +1626,63 @@ impl ClientHandler {
                         target_bytes.len(),
                         target_bytes.len() * 8
                     );
-                    send_client_diff(&client_clone, &state, min_diff);
+                    send_client_diff(&client_clone, &state, min_diff, difficulty_format, &share_handler, false);
                     share_handler.set_client_vardiff(&client_clone, min_diff);
-                } else {
-                    // Check for vardiff update
+                    share_handler.start_client_vardiff_warmup(&client_clone);
+                } else if state.static_diff().is_none() {
+                    // Check for vardiff update; a d= pin belongs to this connection alone, other
+                    // connections under the same worker name keep following vardiff
                     let var_diff = share_handler.get_client_vardiff(&client_clone);
                     if let Some(mut stratum_diff) = state.stratum_diff() {
                         let current_diff = stratum_diff.diff_value;
                         if var_diff != current_diff && var_diff != 0.0 {
-                            tracing::debug!("changing diff from {} to {}", current_diff, var_diff);
+                            let strategy = diff_change_strategy();
+                            tracing::debug!("changing diff from {} to {} ({})", current_diff, var_diff, strategy);
+                            record_diff_change(strategy.as_str());
+                            if strategy == DiffChangeStrategy::Reconnect {
+                                // No job for this connection; the miner picks up work on the new one
+                                send_client_reconnect(&client_clone, current_diff, var_diff);
+                                return;
+                            }
                             // Use miner-specific calculation (IceRiver uses different formula)
                             let remote_app = client_clone.remote_app.lock().clone();
                             stratum_diff.set_diff_value_for_miner(var_diff, &remote_app);
                             state.set_stratum_diff(stratum_diff);
-                            send_client_diff(&client_clone, &state, var_diff);
+                            let resend_extranonce = strategy == DiffChangeStrategy::SetExtranonce;
+                            send_client_diff(&client_clone, &state, var_diff, difficulty_format, &share_handler, resend_extranonce);
                             share_handler.start_client_vardiff(&client_clone);
                         }
                     }
                 }
 
-                // Build job params
-                // Check if this is an IceRiver or Bitmain miner - they need single hex string format
+                // Job data layout for this firmware; the encoded data is shared, only the job ID is per client
                 let remote_app = client_clone.remote_app.lock().clone();
-                let remote_app_lower = remote_app.to_lowercase();
-                let is_iceriver = remote_app_lower.contains("iceriver")
-                    || remote_app_lower.contains("icemining")
-                    || remote_app_lower.contains("icm");
-                let is_bitmain = remote_app_lower.contains("godminer")
-                    || remote_app_lower.contains("bitmain")
-                    || remote_app_lower.contains("antminer");
+                let job_format = JobFormat::for_miner(&remote_app, state.use_big_job());
+                let job_data = shared_job.job_data(job_format, nonce_lane);
 
-                tracing::debug!(
-                    "[JOB] new_block_available: client {}, is_iceriver: {}, is_bitmain: {}, use_big_job: {}",
-                    client_clone.remote_addr,
-                    is_iceriver,
-                    is_bitmain,
-                    state.use_big_job()
-                );
-
-                let mut job_params = vec![serde_json::Value::String(job_id.to_string())];
-                if is_iceriver {
-                    // IceRiver format - single hex string (uses Hash::to_string() to match working stratum code)
-                    // This matches Ghostpool and other working implementations
-                    tracing::debug!("[JOB] new_block_available: Generating IceRiver format job params");
-                    let iceriver_params = generate_iceriver_job_params(&pre_pow_hash, block.header.timestamp);
-                    tracing::debug!("[JOB] new_block_available: IceRiver job_data length: {} (expected 80)", iceriver_params.len());
-                    job_params.push(serde_json::Value::String(iceriver_params));
-                } else if state.use_big_job() && !is_iceriver {
-                    // BzMiner format - single hex string (big endian hash)
-                    // Convert Hash to bytes for BzMiner format
-                    tracing::debug!("[JOB] new_block_available: Generating BzMiner format job params");
-                    let header_bytes = pre_pow_hash.as_bytes();
-                    let large_params = generate_large_job_params(&header_bytes, block.header.timestamp);
-                    tracing::debug!("[JOB] new_block_available: BzMiner job_data length: {} (expected 80)", large_params.len());
-                    job_params.push(serde_json::Value::String(large_params));
-                } else {
-                    // Legacy format - array + number (for Bitmain and other miners)
-                    tracing::debug!("[JOB] new_block_available: Using Legacy format (array + timestamp)");
-                    let header_bytes = pre_pow_hash.as_bytes();
-                    let job_header = generate_job_header(&header_bytes);
-                    job_params
-                        .push(serde_json::Value::Array(job_header.iter().map(|&v| serde_json::Value::Number(v.into())).collect()));
-                    job_params.push(serde_json::Value::Number(block.header.timestamp.into()));
-                }
+                // Under the default policy every job is implicitly clean and no flag is appended
+                let clean_jobs_policy = clean_jobs_policy();
+                let clean_jobs = clean_jobs_policy.clean_jobs(previous_header.as_ref(), &block.header);
+                let clean_flag = clean_jobs_policy.notify_flag(previous_header.as_ref(), &block.header);
 
                 // IceRiver expects minimal notification format (method + params only, no id or jsonrpc)
                 // This matches StratumNotification format used by the stratum crate
-                let is_iceriver_client = {
-                    let remote_app = client_clone.remote_app.lock();
-                    remote_app.contains("IceRiver")
-                };
-                let is_bitmain_client = {
-                    let remote_app = client_clone.remote_app.lock();
-                    let remote_app_lower = remote_app.to_lowercase();
-                    remote_app_lower.contains("godminer")
-                        || remote_app_lower.contains("bitmain")
-                        || remote_app_lower.contains("antminer")
-                };
+                let minimal_notify = remote_app.contains("IceRiver");
 
                 tracing::debug!(
-                    "new_block_available: sending job ID {} to client {} (params count: {}, is_iceriver: {}, is_bitmain: {})",
+                    "new_block_available: sending job ID {} to client {} (format: {:?}, minimal notify: {})",
                     job_id,
                     client_clone.remote_addr,
-                    job_params.len(),
-                    is_iceriver_client,
-                    is_bitmain_client
+                    job_format,
+                    minimal_notify
                 );
 
-                // Send job ID in mining.notify
-                // })
-                let send_result = if is_iceriver_client {
-                    // IceRiver expects minimal notification format (method + params only, no id or jsonrpc)
-                    client_clone.send_notification("mining.notify", job_params.clone()).await
-                } else {
-                    // For non-IceRiver, use standard JSON-RPC format with job ID
-                    let notify_event = JsonRpcEvent {
-                        jsonrpc: "2.0".to_string(),
-                        method: "mining.notify".to_string(),
-                        id: Some(serde_json::Value::Number(job_id.into())),
-                        params: job_params.clone(),
-                    };
-                    client_clone.send(notify_event).await
-                };
+                if log_notify_submit_rtt {
+                    state.record_notify_sent(job_id);
+                }
+
+                let send_result =
+                    client_clone.send_raw("mining.notify", &notify_line(job_id, &job_data, clean_flag, minimal_notify)).await;
 
                 if let Err(e) = send_result {
                     if e.to_string().contains("disconnected") {
@@ -744,9 +1706,33 @@ impl ClientHandler {
                         miner: String::new(),
                         wallet: wallet_addr_str.clone(),
                         ip: format!("{}:{}", client_clone.remote_addr(), client_clone.remote_port()),
+                        port: client_clone.metrics_port.clone(),
                     });
+                    record_job_notify(clean_jobs);
                     tracing::debug!("new_block_available: successfully sent job ID {} to client {}", job_id, client_clone.remote_addr);
                 }
+            }));
+        }
+
+        if !sends.is_empty() {
+            let instance_id = self.instance_id.clone();
+            tokio::spawn(async move {
+                let clients = sends.len();
+                for send in sends {
+                    let _ = send.await;
+                }
+                let elapsed = broadcast_start.elapsed();
+                record_notify_broadcast_latency(elapsed);
+                let budget = Duration::from_millis(NOTIFY_LATENCY_BUDGET_MS.load(Ordering::Relaxed));
+                if budget.is_zero() || elapsed <= budget {
+                    return;
+                }
+                if let Some(suppressed) = NOTIFY_LATENCY_ALERT.lock().over_budget(Instant::now()) {
+                    warn!(
+                        "{} new template took {:?} to reach {} miners as mining.notify (budget {:?}, {} more over budget since the last warning)",
+                        instance_id, elapsed, clients, budget, suppressed
+                    );
+                }
             });
         }
 
@@ -776,13 +1762,24 @@ impl ClientHandler {
     }
 }
 
-// Send difficulty update to client
-fn send_client_diff(client: &StratumContext, _state: &MiningState, diff: f64) {
-    tracing::debug!("[DIFFICULTY] Building difficulty message for {}", client.remote_addr);
-
-    // Send diffValue directly as a number
-    let diff_value =
-        serde_json::Value::Number(serde_json::Number::from_f64(diff).unwrap_or_else(|| serde_json::Number::from(diff as u64)));
+// Send difficulty update to client, optionally followed by its unchanged extranonce (diff_change_strategy: set_extranonce)
+fn send_client_diff(
+    client: &StratumContext,
+    _state: &MiningState,
+    diff: f64,
+    format: DifficultyFormat,
+    share_handler: &ShareHandler,
+    resend_extranonce: bool,
+) {
+    tracing::debug!(
+        "[DIFFICULTY] Building difficulty message for {} ({}, diff {} needs {})",
+        client.remote_addr,
+        format,
+        diff,
+        share_handler.hashrate_estimate(diff)
+    );
+
+    let diff_value = difficulty_to_wire(diff, format);
 
     let client_clone = client.clone();
     tokio::spawn(async move {
@@ -804,6 +1801,538 @@ fn send_client_diff(client: &StratumContext, _state: &MiningState, diff: f64) {
             error!("[DIFFICULTY] ERROR: Failed sending difficulty: {}", e);
             return;
         }
+        // on_disconnect may already have removed the gauge; recording now would bring back a stale series
+        if client_clone.connected() {
+            let worker_name = client_clone.worker_name.lock().clone();
+            let wallet_addr = client_clone.wallet_addr.lock().clone();
+            record_worker_difficulty(&worker_name, &wallet_addr, diff);
+        }
         tracing::debug!("[DIFFICULTY] Successfully sent difficulty {} to {}", diff, client_clone.remote_addr);
+
+        if resend_extranonce {
+            if client_clone.extranonce.lock().is_empty() {
+                tracing::debug!("[DIFFICULTY] {} has no extranonce to resend, set_difficulty only", client_clone.remote_addr);
+                return;
+            }
+            tracing::info!(
+                "[DIFFICULTY] {} ({}): resending extranonce after difficulty {}",
+                worker_name,
+                client_clone.remote_addr,
+                diff
+            );
+            if let Err(e) = crate::default_client::send_extranonce(Arc::new(client_clone)).await {
+                error!("[DIFFICULTY] ERROR: Failed resending extranonce: {}", e);
+            }
+        }
+    });
+}
+
+/// Deliver a vardiff retarget as client.reconnect: the difficulty is handed over to the miner's next
+/// authorize and this connection is closed. Its disconnect and the new connection are accounted as usual
+fn send_client_reconnect(client: &Arc<StratumContext>, current_diff: f64, diff: f64) {
+    let worker_name = client.worker_name.lock().clone();
+    tracing::info!(
+        "[DIFFICULTY] {} ({}): diff {} -> {}, sending client.reconnect",
+        worker_name,
+        client.remote_endpoint(),
+        current_diff,
+        diff
+    );
+    hand_over_reconnect_diff(client.remote_addr(), &worker_name, diff, Instant::now());
+
+    let client = Arc::clone(client);
+    tokio::spawn(async move {
+        if let Err(e) = client.send_notification("client.reconnect", vec![]).await {
+            let wallet_addr = client.wallet_addr.lock().clone();
+            record_worker_error(&wallet_addr, crate::errors::ErrorShortCode::FailedSetDiff.as_str());
+            error!("[DIFFICULTY] ERROR: Failed sending client.reconnect: {}", e);
+        }
+        client.disconnect_for(DisconnectReason::Reconnect);
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_jobs_policy() {
+        use kaspa_consensus_core::header::Header;
+        use kaspa_hashes::Hash;
+        let old = Header::from_precomputed_hash(Hash::default(), vec![]);
+        let mut refresh = old.clone();
+        refresh.timestamp += 1000;
+        refresh.hash_merkle_root = Hash::from_bytes([1; 32]);
+        let mut next_block = old.clone();
+        next_block.daa_score += 1;
+
+        assert!(CleanJobsPolicy::Always.clean_jobs(Some(&old), &refresh));
+        assert!(!CleanJobsPolicy::OnNewBlock.clean_jobs(Some(&old), &refresh), "a refresh keeps in-flight work valid");
+        assert!(CleanJobsPolicy::OnNewBlock.clean_jobs(Some(&old), &next_block));
+        assert!(CleanJobsPolicy::OnNewBlock.clean_jobs(None, &refresh), "a client's first job is always clean");
+        assert!(!CleanJobsPolicy::Never.clean_jobs(Some(&old), &next_block));
+
+        // The default leaves the notify params as they always were; configured policies append the flag
+        assert_eq!(CleanJobsPolicy::Always.notify_flag(None, &refresh), None);
+        assert_eq!(CleanJobsPolicy::Always.notify_flag(Some(&old), &next_block), None);
+        assert_eq!(CleanJobsPolicy::OnNewBlock.notify_flag(Some(&old), &refresh), Some(false));
+        assert_eq!(CleanJobsPolicy::Never.notify_flag(None, &refresh), Some(false));
+
+        assert_eq!("on_new_block".parse::<CleanJobsPolicy>(), Ok(CleanJobsPolicy::OnNewBlock));
+        assert!("sometimes".parse::<CleanJobsPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_duplicate_worker_policy() {
+        assert_eq!("kick_old".parse::<DuplicateWorkerPolicy>(), Ok(DuplicateWorkerPolicy::KickOld));
+        assert_eq!(" Suffix ".parse::<DuplicateWorkerPolicy>(), Ok(DuplicateWorkerPolicy::Suffix));
+        assert!("kick".parse::<DuplicateWorkerPolicy>().is_err());
+        assert_eq!(DuplicateWorkerPolicy::Reject.to_string(), "reject");
+
+        let taken = ["rig", "rig_2", "rig_3"];
+        assert_eq!(suffixed_worker_name("rig", |name| taken.contains(&name)), "rig_4");
+        assert_eq!(suffixed_worker_name("other", |name| taken.contains(&name)), "other_2");
+    }
+
+    #[test]
+    fn test_reconnect_diff_handover() {
+        let now = Instant::now();
+        hand_over_reconnect_diff("10.0.0.7", "rig-1", 8192.0, now);
+        assert_eq!(take_reconnect_diff("10.0.0.7", "rig-2", now), None, "other workers on the IP start fresh");
+        assert_eq!(take_reconnect_diff("10.0.0.7", "rig-1", now + Duration::from_secs(3)), Some(8192.0));
+        assert_eq!(take_reconnect_diff("10.0.0.7", "rig-1", now), None, "taken once");
+
+        hand_over_reconnect_diff("10.0.0.8", "rig-1", 4096.0, now);
+        assert_eq!(take_reconnect_diff("10.0.0.8", "rig-1", now + RECONNECT_DIFF_TTL), None, "expired");
+
+        assert_eq!("reconnect".parse::<DiffChangeStrategy>(), Ok(DiffChangeStrategy::Reconnect));
+        assert_eq!(DiffChangeStrategy::SetExtranonce.to_string(), "set_extranonce");
+        assert!("set_target".parse::<DiffChangeStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_notify_line_matches_serialized_event() {
+        let hash = kaspa_hashes::Hash::from_bytes([7; 32]);
+        let timestamp = 1_700_000_000_000u64;
+        for (format, clean_jobs) in [(JobFormat::IceRiver, None), (JobFormat::BigJob, Some(false)), (JobFormat::Legacy, Some(true))] {
+            let mut params = vec![serde_json::Value::String("42".to_string())];
+            match format {
+                JobFormat::IceRiver => params.push(generate_iceriver_job_params(&hash, timestamp).into()),
+                JobFormat::BigJob => params.push(generate_large_job_params(&hash.as_bytes(), timestamp).into()),
+                JobFormat::Legacy => {
+                    params.push(generate_job_header(&hash.as_bytes()).into());
+                    params.push(timestamp.into());
+                }
+            }
+            params.extend(clean_jobs.map(serde_json::Value::Bool));
+            let job_data = encode_job_data(format, &hash, timestamp);
+
+            let event = JsonRpcEvent {
+                jsonrpc: "2.0".to_string(),
+                method: "mining.notify".to_string(),
+                id: Some(serde_json::Value::Number(42.into())),
+                params: params.clone(),
+            };
+            assert_eq!(notify_line(42, &job_data, clean_jobs, false), serde_json::to_string(&event).unwrap(), "{:?}", format);
+
+            let minimal = serde_json::json!({ "method": "mining.notify", "params": params });
+            assert_eq!(notify_line(42, &job_data, clean_jobs, true), serde_json::to_string(&minimal).unwrap(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_notify_latency_alert_throttled() {
+        let mut alert = NotifyLatencyAlert::default();
+        let start = Instant::now();
+        assert_eq!(alert.over_budget(start), Some(0));
+        assert_eq!(alert.over_budget(start + Duration::from_secs(1)), None);
+        assert_eq!(alert.over_budget(start + Duration::from_secs(59)), None);
+        assert_eq!(
+            alert.over_budget(start + NOTIFY_LATENCY_WARN_INTERVAL),
+            Some(2),
+            "throttled broadcasts are reported with the next warning"
+        );
+        assert_eq!(alert.over_budget(start + NOTIFY_LATENCY_WARN_INTERVAL * 2), Some(0));
+    }
+
+    #[test]
+    fn test_extranonce_reuse_delay_respected() {
+        let mut queue = ExtranonceReuseQueue::new(Duration::from_secs(30));
+        let freed_at = Instant::now();
+        queue.release(7, freed_at);
+
+        // Immediately re-allocating must not hand out the just-freed prefix
+        assert_eq!(queue.take_reusable(freed_at, |_| true), None);
+        assert_eq!(queue.take_reusable(freed_at + Duration::from_secs(29), |_| true), None);
+
+        // Once idle for the full delay it becomes reusable, exactly once
+        assert_eq!(queue.take_reusable(freed_at + Duration::from_secs(30), |_| true), Some(7));
+        assert_eq!(queue.take_reusable(freed_at + Duration::from_secs(60), |_| true), None);
+    }
+
+    #[test]
+    fn test_extranonce_reuse_is_fifo() {
+        let mut queue = ExtranonceReuseQueue::new(Duration::ZERO);
+        let now = Instant::now();
+        queue.release(1, now);
+        queue.release(2, now);
+        queue.release(1, now); // double release is ignored
+        assert_eq!(queue.take_reusable(now, |_| true), Some(1));
+        assert_eq!(queue.take_reusable(now, |_| true), Some(2));
+        assert_eq!(queue.take_reusable(now, |_| true), None);
+    }
+
+    #[test]
+    fn test_nonce_lanes_lowest_free_first() {
+        let mut lanes = NonceLanes::default();
+        assert_eq!(lanes.allocate(), Some(1), "lane 0 belongs to connections with an extranonce prefix");
+        assert_eq!(lanes.allocate(), Some(2));
+        assert_eq!(lanes.allocate(), Some(3));
+        lanes.release(2);
+        assert_eq!(lanes.allocate(), Some(2));
+        assert_eq!(lanes.len(), 3);
+
+        while lanes.allocate().is_some() {}
+        assert_eq!(lanes.len() as u64, MAX_NONCE_LANES);
+        assert_eq!(lanes.allocate(), None, "exhausted lanes fall back to the shared space");
+        lanes.release(500);
+        assert_eq!(lanes.allocate(), Some(500));
+    }
+
+    #[test]
+    fn test_extranonce_exhaustion_refuses_without_grow() {
+        let mut allocator = ExtranonceAllocator::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let mut seen = HashSet::new();
+        for _ in 0..256 {
+            assert!(seen.insert(allocator.allocate(now, false, 1).unwrap()), "prefixes must be unique");
+        }
+        assert_eq!(allocator.utilization(), 1.0);
+        assert!(allocator.allocate(now, false, 1).is_err());
+
+        // A freed prefix only comes back after the reuse delay
+        allocator.release((1, 7), now);
+        assert!(allocator.allocate(now, false, 1).is_err());
+        assert_eq!(allocator.allocate(now + Duration::from_secs(60), false, 1), Ok((1, 7)));
+    }
+
+    async fn subscribed_client(
+        handler: &ClientHandler,
+        listener: &tokio::net::TcpListener,
+    ) -> (Arc<StratumContext>, tokio::net::TcpStream) {
+        let (ctx, client) = crate::mock_connection::tcp_connection(listener).await;
+        handler.on_connect(Arc::clone(&ctx));
+        handler.assign_extranonce_for_miner(&ctx, "IceRiverMiner-v1.1", None).unwrap();
+        (ctx, client)
+    }
+
+    fn assert_unique_extranonces(contexts: &[Arc<StratumContext>]) {
+        let held: HashSet<String> = contexts.iter().map(|c| c.extranonce.lock().clone()).collect();
+        assert_eq!(held.len(), contexts.len(), "extranonce handed out twice");
+    }
+
+    #[tokio::test]
+    async fn test_extranonce_unique_across_many_connections() {
+        let handler = ClientHandler::new(
+            Arc::new(ShareHandler::new("Instance 1".into(), false, crate::share_handler::VarDiffMode::Window, 20, None)),
+            4096.0,
+            1,
+            "Instance 1".into(),
+            false,
+            DifficultyFormat::default(),
+            Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            4096.0,
+            AuthorizeConfig::default(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut contexts = Vec::new();
+        let mut sockets = Vec::new();
+        for _ in 0..200 {
+            let (ctx, client) = subscribed_client(&handler, &listener).await;
+            contexts.push(ctx);
+            sockets.push(client);
+        }
+        assert_unique_extranonces(&contexts);
+
+        // Freed prefixes come back, never one still held
+        for ctx in contexts.drain(..100) {
+            handler.release_extranonce(&ctx);
+            handler.clients.lock().retain(|_, c| !Arc::ptr_eq(c, &ctx));
+        }
+        for _ in 0..50 {
+            let (ctx, client) = subscribed_client(&handler, &listener).await;
+            contexts.push(ctx);
+            sockets.push(client);
+        }
+        assert_unique_extranonces(&contexts);
+
+        let held = contexts[0].extranonce.lock().clone();
+        assert_eq!(handler.extranonce_holder(&held, &contexts[1]), Some(contexts[0].remote_endpoint()), "the guard sees overlaps");
+    }
+
+    #[tokio::test]
+    async fn test_claim_worker_name_sets_the_name() {
+        let handler = ClientHandler::new(
+            Arc::new(ShareHandler::new("Instance 1".into(), false, crate::share_handler::VarDiffMode::Window, 20, None)),
+            4096.0,
+            1,
+            "Instance 1".into(),
+            false,
+            DifficultyFormat::default(),
+            Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            4096.0,
+            AuthorizeConfig { duplicate_worker_policy: DuplicateWorkerPolicy::Suffix, ..Default::default() },
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (first, _first_socket) = subscribed_client(&handler, &listener).await;
+        let (second, _second_socket) = subscribed_client(&handler, &listener).await;
+
+        // The second claim sees the first without anything else setting its name in between
+        assert_eq!(handler.claim_worker_name(&first, "kaspa:qz0000", "rig"), Ok("rig".to_string()));
+        assert_eq!(handler.claim_worker_name(&second, "kaspa:qz0000", "rig"), Ok("rig_2".to_string()));
+        assert_eq!((first.worker_name.lock().clone(), second.worker_name.lock().clone()), ("rig".into(), "rig_2".into()));
+        assert_eq!(*second.wallet_addr.lock(), "kaspa:qz0000");
+    }
+
+    #[tokio::test]
+    async fn test_shared_worker_series_outlive_one_connection() {
+        init_metrics();
+        let handler = ClientHandler::new(
+            Arc::new(ShareHandler::new("Instance 1".into(), false, crate::share_handler::VarDiffMode::Window, 20, None)),
+            4096.0,
+            1,
+            "Instance 1".into(),
+            false,
+            DifficultyFormat::default(),
+            Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            4096.0,
+            AuthorizeConfig { duplicate_worker_policy: DuplicateWorkerPolicy::Allow, ..Default::default() },
+        );
+        let wallet = "kaspa:qzsharedseries";
+        let difficulty_series = || {
+            prometheus::gather()
+                .iter()
+                .filter(|family| family.get_name() == "ks_worker_difficulty")
+                .flat_map(|family| family.get_metric().iter())
+                .filter(|metric| metric.get_label().iter().any(|label| label.get_value() == wallet))
+                .count()
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (first, _first_socket) = subscribed_client(&handler, &listener).await;
+        let (second, _second_socket) = subscribed_client(&handler, &listener).await;
+        assert_eq!(handler.claim_worker_name(&first, wallet, "rig"), Ok("rig".to_string()));
+        assert_eq!(handler.claim_worker_name(&second, wallet, "rig"), Ok("rig".to_string()), "allow keeps the name");
+        record_worker_difficulty("rig", wallet, 4096.0);
+
+        // Both connections feed the same wallet.worker series, so the first to leave must not drop it
+        handler.on_disconnect(&first);
+        assert_eq!(difficulty_series(), 1);
+        handler.on_disconnect(&second);
+        assert_eq!(difficulty_series(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_show_worker_reads_without_creating_stats() {
+        let share_handler =
+            Arc::new(ShareHandler::new("Instance 1".into(), false, crate::share_handler::VarDiffMode::Window, 20, None));
+        let handler = ClientHandler::new(
+            Arc::clone(&share_handler),
+            4096.0,
+            1,
+            "Instance 1".into(),
+            false,
+            DifficultyFormat::default(),
+            Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            4096.0,
+            AuthorizeConfig::default(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, _socket) = subscribed_client(&handler, &listener).await;
+        assert_eq!(handler.claim_worker_name(&ctx, "kaspa:qzshow", "rig"), Ok("rig".to_string()));
+        assert!(handler.show_worker("other").is_empty());
+
+        let shown = handler.show_worker("rig");
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0]["wallet"], "kaspa:qzshow");
+        assert_eq!(shown[0]["address"], ctx.remote_endpoint());
+        assert_eq!(shown[0]["extranonce"], ctx.extranonce.lock().clone());
+        assert!(shown[0]["accepted"].is_null(), "no share yet, and showing doesn't create stats");
+        assert!(share_handler.peek_stats(&ctx).is_none());
+
+        // The stats a share creates are the ones shown, found under the same key
+        *share_handler.get_create_stats(&ctx).shares_found.lock() = 3;
+        assert_eq!(handler.show_worker("rig")[0]["accepted"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_payout_split_series_removed_on_disconnect() {
+        init_metrics();
+        let handler = ClientHandler::new(
+            Arc::new(ShareHandler::new("Instance 1".into(), false, crate::share_handler::VarDiffMode::Window, 20, None)),
+            4096.0,
+            1,
+            "Instance 1".into(),
+            false,
+            DifficultyFormat::default(),
+            Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            4096.0,
+            AuthorizeConfig::default(),
+        );
+        let wallet = "kaspa:qzpayoutsplit";
+        let split_series = || {
+            prometheus::gather()
+                .iter()
+                .filter(|family| family.get_name() == "ks_worker_payout_split_percent")
+                .flat_map(|family| family.get_metric().iter())
+                .filter(|metric| metric.get_label().iter().any(|label| label.get_value() == wallet))
+                .count()
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, _socket) = subscribed_client(&handler, &listener).await;
+        assert_eq!(handler.claim_worker_name(&ctx, wallet, "rig"), Ok("rig".to_string()));
+        let split = crate::default_client::PayoutSplit { address: "kaspa:qzdonate".into(), percent: 2.0 };
+        record_payout_split("rig", wallet, &split.address, split.percent);
+        *ctx.payout_split.lock() = Some(split);
+        assert_eq!(split_series(), 1);
+
+        handler.on_disconnect(&ctx);
+        assert_eq!(split_series(), 0, "a departed worker exports no split");
+    }
+
+    /// kaspad whose templates stop parsing once `readable` is cleared, behind the bridge's last-good fallback
+    #[derive(Default)]
+    struct UnreadableKaspad {
+        readable: AtomicBool,
+        templates: crate::kaspaapi::LastGoodTemplates,
+    }
+
+    #[async_trait::async_trait]
+    impl KaspaApiTrait for UnreadableKaspad {
+        async fn get_block_template(
+            &self,
+            wallet: &str,
+            _: &str,
+            _: &str,
+        ) -> Result<kaspa_consensus_core::block::Block, Box<dyn std::error::Error + Send + Sync>> {
+            use crate::kaspaapi::TemplateParse;
+            use kaspa_consensus_core::{block::Block, header::Header};
+            let converted = if self.readable.load(Ordering::Relaxed) {
+                Ok(Block::new(Header::from_precomputed_hash(kaspa_hashes::Hash::from_bytes([7; 32]), vec![]), vec![]))
+            } else {
+                Err("RpcError: unexpected field in block template")
+            };
+            match self.templates.accept(wallet, converted, 0, 1) {
+                TemplateParse::Parsed(block) => Ok(Arc::unwrap_or_clone(block)),
+                TemplateParse::Unusable(result) => result.map(Arc::unwrap_or_clone).map_err(Into::into),
+                TemplateParse::Retry(e) => Err(e.into()),
+            }
+        }
+
+        async fn submit_block(
+            &self,
+            _: kaspa_consensus_core::block::Block,
+        ) -> Result<kaspa_rpc_core::SubmitBlockResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Err("no kaspad in tests".into())
+        }
+
+        async fn get_balances_by_addresses(
+            &self,
+            _: &[String],
+        ) -> Result<Vec<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jobs_keep_coming_while_templates_are_unreadable() {
+        let handler = ClientHandler::new(
+            Arc::new(ShareHandler::new("Instance 1".into(), false, crate::share_handler::VarDiffMode::Window, 20, None)),
+            4096.0,
+            1,
+            "Instance 1".into(),
+            false,
+            DifficultyFormat::default(),
+            Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            4096.0,
+            AuthorizeConfig::default(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, _socket) = subscribed_client(&handler, &listener).await;
+        *ctx.wallet_addr.lock() = "kaspa:qz0000".to_string();
+        let kaspad = Arc::new(UnreadableKaspad::default());
+        let state = GetMiningState(&ctx);
+
+        kaspad.readable.store(true, Ordering::Relaxed);
+        for round in 1..=3 {
+            // new_block_available sends at most one template per 250ms
+            tokio::time::sleep(Duration::from_millis(260)).await;
+            handler.new_block_available(Arc::clone(&kaspad)).await;
+            // Each client's job goes out on its own task
+            for _ in 0..100 {
+                if state.current_job_counter() == round {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(state.current_job_counter(), round, "a job went out on round {}", round);
+            // kaspad goes bad after the first template
+            kaspad.readable.store(false, Ordering::Relaxed);
+        }
+        assert!(ctx.connected(), "the miner is kept on the last good template, not dropped");
+    }
+
+    #[test]
+    fn test_extranonce_grow_never_overlaps_shorter_prefixes() {
+        let mut allocator = ExtranonceAllocator::new(Duration::ZERO);
+        let now = Instant::now();
+        let short: Vec<_> = (0..231).map(|_| allocator.allocate(now, true, 1).unwrap()).collect();
+        assert!(short.iter().all(|(bytes, _)| *bytes == 1));
+
+        // Past 90% the next prefixes are two bytes wide, carved only out of free one-byte prefixes
+        let (bytes, value) = allocator.allocate(now, true, 1).unwrap();
+        assert_eq!(bytes, 2);
+        assert!(!short.contains(&(1, value >> 8)));
+        assert!(allocator.utilization() < 1.0);
+    }
+
+    #[test]
+    fn test_extranonce_mixed_sizes_never_overlap() {
+        let mut allocator = ExtranonceAllocator::new(Duration::ZERO);
+        let now = Instant::now();
+        let wide = allocator.allocate(now, false, 2).unwrap();
+        assert_eq!(wide, (2, 0));
+        // The one-byte prefix 0x00 would own the two-byte prefix 0x0000 in use
+        assert_eq!(allocator.allocate(now, false, 1), Ok((1, 1)));
+        assert_eq!(allocator.allocate(now, false, 2), Ok((2, 1)));
+        for _ in 0..254 {
+            allocator.allocate(now, false, 2).unwrap();
+        }
+        // Two-byte prefixes under the one-byte 0x01 in use are skipped
+        assert_eq!(allocator.allocate(now, false, 2), Ok((2, 0x0200)));
+    }
+
+    #[test]
+    fn test_extranonce_detection() {
+        let detection = ExtranonceDetection::default();
+        assert_eq!(detection.detect("GodMiner/2.0", None, 2), (0, "agent"));
+        assert_eq!(detection.detect("IceRiverMiner-v1.1", None, 2), (2, "agent"));
+        assert_eq!(detection.detect("lolMiner 1.88", None, 1), (1, "fallback"));
+        assert_eq!(detection.detect("lolMiner 1.88", Some(3), 2), (2, "fallback"), "hints are off by default");
+
+        let detection = ExtranonceDetection { preferred: Some(1), min: 1, max: 2, honor_hints: true };
+        assert_eq!(detection.detect("lolMiner 1.88", Some(3), 2), (2, "hint"), "clamped to max");
+        assert_eq!(detection.detect("BzMiner", None, 2), (1, "agent"));
+        assert_eq!(detection.detect("Antminer KS5", None, 2), (0, "agent"), "Bitmain's 0 is not clamped to min");
+        assert_eq!(detection.detect("Antminer KS5", Some(0), 2), (0, "hint"));
+        assert_eq!(detection.detect("lolMiner 1.88", Some(0), 2), (1, "hint"), "no hinted 0 for other miners");
+
+        let detection = ExtranonceDetection { preferred: None, min: 0, max: 3, honor_hints: true };
+        assert_eq!(detection.detect("lolMiner 1.88", Some(0), 2), (1, "hint"), "floored at 1 even with min 0");
+    }
+}
@@ -1,8 +1,10 @@
 use crate::jsonrpc_event::{JsonRpcEvent, JsonRpcResponse};
+use crate::kaspaapi::KaspaNetwork;
 use crate::stratum_context::StratumContext;
 use kaspa_addresses::Address;
 use regex::Regex;
 use serde_json::Value;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, LazyLock};
 
 /// Regex for matching miners that use big job format
@@ -12,6 +14,93 @@ static BIG_JOB_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r".*(BzMiner
 /// Regex for matching wallet addresses
 static WALLET_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"kaspa(test|dev)?:([a-z0-9]{61}|[a-z0-9]{63})").unwrap());
 
+/// Default cap on worker names, in characters
+pub const DEFAULT_MAX_WORKER_NAME_LEN: usize = 64;
+
+/// Longest wallet string accepted on authorize; real addresses are well under this
+const MAX_WALLET_LEN: usize = 128;
+
+static MAX_WORKER_NAME_LEN: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_WORKER_NAME_LEN);
+
+/// Difficulty settings for the workers a worker_overrides entry matches; unset fields keep the instance's
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerOverride {
+    pub pattern: String,             // Wallet or worker name, `*` matches any run of characters
+    pub min_share_diff: Option<f64>, // Floor for the starting difficulty and for vardiff
+    pub start_diff: Option<f64>,
+    pub var_diff: Option<bool>,
+}
+
+/// What an instance's authorize applies to every miner; each listener has its own
+#[derive(Debug, Clone, Default)]
+pub struct AuthorizeConfig {
+    pub fixed_wallet: Option<String>, // Every connection mines here, usernames only name workers (checked with normalize_wallet)
+    pub worker_overrides: Vec<WorkerOverride>, // Per wallet/worker difficulty settings
+    pub network: Option<KaspaNetwork>, // Wallets must be for this network (None = any)
+    pub duplicate_worker_policy: crate::client_handler::DuplicateWorkerPolicy, // A wallet.worker authorizing twice
+}
+
+impl AuthorizeConfig {
+    /// Whether any override turns vardiff on, so instances without var_diff still need the vardiff thread
+    pub fn overrides_enable_var_diff(&self) -> bool {
+        self.worker_overrides.iter().any(|o| o.var_diff == Some(true))
+    }
+}
+
+/// Default first_share_warn_secs: long enough for a slow miner at its starting difficulty
+pub const DEFAULT_FIRST_SHARE_WARN_SECS: u64 = 600;
+
+static FIRST_SHARE_WARN_SECS: AtomicU64 = AtomicU64::new(DEFAULT_FIRST_SHARE_WARN_SECS);
+
+/// Warn about connections still without an accepted share this long after authorizing (process-wide, zero = off)
+pub fn set_first_share_warn(after: std::time::Duration) {
+    FIRST_SHARE_WARN_SECS.store(after.as_secs(), Ordering::Relaxed);
+}
+
+/// Set the worker name length cap applied on authorize (process-wide)
+pub fn set_max_worker_name_len(max: usize) {
+    MAX_WORKER_NAME_LEN.store(max.max(1), Ordering::Relaxed);
+}
+
+/// First override whose pattern matches the wallet or the worker name (case-insensitive)
+pub fn match_worker_override<'a>(overrides: &'a [WorkerOverride], wallet: &str, worker: &str) -> Option<&'a WorkerOverride> {
+    overrides.iter().find(|o| glob_matches(&o.pattern, wallet) || (!worker.is_empty() && glob_matches(&o.pattern, worker)))
+}
+
+/// `*` wildcard match over the whole of `value`, ignoring ASCII case
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let value = value.to_ascii_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard: the whole value has to match
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Worker name as used in logs, metrics and stats: control characters (newlines, escapes) stripped,
+/// surrounding whitespace trimmed, capped at `max_len` characters. A name made only of control characters is rejected
+pub fn sanitize_worker_name(raw: &str, max_len: usize) -> Result<String, String> {
+    let cleaned: String = raw.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() && raw.chars().any(char::is_control) {
+        return Err(format!("worker name {:?} has no printable characters", raw.chars().take(32).collect::<String>()));
+    }
+    Ok(cleaned.chars().take(max_len).collect())
+}
+
 /// Default logger configuration
 pub fn default_logger() {
     // Logger is configured via tracing-subscriber in main
@@ -42,6 +131,26 @@ pub fn default_handlers() -> std::collections::HashMap<String, crate::stratum_li
         }) as crate::stratum_listener::EventHandler,
     );
 
+    handlers.insert(
+        "mining.ping".to_string(),
+        Arc::new(|ctx: Arc<StratumContext>, event: JsonRpcEvent| {
+            let ctx = ctx.clone();
+            let event = event.clone();
+            Box::pin(async move { handle_ping(ctx, event).await })
+                as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send>>
+        }) as crate::stratum_listener::EventHandler,
+    );
+
+    handlers.insert(
+        "mining.configure".to_string(),
+        Arc::new(|ctx: Arc<StratumContext>, event: JsonRpcEvent| {
+            let ctx = ctx.clone();
+            let event = event.clone();
+            Box::pin(async move { handle_configure(ctx, event).await })
+                as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send>>
+        }) as crate::stratum_listener::EventHandler,
+    );
+
     handlers.insert(
         "mining.authorize".to_string(),
         Arc::new(|ctx: Arc<StratumContext>, event: JsonRpcEvent| {
@@ -87,10 +196,18 @@ pub async fn handle_subscribe(
     }
 
     let remote_app = ctx.remote_app.lock().clone();
+    tracing::info!("[SUBSCRIBE] {} user-agent: '{}'", ctx.remote_endpoint(), remote_app.escape_debug());
+    crate::prom::record_subscribe_user_agent(&remote_app);
 
     // Auto-detect miner type and assign appropriate extranonce
-    if let Some(handler) = client_handler {
-        handler.assign_extranonce_for_miner(&ctx, &remote_app);
+    if let Some(handler) = &client_handler {
+        let size_hint = subscribe_extranonce_hint(&event.params);
+        if let Err(e) = handler.assign_extranonce_for_miner(&ctx, &remote_app, size_hint) {
+            // A duplicate prefix would have two miners hashing the same nonces
+            let _ = ctx.reply(JsonRpcResponse::error(event.id.clone(), 20, &format!("No free extranonce: {}", e), None)).await;
+            ctx.disconnect_for(crate::stratum_context::DisconnectReason::NoExtranonce);
+            return Ok(());
+        }
     }
 
     let extranonce = ctx.extranonce.lock().clone();
@@ -141,18 +258,24 @@ pub async fn handle_subscribe(
     tracing::debug!("[SUBSCRIBE] Sending subscribe response to {}: {}", ctx.remote_addr, response_json);
 
     ctx.reply(response).await.map_err(|e| format!("failed to send response to subscribe: {}", e))?;
+    ctx.state.mark_subscribed();
+    if let Some(handler) = &client_handler {
+        handler.record_connection_states();
+    }
 
     tracing::debug!("[SUBSCRIBE] ===== SUBSCRIBE COMPLETE FOR {} =====", ctx.remote_addr);
     Ok(())
 }
 
 /// Handle extranonce subscribe request
+/// Only miners that send this get in-band extranonce changes; everyone else keeps theirs until reconnect
 async fn handle_extranonce_subscribe(
     ctx: Arc<StratumContext>,
     event: JsonRpcEvent,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     tracing::debug!("[EXTRANONCE_SUBSCRIBE] ===== EXTRANONCE SUBSCRIBE FROM {} =====", ctx.remote_addr);
     tracing::debug!("[EXTRANONCE_SUBSCRIBE] Event ID: {:?}", event.id);
+    ctx.set_extranonce_updates(true);
 
     let response = JsonRpcResponse::new(&event, Some(Value::Bool(true)), None);
     let response_json = serde_json::to_string(&response).unwrap_or_else(|_| "failed".to_string());
@@ -164,6 +287,96 @@ async fn handle_extranonce_subscribe(
     Ok(())
 }
 
+/// Extranonce size a miner advertises in mining.subscribe: the first whole number after the user-agent,
+/// e.g. `["MyMiner/1.0", "EthereumStratum/1.0.0", 2]`. Only sizes the bridge can hand out (0-3) count
+pub fn subscribe_extranonce_hint(params: &[Value]) -> Option<u8> {
+    params.iter().skip(1).find_map(|p| p.as_u64()).filter(|size| *size <= 3).map(|size| size as u8)
+}
+
+/// mining.configure extensions the bridge can honor
+/// version-rolling is always declined: kHeavyHash miners roll the nonce, Kaspa headers have no version bits to hand out
+pub const SUPPORTED_CONFIGURE_EXTENSIONS: &[&str] = &["subscribe-extranonce"];
+
+/// Result object for mining.configure: every requested extension mapped to whether it is enabled
+pub fn negotiate_extensions(params: &[Value]) -> serde_json::Map<String, Value> {
+    let requested = params.first().and_then(|p| p.as_array()).map(|a| a.as_slice()).unwrap_or_default();
+    requested
+        .iter()
+        .filter_map(|ext| ext.as_str())
+        .map(|ext| (ext.to_string(), Value::Bool(SUPPORTED_CONFIGURE_EXTENSIONS.contains(&ext))))
+        .collect()
+}
+
+/// Handle mining.ping, a miner-side heartbeat
+async fn handle_ping(ctx: Arc<StratumContext>, event: JsonRpcEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let response = JsonRpcResponse::new(&event, Some(Value::String("pong".to_string())), None);
+    ctx.reply(response).await.map_err(|e| format!("failed to send response to ping: {}", e))?;
+    Ok(())
+}
+
+/// Handle mining.configure (BIP310 extension negotiation)
+/// Unsupported extensions are answered with false instead of an error, which some firmware treats as fatal
+async fn handle_configure(ctx: Arc<StratumContext>, event: JsonRpcEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let result = negotiate_extensions(&event.params);
+    tracing::debug!("[CONFIGURE] {} requested {:?}, negotiated {:?}", ctx.remote_addr, event.params.first(), result);
+    if result.get("subscribe-extranonce") == Some(&Value::Bool(true)) {
+        ctx.set_extranonce_updates(true);
+    }
+    *ctx.extensions.lock() = result.clone();
+
+    let response = JsonRpcResponse::new(&event, Some(Value::Object(result)), None);
+    ctx.reply(response).await.map_err(|e| format!("failed to send response to configure: {}", e))?;
+    Ok(())
+}
+
+/// Handle mining.suggest_difficulty
+/// An accepted suggestion becomes the client's starting vardiff difficulty instead of min_share_diff
+pub async fn handle_suggest_difficulty(
+    ctx: Arc<StratumContext>,
+    event: JsonRpcEvent,
+    share_handler: Arc<crate::share_handler::ShareHandler>,
+    policy: crate::share_handler::SuggestDifficultyPolicy,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let suggested = event.params.first().and_then(|p| p.as_f64().or_else(|| p.as_str().and_then(|s| s.trim().parse().ok())));
+
+    match suggested.and_then(|d| policy.resolve(d)) {
+        Some(_) if ctx.state.static_diff().is_some() => {
+            tracing::debug!(
+                "[DIFFICULTY] ignoring mining.suggest_difficulty {:?} from {}, its difficulty is pinned with d=",
+                event.params.first(),
+                ctx.remote_addr
+            );
+        }
+        Some(diff) => {
+            let state = crate::mining_state::GetMiningState(&ctx);
+            state.set_suggested_diff(diff);
+            if state.is_initialized() {
+                // The first job already went out, vardiff sends the new difficulty with the next one
+                share_handler.set_client_vardiff(&ctx, diff);
+            }
+            tracing::debug!(
+                "[DIFFICULTY] {} suggested difficulty {}, starting at {}",
+                ctx.remote_addr,
+                suggested.unwrap_or_default(),
+                diff
+            );
+        }
+        None => {
+            tracing::debug!(
+                "[DIFFICULTY] ignoring mining.suggest_difficulty {:?} from {} (outside var_diff_min/var_diff_max)",
+                event.params.first(),
+                ctx.remote_addr
+            );
+        }
+    }
+
+    if event.id.is_some() {
+        let response = JsonRpcResponse::new(&event, Some(Value::Bool(true)), None);
+        ctx.reply(response).await.map_err(|e| format!("failed to send response to suggest difficulty: {}", e))?;
+    }
+    Ok(())
+}
+
 /// Handle authorize request (v0.1 canxium-patch)
 /// If client_handler and kaspa_api are provided, sends immediate job after authorization
 pub async fn handle_authorize(
@@ -186,38 +399,131 @@ pub async fn handle_authorize(
 
     let address_str = address_value.as_str().ok_or("expected param[0] to be address string")?;
 
-    tracing::debug!("[AUTHORIZE] Address string from params[0]: '{}'", address_str);
+    tracing::debug!("[AUTHORIZE] Address string from params[0]: {:?}", address_str);
 
-    let parts: Vec<&str> = address_str.split('.').collect();
+    // Split with decimal extension values (d=0.5) kept whole, so their fraction is never read as a field
+    let fields = join_extension_fields(address_str.split('.'));
+    let parts: Vec<&str> = fields.iter().map(String::as_str).collect();
     tracing::debug!("[AUTHORIZE] Split address into {} parts: {:?}", parts.len(), parts);
+    // key=value fields are worker extensions, so wallet.d=0.5 has no worker name
+    let worker_part = parts.get(1).copied().filter(|field| !field.contains('='));
 
     let mut address = parts[0].to_string();
     let mut worker_name = String::new();
     let mut canxium_address = String::new();
+    let payout_split;
+    // Listeners without a client handler (tests, the default handlers) apply no per-instance settings
+    let default_config = AuthorizeConfig::default();
+    let config = client_handler.as_ref().map_or(&default_config, |handler| handler.authorize_config());
+    let extensions = parse_worker_extensions(parts.get(1..).unwrap_or_default(), config.network);
+
+    if let Some(fixed) = &config.fixed_wallet {
+        // The username only names the worker; a wallet.worker username keeps just its worker part
+        let label = match worker_part {
+            Some(worker) if clean_wallet(parts[0], config.network).is_ok() => worker,
+            _ => address_str,
+        };
+        worker_name = sanitize_worker_name(label, MAX_WORKER_NAME_LEN.load(Ordering::Relaxed))
+            .map_err(|e| format!("{} from {}", e, ctx.remote_addr))?;
+        tracing::debug!("[AUTHORIZE] fixed_wallet set, worker '{}' mines to {}", worker_name, fixed);
+        address = fixed.clone();
+        payout_split = None;
+    } else {
+        if address.len() > MAX_WALLET_LEN || address.chars().any(char::is_control) {
+            return Err(format!("invalid wallet {:?} from {}", address.chars().take(32).collect::<String>(), ctx.remote_addr).into());
+        }
 
-    if parts.len() >= 2 {
-        worker_name = parts[1].to_string();
-        tracing::debug!("[AUTHORIZE] Extracted worker name: '{}'", worker_name);
-        if parts.len() >= 3 {
-            canxium_address = process_canxium_address(parts[2]);
-            tracing::debug!("[AUTHORIZE] Extracted canxium address: '{}'", canxium_address);
+        if let Some(worker) = worker_part {
+            worker_name = sanitize_worker_name(worker, MAX_WORKER_NAME_LEN.load(Ordering::Relaxed))
+                .map_err(|e| format!("{} from {}", e, ctx.remote_addr))?;
+            tracing::debug!("[AUTHORIZE] Extracted worker name: '{}'", worker_name);
+            // key=value fields are worker extensions, not a canxium address
+            if parts.len() >= 3 && !parts[2].contains('=') {
+                canxium_address = process_canxium_address(parts[2]);
+                tracing::debug!("[AUTHORIZE] Extracted canxium address: '{}'", canxium_address);
+            }
         }
-    }
+        payout_split = extensions.payout_split.clone();
 
-    // Clean and validate wallet address
-    tracing::debug!("[AUTHORIZE] Cleaning wallet address: '{}'", address);
-    address = clean_wallet(&address)?;
-    tracing::debug!("[AUTHORIZE] Cleaned address: '{}'", address);
+        // Clean and validate wallet address
+        tracing::debug!("[AUTHORIZE] Cleaning wallet address: '{}'", address);
+        address = clean_wallet(&address, config.network)?;
+        tracing::debug!("[AUTHORIZE] Cleaned address: '{}'", address);
+    }
 
     tracing::debug!("[AUTHORIZE] Final parsed - address: '{}', worker: '{}', canxium: '{}'", address, worker_name, canxium_address);
 
-    *ctx.wallet_addr.lock() = address.clone();
-    *ctx.worker_name.lock() = worker_name.clone();
+    // Pinned to a network: a wallet for another one could never be paid, refuse it outright
+    check_wallet_network(&address, config.network).map_err(|e| format!("{} from {}", e, ctx.remote_addr))?;
+
+    if let Some(expected) = wallet_network_mismatch(&address) {
+        tracing::warn!(
+            "!!! {} authorized with wallet {} but kaspad is on a {}: network, block templates for it will be rejected !!!",
+            worker_name,
+            address,
+            expected
+        );
+    }
+
+    // A wallet.worker that is already connected gets what duplicate_worker_policy says
+    match &client_handler {
+        Some(handler) => match handler.claim_worker_name(&ctx, &address, &worker_name) {
+            Ok(name) => worker_name = name,
+            Err(e) => {
+                let _ = ctx.reply(JsonRpcResponse::error(event.id.clone(), 24, &format!("Unauthorized worker: {}", e), None)).await;
+                ctx.disconnect_for(crate::stratum_context::DisconnectReason::DuplicateWorker);
+                return Ok(());
+            }
+        },
+        None => {
+            *ctx.wallet_addr.lock() = address.clone();
+            *ctx.worker_name.lock() = worker_name.clone();
+        }
+    }
+
+    if let Some(o) = match_worker_override(&config.worker_overrides, &address, &worker_name) {
+        tracing::debug!("[AUTHORIZE] {} ({}) matches worker_overrides entry '{}'", worker_name, address, o.pattern);
+        ctx.state.set_worker_override(o.clone());
+    }
+
+    // Back from a client.reconnect retarget (diff_change_strategy: reconnect): start at the difficulty it was sent away with
+    if let Some(diff) = crate::client_handler::take_reconnect_diff(&ctx.remote_addr, &worker_name, std::time::Instant::now()) {
+        tracing::debug!("[AUTHORIZE] {} reconnected for difficulty {}", worker_name, diff);
+        ctx.state.set_suggested_diff(diff);
+    }
 
     if !canxium_address.is_empty() {
         *ctx.canxium_addr.lock() = canxium_address.clone();
     }
 
+    if let Some(split) = &payout_split {
+        tracing::info!(
+            "[AUTHORIZE] {} ({}) requested payout split: {}% to {} (recorded for external payout tooling)",
+            worker_name,
+            address,
+            split.percent,
+            split.address
+        );
+        crate::prom::record_payout_split(&worker_name, &address, &split.address, split.percent);
+    }
+    *ctx.payout_split.lock() = payout_split;
+
+    if let (Some(requested), Some(handler)) = (extensions.static_diff, &client_handler) {
+        let diff = handler.static_diff(requested);
+        ctx.state.set_static_diff(diff);
+        tracing::info!(
+            "[AUTHORIZE] {} ({}) pinned to static difficulty {} (asked for d={}), vardiff off for this connection",
+            worker_name,
+            ctx.remote_endpoint(),
+            diff,
+            requested
+        );
+    }
+    ctx.state.mark_authorized(std::time::Instant::now());
+    if let Some(handler) = &client_handler {
+        handler.record_connection_states();
+    }
+
     let response = JsonRpcResponse::new(&event, Some(Value::Bool(true)), None);
     let response_json = serde_json::to_string(&response).unwrap_or_else(|_| "failed".to_string());
     tracing::debug!("[AUTHORIZE] Sending authorize response to {}: {}", ctx.remote_addr, response_json);
@@ -225,6 +531,11 @@ pub async fn handle_authorize(
     ctx.reply(response).await.map_err(|e| format!("failed to send response to authorize: {}", e))?;
 
     tracing::debug!("[AUTHORIZE] Authorize response sent successfully");
+    crate::webhook::emit_lifecycle_event("authorize", &ctx, serde_json::json!({ "app": ctx.remote_app.lock().clone() }));
+    let warn_after = FIRST_SHARE_WARN_SECS.load(Ordering::Relaxed);
+    if warn_after > 0 {
+        tokio::spawn(watch_first_share(Arc::clone(&ctx), std::time::Duration::from_secs(warn_after)));
+    }
 
     // CRITICAL: Message order for IceRiver must be:
     // 1. authorize response (done above)
@@ -277,6 +588,74 @@ async fn handle_submit(ctx: Arc<StratumContext>, event: JsonRpcEvent) -> Result<
     Ok(())
 }
 
+/// Payout split requested through a worker-name extension (`wallet.worker.donate=addr:2%`)
+/// Kaspa block templates pay a single address, so the coinbase is not split; the request is
+/// recorded in metrics so external payout tooling can settle it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayoutSplit {
+    pub address: String,
+    pub percent: f64,
+}
+
+/// Extension fields a worker name carried
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkerExtensions {
+    pub payout_split: Option<PayoutSplit>,
+    pub static_diff: Option<f64>, // Requested d= difficulty, before the instance's bounds are applied
+}
+
+/// Username fields split on '.' with the rest of a decimal extension value joined back: a field without
+/// '=' after a key=value continues it (d=1.5, donate=...:2.5)
+fn join_extension_fields<'a>(fields: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut joined: Vec<String> = Vec::new();
+    for field in fields {
+        match joined.last_mut() {
+            Some(last) if !field.contains('=') && last.contains('=') => {
+                last.push('.');
+                last.push_str(field);
+            }
+            _ => joined.push(field.to_string()),
+        }
+    }
+    joined
+}
+
+/// Parse `key=value` extension fields that follow the worker name
+/// Honored: `donate=<kaspa address>:<percent>%` (0 < percent <= 100, `%` optional)
+///          `d=<difficulty>` (a fixed difficulty with vardiff off, as on most Kaspa pools)
+/// Unknown keys and malformed values are ignored. `fields` is the username split on '.', so a field
+/// without '=' after a key=value is the rest of a decimal value (d=1.5, donate=...:2.5) and is joined back.
+/// A donate address without a prefix gets `network`'s
+pub fn parse_worker_extensions(fields: &[&str], network: Option<KaspaNetwork>) -> WorkerExtensions {
+    let mut extensions = WorkerExtensions::default();
+    for field in &join_extension_fields(fields.iter().copied()) {
+        let Some((key, value)) = field.split_once('=') else { continue };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "donate" => match parse_donation(value, network) {
+                Some(s) => extensions.payout_split = Some(s),
+                None => tracing::debug!("[AUTHORIZE] ignoring malformed donate extension '{}'", value),
+            },
+            "d" => match value.trim().parse::<f64>() {
+                Ok(diff) if diff.is_finite() && diff > 0.0 => extensions.static_diff = Some(diff),
+                _ => tracing::debug!("[AUTHORIZE] ignoring malformed d extension '{}'", value),
+            },
+            other => tracing::debug!("[AUTHORIZE] ignoring unknown worker extension '{}'", other),
+        }
+    }
+    extensions
+}
+
+fn parse_donation(value: &str, network: Option<KaspaNetwork>) -> Option<PayoutSplit> {
+    // The address itself contains ':' (kaspa:...), so the percentage is after the last one
+    let (address, percent) = value.rsplit_once(':')?;
+    let percent: f64 = percent.trim().trim_end_matches('%').parse().ok()?;
+    if !(percent > 0.0 && percent <= 100.0) {
+        return None;
+    }
+    let address = clean_wallet(address.trim(), network).ok()?;
+    Some(PayoutSplit { address, percent })
+}
+
 /// Process Canxium address
 fn process_canxium_address(address: &str) -> String {
     let mut addr = address.to_string();
@@ -299,16 +678,61 @@ fn process_canxium_address(address: &str) -> String {
     addr
 }
 
-/// Clean and validate wallet address
-fn clean_wallet(input: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    // Try to decode as Kaspa address (supports kaspa:, kaspatest:, kaspadev:)
+/// Expected address prefix if the wallet's prefix doesn't match the network kaspad reports
+fn wallet_network_mismatch(wallet: &str) -> Option<&'static str> {
+    let network_id = crate::kaspaapi::NODE_STATUS.lock().network_id.clone()?;
+    let expected = crate::kaspaapi::address_prefix_for_network(&network_id)?;
+    let prefix = wallet.split_once(':').map(|(prefix, _)| prefix)?;
+    (prefix != expected).then_some(expected)
+}
+
+/// With `network` configured, a wallet must carry that network's address prefix
+pub fn check_wallet_network(wallet: &str, network: Option<KaspaNetwork>) -> Result<(), String> {
+    let Some(network) = network else { return Ok(()) };
+    let prefix = wallet.split_once(':').map(|(prefix, _)| prefix).unwrap_or_default();
+    if prefix == network.address_prefix() {
+        Ok(())
+    } else {
+        Err(format!("wallet {} is not a {} address (expected {}:)", wallet, network, network.address_prefix()))
+    }
+}
+
+/// Flag a connection that is still up `after` authorizing without one accepted share, which usually
+/// means the bridge and its firmware disagree (nonce_byte_order, extranonce, difficulty format)
+async fn watch_first_share(ctx: Arc<StratumContext>, after: std::time::Duration) {
+    tokio::time::sleep(after).await;
+    if !ctx.connected() || ctx.state.first_share().accepted {
+        return;
+    }
+    tracing::warn!(
+        "[AUTHORIZE] {} ({}, {:?}) has had no accepted share {:?} after authorizing, check nonce_byte_order and difficulty settings for this firmware",
+        ctx.worker_name.lock(),
+        ctx.remote_endpoint(),
+        ctx.remote_app.lock(),
+        after
+    );
+    crate::prom::record_first_share_overdue();
+}
+
+/// Validate a configured wallet the way authorize does on `network`, adding its prefix (kaspa: when None)
+/// if it is missing
+pub fn normalize_wallet(input: &str, network: Option<KaspaNetwork>) -> Result<String, String> {
+    let wallet = clean_wallet(input.trim(), network).map_err(|e| format!("{:?} is not a valid wallet address: {}", input, e))?;
+    check_wallet_network(&wallet, network)?;
+    Ok(wallet)
+}
+
+/// Clean and validate wallet address; one without a prefix gets `network`'s (kaspa: when None)
+fn clean_wallet(input: &str, network: Option<KaspaNetwork>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    // Try to decode as Kaspa address (supports kaspa:, kaspatest:, kaspadev:, kaspasim:)
     if Address::try_from(input).is_ok() {
         return Ok(input.to_string());
     }
 
-    // Try with kaspa: prefix if no recognized prefix
-    if !input.starts_with("kaspa:") && !input.starts_with("kaspatest:") && !input.starts_with("kaspadev:") {
-        return clean_wallet(&format!("kaspa:{}", input));
+    // Try with the network's prefix (kaspa: unless pinned elsewhere) if no recognized prefix
+    if !["kaspa:", "kaspatest:", "kaspadev:", "kaspasim:"].iter().any(|prefix| input.starts_with(prefix)) {
+        let prefix = network.map_or("kaspa", |network| network.address_prefix());
+        return clean_wallet(&format!("{}:{}", prefix, input), network);
     }
 
     // Try regex match
@@ -320,7 +744,7 @@ fn clean_wallet(input: &str) -> Result<String, Box<dyn std::error::Error + Send
 }
 
 /// Send extranonce to client
-async fn send_extranonce(ctx: Arc<StratumContext>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub(crate) async fn send_extranonce(ctx: Arc<StratumContext>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     tracing::debug!("[EXTRANONCE] ===== SENDING EXTRANONCE TO {} =====", ctx.remote_addr);
 
     let remote_app = ctx.remote_app.lock().clone();
@@ -371,3 +795,158 @@ async fn send_extranonce(ctx: Arc<StratumContext>) -> Result<(), Box<dyn std::er
     tracing::debug!("[EXTRANONCE] ===== EXTRANONCE SENT TO {} =====", ctx.remote_addr);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_extensions() {
+        let params = vec![
+            serde_json::json!(["version-rolling", "subscribe-extranonce", "minimum-difficulty"]),
+            serde_json::json!({"version-rolling.mask": "1fffe000", "version-rolling.min-bit-count": 2}),
+        ];
+        let result = negotiate_extensions(&params);
+        assert_eq!(result.get("version-rolling"), Some(&Value::Bool(false)));
+        assert_eq!(result.get("subscribe-extranonce"), Some(&Value::Bool(true)));
+        assert_eq!(result.get("minimum-difficulty"), Some(&Value::Bool(false)));
+        assert!(!result.contains_key("version-rolling.mask"));
+
+        assert!(negotiate_extensions(&[]).is_empty());
+        assert!(negotiate_extensions(&[serde_json::json!("version-rolling")]).is_empty());
+    }
+
+    #[test]
+    fn test_subscribe_extranonce_hint() {
+        assert_eq!(subscribe_extranonce_hint(&[serde_json::json!("MyMiner/1.0"), serde_json::json!("EthereumStratum/1.0.0")]), None);
+        assert_eq!(
+            subscribe_extranonce_hint(&[serde_json::json!("MyMiner/1.0"), serde_json::json!(null), serde_json::json!(1)]),
+            Some(1)
+        );
+        assert_eq!(subscribe_extranonce_hint(&[serde_json::json!("MyMiner/1.0"), serde_json::json!(8)]), None);
+        assert_eq!(subscribe_extranonce_hint(&[serde_json::json!(2)]), None, "params[0] is the user-agent");
+    }
+
+    #[test]
+    fn test_sanitize_worker_name() {
+        assert_eq!(sanitize_worker_name("rig01", 64).unwrap(), "rig01");
+        assert_eq!(sanitize_worker_name("", 64).unwrap(), "");
+
+        // Embedded newlines can't start a forged log line
+        let forged = sanitize_worker_name("rig01\n2024-01-01T00:00:00Z INFO [BLOCK] Block found!\r", 64).unwrap();
+        assert!(!forged.contains('\n') && !forged.contains('\r'), "{:?}", forged);
+        assert_eq!(forged, "rig012024-01-01T00:00:00Z INFO [BLOCK] Block found!");
+        let line = format!("[SUBMIT] share accepted from {}", forged);
+        assert_eq!(line.lines().count(), 1);
+
+        // ANSI escapes lose their ESC byte and can't recolor or clear the console
+        assert_eq!(sanitize_worker_name("\u{1b}[2Jrig", 64).unwrap(), "[2Jrig");
+
+        // Absurd lengths are capped, on character boundaries
+        assert_eq!(sanitize_worker_name(&"a".repeat(1000), 64).unwrap().len(), 64);
+        assert_eq!(sanitize_worker_name(&"é".repeat(1000), 10).unwrap().chars().count(), 10);
+
+        // Nothing printable left
+        assert!(sanitize_worker_name("\n\t\u{7}", 64).is_err());
+        assert_eq!(sanitize_worker_name("   ", 64).unwrap(), "");
+    }
+
+    #[test]
+    fn test_normalize_wallet() {
+        let bare = "q".repeat(61);
+        assert_eq!(normalize_wallet(&format!(" {} ", bare), None).unwrap(), format!("kaspa:{}", bare));
+        assert!(normalize_wallet("rig01", None).is_err());
+        assert!(normalize_wallet("", None).is_err());
+
+        // A bare address takes the network's prefix, and one for another network is refused
+        let testnet = Some(KaspaNetwork::Testnet10);
+        assert_eq!(normalize_wallet(&bare, testnet).unwrap(), format!("kaspatest:{}", bare));
+        assert!(normalize_wallet(&format!("kaspa:{}", bare), testnet).is_err());
+    }
+
+    #[test]
+    fn test_match_worker_override() {
+        let entry = |pattern: &str| WorkerOverride { pattern: pattern.into(), min_share_diff: None, start_diff: None, var_diff: None };
+        let overrides = vec![entry("S21-*"), entry("kaspa:qzfarm*"), entry("*-old")];
+
+        assert_eq!(match_worker_override(&overrides, "kaspa:qzother", "s21-rack4").unwrap().pattern, "S21-*");
+        assert_eq!(match_worker_override(&overrides, "kaspa:qzfarm123", "ks0").unwrap().pattern, "kaspa:qzfarm*");
+        assert_eq!(match_worker_override(&overrides, "kaspa:qzother", "ks3-old").unwrap().pattern, "*-old");
+        assert!(match_worker_override(&overrides, "kaspa:qzother", "ks3-older").is_none());
+        assert!(match_worker_override(&[entry("rig")], "kaspa:qzother", "rig2").is_none(), "no wildcard = exact match");
+    }
+
+    #[test]
+    fn test_parse_worker_extensions() {
+        let donate = format!("kaspa:{}", "q".repeat(61));
+
+        let field = format!("donate={}:2%", donate);
+        let split = parse_worker_extensions(&[field.as_str()], None).payout_split.expect("donate extension should parse");
+        assert_eq!(split, PayoutSplit { address: donate.clone(), percent: 2.0 });
+
+        // Unknown extensions and a canxium address field are skipped
+        let field = format!("donate={}:0.5", donate);
+        let split = parse_worker_extensions(&["0xabc", "rig=7", field.as_str()], None).payout_split.unwrap();
+        assert_eq!(split.percent, 0.5);
+
+        // Static difficulty, alone or next to a donation
+        assert_eq!(parse_worker_extensions(&["d=65536"], None).static_diff, Some(65536.0));
+        let both = parse_worker_extensions(&["D=4096", field.as_str()], None);
+        assert_eq!((both.static_diff, both.payout_split.map(|s| s.percent)), (Some(4096.0), Some(0.5)));
+
+        // Malformed values are ignored
+        assert!(parse_worker_extensions(&["donate=notanaddress:2%"], None).payout_split.is_none());
+        assert!(parse_worker_extensions(&[format!("donate={}:150%", donate).as_str()], None).payout_split.is_none());
+        assert!(parse_worker_extensions(&[format!("donate={}", donate).as_str()], None).payout_split.is_none());
+        for malformed in ["d=", "d=fast", "d=-8", "d=0", "d=inf"] {
+            assert_eq!(parse_worker_extensions(&[malformed], None), WorkerExtensions::default(), "{}", malformed);
+        }
+        assert_eq!(parse_worker_extensions(&[], None), WorkerExtensions::default());
+
+        // Decimals split apart by the '.' separator are joined back
+        let donate_field = format!("donate={}:2", donate);
+        let split = parse_worker_extensions(&[donate_field.as_str(), "5", "d=1", "5"], None);
+        assert_eq!((split.payout_split.map(|s| s.percent), split.static_diff), (Some(2.5), Some(1.5)));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_keeps_decimal_donation() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, _client) = crate::mock_connection::tcp_connection(&listener).await;
+        let wallet = format!("kaspa:{}", "q".repeat(61));
+        let username = format!("{}.rig01.donate={}:2.5%", wallet, wallet);
+        let event = JsonRpcEvent::new(Some("2".to_string()), "mining.authorize", vec![Value::String(username)]);
+
+        handle_authorize(Arc::clone(&ctx), event, None, None).await.unwrap();
+        assert_eq!(*ctx.worker_name.lock(), "rig01");
+        assert_eq!(ctx.payout_split.lock().clone(), Some(PayoutSplit { address: wallet, percent: 2.5 }));
+        assert!(ctx.canxium_addr.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authorize_keeps_decimal_static_diff() {
+        use crate::share_handler::{ShareHandler, SuggestDifficultyPolicy, VarDiffMode};
+        let handler = Arc::new(crate::client_handler::ClientHandler::new(
+            Arc::new(ShareHandler::new("Instance 1".into(), false, VarDiffMode::Window, 20, None)),
+            0.25,
+            1,
+            "Instance 1".into(),
+            false,
+            crate::hasher::DifficultyFormat::default(),
+            std::time::Duration::ZERO,
+            SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false },
+            0.25,
+            AuthorizeConfig::default(),
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let wallet = format!("kaspa:{}", "q".repeat(61));
+        for (username, worker) in [(format!("{}.rig01.d=0.5", wallet), "rig01"), (format!("{}.d=0.5", wallet), "")] {
+            let (ctx, _client) = crate::mock_connection::tcp_connection(&listener).await;
+            let event = JsonRpcEvent::new(Some("2".to_string()), "mining.authorize", vec![Value::String(username.clone())]);
+            handle_authorize(Arc::clone(&ctx), event, Some(Arc::clone(&handler)), None).await.unwrap();
+            assert_eq!(ctx.state.static_diff(), Some(0.5), "{}", username);
+            assert_eq!(*ctx.worker_name.lock(), worker, "the fraction is not a worker or canxium field");
+            assert!(ctx.canxium_addr.lock().is_empty());
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::kaspaapi::{configured_network, KaspaNetwork};
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+
+/// Every network the bridge knows, for code that has to cover all of them
+pub const ALL_NETWORKS: [KaspaNetwork; 5] =
+    [KaspaNetwork::Mainnet, KaspaNetwork::Testnet10, KaspaNetwork::Testnet11, KaspaNetwork::Devnet, KaspaNetwork::Simnet];
+
+/// The constants difficulty and target conversions depend on, for one network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DifficultyParams {
+    /// Highest target a block may have (consensus pow limit), as 2^bits - 1
+    pub pow_limit_bits: u32,
+    /// Target of share difficulty 1, as 2^bits - 1. A stratum convention miners' firmware shares,
+    /// not consensus, so it is the same wherever the bridge runs
+    pub share_diff1_bits: u32,
+    /// Largest target of the (2^bits - 1) * k / (diff * k) calculation some ASIC firmware expects,
+    /// as 2^bits - 1. Also a firmware convention
+    pub stratum_max_target_bits: u32,
+}
+
+impl DifficultyParams {
+    /// Constants of `network`. Every Kaspa network currently shares a 2^255 - 1 pow limit; a network
+    /// that changes it gets its own arm here and nothing else has to follow
+    pub const fn for_network(network: KaspaNetwork) -> Self {
+        match network {
+            KaspaNetwork::Mainnet
+            | KaspaNetwork::Testnet10
+            | KaspaNetwork::Testnet11
+            | KaspaNetwork::Devnet
+            | KaspaNetwork::Simnet => Self { pow_limit_bits: 255, share_diff1_bits: 224, stratum_max_target_bits: 64 },
+        }
+    }
+
+    pub fn pow_limit(&self) -> BigUint {
+        (BigUint::one() << self.pow_limit_bits) - 1u32
+    }
+
+    pub fn share_diff1_target(&self) -> BigUint {
+        (BigUint::one() << self.share_diff1_bits) - 1u32
+    }
+
+    pub fn stratum_max_target(&self) -> BigUint {
+        (BigUint::one() << self.stratum_max_target_bits) - 1u32
+    }
+
+    /// Hashes a share of difficulty 1 takes on average: 2^256 / diff-1 target
+    pub fn min_hash(&self) -> f64 {
+        2_f64.powi(256 - self.share_diff1_bits as i32)
+    }
+
+    /// Target a share of difficulty `diff` has to meet: diff-1 target / diff, never above the pow limit.
+    /// Zero or negative difficulties get the diff-1 target
+    pub fn share_target(&self, diff: f64) -> BigUint {
+        let diff1 = self.share_diff1_target();
+        if diff <= 0.0 {
+            return diff1;
+        }
+        // Divide with 18 decimals of the difficulty kept, like big.Float division followed by Int()
+        const SCALE: u128 = 1_000_000_000_000_000_000;
+        let scaled = BigUint::from((diff * SCALE as f64) as u128);
+        if scaled.is_zero() {
+            return self.pow_limit();
+        }
+        ((diff1 * SCALE) / scaled).min(self.pow_limit())
+    }
+
+    /// Target from the stratum max target with 6 decimals of the difficulty kept (TESTING ONLY).
+    /// Zero or negative difficulties get the diff-1 target
+    pub fn alternative_share_target(&self, diff: f64) -> BigUint {
+        if diff <= 0.0 {
+            return self.share_diff1_target();
+        }
+        self.stratum_max_target() * 1_000_000u64 / BigUint::from((diff * 1_000_000.0) as u64)
+    }
+
+    /// Target of a whole stratum difficulty the way IceRiver ASICs compute it:
+    /// stratum max target * 1000 / (diff * 1000). Difficulty 0 gets stratum max target * 1000
+    pub fn stratum_share_target(&self, stratum_diff: u64) -> BigUint {
+        if stratum_diff == 0 {
+            return self.stratum_max_target() * 1000u64;
+        }
+        self.stratum_max_target() * 1000u64 / (BigUint::from(stratum_diff) * 1000u64)
+    }
+
+    /// Float difficulty of a big difficulty value: 2^(pow limit bits - 1) / diff / 2^31
+    pub fn big_diff_to_little(&self, diff: &BigUint) -> f64 {
+        let numerator = (BigUint::one() << (self.pow_limit_bits - 1)).to_f64().unwrap_or(0.0);
+        numerator / diff.to_f64().unwrap_or(1.0) / 2_f64.powi(31)
+    }
+
+    /// Share difficulty a hash or target achieves: the difficulty whose share target it would just meet
+    pub fn target_difficulty(&self, target: &BigUint) -> f64 {
+        if target.is_zero() {
+            return f64::MAX;
+        }
+        self.share_diff1_target().to_f64().unwrap_or(0.0) / target.to_f64().unwrap_or(1.0)
+    }
+
+    /// Share difficulty of the network target a header's `bits` encode
+    pub fn network_difficulty(&self, bits: u32) -> f64 {
+        self.target_difficulty(&bits_to_target(bits))
+    }
+
+    /// Whether `bits` encode a target the network accepts at all (non-zero, within the pow limit)
+    pub fn is_valid_bits(&self, bits: u32) -> bool {
+        let target = bits_to_target(bits);
+        !target.is_zero() && target <= self.pow_limit()
+    }
+}
+
+/// Constants of the configured network; mainnet's when the bridge follows whatever kaspad is on
+pub fn difficulty_params() -> DifficultyParams {
+    DifficultyParams::for_network(configured_network().unwrap_or(KaspaNetwork::Mainnet))
+}
+
+/// Target encoded in a header's compact `bits`: [exponent (1 byte)][mantissa (3 bytes)],
+/// target = mantissa << 8 * (exponent - 3). The encoding is the same on every network
+pub fn bits_to_target(bits: u32) -> BigUint {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0xFFFFFF;
+    if exponent <= 3 {
+        BigUint::from(mantissa >> (8 * (3 - exponent)))
+    } else {
+        BigUint::from(mantissa) << (8 * (exponent - 3))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::Num;
+
+    fn hex(value: &str) -> BigUint {
+        BigUint::from_str_radix(value, 16).unwrap()
+    }
+
+    #[test]
+    fn test_share_target_vectors() {
+        let vectors = [
+            (1.0, "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            (0.5, "00000001fffffffffffffffffffffffffffffffffffffffffffffffffffffffe"),
+            (4096.0, "00000000000fffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            (65536.0, "000000000000ffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+        ];
+        for network in ALL_NETWORKS {
+            let params = DifficultyParams::for_network(network);
+            for (diff, target) in vectors {
+                assert_eq!(params.share_target(diff), hex(target), "{} diff {}", network, diff);
+                let back = params.target_difficulty(&params.share_target(diff));
+                assert!((back - diff).abs() / diff < 1e-9, "{} diff {} came back as {}", network, diff, back);
+            }
+            assert_eq!(params.share_target(0.0), params.share_diff1_target());
+            assert_eq!(params.share_target(1e-12), params.pow_limit(), "{}: clamped to the pow limit", network);
+        }
+    }
+
+    #[test]
+    fn test_bits_to_target_vectors() {
+        assert_eq!(bits_to_target(0x1e21bc1c), hex("21bc1c000000000000000000000000000000000000000000000000000000"));
+        assert_eq!(bits_to_target(0x207fffff), hex("7fffff0000000000000000000000000000000000000000000000000000000000"));
+        assert_eq!(bits_to_target(0x0300ffff), BigUint::from(0xffffu32));
+        assert_eq!(bits_to_target(0x02ffff00), BigUint::from(0xffffu32), "exponent below 3 shifts right");
+    }
+
+    #[test]
+    fn test_network_target_vectors() {
+        let pow_limit = hex("7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff");
+        for network in ALL_NETWORKS {
+            let params = DifficultyParams::for_network(network);
+            assert_eq!(params.pow_limit(), pow_limit, "{}", network);
+            assert!((params.network_difficulty(0x1b0ffff0) - 4096.0625).abs() < 1e-3, "{}", network);
+            assert!(params.is_valid_bits(0x207fffff), "{}: the easiest target is allowed", network);
+            assert!(!params.is_valid_bits(0x21010000), "{}: above the pow limit", network);
+            assert!(!params.is_valid_bits(0x01000000), "{}: zero target", network);
+            assert_eq!(params.min_hash(), 4294967296.0, "{}", network);
+            assert_eq!(params.stratum_share_target(4), BigUint::from(u64::MAX / 4), "{}", network);
+            assert_eq!(params.alternative_share_target(0.0), params.share_diff1_target(), "{}", network);
+            assert_eq!(params.big_diff_to_little(&(BigUint::one() << 200u32)), 2_f64.powi(23), "{}", network);
+        }
+    }
+}
@@ -1,21 +1,8 @@
+use crate::difficulty::{bits_to_target, difficulty_params};
 use kaspa_hashes::{BlockHash, HasherBase};
 use num_bigint::BigUint;
 use num_traits::{ToPrimitive, Zero};
 
-/// Maximum target value (2^224 - 1)
-/// This is 28 bytes = 56 hex characters = 224 bits = 2^224 - 1
-const MAX_TARGET: &str = "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF";
-
-/// Minimum hash value (calculated at runtime)
-/// Formula: minHash = 2^256 / maxTarget = 2^256 / (2^224 - 1) ≈ 2^32
-/// Formula: minHash = 2^256 / maxTarget where maxTarget = 2^224 - 1
-fn min_hash() -> f64 {
-    // maxTarget = 2^224 - 1 ≈ 2^224
-    // minHash = 2^256 / 2^224 = 2^32
-    // Using f64: 2^32 = 4294967296.0
-    2_f64.powi(32)
-}
-
 /// Big gig (1e9)
 const BIG_GIG: f64 = 1_000_000_000.0;
 
@@ -54,43 +41,20 @@ impl KaspaDiff {
     }
 }
 
-/// Alternative target calculation (TESTING ONLY)
-/// Uses: target = (2^64 - 1) * 1000000 / (difficulty * 1000000)
+/// Alternative target calculation (TESTING ONLY), see [`DifficultyParams::alternative_share_target`]
 /// This is a test implementation to verify ASIC expectations
+///
+/// [`DifficultyParams::alternative_share_target`]: crate::difficulty::DifficultyParams::alternative_share_target
 pub fn diff_to_target_alternative(diff: f64) -> BigUint {
-    use num_traits::Num;
-
-    // Handle edge cases
-    if diff <= 0.0 {
-        return <BigUint as Num>::from_str_radix(MAX_TARGET, 16).unwrap();
-    }
-
-    // KASPA_MAX_TARGET = 2^64 - 1
-    const KASPA_MAX_TARGET: u64 = 0xFFFFFFFFFFFFFFFF;
-    let max_target_big = BigUint::from(KASPA_MAX_TARGET);
-    let difficulty_big = BigUint::from((diff * 1000000.0) as u64);
-
-    // Calculate target = max_target * 1000000 / (difficulty * 1000000)
-    max_target_big * BigUint::from(1000000u64) / difficulty_big
+    difficulty_params().alternative_share_target(diff)
 }
 
-/// Stratum difficulty to target (IceRiver specific)
-/// Uses: target = (2^64 - 1) * 1000 / (stratum_diff * 1000)
+/// Stratum difficulty to target (IceRiver specific), see [`DifficultyParams::stratum_share_target`]
 /// This is the calculation method expected by IceRiver ASICs
+///
+/// [`DifficultyParams::stratum_share_target`]: crate::difficulty::DifficultyParams::stratum_share_target
 pub fn stratum_difficulty_to_target_kaspa(stratum_diff: u64) -> BigUint {
-    // Handle zero difficulty - return maximum target
-    if stratum_diff == 0 {
-        const KASPA_MAX_TARGET: u64 = 0xFFFFFFFFFFFFFFFF;
-        return BigUint::from(KASPA_MAX_TARGET) * BigUint::from(1000u64);
-    }
-
-    // KASPA_MAX_TARGET = 2^64 - 1
-    const KASPA_MAX_TARGET: u64 = 0xFFFFFFFFFFFFFFFF;
-    let base_target = BigUint::from(KASPA_MAX_TARGET);
-    let scaled_diff = BigUint::from(stratum_diff) * BigUint::from(1000u64);
-
-    // target = max_target * 1000 / (stratum_diff * 1000)
-    base_target * BigUint::from(1000u64) / scaled_diff
+    difficulty_params().stratum_share_target(stratum_diff)
 }
 
 /// Convert difficulty to target
@@ -150,40 +114,23 @@ pub fn diff_to_target(diff: f64) -> BigUint {
 }
 
 /// Standard target calculation
-/// Formula: target = maxTarget / diff
-/// This uses floating point division then converts to integer
+/// Formula: target = maxTarget / diff, with the configured network's constants
 fn diff_to_target_standard(diff: f64) -> BigUint {
-    use num_traits::Num;
-
-    // Handle edge cases
-    if diff <= 0.0 {
-        // Return maximum target for invalid difficulty
-        return <BigUint as Num>::from_str_radix(MAX_TARGET, 16).unwrap();
-    }
-
-    // maxTarget = 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF
-    // This is 2^224 - 1, NOT the old 0xffff * 2^208!
-    let max_target = <BigUint as Num>::from_str_radix(MAX_TARGET, 16).unwrap();
-
-    // Calculate target = maxTarget / difficulty (EXACTLY like Go)
-    // Go uses big.Float division: target = maxTarget / diff
-    // Then converts to big.Int by truncating
-    // We need high precision, so use a large scaling factor
-    // Use 18 decimal places of precision (multiply by 1e18) to match big.Float precision
-    let diff_scaled = (diff * 1_000_000_000_000_000_000.0) as u128;
-    let diff_big = BigUint::from(diff_scaled);
-
-    // target = (maxTarget * 1e18) / (diff * 1e18)
-    // Uses big.Float division followed by Int() truncation
-    (max_target * BigUint::from(1_000_000_000_000_000_000u128)) / diff_big
+    difficulty_params().share_target(diff)
 }
 
 /// Convert difficulty to hash value
 pub fn diff_to_hash(diff: f64) -> f64 {
-    let hash_val = min_hash() * diff;
+    let hash_val = difficulty_params().min_hash() * diff;
     hash_val / BIG_GIG
 }
 
+/// Difficulty a PoW value actually achieved, in the same units as the assigned pool difficulty
+/// (the difficulty whose target the hash would just meet)
+pub fn pow_to_diff(pow_value: &BigUint) -> f64 {
+    difficulty_params().target_difficulty(pow_value)
+}
+
 /// Serialize block header for mining
 /// This creates the pre-PoW hash (hash WITHOUT timestamp and nonce)
 /// Uses kaspa_hashes::BlockHash to match the working stratum implementation
@@ -389,47 +336,157 @@ pub fn generate_large_job_params(header_data: &[u8], timestamp: u64) -> String {
     format!("{:016x}{:016x}{:016x}{:016x}{:016x}", ids[0], ids[1], ids[2], ids[3], ids[4])
 }
 
-/// Calculate target from bits (compact format)
-/// Bits format: [exponent (1 byte)][mantissa (3 bytes)]
-/// Target = mantissa << (8 * (exponent - 3))
-/// This matches Kaspa's compact difficulty format
+/// Calculate target from bits (compact format), see [`bits_to_target`]
 pub fn calculate_target(bits: u64) -> BigUint {
-    let exponent = bits >> 24; // First byte is exponent
-    let mantissa = bits & 0xFFFFFF; // Last 3 bytes are mantissa
+    bits_to_target(bits as u32)
+}
 
-    let (mantissa, exponent) = if exponent <= 3 {
-        // Special case: if exponent <= 3, shift mantissa right instead
-        let shift = 8 * (3 - exponent);
-        (mantissa >> shift, 0u32)
-    } else {
-        // Normal case: target = mantissa << (8 * (exponent - 3))
-        (mantissa, (8 * (exponent - 3)) as u32)
-    };
+/// Convert big difficulty to little (float representation), with the configured network's constants
+pub fn big_diff_to_little(diff: &BigUint) -> f64 {
+    difficulty_params().big_diff_to_little(diff)
+}
 
-    // Calculate final target: mantissa << exponent
-    let mut target = BigUint::from(mantissa);
-    target <<= exponent;
+/// Encoding of the difficulty sent to miners in mining.set_difficulty
+/// Firmware differs in what it expects, so this is configurable per instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DifficultyFormat {
+    /// Plain pool difficulty number (target = 2^224 / diff), what the bridge has always sent
+    #[default]
+    KaspaLegacy,
+    /// Difficulty rescaled to a 2^256 difficulty-1 target (diff * 2^32)
+    Scaled,
+    /// The share target itself as a 64-character big-endian hex string
+    TargetHex,
+}
 
-    target
+impl DifficultyFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DifficultyFormat::KaspaLegacy => "kaspa_legacy",
+            DifficultyFormat::Scaled => "scaled",
+            DifficultyFormat::TargetHex => "target_hex",
+        }
+    }
 }
 
-/// Convert big difficulty to little (float representation)
-pub fn big_diff_to_little(diff: &BigUint) -> f64 {
-    use num_traits::ToPrimitive;
-    // numerator = 2^254
-    let numerator = BigUint::from(2u64).pow(254);
+impl std::str::FromStr for DifficultyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "kaspa_legacy" | "legacy" => Ok(DifficultyFormat::KaspaLegacy),
+            "scaled" => Ok(DifficultyFormat::Scaled),
+            "target_hex" => Ok(DifficultyFormat::TargetHex),
+            other => Err(format!("unknown difficulty_format '{}' (expected kaspa_legacy, scaled or target_hex)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for DifficultyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Convert a pool difficulty into the mining.set_difficulty parameter for the given format
+pub fn difficulty_to_wire(diff: f64, format: DifficultyFormat) -> serde_json::Value {
+    fn number(value: f64) -> serde_json::Value {
+        // Whole values are sent as integers, some firmware rejects "8192.0"
+        if value.fract() == 0.0 && (0.0..=u64::MAX as f64).contains(&value) {
+            serde_json::Value::Number(serde_json::Number::from(value as u64))
+        } else {
+            serde_json::Number::from_f64(value).map(serde_json::Value::Number).unwrap_or(serde_json::Value::Null)
+        }
+    }
+
+    match format {
+        DifficultyFormat::KaspaLegacy => {
+            serde_json::Value::Number(serde_json::Number::from_f64(diff).unwrap_or_else(|| serde_json::Number::from(diff as u64)))
+        }
+        DifficultyFormat::Scaled => number(diff * difficulty_params().min_hash()),
+        DifficultyFormat::TargetHex => serde_json::Value::String(format!("{:064x}", diff_to_target(diff))),
+    }
+}
+
+/// Byte order of the hex nonce in mining.submit. Most firmware writes it most significant byte
+/// first; some send the bytes swapped, which fails every share until this is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonceByteOrder {
+    /// Most significant byte first, as the hex is read (default)
+    #[default]
+    Be,
+    /// Least significant byte first: the submitted bytes are reversed before the nonce is assembled
+    Le,
+}
+
+impl NonceByteOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NonceByteOrder::Be => "be",
+            NonceByteOrder::Le => "le",
+        }
+    }
+}
 
-    let numerator_f = ToPrimitive::to_f64(&numerator).unwrap_or(0.0);
-    let diff_f = ToPrimitive::to_f64(diff).unwrap_or(1.0);
+impl std::str::FromStr for NonceByteOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "be" | "big" => Ok(NonceByteOrder::Be),
+            "le" | "little" => Ok(NonceByteOrder::Le),
+            other => Err(format!("unknown nonce_byte_order '{}' (expected le or be)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for NonceByteOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The submitted hex nonce (without 0x) in most-significant-first order, ready for assemble_nonce.
+/// Only the miner's own digits are swapped; the extranonce is the bridge's and always in order
+pub fn nonce_to_big_endian(order: NonceByteOrder, miner_nonce: &str) -> Result<String, String> {
+    match order {
+        NonceByteOrder::Be => Ok(miner_nonce.to_string()),
+        NonceByteOrder::Le => {
+            if miner_nonce.len() % 2 != 0 || !miner_nonce.is_ascii() {
+                return Err(format!("nonce '{}' is not whole bytes, cannot swap its byte order", miner_nonce));
+            }
+            Ok(miner_nonce.as_bytes().rchunks(2).map(|byte| std::str::from_utf8(byte).unwrap_or_default()).collect())
+        }
+    }
+}
 
-    let result = numerator_f / diff_f;
-    result / (2.0_f64.powi(31)) // Divide by 2^31
+/// Rebuild the block nonce from the hex nonce a miner submitted (without 0x) and its extranonce: the
+/// extranonce in the top digits, the miner nonce zero-padded below it. A submitted nonce longer than the
+/// miner's share of the 16 digits is taken as the complete nonce
+pub fn assemble_nonce(extranonce: &str, miner_nonce: &str) -> Result<u64, String> {
+    let miner_width = 16usize.saturating_sub(extranonce.len());
+    let nonce = if extranonce.is_empty() || miner_nonce.len() > miner_width {
+        miner_nonce.to_string()
+    } else {
+        format!("{}{:0>width$}", extranonce, miner_nonce, width = miner_width)
+    };
+    u64::from_str_radix(&nonce, 16).map_err(|e| format!("failed parsing noncestr '{}': {}", nonce, e))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pow_to_diff_inverts_target() {
+        for diff in [1.0, 4096.0, 1_048_576.0] {
+            let achieved = pow_to_diff(&diff_to_target_standard(diff));
+            assert!((achieved - diff).abs() / diff < 1e-9, "diff {} came back as {}", diff, achieved);
+        }
+        // A hash twice the target only achieves half the difficulty
+        assert!((pow_to_diff(&(diff_to_target_standard(4096.0) * 2u32)) - 2048.0).abs() < 1e-6);
+    }
+
     #[test]
     #[ignore] // Diagnostic test - values may vary based on implementation
     fn test_diff_to_target() {
@@ -581,4 +638,61 @@ mod tests {
             println!("WARNING: First byte comparison suggests invalid share, but numeric comparison may differ");
         }
     }
+
+    #[test]
+    fn test_difficulty_format_parse() {
+        assert_eq!("kaspa_legacy".parse::<DifficultyFormat>(), Ok(DifficultyFormat::KaspaLegacy));
+        assert_eq!("Scaled".parse::<DifficultyFormat>(), Ok(DifficultyFormat::Scaled));
+        assert_eq!("target_hex".parse::<DifficultyFormat>(), Ok(DifficultyFormat::TargetHex));
+        assert!("hex".parse::<DifficultyFormat>().is_err());
+        assert_eq!(DifficultyFormat::default().as_str(), "kaspa_legacy");
+    }
+
+    #[test]
+    fn test_assemble_nonce_vectors() {
+        // 2-byte extranonce on top, 12-digit miner nonce below
+        assert_eq!(assemble_nonce("abcd", "1234"), Ok(0xabcd_0000_0000_1234));
+        assert_eq!(assemble_nonce("abcd", "000000001234"), Ok(0xabcd_0000_0000_1234));
+        // No extranonce, or a nonce that is already complete
+        assert_eq!(assemble_nonce("", "00000000deadbeef"), Ok(0xdead_beef));
+        assert_eq!(assemble_nonce("abcd", "ffff00000000beef"), Ok(0xffff_0000_0000_beef));
+        assert!(assemble_nonce("abcd", "xyz").is_err());
+    }
+
+    #[test]
+    fn test_nonce_byte_order_vectors() {
+        // Known-good big endian submissions pass through untouched
+        assert_eq!(nonce_to_big_endian(NonceByteOrder::Be, "00000000deadbeef"), Ok("00000000deadbeef".to_string()));
+        // The same nonce byte-swapped by the firmware
+        let swapped = nonce_to_big_endian(NonceByteOrder::Le, "efbeadde00000000").unwrap();
+        assert_eq!(swapped, "00000000deadbeef");
+        assert_eq!(assemble_nonce("", &swapped), Ok(0xdead_beef));
+        // A partial miner nonce is swapped on its own, then placed under the extranonce
+        let swapped = nonce_to_big_endian(NonceByteOrder::Le, "3412").unwrap();
+        assert_eq!(assemble_nonce("abcd", &swapped), Ok(0xabcd_0000_0000_1234));
+        assert!(nonce_to_big_endian(NonceByteOrder::Le, "123").is_err());
+        assert_eq!("LE".parse::<NonceByteOrder>(), Ok(NonceByteOrder::Le));
+        assert!("middle".parse::<NonceByteOrder>().is_err());
+    }
+
+    #[test]
+    fn test_difficulty_to_wire_vectors() {
+        use serde_json::json;
+
+        // kaspa_legacy keeps the number the bridge has always sent
+        assert_eq!(difficulty_to_wire(8192.0, DifficultyFormat::KaspaLegacy), json!(8192.0));
+        assert_eq!(difficulty_to_wire(0.5, DifficultyFormat::KaspaLegacy), json!(0.5));
+
+        // scaled: diff 1 against a 2^224 target is diff 2^32 against a 2^256 target
+        assert_eq!(difficulty_to_wire(1.0, DifficultyFormat::Scaled), json!(4294967296u64));
+        assert_eq!(difficulty_to_wire(8192.0, DifficultyFormat::Scaled), json!(35184372088832u64));
+        assert_eq!(difficulty_to_wire(0.5, DifficultyFormat::Scaled), json!(2147483648u64));
+
+        // target_hex: diff 1 is the max target 2^224 - 1, padded to 32 bytes
+        assert_eq!(difficulty_to_wire(1.0, DifficultyFormat::TargetHex), json!(format!("{}{}", "0".repeat(8), "f".repeat(56))));
+        // diff 2 halves it: 2^223 - 1
+        assert_eq!(difficulty_to_wire(2.0, DifficultyFormat::TargetHex), json!(format!("{}7{}", "0".repeat(8), "f".repeat(55))));
+        // diff 8192 = 2^13: 2^211 - 1
+        assert_eq!(difficulty_to_wire(8192.0, DifficultyFormat::TargetHex), json!(format!("{}7{}", "0".repeat(11), "f".repeat(52))));
+    }
 }
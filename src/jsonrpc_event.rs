@@ -19,6 +19,8 @@ pub enum StratumMethod {
     Notify,
     #[serde(rename = "mining.set_extranonce")]
     SetExtranonce,
+    #[serde(rename = "mining.suggest_difficulty")]
+    SuggestDifficulty,
     #[serde(untagged)]
     Other(String),
 }
@@ -33,6 +35,7 @@ impl From<&str> for StratumMethod {
             "mining.set_difficulty" => StratumMethod::SetDifficulty,
             "mining.notify" => StratumMethod::Notify,
             "mining.set_extranonce" => StratumMethod::SetExtranonce,
+            "mining.suggest_difficulty" => StratumMethod::SuggestDifficulty,
             other => StratumMethod::Other(other.to_string()),
         }
     }
@@ -48,6 +51,7 @@ impl From<StratumMethod> for String {
             StratumMethod::SetDifficulty => "mining.set_difficulty".to_string(),
             StratumMethod::Notify => "mining.notify".to_string(),
             StratumMethod::SetExtranonce => "mining.set_extranonce".to_string(),
+            StratumMethod::SuggestDifficulty => "mining.suggest_difficulty".to_string(),
             StratumMethod::Other(s) => s,
         }
     }
@@ -111,6 +115,52 @@ impl JsonRpcResponse {
     }
 }
 
+/// Shape of the result sent back for an accepted mining.submit
+/// Most firmware expects a bare `true`; some reject anything but an object and disconnect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShareReplyFormat {
+    /// "result": true, what the bridge has always sent
+    #[default]
+    Bool,
+    /// "result": {"status": "OK"}
+    Object,
+}
+
+impl ShareReplyFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareReplyFormat::Bool => "bool",
+            ShareReplyFormat::Object => "object",
+        }
+    }
+
+    /// Reply to an accepted share in this format
+    pub fn accepted(&self, id: Option<Value>) -> JsonRpcResponse {
+        match self {
+            ShareReplyFormat::Bool => JsonRpcResponse::success(id, Value::Bool(true)),
+            ShareReplyFormat::Object => JsonRpcResponse::success(id, serde_json::json!({ "status": "OK" })),
+        }
+    }
+}
+
+impl std::str::FromStr for ShareReplyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "bool" => Ok(ShareReplyFormat::Bool),
+            "object" => Ok(ShareReplyFormat::Object),
+            other => Err(format!("unknown share_reply_format '{}' (expected bool or object)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ShareReplyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Unmarshal a JSON-RPC event from a string
 pub fn unmarshal_event(input: &str) -> Result<JsonRpcEvent, serde_json::Error> {
     serde_json::from_str(input)
@@ -120,3 +170,21 @@ pub fn unmarshal_event(input: &str) -> Result<JsonRpcEvent, serde_json::Error> {
 pub fn unmarshal_response(input: &str) -> Result<JsonRpcResponse, serde_json::Error> {
     serde_json::from_str(input)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepted_share_reply_shapes() {
+        let id = Some(Value::from(7));
+        let reply = serde_json::to_string(&ShareReplyFormat::Bool.accepted(id.clone())).unwrap();
+        assert_eq!(reply, r#"{"id":7,"result":true}"#);
+        let reply = serde_json::to_string(&ShareReplyFormat::Object.accepted(id)).unwrap();
+        assert_eq!(reply, r#"{"id":7,"result":{"status":"OK"}}"#);
+
+        assert_eq!("Object".parse::<ShareReplyFormat>(), Ok(ShareReplyFormat::Object));
+        assert!("true".parse::<ShareReplyFormat>().is_err());
+        assert_eq!(ShareReplyFormat::default(), ShareReplyFormat::Bool);
+    }
+}
@@ -1,17 +1,23 @@
+use crate::kaspad_pool::{KaspadPool, SubmitTarget, PRIMARY_POOL_NODE};
 use crate::log_colors::LogColors;
+use crate::prom::{
+    record_block_submit_latency, record_block_template, record_block_template_age, record_kaspad_connected, record_kaspad_degraded,
+    record_kaspad_offline_seconds, record_kaspad_sync_status, record_template_parse_error, record_template_ticker_mode,
+};
 use crate::share_handler::KaspaApiTrait;
 use anyhow::{Context, Result};
-use kaspa_addresses::Address;
+use kaspa_addresses::{Address, Prefix, Version};
 use kaspa_consensus_core::block::Block;
 use kaspa_grpc_client::GrpcClient;
 use kaspa_notify::{listener::ListenerId, scope::NewBlockTemplateScope};
 use kaspa_rpc_core::notify::mode::NotificationMode;
 use kaspa_rpc_core::{
     api::rpc::RpcApi, GetBlockDagInfoRequest, GetBlockTemplateRequest, GetConnectedPeerInfoRequest, GetInfoRequest,
-    GetServerInfoRequest, Notification, RpcRawBlock, SubmitBlockRequest, SubmitBlockResponse,
+    GetServerInfoRequest, Notification, RpcRawBlock, SubmitBlockReport, SubmitBlockRequest, SubmitBlockResponse,
 };
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -32,21 +38,539 @@ pub struct NodeStatusSnapshot {
     pub tip_hash: Option<String>,
     pub peers: Option<usize>,
     pub mempool_size: Option<u64>,
+    pub last_template: Option<std::time::Instant>,
 }
 
 pub static NODE_STATUS: Lazy<Mutex<NodeStatusSnapshot>> = Lazy::new(|| Mutex::new(NodeStatusSnapshot::default()));
 
+/// Wallets whose last good template is kept; templates hold every mempool transaction, so this stays small
+const MAX_LAST_GOOD_TEMPLATES: usize = 256;
+
+/// The last template that parsed, per wallet. When kaspad sends one the bridge can't read (a version
+/// mismatch, unexpected fields) miners keep working on this one until a readable template arrives.
+/// Templates are shared with the caller, never copied into the cache
+#[derive(Default)]
+pub struct LastGoodTemplates {
+    templates: Mutex<std::collections::HashMap<String, Arc<Block>>>,
+}
+
+impl LastGoodTemplates {
+    /// Remember a template that parsed
+    pub fn keep(&self, wallet: &str, block: Arc<Block>) {
+        let mut templates = self.templates.lock();
+        if templates.len() >= MAX_LAST_GOOD_TEMPLATES && !templates.contains_key(wallet) {
+            // Evict any one; a wallet that lost its fallback just gets the error on a bad template
+            if let Some(evict) = templates.keys().next().cloned() {
+                templates.remove(&evict);
+            }
+        }
+        templates.insert(wallet.to_string(), block);
+    }
+
+    /// Check a template kaspad sent for `wallet` (already converted from RPC, or the conversion error) on
+    /// attempt `attempt` of `max_retries`: a usable one is kept as the wallet's last good template
+    pub fn accept<E: std::fmt::Debug>(
+        &self,
+        wallet: &str,
+        converted: std::result::Result<Block, E>,
+        attempt: usize,
+        max_retries: usize,
+    ) -> TemplateParse {
+        let retry = attempt < max_retries - 1;
+        match converted {
+            Ok(block) => {
+                check_template_bits(block.header.bits);
+
+                // Validate that we can serialize the block header, this catches "Odd number of digits"
+                // errors early. Convert the error to String immediately to avoid Send issues
+                match crate::hasher::serialize_block_header(&block).map_err(|e| e.to_string()) {
+                    Ok(_) => {
+                        let block = Arc::new(block);
+                        self.keep(wallet, Arc::clone(&block));
+                        TemplateParse::Parsed(block)
+                    }
+                    Err(error_str) if error_str.contains("Odd number of digits") && retry => {
+                        warn!("Block template has malformed hash field (attempt {}/{}), retrying...", attempt + 1, max_retries);
+                        TemplateParse::Retry(format!("Block has malformed hash field: {}", error_str))
+                    }
+                    // Unusable header: keep miners on the last template that worked
+                    Err(error_str) => TemplateParse::Unusable(self.fall_back(
+                        wallet,
+                        "header",
+                        anyhow::anyhow!("Failed to serialize block header: {}", error_str),
+                    )),
+                }
+            }
+            Err(e) => {
+                let error_str = format!("{:?}", e);
+                if error_str.contains("Odd number of digits") && retry {
+                    warn!(
+                        "Block conversion failed with 'Odd number of digits' error (attempt {}/{}), retrying...",
+                        attempt + 1,
+                        max_retries
+                    );
+                    return TemplateParse::Retry(error_str);
+                }
+                // If the error contains "Odd number of digits", provide more context
+                let error = if error_str.contains("Odd number of digits") {
+                    anyhow::anyhow!("Failed to convert RPC block to Block after {} attempts: {} - This usually indicates a malformed hash field in the block template from the Kaspa node. The block may have a hash with an odd-length hex string.", max_retries, error_str)
+                } else {
+                    anyhow::anyhow!("Failed to convert RPC block to Block: {}", error_str)
+                };
+                TemplateParse::Unusable(self.fall_back(wallet, "convert", error))
+            }
+        }
+    }
+
+    /// Count a template that failed to parse at `stage` and fall back to the wallet's last good one,
+    /// or return the parse error when there is none yet
+    pub fn fall_back(&self, wallet: &str, stage: &str, error: anyhow::Error) -> Result<Arc<Block>> {
+        record_template_parse_error(stage);
+        match self.templates.lock().get(wallet) {
+            Some(block) => {
+                warn!("could not parse block template from kaspad ({}), serving the last good one: {}", stage, error);
+                Ok(Arc::clone(block))
+            }
+            None => {
+                error!("could not parse block template from kaspad ({}) and have no earlier one for {}: {}", stage, wallet, error);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// What became of one template from kaspad
+pub enum TemplateParse {
+    /// Usable, and now the wallet's last good template
+    Parsed(Arc<Block>),
+    /// Unusable: the wallet's last good template, or the error when it has none
+    Unusable(Result<Arc<Block>>),
+    /// Malformed in a way that is worth asking kaspad again (the error, for the final report)
+    Retry(String),
+}
+
+/// When block submissions count as a kaspad problem and what to do about it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubmitBreakerConfig {
+    pub error_pct: f64,     // Failed share of submits in the window that trips the breaker (0 = off)
+    pub window: Duration,   // How far back submits are counted
+    pub min_submits: usize, // Submits needed in the window before the rate means anything
+    pub pause_jobs: bool,   // Stop sending new jobs while tripped (otherwise only warn)
+}
+
+impl Default for SubmitBreakerConfig {
+    fn default() -> Self {
+        Self { error_pct: 50.0, window: Duration::from_secs(600), min_submits: 3, pause_jobs: false }
+    }
+}
+
+/// Rolling block-submit error rate; trips when kaspad rejects too much of what the bridge submits
+#[derive(Debug, Default)]
+pub struct SubmitBreaker {
+    config: SubmitBreakerConfig,
+    submits: std::collections::VecDeque<(std::time::Instant, bool)>,
+    tripped: bool,
+}
+
+impl SubmitBreaker {
+    pub fn new(config: SubmitBreakerConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    /// Record one submit outcome; returns the new state when it changed
+    pub fn record(&mut self, failed: bool, now: std::time::Instant) -> Option<bool> {
+        self.submits.push_back((now, failed));
+        self.evaluate(now)
+    }
+
+    /// Drop submits older than the window and re-check the rate; returns the new state when it changed
+    pub fn evaluate(&mut self, now: std::time::Instant) -> Option<bool> {
+        while self.submits.front().is_some_and(|(at, _)| now.duration_since(*at) > self.config.window) {
+            self.submits.pop_front();
+        }
+        let tripped = self.config.error_pct > 0.0
+            && self.submits.len() >= self.config.min_submits.max(1)
+            && self.error_pct() >= self.config.error_pct;
+        if tripped == self.tripped {
+            return None;
+        }
+        self.tripped = tripped;
+        Some(tripped)
+    }
+
+    pub fn error_pct(&self) -> f64 {
+        if self.submits.is_empty() {
+            return 0.0;
+        }
+        self.submits.iter().filter(|(_, failed)| *failed).count() as f64 * 100.0 / self.submits.len() as f64
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+}
+
+static SUBMIT_BREAKER: Lazy<Mutex<SubmitBreaker>> = Lazy::new(|| Mutex::new(SubmitBreaker::new(SubmitBreakerConfig::default())));
+
+/// Apply the configured block-submit circuit breaker (process-wide, all instances share kaspad)
+pub fn set_submit_breaker(config: SubmitBreakerConfig) {
+    *SUBMIT_BREAKER.lock() = SubmitBreaker::new(config);
+    record_kaspad_degraded(false);
+}
+
+/// Outcome label of one submit in ks_kaspad_pool_submit_counter
+fn pool_submit_outcome<E: std::fmt::Display>(result: &std::result::Result<SubmitBlockResponse, E>) -> &'static str {
+    match result {
+        Ok(response) if matches!(response.report, SubmitBlockReport::Success) => "accepted",
+        Ok(_) => "rejected",
+        Err(e) if e.to_string().contains("ErrDuplicateBlock") => "duplicate",
+        Err(_) => "error",
+    }
+}
+
+/// Whether a kaspad_pool node's answer settles a submit; anything else is resubmitted to the primary
+fn pool_submit_settled(outcome: &str) -> bool {
+    matches!(outcome, "accepted" | "duplicate")
+}
+
+/// Which of two broadcast outcomes to report when no node accepted the block: a duplicate means some
+/// node has it, a rejection is at least an answer, a transport error says the least
+fn broadcast_outcome_rank(outcome: &str) -> u8 {
+    match outcome {
+        "accepted" => 3,
+        "duplicate" => 2,
+        "rejected" => 1,
+        _ => 0,
+    }
+}
+
+static FORCE_TICKER: AtomicBool = AtomicBool::new(false);
+
+/// Never subscribe to template notifications and poll every block_wait_time instead (process-wide,
+/// set before connecting). For kaspad versions whose notifications are unreliable
+pub fn set_force_ticker(force: bool) {
+    FORCE_TICKER.store(force, Ordering::Relaxed);
+}
+
+fn force_ticker() -> bool {
+    FORCE_TICKER.load(Ordering::Relaxed)
+}
+
+static TEMPLATE_REFRESH_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Re-broadcast the current template after `interval` without a new one, so miners get a fresh header
+/// timestamp during lulls (process-wide, set before the template listener starts, zero = off)
+pub fn set_template_refresh(interval: Duration) {
+    TEMPLATE_REFRESH_SECS.store(interval.as_secs(), Ordering::Relaxed);
+}
+
+pub fn template_refresh() -> Duration {
+    Duration::from_secs(TEMPLATE_REFRESH_SECS.load(Ordering::Relaxed))
+}
+
+/// What connected miners get once kaspad has been unreachable for offline_grace_secs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OfflinePolicy {
+    /// Keep connections open without new jobs until kaspad is back (default)
+    Hold,
+    /// Disconnect everyone and refuse new miners, so they fail over to a backup pool
+    Disconnect,
+}
+
+impl std::str::FromStr for OfflinePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hold" => Ok(OfflinePolicy::Hold),
+            "disconnect" => Ok(OfflinePolicy::Disconnect),
+            other => Err(format!("unknown offline_policy '{}', expected hold or disconnect", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for OfflinePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OfflinePolicy::Hold => write!(f, "hold"),
+            OfflinePolicy::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+/// Default offline_grace_secs
+pub const DEFAULT_OFFLINE_GRACE: Duration = Duration::from_secs(60);
+
+static OFFLINE_POLICY: AtomicU8 = AtomicU8::new(OfflinePolicy::Hold as u8);
+static OFFLINE_GRACE_SECS: AtomicU64 = AtomicU64::new(DEFAULT_OFFLINE_GRACE.as_secs());
+
+/// When kaspad was last found unreachable; None while connected
+static KASPAD_OFFLINE_SINCE: Lazy<Mutex<Option<std::time::Instant>>> = Lazy::new(|| Mutex::new(None));
+
+/// Apply `policy` once kaspad has been unreachable for `grace` (process-wide)
+pub fn set_offline_policy(policy: OfflinePolicy, grace: Duration) {
+    OFFLINE_POLICY.store(policy as u8, Ordering::Relaxed);
+    OFFLINE_GRACE_SECS.store(grace.as_secs(), Ordering::Relaxed);
+}
+
+fn offline_policy() -> (OfflinePolicy, Duration) {
+    let policy = match OFFLINE_POLICY.load(Ordering::Relaxed) {
+        p if p == OfflinePolicy::Disconnect as u8 => OfflinePolicy::Disconnect,
+        _ => OfflinePolicy::Hold,
+    };
+    (policy, Duration::from_secs(OFFLINE_GRACE_SECS.load(Ordering::Relaxed)))
+}
+
+/// How long kaspad has been unreachable, None while connected
+pub fn kaspad_offline_for() -> Option<Duration> {
+    KASPAD_OFFLINE_SINCE.lock().map(|since| since.elapsed())
+}
+
+/// Whether the offline policy has kicked in for the current outage: `offline_for` is past `grace`
+fn offline_grace_over(offline_for: Option<Duration>, grace: Duration) -> bool {
+    offline_for.is_some_and(|offline| offline >= grace)
+}
+
+/// Whether new miners are refused: kaspad is past its offline grace under offline_policy: disconnect
+pub fn kaspad_offline_refusing() -> bool {
+    let (policy, grace) = offline_policy();
+    policy == OfflinePolicy::Disconnect && offline_grace_over(kaspad_offline_for(), grace)
+}
+
+/// Default kaspad_connect_timeout_secs
+pub const DEFAULT_KASPAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static KASPAD_CONNECT_TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_KASPAD_CONNECT_TIMEOUT.as_millis() as u64);
+
+/// Give up on one attempt to open the gRPC connection to kaspad after this long (process-wide, set before
+/// connecting). Covers the initial connection and reconnects; waiting for sync is not limited by it
+pub fn set_kaspad_connect_timeout(timeout: Duration) {
+    KASPAD_CONNECT_TIMEOUT_MS.store(timeout.as_millis().max(1) as u64, Ordering::Relaxed);
+}
+
+fn kaspad_connect_timeout() -> Duration {
+    Duration::from_millis(KASPAD_CONNECT_TIMEOUT_MS.load(Ordering::Relaxed))
+}
+
+/// Whether new jobs should be held back because kaspad keeps failing block submissions
+pub fn submit_breaker_pausing_jobs() -> bool {
+    let mut breaker = SUBMIT_BREAKER.lock();
+    if let Some(tripped) = breaker.evaluate(std::time::Instant::now()) {
+        log_breaker_change(&breaker, tripped);
+    }
+    breaker.is_tripped() && breaker.config.pause_jobs
+}
+
+fn record_submit_outcome(failed: bool) {
+    let mut breaker = SUBMIT_BREAKER.lock();
+    if let Some(tripped) = breaker.record(failed, std::time::Instant::now()) {
+        log_breaker_change(&breaker, tripped);
+    }
+}
+
+fn log_breaker_change(breaker: &SubmitBreaker, tripped: bool) {
+    record_kaspad_degraded(tripped);
+    if tripped {
+        error!(
+            "{} {}",
+            LogColors::api("[API]"),
+            LogColors::error(&format!(
+                "===== KASPAD DEGRADED ===== {:.0}% of block submits failed in the last {:?}{}",
+                breaker.error_pct(),
+                breaker.config.window,
+                if breaker.config.pause_jobs { ", pausing new jobs" } else { "; check the node is synced and on the right network" }
+            ))
+        );
+    } else {
+        info!(
+            "{} kaspad block submits recovered{}",
+            LogColors::api("[API]"),
+            if breaker.config.pause_jobs { ", resuming jobs" } else { "" }
+        );
+    }
+}
+
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Templates older than block_wait_time * this factor (and at least STALE_TEMPLATE_MIN_AGE) are reported as stale
+const STALE_TEMPLATE_FACTOR: u32 = 10;
+const STALE_TEMPLATE_MIN_AGE: Duration = Duration::from_secs(30);
+const STALE_TEMPLATE_WARN_INTERVAL: Duration = Duration::from_secs(60);
+/// Remote app reported to kaspad for the startup check template
+const STARTUP_CHECK_APP: &str = "startup-check";
+/// Longest block_tag kaspad accepts as template extra data: its 204-byte coinbase payload
+/// limit minus the fixed header (19 bytes) and the longest miner script (35 bytes)
+pub const MAX_BLOCK_TAG_BYTES: usize = 150;
+
+/// Delay before reconnect attempt `attempt` (0-based): base * 2^attempt, capped at max
+pub fn reconnect_backoff(attempt: u32, base: Duration, max: Duration) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt)).min(max)
+}
+
+/// Spread a delay over [delay/2, delay] so several bridges don't hammer a restarted kaspad in lockstep
+fn with_jitter(delay: Duration, fraction: f64) -> Duration {
+    delay.mul_f64(0.5 + 0.5 * fraction.clamp(0.0, 1.0))
+}
+
+fn jitter_fraction() -> f64 {
+    (uuid::Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0
+}
+
+/// Whether kaspad has been silent long enough that miners are likely working on outdated templates
+fn template_is_stale(age: Duration, block_wait_time: Duration) -> bool {
+    age > block_wait_time.saturating_mul(STALE_TEMPLATE_FACTOR).max(STALE_TEMPLATE_MIN_AGE)
+}
+
+/// Likely cause of a failed startup check, going by the error text
+fn startup_check_hint(error: &str) -> &'static str {
+    let error = error.to_lowercase();
+    if error.contains("not synced") {
+        "kaspad is not synced yet and refuses to serve templates"
+    } else if error.contains("unauthenticated") || error.contains("permission denied") {
+        "kaspad rejected the request (authentication)"
+    } else if error.contains("could not decode address") || error.contains("invalid address") {
+        "the wallet address is not valid on kaspad's network"
+    } else if error.contains("connection refused") || error.contains("transport") || error.contains("unavailable") {
+        "kaspad is unreachable (wrong kaspad_address or port?)"
+    } else {
+        "kaspad did not serve a block template"
+    }
+}
+
+/// Address prefix miners should be using for a kaspad network id ("mainnet", "testnet-10", ...)
+pub fn address_prefix_for_network(network_id: &str) -> Option<&'static str> {
+    let network = network_id.to_lowercase();
+    if network.starts_with("mainnet") {
+        Some("kaspa")
+    } else if network.starts_with("testnet") {
+        Some("kaspatest")
+    } else if network.starts_with("devnet") {
+        Some("kaspadev")
+    } else if network.starts_with("simnet") {
+        Some("kaspasim")
+    } else {
+        None
+    }
+}
+
+/// A Kaspa network the bridge can be pinned to with the `network` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KaspaNetwork {
+    Mainnet,
+    Testnet10,
+    Testnet11,
+    Devnet,
+    Simnet,
+}
+
+impl KaspaNetwork {
+    /// Network id as kaspad reports it
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KaspaNetwork::Mainnet => "mainnet",
+            KaspaNetwork::Testnet10 => "testnet-10",
+            KaspaNetwork::Testnet11 => "testnet-11",
+            KaspaNetwork::Devnet => "devnet",
+            KaspaNetwork::Simnet => "simnet",
+        }
+    }
+
+    /// Bech32 prefix of wallet addresses on this network
+    pub fn address_prefix(&self) -> &'static str {
+        match self {
+            KaspaNetwork::Mainnet => "kaspa",
+            KaspaNetwork::Testnet10 | KaspaNetwork::Testnet11 => "kaspatest",
+            KaspaNetwork::Devnet => "kaspadev",
+            KaspaNetwork::Simnet => "kaspasim",
+        }
+    }
+}
+
+impl std::str::FromStr for KaspaNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "mainnet" => Ok(KaspaNetwork::Mainnet),
+            "testnet-10" | "testnet10" => Ok(KaspaNetwork::Testnet10),
+            "testnet-11" | "testnet11" => Ok(KaspaNetwork::Testnet11),
+            "devnet" => Ok(KaspaNetwork::Devnet),
+            "simnet" => Ok(KaspaNetwork::Simnet),
+            other => Err(format!("unknown network '{}', expected mainnet, testnet-10, testnet-11, devnet or simnet", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for KaspaNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+static NETWORK: Mutex<Option<KaspaNetwork>> = Mutex::new(None);
+
+/// Pin the bridge to one network: kaspad must report it and difficulties use its pow limit (process-wide,
+/// all instances share kaspad; None follows whatever kaspad is on). Wallet prefixes are checked per instance
+pub fn set_network(network: Option<KaspaNetwork>) {
+    *NETWORK.lock() = network;
+}
+
+/// The configured network, None when the bridge follows whatever kaspad is on
+pub fn configured_network() -> Option<KaspaNetwork> {
+    *NETWORK.lock()
+}
+
+static TEMPLATE_BITS_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Warn once when a template's difficulty bits fall outside what the network allows: every share and
+/// block would be judged against a target that makes no sense there
+fn check_template_bits(bits: u32) {
+    let params = crate::difficulty::difficulty_params();
+    if !params.is_valid_bits(bits) && !TEMPLATE_BITS_WARNED.swap(true, Ordering::Relaxed) {
+        error!(
+            "block template bits 0x{:08x} encode a target outside {}'s pow limit, check the network setting",
+            bits,
+            configured_network().unwrap_or(KaspaNetwork::Mainnet)
+        );
+    }
+}
+
+/// Loud warning when kaspad is not on the configured network: every template and submit would be for the wrong chain
+fn check_node_network(network_id: &str) {
+    let Some(network) = configured_network() else { return };
+    if network_id.parse::<KaspaNetwork>().ok() != Some(network) {
+        error!("!!! ============================================================ !!!");
+        error!("!!! network is set to {} but kaspad reports {} !!!", network, network_id);
+        error!("!!! check kaspad_address / kaspad's --testnet --netsuffix flags !!!");
+        error!("!!! ============================================================ !!!");
+    }
+}
+
 /// Kaspa API client wrapper using RPC client
 /// Both use gRPC under the hood, but through an RPC client wrapper abstraction
 pub struct KaspaApi {
-    client: Arc<GrpcClient>,
+    address: String,
+    client: Mutex<Arc<GrpcClient>>,
+    notification_tx: mpsc::UnboundedSender<Notification>,
     notification_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<Notification>>>>,
     connected: Arc<Mutex<bool>>,
+    block_tag: Vec<u8>, // Embedded in the coinbase payload of every template we request
+    last_good_templates: LastGoodTemplates,
+    last_template_fetch: Mutex<Option<std::time::Instant>>, // Last template fetched in full, what ticker mode ages by
+    submit_pool: Mutex<Option<Arc<KaspadPool>>>,            // kaspad_pool nodes blocks are spread over, None = primary only
+    broadcast_blocks: AtomicBool,                           // Submit to every healthy pool node at once (broadcast_blocks)
 }
 
 impl KaspaApi {
     /// Create a new Kaspa API client
-    pub async fn new(address: String, _block_wait_time: Duration) -> Result<Arc<Self>> {
+    /// With require_synced the bridge waits for kaspad to sync before starting, otherwise it only warns
+    /// block_tag is passed to kaspad as coinbase extra data and must fit in MAX_BLOCK_TAG_BYTES
+    pub async fn new(address: String, _block_wait_time: Duration, require_synced: bool, block_tag: String) -> Result<Arc<Self>> {
+        if block_tag.len() > MAX_BLOCK_TAG_BYTES {
+            anyhow::bail!("block_tag is {} bytes, kaspad accepts at most {}", block_tag.len(), MAX_BLOCK_TAG_BYTES);
+        }
+
         info!("Connecting to Kaspa node at {}", address);
 
         // GrpcClient requires explicit "grpc://" prefix for connection
@@ -58,21 +582,9 @@ impl KaspaApi {
         tracing::debug!("{} {} {}", LogColors::api("[API]"), LogColors::label("  - Address:"), &grpc_address);
         tracing::debug!("{} {} {}", LogColors::api("[API]"), LogColors::label("  - Protocol:"), "gRPC (via RPC client wrapper)");
 
-        // Connect to Kaspa node with grpc:// prefix, using extended request timeout and reconnection support
-        let client = Arc::new(
-            GrpcClient::connect_with_args(
-                NotificationMode::Direct,
-                grpc_address.clone(),
-                None,
-                true,
-                None,
-                false,
-                Some(500_000),
-                Default::default(),
-            )
-            .await
-            .context("Failed to connect to Kaspa node")?,
-        );
+        // Connect to Kaspa node with grpc:// prefix and subscribe to block template notifications
+        let (notification_tx, notification_rx) = mpsc::unbounded_channel();
+        let client = Self::connect_client(&grpc_address, notification_tx.clone()).await?;
 
         // Log successful connection (detailed logs moved to debug)
         tracing::debug!("{} {}", LogColors::api("[API]"), LogColors::block("✓ RPC Connection Established Successfully"));
@@ -84,33 +596,29 @@ impl KaspaApi {
             "gRPC (via RPC client wrapper)"
         );
 
-        // Start the client (no notify needed for Direct mode)
-        client.start(None).await;
-
-        // Subscribe to block template notifications
-        client
-            .start_notify(ListenerId::default(), NewBlockTemplateScope {}.into())
-            .await
-            .context("Failed to subscribe to block template notifications")?;
-
-        // Start receiving notifications
-        let notification_rx = {
-            let receiver = client.notification_channel_receiver();
-            // Convert async_channel::Receiver to tokio::sync::mpsc::UnboundedReceiver
-            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-            let receiver_clone = receiver.clone();
-            tokio::spawn(async move {
-                while let Ok(notification) = receiver_clone.recv().await {
-                    let _ = tx.send(notification);
-                }
-            });
-            Arc::new(Mutex::new(Some(rx)))
-        };
+        let api = Arc::new(Self {
+            address: grpc_address,
+            client: Mutex::new(client),
+            notification_tx,
+            notification_rx: Arc::new(Mutex::new(Some(notification_rx))),
+            connected: Arc::new(Mutex::new(true)),
+            block_tag: block_tag.into_bytes(),
+            last_good_templates: LastGoodTemplates::default(),
+            last_template_fetch: Mutex::new(None),
+            submit_pool: Mutex::new(None),
+            broadcast_blocks: AtomicBool::new(false),
+        });
+        record_kaspad_connected(true);
 
-        let api = Arc::new(Self { client, notification_rx, connected: Arc::new(Mutex::new(true)) });
+        // Report which network the node is on and whether it's usable
+        let synced = api.log_node_info().await;
 
         // Wait for node to sync
-        api.wait_for_sync(true).await?;
+        if require_synced {
+            api.wait_for_sync(true).await?;
+        } else if synced == Some(false) {
+            warn!("kaspad at {} is not synced, starting anyway (require_synced: false); jobs may be stale", api.address);
+        }
 
         // Start network stats thread
         let api_clone = Arc::clone(&api);
@@ -124,9 +632,223 @@ impl KaspaApi {
             api_clone.start_node_status_thread().await;
         });
 
+        // Start connection watchdog (reconnects with backoff when kaspad goes away)
+        let api_clone = Arc::clone(&api);
+        tokio::spawn(async move {
+            api_clone.start_reconnect_thread().await;
+        });
+        tokio::spawn(Self::start_offline_watch());
+
         Ok(api)
     }
 
+    /// Open a gRPC connection, subscribe to block template notifications and forward them to notification_tx
+    async fn connect_client(grpc_address: &str, notification_tx: mpsc::UnboundedSender<Notification>) -> Result<Arc<GrpcClient>> {
+        // Reconnection is handled by start_reconnect_thread, which also re-subscribes on the new connection
+        let timeout = kaspad_connect_timeout();
+        let client = Arc::new(
+            tokio::time::timeout(
+                timeout,
+                GrpcClient::connect_with_args(
+                    NotificationMode::Direct,
+                    grpc_address.to_string(),
+                    None,
+                    false,
+                    None,
+                    false,
+                    Some(500_000),
+                    Default::default(),
+                ),
+            )
+            .await
+            .map_err(|_| anyhow::anyhow!("no connection within {:?} (kaspad_connect_timeout_secs)", timeout))?
+            .context("Failed to connect to Kaspa node")?,
+        );
+
+        // Start the client (no notify needed for Direct mode)
+        client.start(None).await;
+
+        // Subscribe to block template notifications, unless force_ticker polls instead
+        if !force_ticker() {
+            client
+                .start_notify(ListenerId::default(), NewBlockTemplateScope {}.into())
+                .await
+                .context("Failed to subscribe to block template notifications")?;
+        }
+
+        // Forward notifications (async_channel::Receiver) into the long-lived tokio channel
+        let receiver = client.notification_channel_receiver();
+        tokio::spawn(async move {
+            while let Ok(notification) = receiver.recv().await {
+                if notification_tx.send(notification).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(client)
+    }
+
+    /// Log kaspad's network, sync state and virtual DAA score, returning the sync state if the node answered
+    async fn log_node_info(&self) -> Option<bool> {
+        let info = match self.client().get_server_info_call(None, GetServerInfoRequest {}).await {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("failed to query kaspad server info: {}", e);
+                return None;
+            }
+        };
+
+        let network_id = info.network_id.to_string();
+        info!(
+            "kaspad {} on {}: synced={}, virtual DAA score={}",
+            info.server_version, network_id, info.is_synced, info.virtual_daa_score
+        );
+        check_node_network(&network_id);
+        record_kaspad_sync_status(info.is_synced, info.virtual_daa_score);
+
+        let mut snapshot = NODE_STATUS.lock();
+        snapshot.is_synced = Some(info.is_synced);
+        snapshot.network_id = Some(network_id);
+        snapshot.server_version = Some(info.server_version);
+        snapshot.virtual_daa_score = Some(info.virtual_daa_score);
+
+        Some(info.is_synced)
+    }
+
+    /// Fetch one block template end to end, so a bridge that connected but would never get templates
+    /// fails at startup instead of accepting miners. The template pays `wallet`, or a throwaway
+    /// address on kaspad's network when none is configured. Without `require_synced` a node that is
+    /// still syncing only gets a warning, since it serves no templates until it is synced
+    pub async fn startup_check(&self, wallet: Option<&str>, require_synced: bool) -> Result<()> {
+        let info = self
+            .client()
+            .get_server_info_call(None, GetServerInfoRequest {})
+            .await
+            .map_err(|e| anyhow::anyhow!("{}: {}", startup_check_hint(&e.to_string()), e))?;
+
+        if !info.is_synced && !require_synced {
+            warn!("startup check: kaspad at {} is not synced, skipping the template fetch (require_synced: false)", self.address);
+            return Ok(());
+        }
+
+        let wallet = match wallet {
+            Some(wallet) => wallet.to_string(),
+            None => {
+                let network_id = info.network_id.to_string();
+                let prefix = address_prefix_for_network(&network_id)
+                    .ok_or_else(|| anyhow::anyhow!("kaspad reports an unknown network '{}'", network_id))?;
+                let prefix = Prefix::try_from(prefix).map_err(|e| anyhow::anyhow!("{}", e))?;
+                String::from(&Address::new(prefix, Version::PubKey, &[0u8; 32]))
+            }
+        };
+
+        let block = self
+            .get_block_template(&wallet, STARTUP_CHECK_APP, "")
+            .await
+            .map_err(|e| anyhow::anyhow!("{}: {}", startup_check_hint(&e.to_string()), e))?;
+        info!("startup check passed, kaspad at {} served a template at DAA score {}", self.address, block.header.daa_score);
+        Ok(())
+    }
+
+    /// Current gRPC client (replaced on reconnect)
+    fn client(&self) -> Arc<GrpcClient> {
+        Arc::clone(&self.client.lock())
+    }
+
+    fn set_connected(&self, connected: bool) {
+        *self.connected.lock() = connected;
+        record_kaspad_connected(connected);
+        let mut offline_since = KASPAD_OFFLINE_SINCE.lock();
+        if connected {
+            if let Some(since) = offline_since.take() {
+                info!("kaspad reachable again after {:.0}s offline", since.elapsed().as_secs_f64());
+            }
+        } else {
+            offline_since.get_or_insert_with(std::time::Instant::now);
+        }
+    }
+
+    /// Report how long kaspad has been unreachable and apply offline_policy once per outage, when the
+    /// outage outlasts offline_grace_secs
+    async fn start_offline_watch() {
+        let mut interval = tokio::time::interval(CONNECTION_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut acted = false;
+        loop {
+            interval.tick().await;
+            let offline_for = kaspad_offline_for();
+            record_kaspad_offline_seconds(offline_for.unwrap_or(Duration::ZERO));
+            let (policy, grace) = offline_policy();
+            if !offline_grace_over(offline_for, grace) {
+                acted = false;
+                continue;
+            }
+            if acted {
+                continue;
+            }
+            acted = true;
+            match policy {
+                OfflinePolicy::Hold => {
+                    warn!(
+                        "kaspad unreachable for {:?}, offline_policy hold: keeping {} miner(s) connected without new jobs",
+                        grace,
+                        crate::client_handler::connected_clients()
+                    );
+                }
+                OfflinePolicy::Disconnect => {
+                    let dropped = crate::admin_socket::disconnect_all_miners(crate::stratum_context::DisconnectReason::NodeOffline);
+                    warn!(
+                        "kaspad unreachable for {:?}, offline_policy disconnect: dropped {} miner(s), refusing new ones until it is back",
+                        grace, dropped
+                    );
+                }
+            }
+        }
+    }
+
+    /// Watch the gRPC connection and reconnect with capped, jittered exponential backoff
+    /// Stratum clients stay connected during the outage, they just get no new jobs until kaspad is back,
+    /// unless offline_policy says otherwise (see start_offline_watch)
+    async fn start_reconnect_thread(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(CONNECTION_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            if self.client().is_connected() {
+                continue;
+            }
+
+            self.set_connected(false);
+            warn!("lost connection to kaspad at {}, reconnecting", self.address);
+
+            let mut attempt = 0u32;
+            loop {
+                let delay = reconnect_backoff(attempt, RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY);
+                sleep(with_jitter(delay, jitter_fraction())).await;
+                attempt += 1;
+
+                match Self::connect_client(&self.address, self.notification_tx.clone()).await {
+                    Ok(client) => {
+                        let old = std::mem::replace(&mut *self.client.lock(), client);
+                        let _ = old.disconnect().await;
+                        self.set_connected(true);
+                        info!("reconnected to kaspad at {} after {} attempt(s)", self.address, attempt);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "kaspad reconnect attempt {} failed, retrying in ~{:?}: {}",
+                            attempt,
+                            reconnect_backoff(attempt, RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY),
+                            e
+                        );
+                    }
+                }
+            }
+        }
+    }
+
     /// Start network stats thread
     /// Fetches network stats every 30 seconds and records them in Prometheus
     async fn start_stats_thread(self: Arc<Self>) {
@@ -139,7 +861,7 @@ impl KaspaApi {
 
             // Get block DAG info
             // GetBlockDagInfoRequest is a unit struct, construct directly
-            let dag_response = match self.client.get_block_dag_info_call(None, GetBlockDagInfoRequest {}).await {
+            let dag_response = match self.client().get_block_dag_info_call(None, GetBlockDagInfoRequest {}).await {
                 Ok(r) => r,
                 Err(e) => {
                     warn!("failed to get network hashrate from kaspa, prom stats will be out of date: {}", e);
@@ -161,7 +883,7 @@ impl KaspaApi {
             // new(window_size: u32, start_hash: Option<RpcHash>)
             // RpcHash is the same as Hash, so we can use tip_hash directly
             let hashrate_response = match self
-                .client
+                .client()
                 .estimate_network_hashes_per_second_call(None, EstimateNetworkHashesPerSecondRequest::new(1000, tip_hash))
                 .await
             {
@@ -182,12 +904,13 @@ impl KaspaApi {
         loop {
             interval.tick().await;
 
-            let connected = self.client.is_connected();
+            let client = self.client();
+            let connected = client.is_connected();
 
-            let server_info_fut = self.client.get_server_info_call(None, GetServerInfoRequest {});
-            let dag_info_fut = self.client.get_block_dag_info_call(None, GetBlockDagInfoRequest {});
-            let peers_fut = self.client.get_connected_peer_info_call(None, GetConnectedPeerInfoRequest {});
-            let info_fut = self.client.get_info_call(None, GetInfoRequest {});
+            let server_info_fut = client.get_server_info_call(None, GetServerInfoRequest {});
+            let dag_info_fut = client.get_block_dag_info_call(None, GetBlockDagInfoRequest {});
+            let peers_fut = client.get_connected_peer_info_call(None, GetConnectedPeerInfoRequest {});
+            let info_fut = client.get_info_call(None, GetInfoRequest {});
 
             let (server_info, dag_info, peers_info, info_resp) = tokio::join!(server_info_fut, dag_info_fut, peers_fut, info_fut);
 
@@ -196,8 +919,9 @@ impl KaspaApi {
             snapshot.is_connected = connected;
 
             if let Ok(server_info) = server_info {
+                record_kaspad_sync_status(server_info.is_synced, server_info.virtual_daa_score);
                 snapshot.is_synced = Some(server_info.is_synced);
-                snapshot.network_id = Some(format!("{:?}", server_info.network_id));
+                snapshot.network_id = Some(server_info.network_id.to_string());
                 snapshot.server_version = Some(server_info.server_version);
                 snapshot.virtual_daa_score = Some(server_info.virtual_daa_score);
             }
@@ -211,7 +935,7 @@ impl KaspaApi {
                     snapshot.virtual_daa_score = Some(dag.virtual_daa_score);
                 }
                 if snapshot.network_id.is_none() {
-                    snapshot.network_id = Some(format!("{:?}", dag.network));
+                    snapshot.network_id = Some(dag.network.to_string());
                 }
             }
 
@@ -228,6 +952,94 @@ impl KaspaApi {
         }
     }
 
+    /// Spread block submissions over a kaspad_pool, or with `broadcast` submit each block to the primary and
+    /// every healthy pool node at once instead of the one whose turn it is
+    pub fn set_submit_pool(&self, pool: Arc<KaspadPool>, broadcast: bool) {
+        pool.start();
+        *self.submit_pool.lock() = Some(pool);
+        self.broadcast_blocks.store(broadcast, Ordering::Relaxed);
+    }
+
+    /// Send a block to the kaspad_pool node whose turn it is, or to the primary without a pool. Unless the
+    /// pool node accepted the block or already has it, it goes to the primary too; a node that errors is also
+    /// taken out of rotation
+    async fn submit_to_pool(&self, rpc_block: RpcRawBlock) -> Result<SubmitBlockResponse> {
+        let Some(pool) = self.submit_pool.lock().clone() else {
+            return self
+                .client()
+                .submit_block_call(None, SubmitBlockRequest::new(rpc_block, false))
+                .await
+                .context("Failed to submit block");
+        };
+        if self.broadcast_blocks.load(Ordering::Relaxed) {
+            return self.broadcast_block(pool, rpc_block).await;
+        }
+        if let SubmitTarget::Node { name, client } = pool.next_target(self.is_connected()) {
+            let result = client.submit_block_call(None, SubmitBlockRequest::new(rpc_block.clone(), false)).await;
+            let outcome = pool_submit_outcome(&result);
+            pool.record_submit(&name, outcome);
+            match &result {
+                _ if pool_submit_settled(outcome) => return result.context("Failed to submit block"),
+                Ok(response) => warn!(
+                    "{} kaspad_pool node {} rejected the block ({:?}), resubmitting to primary",
+                    LogColors::api("[API]"),
+                    name,
+                    response.report
+                ),
+                Err(e) => {
+                    warn!(
+                        "{} block submit to kaspad_pool node {} failed, resubmitting to primary: {}",
+                        LogColors::api("[API]"),
+                        name,
+                        e
+                    )
+                }
+            }
+        }
+        let result = self.client().submit_block_call(None, SubmitBlockRequest::new(rpc_block, false)).await;
+        pool.record_submit(PRIMARY_POOL_NODE, pool_submit_outcome(&result));
+        result.context("Failed to submit block")
+    }
+
+    /// Send a block to the primary and every healthy kaspad_pool node concurrently (broadcast_blocks).
+    /// Returns on the first acceptance; the remaining submits run on and are logged and counted as they
+    /// finish, most of them as duplicates once the block has spread
+    async fn broadcast_block(&self, pool: Arc<KaspadPool>, rpc_block: RpcRawBlock) -> Result<SubmitBlockResponse> {
+        let mut targets = vec![(PRIMARY_POOL_NODE.to_string(), self.client())];
+        targets.extend(pool.healthy_nodes());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for (name, client) in targets {
+            let (tx, rpc_block, pool) = (tx.clone(), rpc_block.clone(), Arc::clone(&pool));
+            tokio::spawn(async move {
+                let start = std::time::Instant::now();
+                let result = client.submit_block_call(None, SubmitBlockRequest::new(rpc_block, false)).await;
+                let outcome = pool_submit_outcome(&result);
+                pool.record_submit(&name, outcome);
+                match &result {
+                    Err(e) if outcome == "error" => {
+                        warn!("{} block broadcast to {} failed after {:?}: {}", LogColors::api("[API]"), name, start.elapsed(), e)
+                    }
+                    _ => info!("{} block broadcast to {}: {} after {:?}", LogColors::api("[API]"), name, outcome, start.elapsed()),
+                }
+                let _ = tx.send((outcome, result));
+            });
+        }
+        drop(tx);
+
+        let mut best: Option<(&'static str, std::result::Result<SubmitBlockResponse, _>)> = None;
+        while let Some((outcome, result)) = rx.recv().await {
+            if outcome == "accepted" {
+                return result.context("Failed to submit block");
+            }
+            if best.as_ref().is_none_or(|(b, _)| broadcast_outcome_rank(outcome) > broadcast_outcome_rank(b)) {
+                best = Some((outcome, result));
+            }
+        }
+        // Every target's task may have died before reporting (e.g. a panic), so no result is an error too
+        let (_, result) = best.ok_or_else(|| anyhow::anyhow!("Failed to submit block: no broadcast target reported a result"))?;
+        result.context("Failed to submit block")
+    }
+
     /// Submit a block
     pub async fn submit_block(&self, block: Block) -> Result<SubmitBlockResponse> {
         // Use kaspa_consensus_core::hashing::header::hash() for block hash calculation
@@ -255,8 +1067,19 @@ impl KaspaApi {
 
         // Submit block (don't allow non-DAA blocks)
         tracing::debug!("{} {}", LogColors::api("[API]"), "Calling submit_block via RPC client...");
-        let result =
-            self.client.submit_block_call(None, SubmitBlockRequest::new(rpc_block, false)).await.context("Failed to submit block");
+        let submit_start = std::time::Instant::now();
+        let result = self.submit_to_pool(rpc_block).await;
+        let outcome = match &result {
+            Ok(response) if matches!(response.report, SubmitBlockReport::Success) => "accepted",
+            Ok(_) => "rejected",
+            Err(_) => "error",
+        };
+        record_block_submit_latency(outcome, submit_start.elapsed());
+        // Losing a race to another miner's identical block says nothing about kaspad's health
+        let duplicate = matches!(&result, Err(e) if e.to_string().contains("ErrDuplicateBlock"));
+        if !duplicate {
+            record_submit_outcome(outcome != "accepted");
+        }
 
         match &result {
             Ok(response) => {
@@ -292,7 +1115,7 @@ impl KaspaApi {
 
                 // Optional: Check if block appears in tip hashes (verifies propagation)
                 // This is informational only - block may still propagate even if not immediately in tips
-                let client_clone = Arc::clone(&self.client);
+                let client_clone = self.client();
                 let block_hash_clone = block_hash.clone();
                 let block_hash_for_check = header::hash(&block.header); // Use the actual Hash type
                 tokio::spawn(async move {
@@ -392,7 +1215,7 @@ impl KaspaApi {
         }
 
         loop {
-            match self.client.get_sync_status().await {
+            match self.client().get_sync_status().await {
                 Ok(is_synced) => {
                     if is_synced {
                         if verbose {
@@ -433,7 +1256,8 @@ impl KaspaApi {
                 Address::try_from(wallet_addr).map_err(|e| anyhow::anyhow!("Could not decode address {}: {}", wallet_addr, e))?;
 
             // Request block template using RPC client wrapper
-            let response = match self.client.get_block_template_call(None, GetBlockTemplateRequest::new(address, vec![])).await {
+            let request = GetBlockTemplateRequest::new(address, self.block_tag.clone());
+            let response = match self.client().get_block_template_call(None, request).await {
                 Ok(r) => r,
                 Err(e) => {
                     if attempt < max_retries - 1 {
@@ -445,59 +1269,18 @@ impl KaspaApi {
                 }
             };
 
-            // Get RPC block from response
-            let rpc_block = response.block;
-
-            // Convert RpcRawBlock to Block
-            // The RpcRawBlock contains the block data that we need to convert
-            // The "Odd number of digits" error can occur here if hash fields have malformed hex strings
-            match Block::try_from(rpc_block) {
-                Ok(block) => {
-                    // Validate that we can serialize the block header
-                    // This catches "Odd number of digits" errors early
-                    // Convert error to String immediately to avoid Send issues
-                    let serialize_result = crate::hasher::serialize_block_header(&block).map_err(|e| e.to_string());
-
-                    match serialize_result {
-                        Ok(_) => {
-                            return Ok(block);
-                        }
-                        Err(error_str) => {
-                            if error_str.contains("Odd number of digits") {
-                                last_error = Some(format!("Block has malformed hash field: {}", error_str));
-                                if attempt < max_retries - 1 {
-                                    warn!(
-                                        "Block template has malformed hash field (attempt {}/{}), retrying...",
-                                        attempt + 1,
-                                        max_retries
-                                    );
-                                    sleep(Duration::from_millis(100 * (attempt + 1) as u64)).await;
-                                    continue;
-                                }
-                            }
-                            // If it's a different error, return it
-                            return Err(anyhow::anyhow!("Failed to serialize block header: {}", error_str));
-                        }
-                    }
+            // Convert RpcRawBlock to Block; the "Odd number of digits" error can occur here if hash fields
+            // have malformed hex strings
+            // Block's header and transactions are Arcs of their own, so taking it out of the cache's Arc is cheap
+            match self.last_good_templates.accept(wallet_addr, Block::try_from(response.block), attempt, max_retries) {
+                TemplateParse::Parsed(block) => {
+                    *self.last_template_fetch.lock() = Some(std::time::Instant::now());
+                    return Ok(Arc::unwrap_or_clone(block));
                 }
-                Err(e) => {
-                    let error_str = format!("{:?}", e);
-                    last_error = Some(error_str.clone());
-                    if error_str.contains("Odd number of digits") && attempt < max_retries - 1 {
-                        warn!(
-                            "Block conversion failed with 'Odd number of digits' error (attempt {}/{}), retrying...",
-                            attempt + 1,
-                            max_retries
-                        );
-                        sleep(Duration::from_millis(100 * (attempt + 1) as u64)).await;
-                        continue;
-                    }
-                    // If the error contains "Odd number of digits", provide more context
-                    if error_str.contains("Odd number of digits") {
-                        return Err(anyhow::anyhow!("Failed to convert RPC block to Block after {} attempts: {} - This usually indicates a malformed hash field in the block template from the Kaspa node. The block may have a hash with an odd-length hex string.", max_retries, error_str));
-                    } else {
-                        return Err(anyhow::anyhow!("Failed to convert RPC block to Block: {}", error_str));
-                    }
+                TemplateParse::Unusable(result) => return result.map(Arc::unwrap_or_clone),
+                TemplateParse::Retry(error) => {
+                    last_error = Some(error);
+                    sleep(Duration::from_millis(100 * (attempt + 1) as u64)).await;
                 }
             }
         }
@@ -513,7 +1296,7 @@ impl KaspaApi {
         let addresses = parsed_addresses.map_err(|e| anyhow::anyhow!("Failed to parse addresses: {:?}", e))?;
 
         let utxos = self
-            .client
+            .client()
             .get_utxos_by_addresses_call(None, kaspa_rpc_core::GetUtxosByAddressesRequest::new(addresses))
             .await
             .context("Failed to get UTXOs by addresses")?;
@@ -548,8 +1331,48 @@ impl KaspaApi {
             let mut restart_channel = true;
             let mut ticker = tokio::time::interval(block_wait_time);
             ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut last_template = std::time::Instant::now();
+            let mut last_stale_warning: Option<std::time::Instant> = None;
+            // Ticker mode: templates are being polled, either forced or because notifications went quiet
+            let forced_ticker = force_ticker();
+            let mut ticker_mode = forced_ticker;
+            record_template_ticker_mode(ticker_mode);
+            let refresh_interval = template_refresh();
+            // interval() panics on zero; the branch below is off then anyway
+            let mut refresh = tokio::time::interval(refresh_interval.max(Duration::from_secs(1)));
+            refresh.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            if forced_ticker {
+                info!("force_ticker: template notifications off, polling kaspad every {:?}", block_wait_time);
+            }
 
             loop {
+                // Polling gets no notifications; a template fetched since counts as the new one
+                if ticker_mode {
+                    if let Some(fetched) = api_clone.last_template_fetch.lock().filter(|fetched| *fetched > last_template) {
+                        last_template = fetched;
+                        NODE_STATUS.lock().last_template = Some(fetched);
+                    }
+                }
+                let age = last_template.elapsed();
+                record_block_template_age(age);
+                if template_is_stale(age, block_wait_time)
+                    && last_stale_warning.is_none_or(|t| t.elapsed() >= STALE_TEMPLATE_WARN_INTERVAL)
+                {
+                    warn!(
+                        "no new block template from kaspad for {:.0}s (block_wait_time {:?}), miners may be on stale work",
+                        age.as_secs_f64(),
+                        block_wait_time
+                    );
+                    last_stale_warning = Some(std::time::Instant::now());
+                }
+
+                // kaspad is down, keep miners connected without new jobs until the reconnect thread is done
+                if !api_clone.is_connected() {
+                    sleep(block_wait_time).await;
+                    restart_channel = true;
+                    continue;
+                }
+
                 // Check sync state and reconnect if needed
                 if let Err(e) = api_clone.wait_for_sync(false).await {
                     error!("error checking kaspad sync state, attempting reconnect: {}", e);
@@ -563,8 +1386,8 @@ impl KaspaApi {
                 if restart_channel {
                     // In Go, RegisterForNewBlockTemplateNotifications is called here when restartChannel is true
                     // In Rust, we already subscribed in new(), and the notification channel persists
-                    // If the connection is lost, the gRPC client handles reconnection automatically
-                    // The notification subscription should be maintained by the gRPC client
+                    // If the connection is lost, start_reconnect_thread re-subscribes on the new client
+                    // and forwards into the same channel
                     // If notifications stop working, we'll fall back to ticker polling
                     restart_channel = false;
                 }
@@ -572,11 +1395,20 @@ impl KaspaApi {
                 // Wait for either notification or ticker timeout
                 tokio::select! {
                     // Notification received
-                    notification_result = rx.recv() => {
+                    notification_result = rx.recv(), if !forced_ticker => {
                         match notification_result {
                             Some(Notification::NewBlockTemplate(_)) => {
                                 // Drain any additional notifications
                                 while rx.try_recv().is_ok() {}
+                                last_template = std::time::Instant::now();
+                                NODE_STATUS.lock().last_template = Some(last_template);
+                                record_block_template_age(Duration::ZERO);
+                                record_block_template("notification");
+                                if ticker_mode {
+                                    info!("block template notifications resumed, leaving ticker fallback");
+                                    ticker_mode = false;
+                                    record_template_ticker_mode(false);
+                                }
 
                                 // Call callback
                                 block_cb();
@@ -597,8 +1429,24 @@ impl KaspaApi {
                     }
                     // Ticker timeout - manually check for new blocks
                     _ = ticker.tick() => {
+                        // The interval's immediate first tick is not a missed notification
+                        if !ticker_mode && last_template.elapsed() >= block_wait_time {
+                            info!("no block template notification for {:?}, falling back to polling kaspad", block_wait_time);
+                            ticker_mode = true;
+                            record_template_ticker_mode(true);
+                        }
+                        record_block_template("ticker");
                         block_cb();
                     }
+                    // Periodic refresh, only when no new template came in for the whole interval. Polling
+                    // already re-fetches templates, so there is nothing to add in ticker mode
+                    _ = refresh.tick(), if !refresh_interval.is_zero() => {
+                        if !ticker_mode && last_template.elapsed() >= refresh_interval {
+                            tracing::debug!("no new block template for {:?}, refreshing the current one", refresh_interval);
+                            record_block_template("refresh");
+                            block_cb();
+                        }
+                    }
                 }
             }
         });
@@ -639,3 +1487,186 @@ impl KaspaApiTrait for KaspaApi {
             .map_err(|e| Box::new(std::io::Error::other(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submit_breaker_trips_and_recovers() {
+        let config = SubmitBreakerConfig { error_pct: 50.0, window: Duration::from_secs(60), min_submits: 3, pause_jobs: true };
+        let mut breaker = SubmitBreaker::new(config);
+        let start = std::time::Instant::now();
+
+        // Too few submits to judge, even if they all failed
+        assert_eq!(breaker.record(true, start), None);
+        assert_eq!(breaker.record(true, start), None);
+        assert!(!breaker.is_tripped());
+
+        assert_eq!(breaker.record(false, start + Duration::from_secs(1)), Some(true));
+        assert!(breaker.is_tripped());
+        assert_eq!(breaker.record(true, start + Duration::from_secs(2)), None);
+
+        // Successes pull the rate back under the threshold
+        assert_eq!(breaker.record(false, start + Duration::from_secs(3)), None);
+        assert_eq!(breaker.record(false, start + Duration::from_secs(4)), None);
+        assert_eq!(breaker.record(false, start + Duration::from_secs(5)), Some(false));
+
+        // Failures age out of the window
+        let mut breaker = SubmitBreaker::new(config);
+        for _ in 0..3 {
+            breaker.record(true, start);
+        }
+        assert!(breaker.is_tripped());
+        assert_eq!(breaker.evaluate(start + Duration::from_secs(61)), Some(false));
+    }
+
+    #[test]
+    fn test_submit_breaker_disabled() {
+        let mut breaker = SubmitBreaker::new(SubmitBreakerConfig { error_pct: 0.0, ..Default::default() });
+        for _ in 0..10 {
+            assert_eq!(breaker.record(true, std::time::Instant::now()), None);
+        }
+        assert!(!breaker.is_tripped());
+    }
+
+    #[test]
+    fn test_offline_policy() {
+        assert_eq!("hold".parse::<OfflinePolicy>(), Ok(OfflinePolicy::Hold));
+        assert_eq!(" Disconnect ".parse::<OfflinePolicy>(), Ok(OfflinePolicy::Disconnect));
+        assert!("failover".parse::<OfflinePolicy>().is_err());
+
+        let grace = Duration::from_secs(30);
+        assert!(!offline_grace_over(None, grace), "connected");
+        assert!(!offline_grace_over(Some(Duration::from_secs(29)), grace));
+        assert!(offline_grace_over(Some(Duration::from_secs(30)), grace));
+        assert!(offline_grace_over(Some(Duration::ZERO), Duration::ZERO), "zero grace acts right away");
+    }
+
+    #[test]
+    fn test_template_is_stale() {
+        let wait = Duration::from_millis(1000);
+        assert!(!template_is_stale(Duration::from_secs(5), wait));
+        // Short block_wait_time still allows STALE_TEMPLATE_MIN_AGE before complaining
+        assert!(!template_is_stale(Duration::from_secs(29), wait));
+        assert!(template_is_stale(Duration::from_secs(31), wait));
+        assert!(!template_is_stale(Duration::from_secs(45), Duration::from_secs(5)));
+        assert!(template_is_stale(Duration::from_secs(51), Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_address_prefix_for_network() {
+        assert_eq!(address_prefix_for_network("mainnet"), Some("kaspa"));
+        assert_eq!(address_prefix_for_network("testnet-10"), Some("kaspatest"));
+        assert_eq!(address_prefix_for_network("devnet"), Some("kaspadev"));
+        assert_eq!(address_prefix_for_network("simnet"), Some("kaspasim"));
+        assert_eq!(address_prefix_for_network("unknown"), None);
+
+        assert_eq!("testnet-11".parse::<KaspaNetwork>(), Ok(KaspaNetwork::Testnet11));
+        assert_eq!("Testnet_10".parse::<KaspaNetwork>(), Ok(KaspaNetwork::Testnet10));
+        assert_eq!(KaspaNetwork::Testnet11.address_prefix(), "kaspatest");
+        assert_eq!(KaspaNetwork::Simnet.to_string(), "simnet");
+        assert!("testnet".parse::<KaspaNetwork>().is_err(), "a testnet is named by its suffix");
+    }
+
+    #[test]
+    fn test_startup_check_hints() {
+        assert!(startup_check_hint("Rpc error: node is not synced").contains("not synced"));
+        assert!(startup_check_hint("status: Unavailable, message: \"tcp connect error: Connection refused\"").contains("unreachable"));
+        assert!(startup_check_hint("Could not decode address kaspa:qq: checksum").contains("wallet address"));
+        assert!(startup_check_hint("status: Unauthenticated").contains("authentication"));
+        assert_eq!(startup_check_hint("something else"), "kaspad did not serve a block template");
+    }
+
+    #[test]
+    fn test_unparseable_template_serves_last_good() {
+        use kaspa_consensus_core::header::Header;
+        crate::prom::init_metrics();
+        let parse_errors = || -> f64 {
+            prometheus::gather()
+                .iter()
+                .filter(|family| family.get_name() == "ks_template_parse_error_counter")
+                .flat_map(|family| family.get_metric().iter().map(|m| m.get_counter().get_value()))
+                .sum()
+        };
+        let before = parse_errors();
+        let templates = LastGoodTemplates::default();
+        // What Block::try_from gives get_block_template for a template it can't read
+        let malformed = || Err::<Block, _>("RpcError: unexpected field in block template");
+
+        // Nothing to fall back to yet: the error goes to the caller, which skips this broadcast
+        assert!(matches!(templates.accept("kaspa:wallet", malformed(), 0, 3), TemplateParse::Unusable(Err(_))));
+
+        // A template that converts from RPC is served and becomes the fallback
+        let good = Block::new(Header::from_precomputed_hash(kaspa_hashes::Hash::from_bytes([3; 32]), vec![]), vec![]);
+        let rpc_block: RpcRawBlock = (&good).into();
+        let TemplateParse::Parsed(parsed) = templates.accept("kaspa:wallet", Block::try_from(rpc_block), 0, 3) else {
+            panic!("a well-formed template must parse");
+        };
+
+        // The next one is malformed: miners keep the last good job
+        let TemplateParse::Unusable(Ok(served)) = templates.accept("kaspa:wallet", malformed(), 0, 3) else {
+            panic!("expected the last good template");
+        };
+        assert_eq!(served.header.hash, parsed.header.hash);
+        assert!(
+            matches!(templates.accept("kaspa:other", malformed(), 0, 3), TemplateParse::Unusable(Err(_))),
+            "fallbacks are per wallet"
+        );
+
+        // Malformed hex is asked for again while attempts remain, then falls back too
+        let odd = || Err::<Block, _>("Odd number of digits");
+        assert!(matches!(templates.accept("kaspa:wallet", odd(), 0, 3), TemplateParse::Retry(_)));
+        assert!(matches!(templates.accept("kaspa:wallet", odd(), 2, 3), TemplateParse::Unusable(Ok(_))));
+
+        assert!(parse_errors() - before >= 4.0);
+    }
+
+    #[test]
+    fn test_pool_rejection_falls_back_to_primary() {
+        let rejected: std::result::Result<SubmitBlockResponse, String> =
+            Ok(SubmitBlockResponse { report: SubmitBlockReport::Reject(kaspa_rpc_core::SubmitBlockRejectReason::IsInIBD) });
+        let duplicate: std::result::Result<SubmitBlockResponse, String> = Err("ErrDuplicateBlock".to_string());
+        let success: std::result::Result<SubmitBlockResponse, String> = Ok(SubmitBlockResponse { report: SubmitBlockReport::Success });
+        assert!(!pool_submit_settled(pool_submit_outcome(&rejected)));
+        assert!(!pool_submit_settled(pool_submit_outcome(&Err::<SubmitBlockResponse, _>("transport error"))));
+        assert!(pool_submit_settled(pool_submit_outcome(&duplicate)));
+        assert!(pool_submit_settled(pool_submit_outcome(&success)));
+    }
+
+    #[test]
+    fn test_broadcast_outcome_rank() {
+        let mut outcomes = vec!["error", "duplicate", "rejected", "accepted"];
+        outcomes.sort_by_key(|o| std::cmp::Reverse(broadcast_outcome_rank(o)));
+        assert_eq!(outcomes, vec!["accepted", "duplicate", "rejected", "error"]);
+    }
+
+    #[test]
+    fn test_reconnect_backoff_caps() {
+        let base = Duration::from_millis(500);
+        let max = Duration::from_secs(30);
+
+        let schedule: Vec<Duration> = (0..10).map(|attempt| reconnect_backoff(attempt, base, max)).collect();
+        assert_eq!(schedule[0], Duration::from_millis(500));
+        assert_eq!(schedule[1], Duration::from_secs(1));
+        assert_eq!(schedule[2], Duration::from_secs(2));
+        assert_eq!(schedule[5], Duration::from_secs(16));
+        assert_eq!(schedule[6], max);
+        assert!(schedule.windows(2).all(|w| w[0] <= w[1]));
+
+        // Large attempt counts must not overflow
+        assert_eq!(reconnect_backoff(u32::MAX, base, max), max);
+    }
+
+    #[test]
+    fn test_reconnect_jitter_bounds() {
+        let delay = Duration::from_secs(30);
+        assert_eq!(with_jitter(delay, 0.0), Duration::from_secs(15));
+        assert_eq!(with_jitter(delay, 1.0), delay);
+        assert_eq!(with_jitter(delay, 7.0), delay);
+        for _ in 0..100 {
+            let jittered = with_jitter(delay, jitter_fraction());
+            assert!(jittered >= Duration::from_secs(15) && jittered <= delay);
+        }
+    }
+}
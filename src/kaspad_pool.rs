@@ -0,0 +1,245 @@
+use crate::prom::{record_kaspad_pool_node_healthy, record_kaspad_pool_submit};
+use kaspa_grpc_client::GrpcClient;
+use kaspa_rpc_core::notify::mode::NotificationMode;
+use kaspa_rpc_core::{api::rpc::RpcApi, GetServerInfoRequest};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Default health_check_secs for a kaspad_pool node
+pub const DEFAULT_POOL_HEALTH_CHECK_SECS: u64 = 10;
+
+/// Consecutive failed health checks before a node leaves the rotation
+const POOL_UNHEALTHY_AFTER: u32 = 2;
+
+/// Name the primary kaspad (kaspad_address) goes by in pool metrics and logs
+pub const PRIMARY_POOL_NODE: &str = "primary";
+
+/// An extra kaspad that takes a weighted share of block submissions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolNodeConfig {
+    pub address: String,
+    pub weight: u32,
+    pub health_check: Duration, // Zero = only checked until it first connects
+}
+
+/// Smooth weighted round-robin (as in nginx): over any window each node is picked in proportion to
+/// its weight, and picks of a heavy node are spread out rather than bunched
+#[derive(Debug, Clone)]
+pub struct WeightedRoundRobin {
+    weights: Vec<i64>,
+    current: Vec<i64>,
+}
+
+impl WeightedRoundRobin {
+    pub fn new(weights: &[u32]) -> Self {
+        Self { weights: weights.iter().map(|&w| w as i64).collect(), current: vec![0; weights.len()] }
+    }
+
+    /// Next node among the usable ones; None when no usable node has any weight
+    pub fn next(&mut self, usable: &[bool]) -> Option<usize> {
+        let mut total = 0;
+        let mut best: Option<usize> = None;
+        for i in 0..self.weights.len() {
+            if !usable.get(i).copied().unwrap_or(false) || self.weights[i] == 0 {
+                continue;
+            }
+            self.current[i] += self.weights[i];
+            total += self.weights[i];
+            if best.is_none_or(|b| self.current[i] > self.current[b]) {
+                best = Some(i);
+            }
+        }
+        let best = best?;
+        self.current[best] -= total;
+        Some(best)
+    }
+}
+
+/// Where one block submission goes
+pub enum SubmitTarget {
+    Primary,
+    Node { name: String, client: Arc<GrpcClient> },
+}
+
+struct PoolNode {
+    address: String,
+    health_check: Duration,
+    client: Mutex<Option<Arc<GrpcClient>>>,
+    healthy: AtomicBool,
+}
+
+/// Spreads block submissions over the primary kaspad and the kaspad_pool nodes by weight. Templates
+/// never come from here: they stay on the primary so every job builds on the same view of the DAG
+pub struct KaspadPool {
+    nodes: Vec<PoolNode>,
+    rotation: Mutex<WeightedRoundRobin>,
+}
+
+impl KaspadPool {
+    /// Nodes start out of rotation until their first health check connects
+    pub fn new(primary_weight: u32, nodes: Vec<PoolNodeConfig>) -> Arc<Self> {
+        let mut weights = vec![primary_weight];
+        weights.extend(nodes.iter().map(|n| n.weight));
+        Arc::new(Self {
+            nodes: nodes
+                .into_iter()
+                .map(|n| PoolNode {
+                    address: n.address,
+                    health_check: n.health_check,
+                    client: Mutex::new(None),
+                    healthy: AtomicBool::new(false),
+                })
+                .collect(),
+            rotation: Mutex::new(WeightedRoundRobin::new(&weights)),
+        })
+    }
+
+    /// Connect every node and keep checking the ones with a health_check interval
+    pub fn start(self: &Arc<Self>) {
+        for idx in 0..self.nodes.len() {
+            let pool = Arc::clone(self);
+            tokio::spawn(async move { pool.run_health_checks(idx).await });
+        }
+    }
+
+    /// Pick the node for the next submission; the primary is used whenever no pool node is usable
+    pub fn next_target(&self, primary_connected: bool) -> SubmitTarget {
+        let mut usable = vec![primary_connected];
+        usable.extend(self.nodes.iter().map(|n| n.healthy.load(Ordering::Acquire)));
+        match self.rotation.lock().next(&usable) {
+            Some(idx) if idx > 0 => {
+                let node = &self.nodes[idx - 1];
+                match node.client.lock().clone() {
+                    Some(client) => SubmitTarget::Node { name: node.address.clone(), client },
+                    None => SubmitTarget::Primary,
+                }
+            }
+            _ => SubmitTarget::Primary,
+        }
+    }
+
+    /// Every pool node currently in rotation, whatever its weight (broadcast_blocks)
+    pub fn healthy_nodes(&self) -> Vec<(String, Arc<GrpcClient>)> {
+        self.nodes
+            .iter()
+            .filter(|n| n.healthy.load(Ordering::Acquire))
+            .filter_map(|n| n.client.lock().clone().map(|client| (n.address.clone(), client)))
+            .collect()
+    }
+
+    /// Count a submission to `name` and take a node that errored out of rotation until its next good check
+    pub fn record_submit(&self, name: &str, outcome: &str) {
+        record_kaspad_pool_submit(name, outcome);
+        if outcome != "error" {
+            return;
+        }
+        if let Some(node) = self.nodes.iter().find(|n| n.address == name) {
+            if node.healthy.swap(false, Ordering::AcqRel) {
+                warn!("kaspad_pool node {} failed a block submit, out of rotation until it passes a health check", name);
+                record_kaspad_pool_node_healthy(&node.address, false);
+            }
+        }
+    }
+
+    async fn run_health_checks(&self, idx: usize) {
+        let node = &self.nodes[idx];
+        let mut failures = 0u32;
+        loop {
+            let healthy = self.check_node(node).await;
+            failures = if healthy { 0 } else { failures + 1 };
+            // A node that has never passed joins on its first good check; a member needs repeated failures to leave
+            let in_rotation = node.healthy.load(Ordering::Acquire);
+            if healthy && !in_rotation {
+                info!("kaspad_pool node {} is healthy, added to block submit rotation", node.address);
+                node.healthy.store(true, Ordering::Release);
+                record_kaspad_pool_node_healthy(&node.address, true);
+            } else if in_rotation && failures >= POOL_UNHEALTHY_AFTER {
+                warn!("kaspad_pool node {} failed {} health checks, removed from block submit rotation", node.address, failures);
+                node.healthy.store(false, Ordering::Release);
+                record_kaspad_pool_node_healthy(&node.address, false);
+            }
+
+            if node.health_check.is_zero() {
+                if healthy {
+                    return;
+                }
+                // Unchecked nodes still need one connection to join
+                tokio::time::sleep(Duration::from_secs(DEFAULT_POOL_HEALTH_CHECK_SECS)).await;
+            } else {
+                tokio::time::sleep(node.health_check).await;
+            }
+        }
+    }
+
+    /// Healthy = connected, answering and synced. Reconnects a dropped client first
+    async fn check_node(&self, node: &PoolNode) -> bool {
+        let existing = node.client.lock().clone().filter(|c| c.is_connected());
+        let client = match existing {
+            Some(client) => client,
+            None => match connect_pool_client(&node.address).await {
+                Ok(client) => {
+                    *node.client.lock() = Some(Arc::clone(&client));
+                    client
+                }
+                Err(e) => {
+                    tracing::debug!("kaspad_pool node {} unreachable: {}", node.address, e);
+                    return false;
+                }
+            },
+        };
+        match client.get_server_info_call(None, GetServerInfoRequest {}).await {
+            Ok(info) if info.is_synced => true,
+            Ok(_) => {
+                tracing::debug!("kaspad_pool node {} is not synced", node.address);
+                false
+            }
+            Err(e) => {
+                tracing::debug!("kaspad_pool node {} health check failed: {}", node.address, e);
+                false
+            }
+        }
+    }
+}
+
+/// Plain RPC connection for submissions, without the template notifications the primary subscribes to
+async fn connect_pool_client(address: &str) -> anyhow::Result<Arc<GrpcClient>> {
+    let grpc_address = if address.starts_with("grpc://") { address.to_string() } else { format!("grpc://{}", address) };
+    let client = GrpcClient::connect_with_args(
+        NotificationMode::Direct,
+        grpc_address,
+        None,
+        false,
+        None,
+        false,
+        Some(500_000),
+        Default::default(),
+    )
+    .await?;
+    client.start(None).await;
+    Ok(Arc::new(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_round_robin() {
+        let mut rr = WeightedRoundRobin::new(&[5, 1, 1]);
+        let all = [true, true, true];
+        let picks: Vec<_> = (0..7).map(|_| rr.next(&all).unwrap()).collect();
+        assert_eq!(picks, vec![0, 0, 1, 0, 2, 0, 0], "heavy node spread out, not bunched");
+
+        // An unhealthy node's share goes to the rest
+        let mut rr = WeightedRoundRobin::new(&[1, 2]);
+        let picks: Vec<_> = (0..4).map(|_| rr.next(&[true, false]).unwrap()).collect();
+        assert_eq!(picks, vec![0, 0, 0, 0]);
+
+        let mut rr = WeightedRoundRobin::new(&[0, 3]);
+        assert_eq!(rr.next(&[true, false]), None, "zero weight never takes submits");
+        assert_eq!(rr.next(&[true, true]), Some(1));
+    }
+}
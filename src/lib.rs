@@ -1,33 +1,53 @@
+pub mod admin_socket;
+pub mod block_submit_queue;
 pub mod client_handler;
 pub mod default_client;
+pub mod difficulty;
 pub mod errors;
 pub mod hasher;
 pub mod jsonrpc_event;
 pub mod kaspaapi;
+pub mod kaspad_pool;
+pub mod log_buffer;
 pub mod log_colors;
+pub mod log_compress;
+pub mod log_sampler;
 pub mod mining_state;
 pub mod pow_diagnostic;
 pub mod prom;
 pub mod share_handler;
+pub mod share_log;
+pub mod solo_fallback;
 pub mod stratum_context;
 pub mod stratum_listener;
 pub mod stratum_server;
+pub mod webhook;
 
 #[cfg(test)]
 pub mod mock_connection;
 
+pub use admin_socket::*;
+pub use block_submit_queue::*;
 pub use client_handler::*;
 pub use default_client::*;
+pub use difficulty::*;
 pub use errors::*;
 pub use hasher::*;
 pub use jsonrpc_event::*;
 pub use kaspaapi::*;
+pub use kaspad_pool::*;
+pub use log_buffer::*;
+pub use log_compress::*;
+pub use log_sampler::*;
 pub use mining_state::*;
 pub use prom::{WorkerContext, *};
 pub use share_handler::*;
+pub use share_log::*;
+pub use solo_fallback::*;
 pub use stratum_context::*;
 pub use stratum_listener::*;
 pub use stratum_server::*;
+pub use webhook::*;
 
 #[cfg(test)]
 pub use mock_connection::*;
@@ -0,0 +1,144 @@
+use parking_lot::Mutex;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Most lines log_buffer_size may keep
+pub const MAX_LOG_BUFFER_SIZE: usize = 100_000;
+
+/// Longer lines are cut here so a single huge message can't blow the memory bound
+const MAX_LINE_LEN: usize = 2048;
+
+static LOG_BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+
+/// The most recent log lines, oldest overwritten first. Every slot has its own lock, so a
+/// writer only ever waits on another writer that wrapped all the way around to the same slot,
+/// or on a reader copying that one line
+pub struct LogBuffer {
+    slots: Box<[Mutex<(u64, String)>]>, // (sequence number, line); sequence 0 = never written
+    next: AtomicU64,
+}
+
+impl LogBuffer {
+    pub fn new(size: usize) -> Self {
+        let slots = (0..size.clamp(1, MAX_LOG_BUFFER_SIZE)).map(|_| Mutex::new((0, String::new()))).collect();
+        Self { slots, next: AtomicU64::new(1) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn push(&self, line: &str) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut line = line.trim_end_matches(['\r', '\n']);
+        if line.len() > MAX_LINE_LEN {
+            let mut end = MAX_LINE_LEN;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line = &line[..end];
+        }
+        let mut slot = self.slots[(seq % self.slots.len() as u64) as usize].lock();
+        // A writer that lapped this one while it waited already stored a newer line
+        if slot.0 < seq {
+            slot.0 = seq;
+            slot.1.clear();
+            slot.1.push_str(line);
+        }
+    }
+
+    /// Up to `limit` most recent lines, oldest first
+    pub fn recent(&self, limit: usize) -> Vec<String> {
+        let mut lines: Vec<(u64, String)> = self.slots.iter().map(|slot| slot.lock().clone()).filter(|(seq, _)| *seq != 0).collect();
+        lines.sort_unstable_by_key(|(seq, _)| *seq);
+        let skip = lines.len().saturating_sub(limit);
+        lines.into_iter().skip(skip).map(|(_, line)| line).collect()
+    }
+}
+
+/// Start keeping the last `size` log lines (once per process); the returned writer feeds a tracing fmt layer
+pub fn install_log_buffer(size: usize) -> LogBufferWriter {
+    LogBufferWriter(LOG_BUFFER.get_or_init(|| LogBuffer::new(size)))
+}
+
+/// Recent log lines as plain text, None when log_buffer_size is 0
+pub fn recent_logs(limit: usize) -> Option<String> {
+    let buffer = LOG_BUFFER.get()?;
+    let mut text = buffer.recent(limit).join("\n");
+    text.push('\n');
+    Some(text)
+}
+
+/// HTTP response for `GET /logs[?lines=N]`: the buffered lines as plain text, all of them unless N is given
+pub fn logs_http_response(request: &str) -> String {
+    let limit = request
+        .split_whitespace()
+        .nth(1)
+        .and_then(|path| path.split_once("?lines="))
+        .and_then(|(_, n)| n.split('&').next()?.parse().ok())
+        .unwrap_or(usize::MAX);
+    match recent_logs(limit) {
+        Some(text) => {
+            format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}", text.len(), text)
+        }
+        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 32\r\n\r\nlog buffer off (log_buffer_size)".to_string(),
+    }
+}
+
+/// MakeWriter for the log buffer layer
+#[derive(Clone, Copy)]
+pub struct LogBufferWriter(&'static LogBuffer);
+
+/// One formatted event; stored as a line when the fmt layer is done writing it
+pub struct LogLine {
+    buffer: &'static LogBuffer,
+    bytes: Vec<u8>,
+}
+
+impl io::Write for LogLine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogLine {
+    fn drop(&mut self) {
+        if !self.bytes.is_empty() {
+            self.buffer.push(&String::from_utf8_lossy(&self.bytes));
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBufferWriter {
+    type Writer = LogLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogLine { buffer: self.0, bytes: Vec::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_buffer_keeps_most_recent() {
+        let buffer = LogBuffer::new(3);
+        assert!(buffer.recent(10).is_empty());
+        for i in 1..=5 {
+            buffer.push(&format!("line {}\n", i));
+        }
+        assert_eq!(buffer.recent(10), vec!["line 3", "line 4", "line 5"]);
+        assert_eq!(buffer.recent(2), vec!["line 4", "line 5"]);
+
+        buffer.push(&"é".repeat(MAX_LINE_LEN));
+        assert_eq!(buffer.recent(1)[0].len(), MAX_LINE_LEN);
+    }
+}
@@ -14,24 +14,37 @@ impl LogColors {
     /// Initialize color support detection
     /// Should be called once at startup
     pub fn init() {
-        // Check if NO_COLOR environment variable is set (common convention to disable colors)
-        let no_color = std::env::var("NO_COLOR").is_ok();
-
         // Check if stderr is a terminal (where tracing logs go)
         let is_terminal = io::stderr().is_terminal();
 
         // On Windows, enable virtual terminal processing
         let _ = Self::enable_windows_vt();
 
-        // Enable colors only if:
-        // 1. NO_COLOR is not set
-        // 2. We're writing to a terminal
-        // 3. On Windows, also check if ANSI is supported (Windows 10+)
-        let enabled = !no_color && is_terminal && Self::check_windows_ansi_support();
+        // Environment overrides first, then the TTY; on Windows ANSI must also be supported (Windows 10+)
+        let force_color = std::env::var("FORCE_COLOR").ok();
+        let no_color = std::env::var("NO_COLOR").ok();
+        let enabled =
+            Self::colors_wanted(force_color.as_deref(), no_color.as_deref(), is_terminal) && Self::check_windows_ansi_support();
 
         COLORS_ENABLED.store(enabled, Ordering::Relaxed);
     }
 
+    /// Color decision from the environment: FORCE_COLOR > NO_COLOR > TTY detection
+    /// FORCE_COLOR enables colors even when not writing to a terminal (CI, docker logs) unless it is "0" or "false";
+    /// NO_COLOR disables them whatever its value (https://no-color.org)
+    fn colors_wanted(force_color: Option<&str>, no_color: Option<&str>, is_terminal: bool) -> bool {
+        if let Some(force) = force_color {
+            let force = force.trim();
+            if !(force == "0" || force.eq_ignore_ascii_case("false")) {
+                return true;
+            }
+        }
+        if no_color.is_some() {
+            return false;
+        }
+        is_terminal
+    }
+
     /// Enable virtual terminal processing on Windows
     /// This allows ANSI escape codes to work in Windows console
     #[cfg(windows)]
@@ -164,3 +177,33 @@ impl LogColors {
         format!("[Instance {}]", instance_num)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_follow_tty_by_default() {
+        assert!(LogColors::colors_wanted(None, None, true));
+        assert!(!LogColors::colors_wanted(None, None, false));
+    }
+
+    #[test]
+    fn test_no_color_disables_any_value() {
+        assert!(!LogColors::colors_wanted(None, Some("1"), true));
+        assert!(!LogColors::colors_wanted(None, Some(""), true));
+        assert!(!LogColors::colors_wanted(None, Some("1"), false));
+    }
+
+    #[test]
+    fn test_force_color_overrides() {
+        assert!(LogColors::colors_wanted(Some("1"), None, false));
+        assert!(LogColors::colors_wanted(Some(""), None, false));
+        // FORCE_COLOR wins over NO_COLOR
+        assert!(LogColors::colors_wanted(Some("1"), Some("1"), false));
+        // FORCE_COLOR=0 opts out of forcing and falls back to the other rules
+        assert!(!LogColors::colors_wanted(Some("0"), None, false));
+        assert!(LogColors::colors_wanted(Some("false"), None, true));
+        assert!(!LogColors::colors_wanted(Some("0"), Some("1"), true));
+    }
+}
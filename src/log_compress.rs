@@ -0,0 +1,489 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Log files the bridge writes: rustbridge_<unix secs>.log, a new one per start and per log_rotation
+pub const LOG_FILE_PREFIX: &str = "rustbridge_";
+pub const LOG_FILE_EXTENSION: &str = "log";
+
+/// zstd level for rolled-over logs; the slower levels gain little on log text
+const ZSTD_LEVEL: i32 = 9;
+
+/// A file written to more recently than this may belong to another bridge running in the same
+/// directory (or an older one that doesn't lock its log), so it is neither compressed nor pruned
+pub const ROLLED_OVER_MIN_IDLE: Duration = Duration::from_secs(3600);
+
+/// Locks on the files this process is writing, held until they roll over or the process exits
+#[cfg(unix)]
+static ACTIVE_LOCKS: parking_lot::Mutex<Vec<(PathBuf, File)>> = parking_lot::Mutex::new(Vec::new());
+
+/// How rolled-over log files are compressed. The file being written is always left plain for tailing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogCompression {
+    /// Rolled-over logs stay as they are (default)
+    None,
+    /// .log.gz, readable with zcat / zless
+    Gzip,
+    /// .log.zst, smaller and faster than gzip, readable with zstdcat
+    Zstd,
+}
+
+impl LogCompression {
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            LogCompression::None => None,
+            LogCompression::Gzip => Some("gz"),
+            LogCompression::Zstd => Some("zst"),
+        }
+    }
+}
+
+impl std::str::FromStr for LogCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(LogCompression::None),
+            "gzip" | "gz" => Ok(LogCompression::Gzip),
+            "zstd" | "zst" => Ok(LogCompression::Zstd),
+            other => Err(format!("unknown log_compress '{}', expected none, gzip or zstd", other)),
+        }
+    }
+}
+
+/// How often a running bridge moves on to a new log file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogRotation {
+    /// One file per start
+    Never,
+    Hourly,
+    /// Default
+    Daily,
+}
+
+impl LogRotation {
+    fn period(self) -> Option<Duration> {
+        match self {
+            LogRotation::Never => None,
+            LogRotation::Hourly => Some(Duration::from_secs(3600)),
+            LogRotation::Daily => Some(Duration::from_secs(24 * 3600)),
+        }
+    }
+}
+
+impl std::str::FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "never" | "none" => Ok(LogRotation::Never),
+            "hourly" => Ok(LogRotation::Hourly),
+            "" | "daily" => Ok(LogRotation::Daily),
+            other => Err(format!("unknown log_rotation '{}', expected never, hourly or daily", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogRotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogRotation::Never => write!(f, "never"),
+            LogRotation::Hourly => write!(f, "hourly"),
+            LogRotation::Daily => write!(f, "daily"),
+        }
+    }
+}
+
+impl std::fmt::Display for LogCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogCompression::None => write!(f, "none"),
+            LogCompression::Gzip => write!(f, "gzip"),
+            LogCompression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Plain bridge logs in `dir` other than `active`. Other bridges may share the directory, so check
+/// is_in_use before touching one
+pub fn rolled_over_logs(dir: &Path, active: &Path) -> io::Result<Vec<PathBuf>> {
+    rolled_over_files(dir, active, LOG_FILE_PREFIX, LOG_FILE_EXTENSION)
+}
+
+/// Lock the file this process writes (shared lock, held until exit), so another bridge starting in
+/// the same directory sees it as in use. Unix only: Windows locks would block the writer itself
+pub fn hold_active_lock(active: &Path) {
+    #[cfg(unix)]
+    match File::open(active).and_then(|file| file.try_lock_shared().map(|_| file).map_err(io::Error::from)) {
+        Ok(file) => ACTIVE_LOCKS.lock().push((active.to_path_buf(), file)),
+        Err(e) => tracing::debug!("log_compress: could not lock {}: {}", active.display(), e),
+    }
+    #[cfg(not(unix))]
+    let _ = active;
+}
+
+/// Drop the lock hold_active_lock took on `path` once this process stops writing it
+pub fn release_active_lock(path: &Path) {
+    #[cfg(unix)]
+    ACTIVE_LOCKS.lock().retain(|(locked, _)| locked != path);
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Whether another process may still be writing `path`: it was modified within `min_idle`, or a running
+/// bridge holds its lock
+pub fn is_in_use(path: &Path, min_idle: Duration) -> bool {
+    let recently_written = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|modified| SystemTime::now().duration_since(modified).ok().is_none_or(|idle| idle < min_idle))
+        .unwrap_or(true);
+    if recently_written {
+        return true;
+    }
+    #[cfg(unix)]
+    if let Ok(file) = File::open(path) {
+        return matches!(file.try_lock(), Err(std::fs::TryLockError::WouldBlock));
+    }
+    false
+}
+
+/// Bridge logs of earlier runs in `dir`, plain or compressed, oldest first
+pub fn archived_logs(dir: &Path, active: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut logs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name() != active.file_name() && path.is_file() && log_start_secs(&path).is_some() {
+            logs.push(path);
+        }
+    }
+    logs.sort_by_key(|path| log_start_secs(path));
+    Ok(logs)
+}
+
+/// Start time in a bridge log name: rustbridge_<secs>.log, .log.gz or .log.zst
+fn log_start_secs(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix(LOG_FILE_PREFIX)?;
+    let (secs, suffix) = rest.split_once('.')?;
+    matches!(suffix.strip_prefix(LOG_FILE_EXTENSION)?, "" | ".gz" | ".zst").then(|| secs.parse().ok())?
+}
+
+/// Delete the oldest bridge logs in `dir` so at most `keep` from earlier runs remain, compressed ones
+/// included. 0 keeps everything; files still in use are never deleted
+pub fn prune_archived_logs(dir: &Path, active: &Path, keep: usize, min_idle: Duration) -> io::Result<Vec<PathBuf>> {
+    if keep == 0 {
+        return Ok(Vec::new());
+    }
+    let logs = archived_logs(dir, active)?;
+    let excess = logs.len().saturating_sub(keep);
+    let mut removed = Vec::new();
+    for log in logs.into_iter().take(excess) {
+        if !is_in_use(&log, min_idle) {
+            std::fs::remove_file(&log)?;
+            removed.push(log);
+        }
+    }
+    Ok(removed)
+}
+
+/// Files named `<prefix><unix secs>.<extension>` in `dir` other than `active`, for any per-start file
+/// series. Other files sharing the prefix (shares_backup.csv next to shares_<secs>.csv) are not part of it
+pub fn rolled_over_files(dir: &Path, active: &Path, prefix: &str, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut logs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_log = path.file_name().and_then(|n| n.to_str()).is_some_and(|name| {
+            name.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(extension)?.strip_suffix('.'))
+                .is_some_and(|secs| !secs.is_empty() && secs.bytes().all(|b| b.is_ascii_digit()))
+        });
+        if is_log && path.file_name() != active.file_name() && path.is_file() {
+            logs.push(path);
+        }
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+/// Compress one log next to itself (rustbridge_1.log -> rustbridge_1.log.gz) and remove the original.
+/// A partial output from a failed run is removed so it is never mistaken for a complete archive
+pub fn compress_log_file(path: &Path, compression: LogCompression) -> io::Result<Option<PathBuf>> {
+    let Some(extension) = compression.extension() else {
+        return Ok(None);
+    };
+    let mut target = path.as_os_str().to_owned();
+    target.push(".");
+    target.push(extension);
+    let target = PathBuf::from(target);
+
+    let result = (|| -> io::Result<()> {
+        let mut input = BufReader::new(File::open(path)?);
+        let output = BufWriter::new(File::create(&target)?);
+        match compression {
+            LogCompression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()
+            }
+            LogCompression::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(output, ZSTD_LEVEL)?;
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()
+            }
+            LogCompression::None => unreachable!(),
+        }
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&target);
+        return Err(e);
+    }
+    std::fs::remove_file(path)?;
+    Ok(Some(target))
+}
+
+/// Compress the rolled-over logs in `dir`, then keep at most `keep` logs of earlier runs (log_retention,
+/// 0 = all), on a blocking worker so startup and logging never wait on it. Compressed logs keep their
+/// rustbridge_<secs> name, so they still sort and age like the plain ones
+pub fn spawn_log_compression(dir: PathBuf, active: PathBuf, compression: LogCompression, keep: usize) {
+    tokio::task::spawn_blocking(move || {
+        compress_rolled_over(&dir, &active, LOG_FILE_PREFIX, LOG_FILE_EXTENSION, compression);
+        prune_logs(&dir, &active, keep);
+    });
+}
+
+fn prune_logs(dir: &Path, active: &Path, keep: usize) {
+    match prune_archived_logs(dir, active, keep, ROLLED_OVER_MIN_IDLE) {
+        Ok(removed) => {
+            for log in removed {
+                tracing::debug!("log_retention: removed {}", log.display());
+            }
+        }
+        Err(e) => tracing::warn!("log_retention: could not prune {}: {}", dir.display(), e),
+    }
+}
+
+/// The bridge log writer: rustbridge_<secs>.log in `dir`, replaced by a fresh file once per log_rotation
+/// period. The file it leaves is this process's own, so it is compressed right away (not after
+/// ROLLED_OVER_MIN_IDLE) and the series pruned, on a separate thread so logging never waits on it
+pub struct RollingLog {
+    dir: PathBuf,
+    path: PathBuf,
+    file: File,
+    opened: SystemTime,
+    rotation: LogRotation,
+    compression: LogCompression,
+    keep: usize,
+}
+
+impl RollingLog {
+    pub fn open(dir: &Path, rotation: LogRotation, compression: LogCompression, keep: usize) -> io::Result<Self> {
+        let opened = SystemTime::now();
+        let (path, file) = Self::create(dir, opened)?;
+        Ok(RollingLog { dir: dir.to_path_buf(), path, file, opened, rotation, compression, keep })
+    }
+
+    /// The file being written now
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn create(dir: &Path, now: SystemTime) -> io::Result<(PathBuf, File)> {
+        let secs = now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = dir.join(format!("{}{}.{}", LOG_FILE_PREFIX, secs, LOG_FILE_EXTENSION));
+        let file = File::options().create(true).append(true).open(&path)?;
+        hold_active_lock(&path);
+        Ok((path, file))
+    }
+
+    fn roll_due(&self, now: SystemTime) -> bool {
+        self.rotation.period().is_some_and(|period| now.duration_since(self.opened).is_ok_and(|age| age >= period))
+    }
+
+    fn roll(&mut self, now: SystemTime) -> io::Result<()> {
+        let (path, file) = Self::create(&self.dir, now)?;
+        let _ = self.file.flush();
+        self.file = file;
+        self.opened = now;
+        let rolled = std::mem::replace(&mut self.path, path);
+        release_active_lock(&rolled);
+
+        let (dir, active, compression, keep) = (self.dir.clone(), self.path.clone(), self.compression, self.keep);
+        std::thread::spawn(move || {
+            match compress_log_file(&rolled, compression) {
+                Ok(Some(compressed)) => tracing::debug!("log_compress: {} -> {}", rolled.display(), compressed.display()),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("log_compress: could not compress {}: {}", rolled.display(), e),
+            }
+            prune_logs(&dir, &active, keep);
+        });
+        Ok(())
+    }
+}
+
+impl Write for RollingLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = SystemTime::now();
+        if self.roll_due(now) {
+            // Keep writing the old file if a new one can't be created; try again next period
+            if let Err(e) = self.roll(now) {
+                eprintln!("log_rotation: could not start a new log file in {}: {}", self.dir.display(), e);
+                self.opened = now;
+            }
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// spawn_log_compression for another series of per-start files, e.g. the share log (no retention). The
+/// caller locks `active` itself
+pub fn spawn_rolled_over_compression(dir: PathBuf, active: PathBuf, prefix: String, extension: String, compression: LogCompression) {
+    if compression == LogCompression::None {
+        return;
+    }
+    tokio::task::spawn_blocking(move || compress_rolled_over(&dir, &active, &prefix, &extension, compression));
+}
+
+/// Compress every file of a series that no process is still writing
+fn compress_rolled_over(dir: &Path, active: &Path, prefix: &str, extension: &str, compression: LogCompression) {
+    if compression == LogCompression::None {
+        return;
+    }
+    let logs = match rolled_over_files(dir, active, prefix, extension) {
+        Ok(logs) => logs,
+        Err(e) => {
+            tracing::warn!("log_compress: could not list {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    for log in logs {
+        if is_in_use(&log, ROLLED_OVER_MIN_IDLE) {
+            tracing::debug!("log_compress: skipping {}, another process may still be writing it", log.display());
+            continue;
+        }
+        match compress_log_file(&log, compression) {
+            Ok(Some(compressed)) => tracing::debug!("log_compress: {} -> {}", log.display(), compressed.display()),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("log_compress: could not compress {}: {}", log.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_compress_rolled_over_logs_only() {
+        let dir = std::env::temp_dir().join(format!("ks_log_compress_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("rustbridge_100.log");
+        let active = dir.join("rustbridge_200.log");
+        let other = dir.join("notes.log");
+        for path in [&old, &active, &other] {
+            std::fs::write(path, "share accepted\n".repeat(100)).unwrap();
+        }
+
+        assert_eq!(rolled_over_logs(&dir, &active).unwrap(), vec![old.clone()]);
+        let compressed = compress_log_file(&old, LogCompression::Gzip).unwrap().unwrap();
+        assert_eq!(compressed, dir.join("rustbridge_100.log.gz"));
+        assert!(!old.exists() && active.exists() && other.exists());
+
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(&compressed).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "share accepted\n".repeat(100));
+        assert!(rolled_over_logs(&dir, &active).unwrap().is_empty(), "archives are not compressed again");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_live_logs_are_left_alone() {
+        let dir = std::env::temp_dir().join(format!("ks_log_in_use_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let other_bridge = dir.join("rustbridge_100.log");
+        std::fs::write(&other_bridge, "still logging\n").unwrap();
+
+        assert!(is_in_use(&other_bridge, ROLLED_OVER_MIN_IDLE), "written just now");
+        assert!(!is_in_use(&other_bridge, Duration::ZERO));
+        #[cfg(unix)]
+        {
+            hold_active_lock(&other_bridge);
+            assert!(is_in_use(&other_bridge, Duration::ZERO), "locked by a running bridge");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retention_counts_compressed_logs() {
+        let dir = std::env::temp_dir().join(format!("ks_log_retention_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let active = dir.join("rustbridge_400.log");
+        let names = ["rustbridge_100.log.gz", "rustbridge_90.log.zst", "rustbridge_300.log", "rustbridge_200.log.zst", "notes.log"];
+        for name in names.iter().chain(["rustbridge_400.log"].iter()) {
+            std::fs::write(dir.join(name), "x").unwrap();
+        }
+
+        let archived: Vec<_> = archived_logs(&dir, &active).unwrap().into_iter().map(|p| p.file_name().unwrap().to_owned()).collect();
+        assert_eq!(archived, ["rustbridge_90.log.zst", "rustbridge_100.log.gz", "rustbridge_200.log.zst", "rustbridge_300.log"]);
+
+        assert!(prune_archived_logs(&dir, &active, 2, ROLLED_OVER_MIN_IDLE).unwrap().is_empty(), "all still fresh");
+        let removed = prune_archived_logs(&dir, &active, 2, Duration::ZERO).unwrap();
+        assert_eq!(removed, vec![dir.join("rustbridge_90.log.zst"), dir.join("rustbridge_100.log.gz")]);
+        assert!(dir.join("rustbridge_200.log.zst").exists() && active.exists() && dir.join("notes.log").exists());
+        assert!(prune_archived_logs(&dir, &active, 0, Duration::ZERO).unwrap().is_empty(), "0 keeps everything");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rolling_log_compresses_on_roll() {
+        let dir = std::env::temp_dir().join(format!("ks_log_rolling_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut log = RollingLog::open(&dir, LogRotation::Hourly, LogCompression::Gzip, 0).unwrap();
+        let first = log.path().to_path_buf();
+        log.write_all(b"before\n").unwrap();
+
+        let later = log.opened + Duration::from_secs(3600);
+        assert!(!log.roll_due(log.opened + Duration::from_secs(3599)) && log.roll_due(later));
+        log.roll(later).unwrap();
+        log.write_all(b"after\n").unwrap();
+        assert_ne!(log.path(), first);
+        assert_eq!(std::fs::read_to_string(log.path()).unwrap(), "after\n");
+
+        #[cfg(unix)]
+        assert!(!ACTIVE_LOCKS.lock().iter().any(|(locked, _)| locked == &first), "the rolled file's lock is released");
+
+        // The original is removed once its .gz is complete
+        for _ in 0..250 {
+            if !first.exists() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let mut compressed = first.clone().into_os_string();
+        compressed.push(".gz");
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(PathBuf::from(compressed)).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "before\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_log_compression_parse() {
+        assert_eq!("zstd".parse::<LogCompression>(), Ok(LogCompression::Zstd));
+        assert_eq!("GZIP".parse::<LogCompression>(), Ok(LogCompression::Gzip));
+        assert_eq!("none".parse::<LogCompression>(), Ok(LogCompression::None));
+        assert!("bzip2".parse::<LogCompression>().is_err());
+        assert_eq!("hourly".parse::<LogRotation>(), Ok(LogRotation::Hourly));
+        assert_eq!("".parse::<LogRotation>(), Ok(LogRotation::Daily));
+        assert!("weekly".parse::<LogRotation>().is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// High-volume message logs that can be sampled. Connection setup, block-found and error logs never are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogCategory {
+    /// mining.submit from miners ([ASIC->BRIDGE])
+    Submit,
+    /// Responses to miners, mostly share results ([BRIDGE->ASIC])
+    Reply,
+    /// mining.notify and mining.set_difficulty to miners ([BRIDGE->ASIC])
+    Job,
+}
+
+impl LogCategory {
+    pub const ALL: [LogCategory; 3] = [LogCategory::Submit, LogCategory::Reply, LogCategory::Job];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl std::str::FromStr for LogCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "submit" => Ok(LogCategory::Submit),
+            "reply" => Ok(LogCategory::Reply),
+            "job" => Ok(LogCategory::Job),
+            other => Err(format!("unknown log_sample_rate category '{}', expected submit, reply or job", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogCategory::Submit => write!(f, "submit"),
+            LogCategory::Reply => write!(f, "reply"),
+            LogCategory::Job => write!(f, "job"),
+        }
+    }
+}
+
+/// Log 1 in N messages per category (1 = all)
+static SAMPLE_RATES: [AtomicU64; 3] = [AtomicU64::new(1), AtomicU64::new(1), AtomicU64::new(1)];
+static SEEN: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+
+/// Log only 1 in `rate` messages of a category at debug level (process-wide)
+pub fn set_log_sample_rate(category: LogCategory, rate: u64) {
+    SAMPLE_RATES[category.index()].store(rate.max(1), Ordering::Relaxed);
+}
+
+/// Whether this message's debug logs should be written. Call before building any log arguments;
+/// nothing is counted while debug logging is off
+pub fn log_sampled(category: LogCategory) -> bool {
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return false;
+    }
+    let rate = SAMPLE_RATES[category.index()].load(Ordering::Relaxed);
+    rate <= 1 || SEEN[category.index()].fetch_add(1, Ordering::Relaxed) % rate == 0
+}
+
+/// Category of an outgoing event by method; other methods are rare and always logged
+pub fn category_for_method(method: &str) -> Option<LogCategory> {
+    match method {
+        "mining.submit" => Some(LogCategory::Submit),
+        "mining.notify" | "mining.set_difficulty" => Some(LogCategory::Job),
+        _ => None,
+    }
+}
+
+/// log_sampled for a message by method, without sampling the rare ones
+pub fn log_sampled_method(method: &str) -> bool {
+    match category_for_method(method) {
+        Some(category) => log_sampled(category),
+        None => tracing::enabled!(tracing::Level::DEBUG),
+    }
+}
+
+/// log_sampled for a raw line from a miner, classified without parsing it
+pub fn log_sampled_line(line: &str) -> bool {
+    if line.contains("\"mining.submit\"") {
+        log_sampled(LogCategory::Submit)
+    } else {
+        tracing::enabled!(tracing::Level::DEBUG)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_category_parsing() {
+        assert_eq!("Submit".parse::<LogCategory>(), Ok(LogCategory::Submit));
+        assert_eq!("job".parse::<LogCategory>(), Ok(LogCategory::Job));
+        assert!("shares".parse::<LogCategory>().is_err());
+        assert_eq!(category_for_method("mining.set_difficulty"), Some(LogCategory::Job));
+        assert_eq!(category_for_method("mining.authorize"), None);
+    }
+
+    #[test]
+    fn test_one_in_n_logged_while_debug_on() {
+        let subscriber = tracing_subscriber::fmt().with_max_level(tracing::Level::DEBUG).with_writer(std::io::sink).finish();
+        tracing::subscriber::with_default(subscriber, || {
+            set_log_sample_rate(LogCategory::Reply, 4);
+            let logged = (0..40).filter(|_| log_sampled(LogCategory::Reply)).count();
+            set_log_sample_rate(LogCategory::Reply, 1);
+            assert_eq!(logged, 10);
+        });
+        assert!(!log_sampled(LogCategory::Reply), "nothing is logged with debug off");
+    }
+}
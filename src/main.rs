@@ -5,12 +5,13 @@ use kaspa_stratum_bridge::*;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
 use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-use yaml_rust::YamlLoader;
+use yaml_rust::{Yaml, YamlLoader};
 
 use kaspa_core::signals::Shutdown;
 use kaspa_utils::fd_budget;
@@ -35,8 +36,20 @@ struct Cli {
 
     #[arg(long, action = clap::ArgAction::Append)]
     node_arg: Vec<String>,
+
+    /// Validate the config, print the effective settings and exit without starting the bridge
+    #[arg(long)]
+    check_config: bool,
+
+    /// Document of a multi-document YAML config to load, by its `profile:` name
+    /// (default: $KASPA_BRIDGE_PROFILE, else the first document)
+    #[arg(long)]
+    profile: Option<String>,
 }
 
+/// Environment fallback for --profile
+const PROFILE_ENV: &str = "KASPA_BRIDGE_PROFILE";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 enum NodeMode {
     External,
@@ -91,21 +104,42 @@ fn split_shell_words(input: &str) -> Result<Vec<String>, anyhow::Error> {
     Ok(out)
 }
 
+/// Longest a ctrl-c shutdown waits for miner connections to finish closing
+const SHUTDOWN_DISCONNECT_WAIT: Duration = Duration::from_secs(2);
+
+/// Pause before the first kaspad_connect_retries retry; doubled after each failure up to MAX_KASPAD_RETRY_DELAY
+const KASPAD_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_KASPAD_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Pause before retry `attempt` (1-based): `first` doubled per earlier retry, capped at `max`
+fn kaspad_retry_delay(attempt: u32, first: Duration, max: Duration) -> Duration {
+    first.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1))).min(max)
+}
+
+/// Connect to kaspad, retrying up to `retries` more times with a growing pause, e.g. while a node
+/// started alongside the bridge (docker-compose, in-process) is still coming up
 async fn kaspa_api_with_retry(
     kaspad_address: String,
     block_wait_time: Duration,
+    require_synced: bool,
+    block_tag: String,
+    retries: u32,
+    first_delay: Duration,
+    max_delay: Duration,
 ) -> Result<Arc<kaspa_stratum_bridge::KaspaApi>, anyhow::Error> {
-    let mut last_err: Option<anyhow::Error> = None;
-    for _ in 0..60 {
-        match kaspa_stratum_bridge::KaspaApi::new(kaspad_address.clone(), block_wait_time).await {
+    let mut attempt = 0;
+    loop {
+        match kaspa_stratum_bridge::KaspaApi::new(kaspad_address.clone(), block_wait_time, require_synced, block_tag.clone()).await {
             Ok(api) => return Ok(api),
-            Err(e) => {
-                last_err = Some(anyhow::anyhow!("{}", e));
-                tokio::time::sleep(Duration::from_millis(500)).await;
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                let delay = kaspad_retry_delay(attempt, first_delay, max_delay);
+                tracing::warn!("kaspad at {} not reachable ({}), retry {}/{} in {:?}", kaspad_address, e, attempt, retries, delay);
+                tokio::time::sleep(delay).await;
             }
+            Err(e) => return Err(anyhow::anyhow!("{} (after {} attempts)", e, attempt + 1)),
         }
     }
-    Err(last_err.unwrap_or_else(|| anyhow::anyhow!("failed to connect to kaspad")))
 }
 
 /// Instance-specific configuration
@@ -113,6 +147,7 @@ async fn kaspa_api_with_retry(
 struct InstanceConfig {
     stratum_port: String,
     min_share_diff: u32,
+    start_diff: Option<u32>,   // Optional per-instance starting difficulty
     prom_port: Option<String>, // Optional per-instance prom port
     log_to_file: Option<bool>, // Optional per-instance logging
     // Instance-specific settings that can override global defaults
@@ -120,21 +155,103 @@ struct InstanceConfig {
     shares_per_min: Option<u32>,
     var_diff_stats: Option<bool>,
     pow2_clamp: Option<bool>,
+    difficulty_format: Option<kaspa_stratum_bridge::DifficultyFormat>,
+    max_connections: Option<usize>,
+    label: Option<String>, // Tags connections in logs and the "port" metric label, e.g. "high-diff"
 }
 
 /// Global configuration (shared across all instances)
 #[derive(Debug, Clone)]
 struct GlobalConfig {
     kaspad_address: String,
+    profile: String,                                        // `profile:` of the YAML document loaded (empty = untagged)
+    network: Option<kaspa_stratum_bridge::KaspaNetwork>,    // Pin wallets and kaspad to this network (None = follow kaspad)
+    kaspad_weight: u32,                                     // kaspad_address's share of block submits against kaspad_pool
+    kaspad_pool: Vec<kaspa_stratum_bridge::PoolNodeConfig>, // Extra nodes block submits are spread over (empty = primary only)
+    broadcast_blocks: bool,   // Submit every block to the primary and all healthy kaspad_pool nodes at once
+    submit_queue_size: usize, // Found blocks queued for the background submitter (0 = submit inline)
+    submit_queue_policy: kaspa_stratum_bridge::SubmitQueuePolicy,
     block_wait_time: Duration,
     print_stats: bool,
-    log_to_file: bool, // Default for instances that don't specify
+    stats_interval: Duration,
+    show_hashrate_estimate: bool, // Stats block lists the hashrate each worker's difficulty implies
+    log_to_file: bool,            // Default for instances that don't specify
+    log_rotation: kaspa_stratum_bridge::LogRotation, // How often a running bridge starts a new log file
+    log_compress: kaspa_stratum_bridge::LogCompression, // Compression for rolled-over log files
+    log_retention: usize,         // Log files of earlier runs to keep, compressed or not (0 = all)
+    log_buffer_size: usize,       // Recent log lines kept in memory for GET /logs (0 = off)
     health_check_port: String,
+    stats_port: String,                  // Plain-text stats report on connect (empty = disabled)
+    prom_auth: Option<(String, String)>, // Basic-auth user and password required on prom_port
+    admin_socket: String,                // Unix socket path for admin commands (empty = disabled)
+    webhook_url: Option<kaspa_stratum_bridge::WebhookUrl>,
+    share_log_path: String, // Empty = no share log
+    share_log_format: kaspa_stratum_bridge::ShareLogFormat,
     var_diff: bool,
     shares_per_min: u32,
     var_diff_stats: bool,
     extranonce_size: u8,
     pow2_clamp: bool,
+    log_notify_submit_rtt: bool,
+    dedup_templates: bool, // Skip notifies for templates that only moved the timestamp
+    job_history: u16,      // Recent jobs kept per connection for share validation
+    clean_jobs: kaspa_stratum_bridge::CleanJobsPolicy,
+    diff_change_strategy: kaspa_stratum_bridge::DiffChangeStrategy, // How vardiff retargets reach miners
+    duplicate_worker_policy: kaspa_stratum_bridge::DuplicateWorkerPolicy, // A wallet.worker authorizing twice
+    near_miss_ratio: f64,                                           // Fraction of network difficulty counted as a near miss (0 = off)
+    log_sample_rates: Vec<(kaspa_stratum_bridge::LogCategory, u64)>, // Categories logged 1 in N at debug (others: all)
+    account_shares_while_paused: bool,
+    reject_warmup: Duration,           // After authorize, rejects counted as warmup (zero = off)
+    reject_warmup_after_job: Duration, // After each new job, likewise
+    stale_warn_pct: f64,               // Warn about connections with a higher stale rate (0 = off), reloadable
+    difficulty_format: kaspa_stratum_bridge::DifficultyFormat,
+    nonce_byte_order: kaspa_stratum_bridge::NonceByteOrder,
+    timestamp_tolerance: Duration, // Zero = submitted header timestamps are ignored
+    share_reply_format: kaspa_stratum_bridge::ShareReplyFormat,
+    max_shares_per_sec: u32, // Per connection, 0 = unlimited
+    share_flood_action: kaspa_stratum_bridge::ShareFloodAction,
+    max_connections: usize, // 0 = unlimited
+    label_metrics_by_port: bool,
+    metrics_worker_labels: kaspa_stratum_bridge::WorkerLabelMode,
+    max_worker_name_len: usize,
+    extranonce_reuse_delay: Duration,
+    auto_extranonce_grow: bool,
+    partition_nonce: bool, // Timestamp lanes for miners without an extranonce prefix
+    extranonce_detection: kaspa_stratum_bridge::ExtranonceDetection, // How per-miner extranonce sizes are picked
+    honor_suggest_difficulty: bool,
+    start_diff: u32,   // 0 = start at min_share_diff
+    var_diff_min: u32, // 0 = no lower bound
+    var_diff_max: u32, // 0 = no upper bound
+    require_synced: bool,
+    kaspad_connect_retries: u32,                         // Extra attempts at the initial kaspad connection
+    kaspad_connect_timeout: Duration,                    // Limit on opening one kaspad connection
+    offline_policy: kaspa_stratum_bridge::OfflinePolicy, // What miners get while kaspad is unreachable
+    offline_grace: Duration,                             // How long kaspad may be unreachable before offline_policy applies
+    submit_breaker: kaspa_stratum_bridge::SubmitBreakerConfig,
+    max_line_bytes: usize,
+    socket_tuning: kaspa_stratum_bridge::SocketTuning, // Listen backlog and socket buffer sizes for stratum ports
+    worker_threads: usize,                             // Tokio worker threads (0 = one per CPU core)
+    max_blocking_threads: usize,                       // Cap on tokio's blocking thread pool
+    keepalive_interval: Duration,                      // Zero = keepalive off
+    keepalive_timeout: Duration,
+    authorize_timeout: Duration,     // Zero = unauthorized connections are kept
+    first_share_warn: Duration,      // Zero = no warning for connections without accepted shares
+    notify_latency_budget: Duration, // Warn when a template takes longer to reach every miner (zero = off)
+    maintenance_drain: Duration,     // SIGUSR1 maintenance disconnects miners after this long (zero = leave them)
+    var_diff_mode: kaspa_stratum_bridge::VarDiffMode,
+    var_diff_freeze: kaspa_stratum_bridge::VarDiffFreezePolicy,
+    var_diff_retarget: Duration, // How often vardiff evaluates each worker, reloadable
+    var_diff_hysteresis: f64,    // Percent change a retarget must exceed to be sent (0 = all), reloadable
+    var_diff_warmup_shares: u32, // Shares a new connection needs before vardiff retargets it (0 = none), reloadable
+    var_diff_warmup: Duration,   // ...or how long it waits without them, reloadable
+    block_tag: String,           // Coinbase extra data for found blocks (empty = none)
+    solo_fallback: bool,
+    solo_fallback_wallet: String,
+    fixed_wallet: String, // Every connection mines here, usernames only name workers (empty = parse usernames)
+    worker_overrides: Vec<kaspa_stratum_bridge::WorkerOverride>, // Per wallet/worker difficulty settings, first match wins
+    skip_startup_check: bool,
+    force_ticker: bool,         // Poll templates every block_wait_time, no notifications
+    template_refresh: Duration, // Re-broadcast the current template after this long without a new one (zero = off)
 }
 
 /// Bridge configuration (supports both single and multi-instance modes)
@@ -142,21 +259,286 @@ struct GlobalConfig {
 struct BridgeConfig {
     global: GlobalConfig,
     instances: Vec<InstanceConfig>,
+    unknown_keys: Vec<String>, // Unrecognized top-level keys, warned about at startup
+}
+
+/// Largest timestamp_tolerance_secs; beyond this a "skewed" clock is simply wrong
+const MAX_TIMESTAMP_TOLERANCE_SECS: i64 = 60;
+
+/// Largest submit_queue_size; a queue this deep means kaspad is not taking blocks at all
+const MAX_SUBMIT_QUEUE_SIZE: i64 = 4096;
+
+/// Bounds for socket_send_buffer_bytes / socket_recv_buffer_bytes
+const MIN_SOCKET_BUFFER_BYTES: i64 = 4096;
+const MAX_SOCKET_BUFFER_BYTES: i64 = 64 * 1024 * 1024;
+
+/// Largest worker_threads / max_blocking_threads
+const MAX_WORKER_THREADS: i64 = 1024;
+const MAX_BLOCKING_THREADS: i64 = 16384;
+
+/// Default max_blocking_threads, tokio's own default
+const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+
+/// Top-level config.yaml keys understood by the bridge (strict_config rejects anything else)
+const KNOWN_CONFIG_KEYS: &[&str] = &[
+    "strict_config",
+    "profile",
+    "kaspad_address",
+    "network",
+    "kaspad_weight",
+    "kaspad_pool",
+    "broadcast_blocks",
+    "submit_queue_size",
+    "submit_queue_policy",
+    "require_synced",
+    "kaspad_connect_retries",
+    "kaspad_connect_timeout_secs",
+    "offline_policy",
+    "offline_grace_secs",
+    "skip_startup_check",
+    "block_tag",
+    "solo_fallback",
+    "solo_fallback_wallet",
+    "fixed_wallet",
+    "worker_overrides",
+    "block_wait_time",
+    "force_ticker",
+    "template_refresh_secs",
+    "print_stats",
+    "stats_interval_secs",
+    "show_hashrate_estimate",
+    "log_to_file",
+    "log_rotation",
+    "log_compress",
+    "log_retention",
+    "log_buffer_size",
+    "health_check_port",
+    "stats_port",
+    "prom_auth_user",
+    "prom_auth_pass",
+    "admin_socket",
+    "webhook_url",
+    "share_log_path",
+    "share_log_format",
+    "var_diff",
+    "shares_per_min",
+    "var_diff_stats",
+    "var_diff_mode",
+    "ewma_alpha",
+    "var_diff_freeze_tolerance",
+    "var_diff_freeze_after",
+    "var_diff_retarget_secs",
+    "var_diff_hysteresis",
+    "var_diff_warmup_shares",
+    "var_diff_warmup_secs",
+    "honor_suggest_difficulty",
+    "var_diff_min",
+    "var_diff_max",
+    "pow2_clamp",
+    "extranonce_size",
+    "extranonce_reuse_delay_secs",
+    "auto_extranonce_grow",
+    "partition_nonce",
+    "extranonce_preferred_size",
+    "extranonce_min_size",
+    "extranonce_max_size",
+    "extranonce_honor_hints",
+    "log_notify_submit_rtt",
+    "dedup_templates",
+    "job_history",
+    "clean_jobs",
+    "diff_change_strategy",
+    "duplicate_worker_policy",
+    "near_miss_ratio",
+    "log_sample_rate",
+    "account_shares_while_paused",
+    "reject_warmup_secs",
+    "reject_warmup_after_job_ms",
+    "stale_warn_pct",
+    "difficulty_format",
+    "nonce_byte_order",
+    "timestamp_tolerance_secs",
+    "share_reply_format",
+    "max_shares_per_sec",
+    "share_flood_action",
+    "max_connections",
+    "label_metrics_by_port",
+    "metrics_worker_labels",
+    "max_worker_name_len",
+    "max_line_bytes",
+    "listen_backlog",
+    "socket_send_buffer_bytes",
+    "socket_recv_buffer_bytes",
+    "worker_threads",
+    "max_blocking_threads",
+    "keepalive_interval_secs",
+    "keepalive_timeout_secs",
+    "authorize_timeout_secs",
+    "first_share_warn_secs",
+    "notify_latency_budget_ms",
+    "maintenance_drain_secs",
+    "submit_breaker_error_pct",
+    "submit_breaker_window_secs",
+    "submit_breaker_min_submits",
+    "submit_breaker_pause_jobs",
+    "instances",
+    // Single-instance mode
+    "stratum_port",
+    "min_share_diff",
+    "start_diff",
+    "prom_port",
+];
+
+/// Actual local address of a bound listener for startup logs ("0.0.0.0:5555", "[::]:5555"),
+/// falling back to the configured string if the socket can't report it
+fn bound_address(listener: &tokio::net::TcpListener, configured: &str) -> String {
+    listener.local_addr().map(|a| a.to_string()).unwrap_or_else(|_| configured.to_string())
+}
+
+/// Longest listener label; it ends up as a metric label value on every worker series
+const MAX_LISTENER_LABEL_LEN: usize = 32;
+
+/// Listener labels are short printable tags ("high-diff", "s19-farm")
+fn parse_listener_label(label: &str) -> anyhow::Result<String> {
+    let label = label.trim();
+    if label.is_empty() || label.len() > MAX_LISTENER_LABEL_LEN || label.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return Err(anyhow::anyhow!(
+            "listener label {:?} must be 1-{} characters with no spaces or control characters",
+            label,
+            MAX_LISTENER_LABEL_LEN
+        ));
+    }
+    Ok(label.to_string())
+}
+
+/// Each listener needs its own port
+fn check_unique_ports(instances: &[InstanceConfig]) -> anyhow::Result<()> {
+    let mut ports = std::collections::HashSet::new();
+    for instance in instances {
+        if !ports.insert(&instance.stratum_port) {
+            return Err(anyhow::anyhow!("Duplicate stratum_port: {}", instance.stratum_port));
+        }
+    }
+    Ok(())
+}
+
+/// ":PORT" shorthand (all interfaces) for a bare port number; "HOST:PORT" binds exactly there
+fn normalize_listen_port(key: &str, port: &str) -> anyhow::Result<String> {
+    if key != "stratum_port" && kaspa_stratum_bridge::unix_socket_path(port).is_some() {
+        return Err(anyhow::anyhow!("invalid {}: unix sockets are only supported for stratum_port", key));
+    }
+    let port = if port.contains(':') { port.to_string() } else { format!(":{}", port) };
+    kaspa_stratum_bridge::validate_listen_address(&port).map_err(|e| anyhow::anyhow!("invalid {}: {}", key, e))?;
+    Ok(port)
+}
+
+/// An extranonce size setting, in bytes (0-3)
+fn extranonce_size_key(doc: &Yaml, key: &str) -> anyhow::Result<Option<u8>> {
+    match doc[key].as_i64() {
+        Some(size @ 0..=3) => Ok(Some(size as u8)),
+        Some(size) => Err(anyhow::anyhow!("{} must be between 0 and 3 bytes, got {}", key, size)),
+        None => Ok(None),
+    }
+}
+
+fn unknown_config_keys(doc: &Yaml) -> Vec<String> {
+    doc.as_hash()
+        .map(|hash| hash.keys().filter_map(|k| k.as_str()).filter(|k| !KNOWN_CONFIG_KEYS.contains(k)).map(str::to_string).collect())
+        .unwrap_or_default()
 }
 
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
             kaspad_address: "localhost:16110".to_string(),
+            profile: String::new(),
+            network: None,
+            kaspad_weight: 1,
+            kaspad_pool: Vec::new(),
+            broadcast_blocks: false,
+            submit_queue_size: kaspa_stratum_bridge::DEFAULT_SUBMIT_QUEUE_SIZE,
+            submit_queue_policy: kaspa_stratum_bridge::SubmitQueuePolicy::Wait,
             block_wait_time: Duration::from_millis(1000),
             print_stats: true,
+            stats_interval: kaspa_stratum_bridge::DEFAULT_STATS_PRINT_INTERVAL,
+            show_hashrate_estimate: false,
             log_to_file: true,
+            log_rotation: kaspa_stratum_bridge::LogRotation::Daily,
+            log_compress: kaspa_stratum_bridge::LogCompression::None,
+            log_retention: 0,
+            log_buffer_size: 0,
             health_check_port: String::new(),
+            stats_port: String::new(),
+            prom_auth: None,
+            admin_socket: String::new(),
+            webhook_url: None,
+            share_log_path: String::new(),
+            share_log_format: kaspa_stratum_bridge::ShareLogFormat::Jsonl,
             var_diff: true,
             shares_per_min: 20,
             var_diff_stats: false,
             extranonce_size: 0,
             pow2_clamp: false,
+            log_notify_submit_rtt: false,
+            dedup_templates: false,
+            job_history: kaspa_stratum_bridge::DEFAULT_JOB_HISTORY,
+            clean_jobs: kaspa_stratum_bridge::CleanJobsPolicy::Always,
+            diff_change_strategy: kaspa_stratum_bridge::DiffChangeStrategy::SetDifficulty,
+            duplicate_worker_policy: kaspa_stratum_bridge::DuplicateWorkerPolicy::Allow,
+            near_miss_ratio: 0.1,
+            log_sample_rates: Vec::new(),
+            account_shares_while_paused: false,
+            reject_warmup: Duration::ZERO,
+            reject_warmup_after_job: Duration::ZERO,
+            stale_warn_pct: 0.0,
+            difficulty_format: kaspa_stratum_bridge::DifficultyFormat::KaspaLegacy,
+            nonce_byte_order: kaspa_stratum_bridge::NonceByteOrder::Be,
+            timestamp_tolerance: Duration::ZERO,
+            share_reply_format: kaspa_stratum_bridge::ShareReplyFormat::Bool,
+            max_shares_per_sec: kaspa_stratum_bridge::DEFAULT_MAX_SHARES_PER_SEC,
+            share_flood_action: kaspa_stratum_bridge::ShareFloodAction::Throttle,
+            max_connections: 0,
+            label_metrics_by_port: false,
+            metrics_worker_labels: kaspa_stratum_bridge::WorkerLabelMode::Full,
+            max_worker_name_len: kaspa_stratum_bridge::DEFAULT_MAX_WORKER_NAME_LEN,
+            extranonce_reuse_delay: Duration::from_secs(60),
+            auto_extranonce_grow: false,
+            partition_nonce: false,
+            extranonce_detection: kaspa_stratum_bridge::ExtranonceDetection::default(),
+            honor_suggest_difficulty: true,
+            start_diff: 0,
+            var_diff_min: 0,
+            var_diff_max: 0,
+            require_synced: true,
+            kaspad_connect_retries: 5,
+            kaspad_connect_timeout: kaspa_stratum_bridge::DEFAULT_KASPAD_CONNECT_TIMEOUT,
+            offline_policy: kaspa_stratum_bridge::OfflinePolicy::Hold,
+            offline_grace: kaspa_stratum_bridge::DEFAULT_OFFLINE_GRACE,
+            max_line_bytes: kaspa_stratum_bridge::DEFAULT_MAX_LINE_BYTES,
+            socket_tuning: kaspa_stratum_bridge::SocketTuning::default(),
+            worker_threads: 0,
+            max_blocking_threads: DEFAULT_MAX_BLOCKING_THREADS,
+            keepalive_interval: Duration::ZERO,
+            keepalive_timeout: Duration::from_secs(30),
+            authorize_timeout: Duration::ZERO,
+            first_share_warn: Duration::from_secs(kaspa_stratum_bridge::DEFAULT_FIRST_SHARE_WARN_SECS),
+            notify_latency_budget: Duration::ZERO,
+            maintenance_drain: Duration::ZERO,
+            var_diff_mode: kaspa_stratum_bridge::VarDiffMode::Window,
+            submit_breaker: kaspa_stratum_bridge::SubmitBreakerConfig::default(),
+            var_diff_freeze: kaspa_stratum_bridge::VarDiffFreezePolicy { tolerance_pct: 0.0, after_checks: 6 },
+            var_diff_retarget: Duration::from_secs(kaspa_stratum_bridge::DEFAULT_VAR_DIFF_RETARGET_SECS),
+            var_diff_hysteresis: 0.0,
+            var_diff_warmup_shares: kaspa_stratum_bridge::DEFAULT_VAR_DIFF_WARMUP_SHARES,
+            var_diff_warmup: Duration::from_secs(kaspa_stratum_bridge::DEFAULT_VAR_DIFF_WARMUP_SECS),
+            block_tag: String::new(),
+            solo_fallback: false,
+            solo_fallback_wallet: String::new(),
+            fixed_wallet: String::new(),
+            worker_overrides: Vec::new(),
+            skip_startup_check: false,
+            force_ticker: false,
+            template_refresh: Duration::ZERO,
         }
     }
 }
@@ -166,20 +548,102 @@ impl Default for InstanceConfig {
         Self {
             stratum_port: ":5555".to_string(),
             min_share_diff: 8192,
+            start_diff: None,
             prom_port: None,
             log_to_file: None,
             var_diff: None,
             shares_per_min: None,
             var_diff_stats: None,
             pow2_clamp: None,
+            difficulty_format: None,
+            max_connections: None,
+            label: None,
+        }
+    }
+}
+
+/// Config file syntax, chosen by file extension (anything but .toml is YAML)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()) {
+            Some(ext) if ext == "toml" => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
         }
     }
 }
 
+/// Convert a TOML document to the YAML tree the config parser reads, so both formats share one set of keys
+fn toml_to_yaml(value: &toml::Value) -> Yaml {
+    match value {
+        toml::Value::String(s) => Yaml::String(s.clone()),
+        toml::Value::Integer(i) => Yaml::Integer(*i),
+        toml::Value::Float(f) => Yaml::Real(f.to_string()),
+        toml::Value::Boolean(b) => Yaml::Boolean(*b),
+        toml::Value::Datetime(d) => Yaml::String(d.to_string()),
+        toml::Value::Array(items) => Yaml::Array(items.iter().map(toml_to_yaml).collect()),
+        toml::Value::Table(table) => Yaml::Hash(table.iter().map(|(k, v)| (Yaml::String(k.clone()), toml_to_yaml(v))).collect()),
+    }
+}
+
+/// The document a profile selects from a multi-document YAML. An untagged document (no `profile:`) holds
+/// the shared settings: the profile's top-level keys replace its keys, everything else is kept.
+/// Without a profile the first document is used on its own
+fn select_profile(docs: &[Yaml], profile: Option<&str>) -> Result<Yaml, anyhow::Error> {
+    let Some(profile) = profile else {
+        return docs.first().cloned().ok_or_else(|| anyhow::anyhow!("empty YAML document"));
+    };
+    let doc_profile = |doc: &Yaml| doc["profile"].as_str().map(str::trim).map(str::to_string);
+    let selected = docs.iter().find(|doc| doc_profile(doc).as_deref() == Some(profile)).ok_or_else(|| {
+        let available: Vec<String> = docs.iter().filter_map(doc_profile).collect();
+        anyhow::anyhow!("no document with profile '{}' (available: {})", profile, available.join(", "))
+    })?;
+    let (Some(base), Some(overrides)) = (docs.iter().find(|doc| doc_profile(doc).is_none()), selected.as_hash()) else {
+        return Ok(selected.clone());
+    };
+    let mut merged = base.as_hash().cloned().unwrap_or_default();
+    for (key, value) in overrides {
+        merged.insert(key.clone(), value.clone());
+    }
+    Ok(Yaml::Hash(merged))
+}
+
 impl BridgeConfig {
+    /// Read a config file as YAML or TOML depending on its extension; `profile` picks a YAML document
+    fn load(path: &Path, profile: Option<&str>) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path)?;
+        match ConfigFormat::from_path(path) {
+            ConfigFormat::Yaml => Self::from_yaml_profile(&content, profile),
+            ConfigFormat::Toml if profile.is_some() => Err(anyhow::anyhow!("profiles need a multi-document YAML config")),
+            ConfigFormat::Toml => Self::from_toml(&content),
+        }
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
     fn from_yaml(content: &str) -> Result<Self, anyhow::Error> {
-        let docs = YamlLoader::load_from_str(content)?;
-        let doc = docs.first().ok_or_else(|| anyhow::anyhow!("empty YAML document"))?;
+        Self::from_yaml_profile(content, None)
+    }
+
+    fn from_yaml_profile(content: &str, profile: Option<&str>) -> Result<Self, anyhow::Error> {
+        let docs = YamlLoader::load_from_str(content).map_err(|e| anyhow::anyhow!("invalid YAML: {}", e))?;
+        Self::from_doc(&select_profile(&docs, profile)?)
+    }
+
+    fn from_toml(content: &str) -> Result<Self, anyhow::Error> {
+        let value: toml::Value = content.parse().map_err(|e| anyhow::anyhow!("invalid TOML: {}", e))?;
+        Self::from_doc(&toml_to_yaml(&value))
+    }
+
+    fn from_doc(doc: &Yaml) -> Result<Self, anyhow::Error> {
+        let unknown_keys = unknown_config_keys(doc);
+        if doc["strict_config"].as_bool().unwrap_or(false) && !unknown_keys.is_empty() {
+            return Err(anyhow::anyhow!("unknown config key(s) with strict_config enabled: {}", unknown_keys.join(", ")));
+        }
 
         // Parse global config
         let mut global = GlobalConfig::default();
@@ -188,16 +652,266 @@ impl BridgeConfig {
             global.kaspad_address = addr.to_string();
         }
 
+        if let Some(profile) = doc["profile"].as_str() {
+            global.profile = profile.trim().to_string();
+        }
+
+        if let Some(network) = doc["network"].as_str().filter(|n| !n.trim().is_empty()) {
+            global.network = Some(network.parse().map_err(|e: String| anyhow::anyhow!(e))?);
+        }
+
+        if let Some(weight) = doc["kaspad_weight"].as_i64() {
+            global.kaspad_weight =
+                u32::try_from(weight).map_err(|_| anyhow::anyhow!("kaspad_weight must be 0 or more, got {}", weight))?;
+        }
+
+        if let Some(nodes) = doc["kaspad_pool"].as_vec() {
+            for node in nodes {
+                let address = node["address"]
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .ok_or_else(|| anyhow::anyhow!("every kaspad_pool entry needs an address"))?;
+                let weight = node["weight"].as_i64().unwrap_or(1);
+                if weight < 0 {
+                    return Err(anyhow::anyhow!("kaspad_pool weight for {} must be 0 or more, got {}", address, weight));
+                }
+                let health_check_secs =
+                    node["health_check_secs"].as_i64().unwrap_or(kaspa_stratum_bridge::DEFAULT_POOL_HEALTH_CHECK_SECS as i64);
+                if address == global.kaspad_address || global.kaspad_pool.iter().any(|n| n.address == address) {
+                    return Err(anyhow::anyhow!("kaspad_pool lists {} more than once (kaspad_address is already a member)", address));
+                }
+                global.kaspad_pool.push(kaspa_stratum_bridge::PoolNodeConfig {
+                    address: address.to_string(),
+                    weight: weight as u32,
+                    health_check: Duration::from_secs(health_check_secs.max(0) as u64),
+                });
+            }
+        }
+
+        if let Some(broadcast) = doc["broadcast_blocks"].as_bool() {
+            if broadcast && global.kaspad_pool.is_empty() {
+                return Err(anyhow::anyhow!("broadcast_blocks needs kaspad_pool nodes to broadcast to"));
+            }
+            global.broadcast_blocks = broadcast;
+        }
+
+        if let Some(size) = doc["submit_queue_size"].as_i64() {
+            if !(0..=MAX_SUBMIT_QUEUE_SIZE).contains(&size) {
+                return Err(anyhow::anyhow!("submit_queue_size must be between 0 and {}, got {}", MAX_SUBMIT_QUEUE_SIZE, size));
+            }
+            global.submit_queue_size = size as usize;
+        }
+
+        if let Some(policy) = doc["submit_queue_policy"].as_str() {
+            global.submit_queue_policy = policy.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(require_synced) = doc["require_synced"].as_bool() {
+            global.require_synced = require_synced;
+        }
+
+        if let Some(retries) = doc["kaspad_connect_retries"].as_i64() {
+            if !(0..=1000).contains(&retries) {
+                return Err(anyhow::anyhow!("kaspad_connect_retries must be between 0 and 1000, got {}", retries));
+            }
+            global.kaspad_connect_retries = retries as u32;
+        }
+
+        if let Some(secs) = doc["kaspad_connect_timeout_secs"].as_i64() {
+            if secs <= 0 {
+                return Err(anyhow::anyhow!("kaspad_connect_timeout_secs must be positive, got {}", secs));
+            }
+            global.kaspad_connect_timeout = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(policy) = doc["offline_policy"].as_str() {
+            global.offline_policy = policy.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(secs) = doc["offline_grace_secs"].as_i64() {
+            if secs < 0 {
+                return Err(anyhow::anyhow!("offline_grace_secs must not be negative, got {}", secs));
+            }
+            global.offline_grace = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(skip) = doc["skip_startup_check"].as_bool() {
+            global.skip_startup_check = skip;
+        }
+
+        if let Some(tag) = doc["block_tag"].as_str() {
+            if tag.len() > kaspa_stratum_bridge::MAX_BLOCK_TAG_BYTES {
+                return Err(anyhow::anyhow!(
+                    "block_tag is {} bytes, the maximum is {}",
+                    tag.len(),
+                    kaspa_stratum_bridge::MAX_BLOCK_TAG_BYTES
+                ));
+            }
+            global.block_tag = tag.to_string();
+        }
+
+        if let Some(wallet) = doc["fixed_wallet"].as_str() {
+            global.fixed_wallet = if wallet.trim().is_empty() {
+                String::new()
+            } else {
+                kaspa_stratum_bridge::normalize_wallet(wallet, global.network)
+                    .map_err(|e| anyhow::anyhow!("invalid fixed_wallet: {}", e))?
+            };
+        }
+
+        let overrides = &doc["worker_overrides"];
+        if let Some(entries) = overrides.as_hash() {
+            for (pattern, entry) in entries {
+                let pattern = pattern
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .ok_or_else(|| anyhow::anyhow!("worker_overrides keys must be wallet or worker name patterns"))?;
+                let settings = entry.as_hash().ok_or_else(|| anyhow::anyhow!("worker_overrides.{} must be a map", pattern))?;
+                if let Some(key) =
+                    settings.keys().filter_map(|k| k.as_str()).find(|k| !["min_share_diff", "start_diff", "var_diff"].contains(k))
+                {
+                    return Err(anyhow::anyhow!(
+                        "worker_overrides.{}: unknown setting '{}', expected min_share_diff, start_diff or var_diff",
+                        pattern,
+                        key
+                    ));
+                }
+                let diff = |key: &str| -> anyhow::Result<Option<f64>> {
+                    match &entry[key] {
+                        value if value.is_badvalue() => Ok(None),
+                        value => match value.as_i64() {
+                            Some(diff) if diff > 0 => Ok(Some(diff as f64)),
+                            _ => Err(anyhow::anyhow!("worker_overrides.{}.{} must be a positive integer", pattern, key)),
+                        },
+                    }
+                };
+                let worker_override = kaspa_stratum_bridge::WorkerOverride {
+                    pattern: pattern.to_string(),
+                    min_share_diff: diff("min_share_diff")?,
+                    start_diff: diff("start_diff")?,
+                    var_diff: entry["var_diff"].as_bool(),
+                };
+                if worker_override.min_share_diff.is_none()
+                    && worker_override.start_diff.is_none()
+                    && worker_override.var_diff.is_none()
+                {
+                    return Err(anyhow::anyhow!("worker_overrides.{} sets nothing", pattern));
+                }
+                global.worker_overrides.push(worker_override);
+            }
+        } else if !overrides.is_badvalue() {
+            return Err(anyhow::anyhow!("worker_overrides must be a map of wallet or worker patterns to settings"));
+        }
+
+        if let Some(wallet) = doc["solo_fallback_wallet"].as_str() {
+            global.solo_fallback_wallet = if wallet.trim().is_empty() {
+                String::new()
+            } else {
+                kaspa_stratum_bridge::normalize_wallet(wallet, global.network)
+                    .map_err(|e| anyhow::anyhow!("invalid solo_fallback_wallet: {}", e))?
+            };
+        }
+
+        if let Some(solo) = doc["solo_fallback"].as_bool() {
+            if solo && global.solo_fallback_wallet.is_empty() {
+                return Err(anyhow::anyhow!("solo_fallback needs a solo_fallback_wallet to mine to"));
+            }
+            global.solo_fallback = solo;
+        }
+
+        if let Some(network) = global.network {
+            for (key, wallet) in [("fixed_wallet", &global.fixed_wallet), ("solo_fallback_wallet", &global.solo_fallback_wallet)] {
+                if !wallet.is_empty() && !wallet.starts_with(&format!("{}:", network.address_prefix())) {
+                    return Err(anyhow::anyhow!(
+                        "{} {} is not a {} address (expected {}:)",
+                        key,
+                        wallet,
+                        network,
+                        network.address_prefix()
+                    ));
+                }
+            }
+        }
+
         if let Some(stats) = doc["print_stats"].as_bool() {
             global.print_stats = stats;
         }
 
+        if let Some(secs) = doc["stats_interval_secs"].as_i64() {
+            if secs <= 0 {
+                return Err(anyhow::anyhow!("stats_interval_secs must be positive, got {}", secs));
+            }
+            global.stats_interval = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(show) = doc["show_hashrate_estimate"].as_bool() {
+            global.show_hashrate_estimate = show;
+        }
+
         if let Some(log) = doc["log_to_file"].as_bool() {
             global.log_to_file = log;
         }
 
+        if let Some(rotation) = doc["log_rotation"].as_str() {
+            global.log_rotation = rotation.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(compression) = doc["log_compress"].as_str() {
+            global.log_compress = compression.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(keep) = doc["log_retention"].as_i64() {
+            if keep < 0 {
+                return Err(anyhow::anyhow!("log_retention must be 0 (keep all) or more, got {}", keep));
+            }
+            global.log_retention = keep as usize;
+        }
+
+        if let Some(size) = doc["log_buffer_size"].as_i64() {
+            if !(0..=kaspa_stratum_bridge::MAX_LOG_BUFFER_SIZE as i64).contains(&size) {
+                return Err(anyhow::anyhow!(
+                    "log_buffer_size must be between 0 and {}, got {}",
+                    kaspa_stratum_bridge::MAX_LOG_BUFFER_SIZE,
+                    size
+                ));
+            }
+            global.log_buffer_size = size as usize;
+        }
+
         if let Some(port) = doc["health_check_port"].as_str() {
-            global.health_check_port = port.to_string();
+            global.health_check_port = if port.is_empty() { String::new() } else { normalize_listen_port("health_check_port", port)? };
+        }
+
+        if let Some(port) = doc["stats_port"].as_str() {
+            global.stats_port = if port.is_empty() { String::new() } else { normalize_listen_port("stats_port", port)? };
+        }
+
+        let prom_auth_user = doc["prom_auth_user"].as_str().unwrap_or("");
+        let prom_auth_pass = doc["prom_auth_pass"].as_str().unwrap_or("");
+        match (prom_auth_user.is_empty(), prom_auth_pass.is_empty()) {
+            (true, true) => {}
+            (false, false) => global.prom_auth = Some((prom_auth_user.to_string(), prom_auth_pass.to_string())),
+            _ => return Err(anyhow::anyhow!("prom_auth_user and prom_auth_pass must be set together")),
+        }
+
+        if let Some(path) = doc["admin_socket"].as_str() {
+            global.admin_socket = path.to_string();
+        }
+
+        if let Some(url) = doc["webhook_url"].as_str() {
+            if !url.trim().is_empty() {
+                global.webhook_url = Some(url.parse().map_err(|e: String| anyhow::anyhow!(e))?);
+            }
+        }
+
+        if let Some(path) = doc["share_log_path"].as_str() {
+            global.share_log_path = path.trim().to_string();
+        }
+
+        if let Some(format) = doc["share_log_format"].as_str() {
+            global.share_log_format = format.parse().map_err(|e: String| anyhow::anyhow!(e))?;
         }
 
         if let Some(vd) = doc["var_diff"].as_bool() {
@@ -220,6 +934,339 @@ impl BridgeConfig {
             global.pow2_clamp = clamp;
         }
 
+        if let Some(rtt) = doc["log_notify_submit_rtt"].as_bool() {
+            global.log_notify_submit_rtt = rtt;
+        }
+
+        if let Some(dedup) = doc["dedup_templates"].as_bool() {
+            global.dedup_templates = dedup;
+        }
+
+        if let Some(depth) = doc["job_history"].as_i64() {
+            if !(1..=u16::MAX as i64).contains(&depth) {
+                return Err(anyhow::anyhow!("job_history must be between 1 and {}, got {}", u16::MAX, depth));
+            }
+            global.job_history = depth as u16;
+        }
+
+        if let Some(policy) = doc["clean_jobs"].as_str() {
+            global.clean_jobs = policy.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(strategy) = doc["diff_change_strategy"].as_str() {
+            global.diff_change_strategy = strategy.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(policy) = doc["duplicate_worker_policy"].as_str() {
+            global.duplicate_worker_policy = policy.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(ratio) = doc["near_miss_ratio"].as_f64().or_else(|| doc["near_miss_ratio"].as_i64().map(|r| r as f64)) {
+            if !(0.0..1.0).contains(&ratio) {
+                return Err(anyhow::anyhow!("near_miss_ratio must be a fraction in [0, 1), got {}", ratio));
+            }
+            global.near_miss_ratio = ratio;
+        }
+
+        // A single number samples every category; a map samples just the listed ones
+        let sample_rate = &doc["log_sample_rate"];
+        if let Some(rate) = sample_rate.as_i64() {
+            if rate < 1 {
+                return Err(anyhow::anyhow!("log_sample_rate must be at least 1, got {}", rate));
+            }
+            global.log_sample_rates = kaspa_stratum_bridge::LogCategory::ALL.iter().map(|&c| (c, rate as u64)).collect();
+        } else if let Some(rates) = sample_rate.as_hash() {
+            for (category, rate) in rates {
+                let category: kaspa_stratum_bridge::LogCategory =
+                    category.as_str().unwrap_or_default().parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                match rate.as_i64() {
+                    Some(rate) if rate >= 1 => global.log_sample_rates.push((category, rate as u64)),
+                    _ => return Err(anyhow::anyhow!("log_sample_rate.{} must be an integer of at least 1", category)),
+                }
+            }
+        } else if !sample_rate.is_badvalue() {
+            return Err(anyhow::anyhow!("log_sample_rate must be a number or a map of submit/reply/job to numbers"));
+        }
+
+        if let Some(account) = doc["account_shares_while_paused"].as_bool() {
+            global.account_shares_while_paused = account;
+        }
+
+        if let Some(secs) = doc["reject_warmup_secs"].as_i64() {
+            if secs < 0 {
+                return Err(anyhow::anyhow!("reject_warmup_secs must not be negative, got {}", secs));
+            }
+            global.reject_warmup = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(ms) = doc["reject_warmup_after_job_ms"].as_i64() {
+            if ms < 0 {
+                return Err(anyhow::anyhow!("reject_warmup_after_job_ms must not be negative, got {}", ms));
+            }
+            global.reject_warmup_after_job = Duration::from_millis(ms as u64);
+        }
+
+        if let Some(pct) = doc["stale_warn_pct"].as_f64().or_else(|| doc["stale_warn_pct"].as_i64().map(|p| p as f64)) {
+            if !(0.0..100.0).contains(&pct) {
+                return Err(anyhow::anyhow!("stale_warn_pct must be a percentage in [0, 100), got {}", pct));
+            }
+            global.stale_warn_pct = pct;
+        }
+
+        if let Some(format) = doc["difficulty_format"].as_str() {
+            global.difficulty_format = format.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(order) = doc["nonce_byte_order"].as_str() {
+            global.nonce_byte_order = order.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(secs) = doc["timestamp_tolerance_secs"].as_i64() {
+            if !(0..=MAX_TIMESTAMP_TOLERANCE_SECS).contains(&secs) {
+                return Err(anyhow::anyhow!(
+                    "timestamp_tolerance_secs must be between 0 and {}, got {}",
+                    MAX_TIMESTAMP_TOLERANCE_SECS,
+                    secs
+                ));
+            }
+            global.timestamp_tolerance = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(format) = doc["share_reply_format"].as_str() {
+            global.share_reply_format = format.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(max) = doc["max_shares_per_sec"].as_i64() {
+            global.max_shares_per_sec = max.clamp(0, u32::MAX as i64) as u32;
+        }
+
+        if let Some(action) = doc["share_flood_action"].as_str() {
+            global.share_flood_action = action.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(max) = doc["max_connections"].as_i64() {
+            global.max_connections = max.max(0) as usize;
+        }
+
+        if let Some(by_port) = doc["label_metrics_by_port"].as_bool() {
+            global.label_metrics_by_port = by_port;
+        }
+
+        if let Some(mode) = doc["metrics_worker_labels"].as_str() {
+            global.metrics_worker_labels = mode.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(max) = doc["max_worker_name_len"].as_i64() {
+            if max <= 0 {
+                return Err(anyhow::anyhow!("max_worker_name_len must be positive, got {}", max));
+            }
+            global.max_worker_name_len = max as usize;
+        }
+
+        if let Some(mode) = doc["var_diff_mode"].as_str() {
+            global.var_diff_mode = mode.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        }
+
+        if let Some(alpha) = doc["ewma_alpha"].as_f64().or_else(|| doc["ewma_alpha"].as_i64().map(|a| a as f64)) {
+            if !(alpha > 0.0 && alpha <= 1.0) {
+                return Err(anyhow::anyhow!("ewma_alpha must be in (0, 1], got {}", alpha));
+            }
+            if let kaspa_stratum_bridge::VarDiffMode::Ewma { alpha: a } = &mut global.var_diff_mode {
+                *a = alpha;
+            }
+        }
+
+        if let Some(tolerance) =
+            doc["var_diff_freeze_tolerance"].as_f64().or_else(|| doc["var_diff_freeze_tolerance"].as_i64().map(|t| t as f64))
+        {
+            if !(0.0..100.0).contains(&tolerance) {
+                return Err(anyhow::anyhow!("var_diff_freeze_tolerance must be a percentage in [0, 100), got {}", tolerance));
+            }
+            global.var_diff_freeze.tolerance_pct = tolerance;
+        }
+
+        if let Some(after) = doc["var_diff_freeze_after"].as_i64() {
+            global.var_diff_freeze.after_checks = after.max(0) as u32;
+        }
+
+        if let Some(secs) = doc["var_diff_retarget_secs"].as_i64() {
+            if !(1..=3600).contains(&secs) {
+                return Err(anyhow::anyhow!("var_diff_retarget_secs must be between 1 and 3600, got {}", secs));
+            }
+            global.var_diff_retarget = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(pct) = doc["var_diff_hysteresis"].as_f64().or_else(|| doc["var_diff_hysteresis"].as_i64().map(|p| p as f64)) {
+            if !(0.0..100.0).contains(&pct) {
+                return Err(anyhow::anyhow!("var_diff_hysteresis must be a percentage in [0, 100), got {}", pct));
+            }
+            global.var_diff_hysteresis = pct;
+        }
+
+        if let Some(shares) = doc["var_diff_warmup_shares"].as_i64() {
+            if !(0..=1000).contains(&shares) {
+                return Err(anyhow::anyhow!("var_diff_warmup_shares must be between 0 and 1000, got {}", shares));
+            }
+            global.var_diff_warmup_shares = shares as u32;
+        }
+
+        if let Some(secs) = doc["var_diff_warmup_secs"].as_i64() {
+            if secs < 0 {
+                return Err(anyhow::anyhow!("var_diff_warmup_secs must not be negative, got {}", secs));
+            }
+            global.var_diff_warmup = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(pct) =
+            doc["submit_breaker_error_pct"].as_f64().or_else(|| doc["submit_breaker_error_pct"].as_i64().map(|p| p as f64))
+        {
+            if !(0.0..=100.0).contains(&pct) {
+                return Err(anyhow::anyhow!("submit_breaker_error_pct must be a percentage in [0, 100], got {}", pct));
+            }
+            global.submit_breaker.error_pct = pct;
+        }
+
+        if let Some(secs) = doc["submit_breaker_window_secs"].as_i64() {
+            if secs <= 0 {
+                return Err(anyhow::anyhow!("submit_breaker_window_secs must be positive, got {}", secs));
+            }
+            global.submit_breaker.window = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(min) = doc["submit_breaker_min_submits"].as_i64() {
+            global.submit_breaker.min_submits = min.max(1) as usize;
+        }
+
+        if let Some(pause) = doc["submit_breaker_pause_jobs"].as_bool() {
+            global.submit_breaker.pause_jobs = pause;
+        }
+
+        if let Some(max) = doc["max_line_bytes"].as_i64() {
+            if max <= 0 {
+                return Err(anyhow::anyhow!("max_line_bytes must be positive, got {}", max));
+            }
+            global.max_line_bytes = max as usize;
+        }
+
+        if let Some(threads) = doc["worker_threads"].as_i64() {
+            if !(0..=MAX_WORKER_THREADS).contains(&threads) {
+                return Err(anyhow::anyhow!(
+                    "worker_threads must be between 0 (one per CPU core) and {}, got {}",
+                    MAX_WORKER_THREADS,
+                    threads
+                ));
+            }
+            global.worker_threads = threads as usize;
+        }
+
+        if let Some(threads) = doc["max_blocking_threads"].as_i64() {
+            if !(1..=MAX_BLOCKING_THREADS).contains(&threads) {
+                return Err(anyhow::anyhow!("max_blocking_threads must be between 1 and {}, got {}", MAX_BLOCKING_THREADS, threads));
+            }
+            global.max_blocking_threads = threads as usize;
+        }
+
+        if let Some(backlog) = doc["listen_backlog"].as_i64() {
+            if !(1..=65535).contains(&backlog) {
+                return Err(anyhow::anyhow!("listen_backlog must be between 1 and 65535, got {}", backlog));
+            }
+            global.socket_tuning.listen_backlog = backlog as u32;
+        }
+
+        for (key, size) in [
+            ("socket_send_buffer_bytes", &mut global.socket_tuning.send_buffer_size),
+            ("socket_recv_buffer_bytes", &mut global.socket_tuning.recv_buffer_size),
+        ] {
+            if let Some(bytes) = doc[key].as_i64() {
+                if !(MIN_SOCKET_BUFFER_BYTES..=MAX_SOCKET_BUFFER_BYTES).contains(&bytes) {
+                    return Err(anyhow::anyhow!(
+                        "{} must be between {} and {} bytes, got {}",
+                        key,
+                        MIN_SOCKET_BUFFER_BYTES,
+                        MAX_SOCKET_BUFFER_BYTES,
+                        bytes
+                    ));
+                }
+                *size = Some(bytes as u32);
+            }
+        }
+
+        if let Some(secs) = doc["keepalive_interval_secs"].as_i64() {
+            global.keepalive_interval = Duration::from_secs(secs.max(0) as u64);
+        }
+
+        if let Some(secs) = doc["keepalive_timeout_secs"].as_i64() {
+            if secs <= 0 {
+                return Err(anyhow::anyhow!("keepalive_timeout_secs must be positive, got {}", secs));
+            }
+            global.keepalive_timeout = Duration::from_secs(secs as u64);
+        }
+
+        if let Some(secs) = doc["authorize_timeout_secs"].as_i64() {
+            global.authorize_timeout = Duration::from_secs(secs.max(0) as u64);
+        }
+
+        if let Some(secs) = doc["first_share_warn_secs"].as_i64() {
+            global.first_share_warn = Duration::from_secs(secs.max(0) as u64);
+        }
+
+        if let Some(ms) = doc["notify_latency_budget_ms"].as_i64() {
+            global.notify_latency_budget = Duration::from_millis(ms.max(0) as u64);
+        }
+
+        if let Some(secs) = doc["maintenance_drain_secs"].as_i64() {
+            global.maintenance_drain = Duration::from_secs(secs.max(0) as u64);
+        }
+
+        if let Some(delay) = doc["extranonce_reuse_delay_secs"].as_i64() {
+            global.extranonce_reuse_delay = Duration::from_secs(delay.max(0) as u64);
+        }
+
+        if let Some(grow) = doc["auto_extranonce_grow"].as_bool() {
+            global.auto_extranonce_grow = grow;
+        }
+
+        if let Some(partition) = doc["partition_nonce"].as_bool() {
+            global.partition_nonce = partition;
+        }
+
+        let detection = &mut global.extranonce_detection;
+        if let Some(size) = extranonce_size_key(doc, "extranonce_preferred_size")? {
+            detection.preferred = Some(size);
+        }
+        if let Some(size) = extranonce_size_key(doc, "extranonce_min_size")? {
+            detection.min = size;
+        }
+        if let Some(size) = extranonce_size_key(doc, "extranonce_max_size")? {
+            detection.max = size;
+        }
+        if detection.min > detection.max {
+            return Err(anyhow::anyhow!(
+                "extranonce_min_size ({}) must not exceed extranonce_max_size ({})",
+                detection.min,
+                detection.max
+            ));
+        }
+        if let Some(honor) = doc["extranonce_honor_hints"].as_bool() {
+            detection.honor_hints = honor;
+        }
+
+        if let Some(diff) = doc["start_diff"].as_i64() {
+            global.start_diff = diff.max(0) as u32;
+        }
+
+        if let Some(honor) = doc["honor_suggest_difficulty"].as_bool() {
+            global.honor_suggest_difficulty = honor;
+        }
+
+        if let Some(min) = doc["var_diff_min"].as_i64() {
+            global.var_diff_min = min.max(0) as u32;
+        }
+
+        if let Some(max) = doc["var_diff_max"].as_i64() {
+            global.var_diff_max = max.max(0) as u32;
+        }
+
         // Parse block_wait_time from config (in milliseconds, convert to Duration)
         if let Some(bwt) = doc["block_wait_time"].as_i64() {
             global.block_wait_time = Duration::from_millis(bwt as u64);
@@ -227,6 +1274,17 @@ impl BridgeConfig {
             global.block_wait_time = Duration::from_millis(bwt as u64);
         }
 
+        if let Some(force) = doc["force_ticker"].as_bool() {
+            global.force_ticker = force;
+        }
+
+        if let Some(secs) = doc["template_refresh_secs"].as_i64() {
+            if secs < 0 {
+                return Err(anyhow::anyhow!("template_refresh_secs must not be negative, got {}", secs));
+            }
+            global.template_refresh = Duration::from_secs(secs as u64);
+        }
+
         // Check if multi-instance mode (instances array exists)
         if let Some(instances_yaml) = doc["instances"].as_vec() {
             // Multi-instance mode
@@ -237,7 +1295,7 @@ impl BridgeConfig {
 
                 // Required: stratum_port
                 if let Some(port) = instance_yaml["stratum_port"].as_str() {
-                    instance.stratum_port = if port.starts_with(':') { port.to_string() } else { format!(":{}", port) };
+                    instance.stratum_port = normalize_listen_port("stratum_port", port)?;
                 } else {
                     return Err(anyhow::anyhow!("Instance {} missing required 'stratum_port'", idx));
                 }
@@ -249,9 +1307,14 @@ impl BridgeConfig {
                     return Err(anyhow::anyhow!("Instance {} missing required 'min_share_diff'", idx));
                 }
 
+                // Optional: start_diff (per-instance)
+                if let Some(diff) = instance_yaml["start_diff"].as_i64() {
+                    instance.start_diff = Some(diff.max(0) as u32);
+                }
+
                 // Optional: prom_port (per-instance)
                 if let Some(port) = instance_yaml["prom_port"].as_str() {
-                    instance.prom_port = Some(if port.starts_with(':') { port.to_string() } else { format!(":{}", port) });
+                    instance.prom_port = Some(normalize_listen_port("prom_port", port)?);
                 }
 
                 // Optional: log_to_file (per-instance)
@@ -272,50 +1335,198 @@ impl BridgeConfig {
                     instance.var_diff_stats = Some(vds);
                 }
 
-                if let Some(clamp) = instance_yaml["pow2_clamp"].as_bool() {
-                    instance.pow2_clamp = Some(clamp);
-                }
+                if let Some(clamp) = instance_yaml["pow2_clamp"].as_bool() {
+                    instance.pow2_clamp = Some(clamp);
+                }
+
+                if let Some(format) = instance_yaml["difficulty_format"].as_str() {
+                    instance.difficulty_format = Some(format.parse().map_err(|e: String| anyhow::anyhow!("Instance {}: {}", idx, e))?);
+                }
+
+                if let Some(max) = instance_yaml["max_connections"].as_i64() {
+                    instance.max_connections = Some(max.max(0) as usize);
+                }
+
+                if let Some(label) = instance_yaml["label"].as_str() {
+                    instance.label = Some(parse_listener_label(label).map_err(|e| anyhow::anyhow!("Instance {}: {}", idx, e))?);
+                }
+
+                instances.push(instance);
+            }
+
+            if instances.is_empty() {
+                return Err(anyhow::anyhow!("instances array cannot be empty"));
+            }
+
+            check_unique_ports(&instances)?;
+
+            Ok(BridgeConfig { global, instances, unknown_keys })
+        } else if let Some(listeners) = doc["stratum_port"].as_vec() {
+            // Tagged ports: each {port, min_diff, label} entry runs as its own instance and
+            // takes everything it doesn't set from the global settings
+            let default_diff = doc["min_share_diff"].as_i64().map(|d| d as u32).unwrap_or(InstanceConfig::default().min_share_diff);
+            let mut instances = Vec::new();
+
+            for (idx, listener) in listeners.iter().enumerate() {
+                let mut instance = InstanceConfig::default();
+
+                let port = listener["port"]
+                    .as_str()
+                    .map(str::to_string)
+                    .or_else(|| listener["port"].as_i64().map(|p| p.to_string()))
+                    .ok_or_else(|| anyhow::anyhow!("stratum_port entry {} missing required 'port'", idx))?;
+                instance.stratum_port = normalize_listen_port("stratum_port", &port)?;
+                instance.min_share_diff = listener["min_diff"].as_i64().map(|d| d as u32).unwrap_or(default_diff);
+                if let Some(label) = listener["label"].as_str() {
+                    instance.label =
+                        Some(parse_listener_label(label).map_err(|e| anyhow::anyhow!("stratum_port entry {}: {}", idx, e))?);
+                }
+                instance.log_to_file = Some(global.log_to_file);
+                instances.push(instance);
+            }
+
+            if instances.is_empty() {
+                return Err(anyhow::anyhow!("stratum_port list cannot be empty"));
+            }
+            check_unique_ports(&instances)?;
+
+            // One metrics endpoint for the process, as in single-port mode
+            if let Some(port) = doc["prom_port"].as_str() {
+                instances[0].prom_port = Some(normalize_listen_port("prom_port", port)?);
+            }
+
+            Ok(BridgeConfig { global, instances, unknown_keys })
+        } else {
+            // Single-instance mode (backward compatible)
+            let mut instance = InstanceConfig::default();
+
+            if let Some(port) = doc["stratum_port"].as_str() {
+                instance.stratum_port = normalize_listen_port("stratum_port", port)?;
+            }
+
+            if let Some(diff) = doc["min_share_diff"].as_i64() {
+                instance.min_share_diff = diff as u32;
+            }
+
+            if let Some(port) = doc["prom_port"].as_str() {
+                instance.prom_port = Some(normalize_listen_port("prom_port", port)?);
+            }
+
+            // Single-instance mode: use global log_to_file as instance default
+            instance.log_to_file = Some(global.log_to_file);
+
+            Ok(BridgeConfig { global, instances: vec![instance], unknown_keys })
+        }
+    }
+}
+
+fn format_max_connections(max: usize) -> String {
+    if max == 0 {
+        "unlimited".to_string()
+    } else {
+        max.to_string()
+    }
+}
+
+/// Re-read the config file on SIGHUP and apply settings that can change at runtime
+#[cfg(unix)]
+fn spawn_sighup_reload(config_path: PathBuf, profile: Option<String>, max_connections: Vec<Arc<AtomicUsize>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!("failed to install SIGHUP handler, config reload disabled: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading {}", config_path.display());
+            let reloaded = BridgeConfig::load(&config_path, profile.as_deref());
+            let reloaded = match reloaded {
+                Ok(c) => c,
+                Err(e) => {
+                    tracing::error!("config reload failed, keeping current settings: {}", e);
+                    continue;
+                }
+            };
+
+            let old_retarget = kaspa_stratum_bridge::var_diff_retarget();
+            kaspa_stratum_bridge::set_var_diff_retarget(reloaded.global.var_diff_retarget);
+            if old_retarget != reloaded.global.var_diff_retarget {
+                tracing::info!("var_diff_retarget_secs changed: {:?} -> {:?}", old_retarget, reloaded.global.var_diff_retarget);
+            }
 
-                instances.push(instance);
+            let old_hysteresis = kaspa_stratum_bridge::var_diff_hysteresis();
+            kaspa_stratum_bridge::set_var_diff_hysteresis(reloaded.global.var_diff_hysteresis);
+            if old_hysteresis != reloaded.global.var_diff_hysteresis {
+                tracing::info!("var_diff_hysteresis changed: {}% -> {}%", old_hysteresis, reloaded.global.var_diff_hysteresis);
             }
 
-            if instances.is_empty() {
-                return Err(anyhow::anyhow!("instances array cannot be empty"));
+            let old_stale_warn = kaspa_stratum_bridge::stale_warn_pct();
+            kaspa_stratum_bridge::set_stale_warn_pct(reloaded.global.stale_warn_pct);
+            if old_stale_warn != reloaded.global.stale_warn_pct {
+                tracing::info!("stale_warn_pct changed: {}% -> {}%", old_stale_warn, reloaded.global.stale_warn_pct);
+            }
+
+            let old_warmup = kaspa_stratum_bridge::var_diff_warmup();
+            let new_warmup = (reloaded.global.var_diff_warmup_shares, reloaded.global.var_diff_warmup);
+            kaspa_stratum_bridge::set_var_diff_warmup(new_warmup.0, new_warmup.1);
+            if old_warmup != new_warmup {
+                tracing::info!("var_diff warmup changed: {:?} -> {:?}", old_warmup, new_warmup);
             }
 
-            // Validate unique ports
-            let mut ports = std::collections::HashSet::new();
-            for instance in &instances {
-                if !ports.insert(&instance.stratum_port) {
-                    return Err(anyhow::anyhow!("Duplicate stratum_port: {}", instance.stratum_port));
+            for (idx, handle) in max_connections.iter().enumerate() {
+                let Some(instance) = reloaded.instances.get(idx) else { continue };
+                let new_max = instance.max_connections.unwrap_or(reloaded.global.max_connections);
+                let old_max = handle.swap(new_max, Ordering::AcqRel);
+                if old_max != new_max {
+                    tracing::info!(
+                        "[Instance {}] max_connections changed: {} -> {}",
+                        idx + 1,
+                        format_max_connections(old_max),
+                        format_max_connections(new_max)
+                    );
                 }
             }
+        }
+    });
+}
 
-            Ok(BridgeConfig { global, instances })
-        } else {
-            // Single-instance mode (backward compatible)
-            let mut instance = InstanceConfig::default();
+#[cfg(not(unix))]
+fn spawn_sighup_reload(_config_path: PathBuf, _profile: Option<String>, _max_connections: Vec<Arc<AtomicUsize>>) {}
 
-            if let Some(port) = doc["stratum_port"].as_str() {
-                instance.stratum_port = if port.starts_with(':') { port.to_string() } else { format!(":{}", port) };
-            }
+/// Toggle maintenance mode on SIGUSR1: refuse new miners and drain the connected ones after `drain`
+#[cfg(unix)]
+fn spawn_sigusr1_maintenance(drain: Duration) {
+    use tokio::signal::unix::{signal, SignalKind};
 
-            if let Some(diff) = doc["min_share_diff"].as_i64() {
-                instance.min_share_diff = diff as u32;
+    tokio::spawn(async move {
+        let mut usr1 = match signal(SignalKind::user_defined1()) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!("failed to install SIGUSR1 handler, maintenance toggle disabled: {}", e);
+                return;
             }
+        };
 
-            if let Some(port) = doc["prom_port"].as_str() {
-                instance.prom_port = Some(if port.starts_with(':') { port.to_string() } else { format!(":{}", port) });
+        while usr1.recv().await.is_some() {
+            if kaspa_stratum_bridge::maintenance_mode() {
+                tracing::info!("SIGUSR1 received, leaving maintenance mode");
+                kaspa_stratum_bridge::exit_maintenance();
+            } else {
+                tracing::info!("SIGUSR1 received, entering maintenance mode");
+                kaspa_stratum_bridge::enter_maintenance((!drain.is_zero()).then_some(drain));
             }
-
-            // Single-instance mode: use global log_to_file as instance default
-            instance.log_to_file = Some(global.log_to_file);
-
-            Ok(BridgeConfig { global, instances: vec![instance] })
         }
-    }
+    });
 }
 
+#[cfg(not(unix))]
+fn spawn_sigusr1_maintenance(_drain: Duration) {}
+
 struct InProcessNode {
     core: Arc<kaspa_core::core::Core>,
     workers: Vec<std::thread::JoinHandle<()>>,
@@ -343,10 +1554,338 @@ async fn shutdown_inprocess(node: InProcessNode) {
     let _ = tokio::task::spawn_blocking(move || node.shutdown()).await;
 }
 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+/// Log the resolved settings, at startup and for --check-config
+fn log_effective_config(config: &BridgeConfig) {
+    let instance_count = config.instances.len();
+    tracing::info!("----------------------------------");
+    tracing::info!("initializing bridge ({} instance{})", instance_count, if instance_count > 1 { "s" } else { "" });
+    tracing::info!("\tversion:         {} ({})", kaspa_stratum_bridge::BUILD_VERSION, kaspa_stratum_bridge::BUILD_COMMIT);
+    if !config.global.profile.is_empty() {
+        tracing::info!("\tprofile:         {}", config.global.profile);
+    }
+    tracing::info!("\tkaspad:          {} (shared)", config.global.kaspad_address);
+    match config.global.network {
+        Some(network) => tracing::info!("\tnetwork:         {} ({}: wallets only)", network, network.address_prefix()),
+        None => tracing::info!("\tnetwork:         as reported by kaspad"),
+    }
+    if !config.global.kaspad_pool.is_empty() {
+        let nodes: Vec<_> = config.global.kaspad_pool.iter().map(|n| format!("{} x{}", n.address, n.weight)).collect();
+        tracing::info!("\tkaspad pool:     primary x{}, {} (block submits only)", config.global.kaspad_weight, nodes.join(", "));
+        if config.global.broadcast_blocks {
+            tracing::info!("\tblock broadcast: every block to all healthy nodes at once");
+        }
+    }
+    match config.global.submit_queue_size {
+        0 => tracing::info!("\tsubmit queue:    off (blocks submitted inline)"),
+        size => tracing::info!("\tsubmit queue:    {} blocks, {} when full", size, config.global.submit_queue_policy),
+    }
+    tracing::info!("\trequire synced:  {}", config.global.require_synced);
+    tracing::info!(
+        "\tkaspad connect:  {:?} per attempt, {} retries",
+        config.global.kaspad_connect_timeout,
+        config.global.kaspad_connect_retries
+    );
+    tracing::info!("\tkaspad offline:  {} after {:?}", config.global.offline_policy, config.global.offline_grace);
+    tracing::info!("\tstartup check:   {}", if config.global.skip_startup_check { "skipped" } else { "on" });
+    if !config.global.block_tag.is_empty() {
+        tracing::info!("\tblock tag:       {:?}", config.global.block_tag);
+    }
+    tracing::info!("\tblock wait:      {:?}", config.global.block_wait_time);
+    tracing::info!(
+        "\ttemplates:       {}",
+        if config.global.force_ticker { "polled only (force_ticker)" } else { "notifications, polling as fallback" }
+    );
+    if !config.global.template_refresh.is_zero() {
+        tracing::info!("\ttmpl refresh:    after {:?} without a new template", config.global.template_refresh);
+    }
+    if !config.global.fixed_wallet.is_empty() {
+        tracing::info!("\tfixed wallet:    {} (usernames name workers only)", config.global.fixed_wallet);
+    }
+    if !config.global.worker_overrides.is_empty() {
+        let patterns: Vec<_> = config.global.worker_overrides.iter().map(|o| o.pattern.as_str()).collect();
+        tracing::info!("\toverrides:       {} (worker_overrides)", patterns.join(", "));
+    }
+    if config.global.solo_fallback {
+        tracing::info!("\tsolo fallback:   {} (while no miners are connected)", config.global.solo_fallback_wallet);
+    }
+    tracing::info!("\tprint stats:     {} (every {:?})", config.global.print_stats, config.global.stats_interval);
+    tracing::info!("\thashrate est.:   {}", config.global.show_hashrate_estimate);
+    tracing::info!("\tvar diff:        {}", config.global.var_diff);
+    tracing::info!("\tshares per min:  {}", config.global.shares_per_min);
+    tracing::info!("\tvar diff stats:  {}", config.global.var_diff_stats);
+    tracing::info!("\tvar diff mode:   {}", config.global.var_diff_mode);
+    tracing::info!("\tvar diff tick:   {:?}", config.global.var_diff_retarget);
+    if config.global.var_diff_hysteresis > 0.0 {
+        tracing::info!("\tvar diff hyst.:  retarget only beyond {}% change", config.global.var_diff_hysteresis);
+    }
+    if config.global.var_diff_warmup_shares > 0 {
+        tracing::info!(
+            "\tvar diff warmup: {} shares or {:?} after authorize",
+            config.global.var_diff_warmup_shares,
+            config.global.var_diff_warmup
+        );
+    }
+    if config.global.var_diff_freeze.enabled() {
+        tracing::info!(
+            "\tvar diff freeze: within {}% for {} checks",
+            config.global.var_diff_freeze.tolerance_pct,
+            config.global.var_diff_freeze.after_checks
+        );
+    }
+    tracing::info!(
+        "\tsuggest diff:    {}",
+        if config.global.honor_suggest_difficulty {
+            format!("honored within [{}, {}]", config.global.var_diff_min, config.global.var_diff_max)
+        } else {
+            "ignored".to_string()
+        }
+    );
+    tracing::info!("\tpow2 clamp:      {}", config.global.pow2_clamp);
+    let detection = &config.global.extranonce_detection;
+    let fallback = if config.global.extranonce_size > 0 { config.global.extranonce_size.min(3) } else { 2 };
+    tracing::info!(
+        "\textranonce:      per client, {}..={} bytes, preferred {}, fallback {}, miner hints {}",
+        detection.min,
+        detection.max,
+        detection.preferred.unwrap_or(fallback),
+        fallback,
+        if detection.honor_hints { "honored" } else { "ignored" }
+    );
+    tracing::info!("\textranonce reuse: after {:?} idle", config.global.extranonce_reuse_delay);
+    tracing::info!("\textranonce grow:  {}", config.global.auto_extranonce_grow);
+    tracing::info!("\tpartition nonce: {}", config.global.partition_nonce);
+    tracing::info!("\thealth check:    {}", config.global.health_check_port);
+    if !config.global.stats_port.is_empty() {
+        tracing::info!("\tstats port:      {}", config.global.stats_port);
+    }
+    if let Some((user, _)) = &config.global.prom_auth {
+        tracing::info!("\tprom auth:       basic, user {:?}", user);
+    }
+    if !config.global.admin_socket.is_empty() {
+        tracing::info!("\tadmin socket:    {}", config.global.admin_socket);
+    }
+    if let Some(url) = &config.global.webhook_url {
+        tracing::info!("\twebhook:         {}", url);
+    }
+    if !config.global.share_log_path.is_empty() {
+        tracing::info!("\tshare log:       {} ({})", config.global.share_log_path, config.global.share_log_format);
+    }
+    tracing::info!("\tnotify rtt log:  {}", config.global.log_notify_submit_rtt);
+    tracing::info!("\tdedup templates: {}", config.global.dedup_templates);
+    tracing::info!("\tjob history:     {} jobs", config.global.job_history);
+    tracing::info!("\tclean jobs:      {}", config.global.clean_jobs);
+    tracing::info!("\tdiff change:     {}", config.global.diff_change_strategy);
+    tracing::info!("\tdup. workers:    {}", config.global.duplicate_worker_policy);
+    if config.global.near_miss_ratio > 0.0 {
+        tracing::info!("\tnear miss:       shares >= {} of network difficulty", config.global.near_miss_ratio);
+    }
+    if !config.global.log_sample_rates.is_empty() {
+        let rates: Vec<String> = config.global.log_sample_rates.iter().map(|(c, n)| format!("{} 1/{}", c, n)).collect();
+        tracing::info!("\tlog sampling:    {}", rates.join(", "));
+    }
+    if config.global.log_to_file {
+        tracing::info!("\tlog rotation:    {}", config.global.log_rotation);
+    }
+    if config.global.log_compress != kaspa_stratum_bridge::LogCompression::None {
+        tracing::info!("\tlog compress:    {} (rolled-over log files)", config.global.log_compress);
+    }
+    if config.global.log_retention > 0 {
+        tracing::info!("\tlog retention:   last {} log files", config.global.log_retention);
+    }
+    if config.global.log_buffer_size > 0 {
+        tracing::info!("\tlog buffer:      {} lines (GET /logs)", config.global.log_buffer_size);
+    }
+    tracing::info!("\tpaused shares:   {}", if config.global.account_shares_while_paused { "accounted" } else { "dropped" });
+    if !config.global.reject_warmup.is_zero() || !config.global.reject_warmup_after_job.is_zero() {
+        tracing::info!(
+            "\treject warmup:   {:?} after authorize, {:?} after each job",
+            config.global.reject_warmup,
+            config.global.reject_warmup_after_job
+        );
+    }
+    if config.global.stale_warn_pct > 0.0 {
+        tracing::info!("\tstale warning:   above {}% stale over 10m", config.global.stale_warn_pct);
+    }
+    tracing::info!("\tdiff format:     {}", config.global.difficulty_format);
+    tracing::info!("\tnonce bytes:     {}", config.global.nonce_byte_order);
+    if !config.global.timestamp_tolerance.is_zero() {
+        tracing::info!("\tshare timestamp: honored within {:?} of the template", config.global.timestamp_tolerance);
+    }
+    tracing::info!("\tshare reply:     {}", config.global.share_reply_format);
+    if config.global.max_shares_per_sec == 0 {
+        tracing::info!("\tshare rate max:  unlimited");
+    } else {
+        tracing::info!(
+            "\tshare rate max:  {}/s per connection, then {}",
+            config.global.max_shares_per_sec,
+            config.global.share_flood_action
+        );
+    }
+    tracing::info!("\tmax connections: {}", format_max_connections(config.global.max_connections));
+    tracing::info!("\tport labels:     {}", config.global.label_metrics_by_port);
+    tracing::info!("\tworker labels:   {}", config.global.metrics_worker_labels);
+    tracing::info!("\tworker name max: {} chars", config.global.max_worker_name_len);
+    tracing::info!("\tmax line bytes:  {}", config.global.max_line_bytes);
+    let tuning = &config.global.socket_tuning;
+    let buffer = |size: Option<u32>| size.map_or("os default".to_string(), |s| format!("{} bytes", s));
+    tracing::info!(
+        "\tsocket tuning:   backlog {}, send buffer {}, recv buffer {}",
+        tuning.listen_backlog,
+        buffer(tuning.send_buffer_size),
+        buffer(tuning.recv_buffer_size)
+    );
+    tracing::info!(
+        "\truntime:         {} worker threads{}, up to {} blocking threads",
+        tokio::runtime::Handle::current().metrics().num_workers(),
+        if config.global.worker_threads == 0 { " (one per CPU core)" } else { "" },
+        config.global.max_blocking_threads
+    );
+    if config.global.keepalive_interval.is_zero() {
+        tracing::info!("\tkeepalive:       off");
+    } else {
+        tracing::info!(
+            "\tkeepalive:       ping after {:?} idle, close after {:?} more",
+            config.global.keepalive_interval,
+            config.global.keepalive_timeout
+        );
+    }
+    if !config.global.notify_latency_budget.is_zero() {
+        tracing::info!("\tnotify budget:   warn when a template takes over {:?} to reach miners", config.global.notify_latency_budget);
+    }
+    if !config.global.first_share_warn.is_zero() {
+        tracing::info!("\tfirst share:     warn after {:?} without one", config.global.first_share_warn);
+    }
+    if !config.global.maintenance_drain.is_zero() {
+        tracing::info!("\tmaintenance:     SIGUSR1 drains miners after {:?}", config.global.maintenance_drain);
+    }
+    if !config.global.authorize_timeout.is_zero() {
+        tracing::info!("\tauthorize within: {:?} of connecting", config.global.authorize_timeout);
+    }
+    let breaker = &config.global.submit_breaker;
+    if breaker.error_pct > 0.0 {
+        tracing::info!(
+            "\tsubmit breaker:  {}% of >= {} submits in {:?}, then {}",
+            breaker.error_pct,
+            breaker.min_submits,
+            breaker.window,
+            if breaker.pause_jobs { "pause jobs" } else { "warn" }
+        );
+    } else {
+        tracing::info!("\tsubmit breaker:  off");
+    }
+
+    for (idx, instance) in config.instances.iter().enumerate() {
+        tracing::info!("\t--- Instance {} ---", idx + 1);
+        tracing::info!("\t  stratum:       {}", instance.stratum_port);
+        if let Some(ref label) = instance.label {
+            tracing::info!("\t  label:         {}", label);
+        }
+        tracing::info!("\t  min diff:      {}", instance.min_share_diff);
+        match instance.start_diff.unwrap_or(config.global.start_diff) {
+            0 => tracing::info!("\t  start diff:    min diff"),
+            start_diff => tracing::info!("\t  start diff:    {}", start_diff),
+        }
+        if let Some(ref prom_port) = instance.prom_port {
+            tracing::info!("\t  prom:          {}", prom_port);
+        }
+        if let Some(log_to_file) = instance.log_to_file {
+            tracing::info!("\t  log to file:   {}", log_to_file);
+        }
+        if let Some(format) = instance.difficulty_format {
+            tracing::info!("\t  diff format:   {}", format);
+        }
+        if let Some(max_connections) = instance.max_connections {
+            tracing::info!("\t  max conns:     {}", format_max_connections(max_connections));
+        }
+    }
+    tracing::info!("----------------------------------");
+    log_difficulty_ladder(config);
+}
+
+/// Rungs of an unbounded ladder (var_diff_max 0) shown before it is cut short
+const LADDER_PREVIEW_RUNGS: usize = 12;
+
+/// One grouped block previewing the difficulties each instance's vardiff can hand out, so a clamp that
+/// collapses the range shows up before miners connect. Informational only: the ladder is shaped by
+/// var_diff_min, var_diff_max and pow2_clamp, min_share_diff only decides where a connection starts;
+/// there is no separate step size to configure
+fn log_difficulty_ladder(config: &BridgeConfig) {
+    let global = &config.global;
+    let mut lines = vec!["difficulty ladder preview:".to_string()];
+    for (idx, instance) in config.instances.iter().enumerate() {
+        let pow2_clamp = instance.pow2_clamp.unwrap_or(global.pow2_clamp);
+        let policy =
+            SuggestDifficultyPolicy { var_diff_min: global.var_diff_min as f64, var_diff_max: global.var_diff_max as f64, pow2_clamp };
+        let min_diff = clamped_min_share_diff(instance.min_share_diff, pow2_clamp);
+        let start_diff = policy.start_diff(instance.start_diff.unwrap_or(global.start_diff) as f64, min_diff);
+        let mut line = format!("\t  instance {} ({}): ", idx + 1, instance.stratum_port);
+        if !instance.var_diff.unwrap_or(global.var_diff) {
+            line.push_str(&format!("fixed at {} (var_diff off)", start_diff));
+        } else if let Some(rungs) = policy.ladder(if global.var_diff_max == 0 { LADDER_PREVIEW_RUNGS } else { usize::MAX }) {
+            let shown: Vec<String> = rungs.iter().map(|diff| diff.to_string()).collect();
+            line.push_str(&shown.join(", "));
+            if global.var_diff_max == 0 {
+                line.push_str(", ... (no var_diff_max)");
+            } else {
+                line.push_str(&format!(" ({} rung{})", rungs.len(), if rungs.len() == 1 { "" } else { "s" }));
+                if rungs.len() == 1 {
+                    line.push_str(", var_diff_max leaves vardiff nowhere to go");
+                }
+            }
+            line.push_str(&format!(", start {}", start_diff));
+        } else {
+            match policy.retarget_range() {
+                (floor, None) => line.push_str(&format!("any difficulty from {} (pow2_clamp off)", floor)),
+                (floor, Some(ceiling)) => line.push_str(&format!("any difficulty from {} to {} (pow2_clamp off)", floor, ceiling)),
+            }
+            line.push_str(&format!(", start {}", start_diff));
+        }
+        if min_diff != instance.min_share_diff as f64 {
+            line.push_str(&format!("; min_share_diff {} applied as {}", instance.min_share_diff, min_diff));
+        }
+        lines.push(line);
+    }
+    tracing::info!("{}", lines.join("\n"));
+}
+
+/// The tokio runtime, sized by worker_threads and max_blocking_threads
+fn build_runtime(global: &GlobalConfig) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().max_blocking_threads(global.max_blocking_threads);
+    if global.worker_threads > 0 {
+        builder.worker_threads(global.worker_threads);
+    }
+    builder.build()
+}
+
+fn main() -> Result<(), anyhow::Error> {
     let cli = Cli::parse();
 
+    // Load config first: it sizes the runtime and decides whether file logging is enabled
+    let config_path = cli.config.as_path();
+    let profile = cli.profile.clone().or_else(|| std::env::var(PROFILE_ENV).ok()).filter(|p| !p.trim().is_empty());
+    let loaded = if config_path.exists() {
+        BridgeConfig::load(config_path, profile.as_deref())
+    } else if cli.check_config {
+        Err(anyhow::anyhow!("config file not found"))
+    } else {
+        // Create default single-instance config
+        Ok(BridgeConfig { global: GlobalConfig::default(), instances: vec![InstanceConfig::default()], unknown_keys: Vec::new() })
+    };
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) if cli.check_config => {
+            eprintln!("{}: FAILED: {:#}", config_path.display(), e);
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let runtime = build_runtime(&config.global).map_err(|e| anyhow::anyhow!("failed to start the tokio runtime: {}", e))?;
+    runtime.block_on(run(cli, profile, config))
+}
+
+async fn run(cli: Cli, profile: Option<String>, config: BridgeConfig) -> Result<(), anyhow::Error> {
     let mut node_args: Vec<String> = Vec::new();
     if let Some(node_args_str) = cli.node_args.as_deref() {
         node_args.extend(split_shell_words(node_args_str)?);
@@ -355,16 +1894,38 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let inferred_mode = if !node_args.is_empty() { NodeMode::Inprocess } else { NodeMode::External };
     let node_mode = cli.node_mode.unwrap_or(inferred_mode);
-
-    // Load config first to check if file logging is enabled
     let config_path = cli.config.as_path();
-    let config = if config_path.exists() {
-        let content = std::fs::read_to_string(config_path)?;
-        BridgeConfig::from_yaml(&content)?
-    } else {
-        // Create default single-instance config
-        BridgeConfig { global: GlobalConfig::default(), instances: vec![InstanceConfig::default()] }
-    };
+
+    kaspa_stratum_bridge::set_worker_label_mode(config.global.metrics_worker_labels);
+    kaspa_stratum_bridge::set_max_worker_name_len(config.global.max_worker_name_len);
+    kaspa_stratum_bridge::set_submit_breaker(config.global.submit_breaker);
+    kaspa_stratum_bridge::set_dedup_templates(config.global.dedup_templates);
+    kaspa_stratum_bridge::set_job_history(config.global.job_history);
+    kaspa_stratum_bridge::set_clean_jobs_policy(config.global.clean_jobs);
+    kaspa_stratum_bridge::set_diff_change_strategy(config.global.diff_change_strategy);
+    kaspa_stratum_bridge::set_near_miss_ratio(config.global.near_miss_ratio);
+    kaspa_stratum_bridge::set_auto_extranonce_grow(config.global.auto_extranonce_grow);
+    kaspa_stratum_bridge::set_partition_nonce(config.global.partition_nonce);
+    kaspa_stratum_bridge::set_extranonce_detection(config.global.extranonce_detection);
+    kaspa_stratum_bridge::set_network(config.global.network);
+    kaspa_stratum_bridge::set_nonce_byte_order(config.global.nonce_byte_order);
+    kaspa_stratum_bridge::set_timestamp_tolerance(config.global.timestamp_tolerance);
+    kaspa_stratum_bridge::set_reject_warmup(config.global.reject_warmup, config.global.reject_warmup_after_job);
+    kaspa_stratum_bridge::set_stale_warn_pct(config.global.stale_warn_pct);
+    kaspa_stratum_bridge::set_share_reply_format(config.global.share_reply_format);
+    kaspa_stratum_bridge::set_share_rate_limit(config.global.max_shares_per_sec, config.global.share_flood_action);
+    kaspa_stratum_bridge::set_force_ticker(config.global.force_ticker);
+    kaspa_stratum_bridge::set_template_refresh(config.global.template_refresh);
+    kaspa_stratum_bridge::set_kaspad_connect_timeout(config.global.kaspad_connect_timeout);
+    kaspa_stratum_bridge::set_offline_policy(config.global.offline_policy, config.global.offline_grace);
+    kaspa_stratum_bridge::set_var_diff_retarget(config.global.var_diff_retarget);
+    kaspa_stratum_bridge::set_var_diff_hysteresis(config.global.var_diff_hysteresis);
+    kaspa_stratum_bridge::set_var_diff_warmup(config.global.var_diff_warmup_shares, config.global.var_diff_warmup);
+    kaspa_stratum_bridge::set_first_share_warn(config.global.first_share_warn);
+    kaspa_stratum_bridge::set_notify_latency_budget(config.global.notify_latency_budget);
+    for &(category, rate) in &config.global.log_sample_rates {
+        kaspa_stratum_bridge::set_log_sample_rate(category, rate);
+    }
 
     // Initialize color support detection
     kaspa_stratum_bridge::log_colors::LogColors::init();
@@ -531,22 +2092,35 @@ async fn main() -> Result<(), anyhow::Error> {
 
     // Setup file logging if enabled (check if any instance has logging enabled)
     // For multi-instance, we use global log_to_file setting or first instance's setting
-    let should_log_to_file = config.global.log_to_file || config.instances.first().and_then(|i| i.log_to_file).unwrap_or(false);
+    // A config check never writes a log file
+    let should_log_to_file =
+        !cli.check_config && (config.global.log_to_file || config.instances.first().and_then(|i| i.log_to_file).unwrap_or(false));
+
+    // Last log_buffer_size lines in memory for GET /logs, formatted like the log file. It sits right on
+    // the registry so either subscriber below can take it; the EnvFilter still decides what it sees
+    let log_buffer_layer = (config.global.log_buffer_size > 0 && !cli.check_config).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(kaspa_stratum_bridge::install_log_buffer(config.global.log_buffer_size))
+            .with_ansi(false)
+            .event_format(CustomFormatter { apply_colors: false })
+    });
 
     // Note: The file_guard must be kept alive for the lifetime of the program
     // to ensure logs are flushed to the file
     let _file_guard: Option<tracing_appender::non_blocking::WorkerGuard> = if should_log_to_file {
-        // Create log file with timestamp
-        use std::time::SystemTime;
-        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-        let log_filename = format!("rustbridge_{}.log", timestamp);
-        let log_path = std::path::Path::new(".").join(&log_filename);
-
-        // Use tracing-appender for file logging
-        let file_appender = tracing_appender::rolling::never(".", &log_filename);
+        // Log file named by start time, moving on to a new one every log_rotation period
+        let file_appender = kaspa_stratum_bridge::RollingLog::open(
+            std::path::Path::new("."),
+            config.global.log_rotation,
+            config.global.log_compress,
+            config.global.log_retention,
+        )
+        .map_err(|e| anyhow::anyhow!("cannot create log file: {}", e))?;
+        let log_path = file_appender.path().to_path_buf();
         let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
 
         let subscriber = tracing_subscriber::registry()
+            .with(log_buffer_layer)
             .with(filter)
             .with(
                 tracing_subscriber::fmt::layer()
@@ -563,6 +2137,13 @@ async fn main() -> Result<(), anyhow::Error> {
         match subscriber.try_init() {
             Ok(()) => {
                 eprintln!("Logging to file: {}", log_path.display());
+                // Compress and prune logs of earlier runs on a blocking worker, away from logging
+                kaspa_stratum_bridge::spawn_log_compression(
+                    std::path::PathBuf::from("."),
+                    log_path,
+                    config.global.log_compress,
+                    config.global.log_retention,
+                );
                 Some(_guard)
             }
             Err(e) => {
@@ -571,7 +2152,7 @@ async fn main() -> Result<(), anyhow::Error> {
             }
         }
     } else {
-        let subscriber = tracing_subscriber::registry().with(filter).with(
+        let subscriber = tracing_subscriber::registry().with(log_buffer_layer).with(filter).with(
             tracing_subscriber::fmt::layer()
                 .with_ansi(LogColors::should_colorize())
                 .event_format(CustomFormatter { apply_colors: LogColors::should_colorize() }),
@@ -584,6 +2165,20 @@ async fn main() -> Result<(), anyhow::Error> {
         None
     };
 
+    if cli.check_config {
+        // The verdict goes to stdout/stderr directly so it shows regardless of the log filter
+        log_effective_config(&config);
+        for key in &config.unknown_keys {
+            eprintln!(
+                "{}: warning: ignoring unknown config key '{}' (set strict_config: true to fail on typos)",
+                config_path.display(),
+                key
+            );
+        }
+        println!("{}: OK", config_path.display());
+        return Ok(());
+    }
+
     // Start in-process node after tracing is initialized so bridge logs (including the stats table)
     // are not filtered out by a tracing subscriber installed by kaspad.
     let mut inprocess_node: Option<InProcessNode> = None;
@@ -596,82 +2191,164 @@ async fn main() -> Result<(), anyhow::Error> {
     }
 
     if !config_path.exists() {
-        tracing::warn!("config.yaml not found, using defaults");
+        tracing::warn!("{} not found, using defaults", config_path.display());
+    }
+    for key in &config.unknown_keys {
+        tracing::warn!("ignoring unknown config key '{}' (set strict_config: true to fail on typos)", key);
     }
 
-    let instance_count = config.instances.len();
-    tracing::info!("----------------------------------");
-    tracing::info!("initializing bridge ({} instance{})", instance_count, if instance_count > 1 { "s" } else { "" });
-    tracing::info!("\tkaspad:          {} (shared)", config.global.kaspad_address);
-    tracing::info!("\tblock wait:      {:?}", config.global.block_wait_time);
-    tracing::info!("\tprint stats:     {}", config.global.print_stats);
-    tracing::info!("\tvar diff:        {}", config.global.var_diff);
-    tracing::info!("\tshares per min:  {}", config.global.shares_per_min);
-    tracing::info!("\tvar diff stats:  {}", config.global.var_diff_stats);
-    tracing::info!("\tpow2 clamp:      {}", config.global.pow2_clamp);
-    tracing::info!("\textranonce:      auto-detected per client");
-    tracing::info!("\thealth check:    {}", config.global.health_check_port);
+    log_effective_config(&config);
 
-    for (idx, instance) in config.instances.iter().enumerate() {
-        tracing::info!("\t--- Instance {} ---", idx + 1);
-        tracing::info!("\t  stratum:       {}", instance.stratum_port);
-        tracing::info!("\t  min diff:      {}", instance.min_share_diff);
-        if let Some(ref prom_port) = instance.prom_port {
-            tracing::info!("\t  prom:          {}", prom_port);
-        }
-        if let Some(log_to_file) = instance.log_to_file {
-            tracing::info!("\t  log to file:   {}", log_to_file);
-        }
+    if let Some(url) = config.global.webhook_url.clone() {
+        kaspa_stratum_bridge::start_webhook(url);
     }
-    tracing::info!("----------------------------------");
+
+    if !config.global.share_log_path.is_empty() {
+        let path = kaspa_stratum_bridge::start_share_log(
+            std::path::Path::new(&config.global.share_log_path),
+            config.global.share_log_format,
+            config.global.log_compress,
+        )
+        .map_err(|e| anyhow::anyhow!("cannot open share_log_path {}: {}", config.global.share_log_path, e))?;
+        tracing::info!("Writing accepted shares to {}", path.display());
+    }
+
+    // Start admin control socket if a path is configured
+    if !config.global.admin_socket.is_empty() {
+        let admin_socket = config.global.admin_socket.clone();
+        tokio::spawn(async move {
+            if let Err(e) = kaspa_stratum_bridge::serve_admin_socket(admin_socket).await {
+                tracing::error!("admin socket failed: {}", e);
+            }
+        });
+    }
+
+    let prom_auth = config.global.prom_auth.as_ref().map(|(user, pass)| kaspa_stratum_bridge::prom_auth_token(user, pass));
 
     // Start global health check server if port is specified
     if !config.global.health_check_port.is_empty() {
         let health_port = config.global.health_check_port.clone();
+        let listener = kaspa_stratum_bridge::bind_listener(&health_port)
+            .await
+            .map_err(|e| anyhow::anyhow!("cannot bind health_check_port {}: {}", health_port, e))?;
+        tracing::info!("Health check server listening on {}", bound_address(&listener, &health_port));
+        let prom_auth = prom_auth.clone();
         tokio::spawn(async move {
             use tokio::io::{AsyncReadExt, AsyncWriteExt};
-            use tokio::net::TcpListener;
-
-            if let Ok(listener) = TcpListener::bind(&health_port).await {
-                tracing::info!("Health check server started on {}", health_port);
-                loop {
-                    if let Ok((mut stream, _)) = listener.accept().await {
-                        let mut buffer = [0; 1024];
-                        if stream.read(&mut buffer).await.is_ok() {
-                            let response = "HTTP/1.1 200 OK\r\n\r\n";
-                            let _ = stream.write_all(response.as_bytes()).await;
-                        }
+
+            loop {
+                if let Ok((mut stream, _)) = listener.accept().await {
+                    let mut buffer = [0; 1024];
+                    if let Ok(n) = stream.read(&mut buffer).await {
+                        let request = String::from_utf8_lossy(&buffer[..n]);
+                        // Logs can carry wallets and addresses, so they sit behind prom_auth like on prom_port;
+                        // the health check itself stays open for probes
+                        let response = if request.starts_with("GET /logs")
+                            && !kaspa_stratum_bridge::prom_auth_allows(&request, prom_auth.as_deref())
+                        {
+                            kaspa_stratum_bridge::UNAUTHORIZED_RESPONSE.to_string()
+                        } else if request.starts_with("GET /logs") {
+                            kaspa_stratum_bridge::logs_http_response(&request)
+                        } else {
+                            "HTTP/1.1 200 OK\r\n\r\n".to_string()
+                        };
+                        let _ = stream.write_all(response.as_bytes()).await;
                     }
                 }
             }
         });
     }
 
-    // Create shared kaspa API client (all instances use the same node)
-    let kaspa_api = if inprocess_node.is_some() {
-        kaspa_api_with_retry(config.global.kaspad_address.clone(), config.global.block_wait_time)
+    // Serve the stats report as plain text to anything that connects, e.g. `nc host port`
+    if !config.global.stats_port.is_empty() {
+        let stats_port = config.global.stats_port.clone();
+        let listener = kaspa_stratum_bridge::bind_listener(&stats_port)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to create Kaspa API client: {}", e))?
+            .map_err(|e| anyhow::anyhow!("cannot bind stats_port {}: {}", stats_port, e))?;
+        tracing::info!("Stats server listening on {}", bound_address(&listener, &stats_port));
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+
+            loop {
+                if let Ok((mut stream, _)) = listener.accept().await {
+                    tokio::spawn(async move {
+                        let report = kaspa_stratum_bridge::stats_report().unwrap_or_else(|| "no instances running yet".to_string());
+                        let _ = stream.write_all(format!("{}\n", report).as_bytes()).await;
+                        let _ = stream.shutdown().await;
+                    });
+                }
+            }
+        });
+    }
+
+    // Create shared kaspa API client (all instances use the same node)
+    // An in-process node gets 30s to open its RPC port, polled every 500ms
+    let (retries, first_delay, max_delay) = if inprocess_node.is_some() {
+        (59, Duration::from_millis(500), Duration::from_millis(500))
     } else {
-        kaspa_stratum_bridge::KaspaApi::new(config.global.kaspad_address.clone(), config.global.block_wait_time)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to create Kaspa API client: {}", e))?
+        (config.global.kaspad_connect_retries, KASPAD_RETRY_DELAY, MAX_KASPAD_RETRY_DELAY)
     };
+    let kaspa_api = kaspa_api_with_retry(
+        config.global.kaspad_address.clone(),
+        config.global.block_wait_time,
+        config.global.require_synced,
+        config.global.block_tag.clone(),
+        retries,
+        first_delay,
+        max_delay,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to create Kaspa API client: {}", e))?;
+
+    // Fail fast, before any stratum port opens, if kaspad can't actually serve templates
+    if !config.global.skip_startup_check {
+        let wallet = Some(config.global.solo_fallback_wallet.as_str()).filter(|w| !w.is_empty());
+        if let Err(e) = kaspa_api.startup_check(wallet, config.global.require_synced).await {
+            tracing::error!("startup check failed: {}", e);
+            return Err(anyhow::anyhow!("startup check against kaspad at {} failed: {}", config.global.kaspad_address, e));
+        }
+    }
+
+    if !config.global.kaspad_pool.is_empty() {
+        kaspa_api.set_submit_pool(
+            kaspa_stratum_bridge::KaspadPool::new(config.global.kaspad_weight, config.global.kaspad_pool.clone()),
+            config.global.broadcast_blocks,
+        );
+    }
+    // One queue for every instance: they all submit to the same kaspad
+    let submit_queue = (config.global.submit_queue_size > 0)
+        .then(|| kaspa_stratum_bridge::BlockSubmitQueue::start(config.global.submit_queue_size, config.global.submit_queue_policy));
+
+    if config.global.solo_fallback {
+        tokio::spawn(kaspa_stratum_bridge::run_solo_fallback(
+            Arc::clone(&kaspa_api),
+            config.global.solo_fallback_wallet.clone(),
+            config.global.block_wait_time,
+        ));
+    }
 
     let mut instance_handles = Vec::new();
+    let mut max_connections_handles = Vec::new();
     for (idx, instance_config) in config.instances.iter().enumerate() {
         let instance_num = idx + 1;
         let instance = instance_config.clone();
         let global = config.global.clone();
         let kaspa_api_clone = Arc::clone(&kaspa_api);
+        let max_connections = Arc::new(AtomicUsize::new(instance.max_connections.unwrap_or(global.max_connections)));
+        max_connections_handles.push(Arc::clone(&max_connections));
 
         let is_first_instance = idx == 0;
+        let submit_queue = submit_queue.clone();
 
         if let Some(ref prom_port) = instance.prom_port {
-            let prom_port = prom_port.clone();
+            let listener = kaspa_stratum_bridge::bind_listener(prom_port)
+                .await
+                .map_err(|e| anyhow::anyhow!("[Instance {}] cannot bind prom_port {}: {}", instance_num, prom_port, e))?;
+            tracing::info!("[Instance {}] Prometheus metrics on http://{}/metrics", instance_num, bound_address(&listener, prom_port));
             let instance_num_prom = instance_num;
+            let prom_auth = prom_auth.clone();
             tokio::spawn(async move {
-                if let Err(e) = prom::start_prom_server(&prom_port).await {
+                if let Err(e) = prom::serve_prom(listener, prom_auth).await {
                     tracing::error!("[Instance {}] Prometheus server error: {}", instance_num_prom, e);
                 }
             });
@@ -694,15 +2371,44 @@ async fn main() -> Result<(), anyhow::Error> {
                 kaspad_address: global.kaspad_address.clone(),
                 prom_port: String::new(),
                 print_stats: global.print_stats,
+                stats_interval: global.stats_interval,
+                show_hashrate_estimate: global.show_hashrate_estimate,
                 log_to_file: instance.log_to_file.unwrap_or(global.log_to_file),
                 health_check_port: String::new(),
                 block_wait_time: global.block_wait_time,
                 min_share_diff: instance.min_share_diff,
+                start_diff: instance.start_diff.unwrap_or(global.start_diff),
                 var_diff: instance.var_diff.unwrap_or(global.var_diff),
                 shares_per_min: instance.shares_per_min.unwrap_or(global.shares_per_min),
                 var_diff_stats: instance.var_diff_stats.unwrap_or(global.var_diff_stats),
                 extranonce_size: global.extranonce_size,
                 pow2_clamp: instance.pow2_clamp.unwrap_or(global.pow2_clamp),
+                log_notify_submit_rtt: global.log_notify_submit_rtt,
+                account_shares_while_paused: global.account_shares_while_paused,
+                difficulty_format: instance.difficulty_format.unwrap_or(global.difficulty_format),
+                max_connections,
+                label_metrics_by_port: global.label_metrics_by_port,
+                listener_label: instance.label.clone(),
+                extranonce_reuse_delay: global.extranonce_reuse_delay,
+                honor_suggest_difficulty: global.honor_suggest_difficulty,
+                var_diff_min: global.var_diff_min,
+                var_diff_max: global.var_diff_max,
+                max_line_bytes: global.max_line_bytes,
+                socket_tuning: global.socket_tuning,
+                keepalive: (!global.keepalive_interval.is_zero()).then_some(kaspa_stratum_bridge::KeepaliveConfig {
+                    interval: global.keepalive_interval,
+                    timeout: global.keepalive_timeout,
+                }),
+                authorize_timeout: (!global.authorize_timeout.is_zero()).then_some(global.authorize_timeout),
+                var_diff_mode: global.var_diff_mode,
+                var_diff_freeze: global.var_diff_freeze,
+                submit_queue,
+                authorize: kaspa_stratum_bridge::AuthorizeConfig {
+                    fixed_wallet: Some(global.fixed_wallet.clone()).filter(|wallet| !wallet.is_empty()),
+                    worker_overrides: global.worker_overrides.clone(),
+                    network: global.network,
+                    duplicate_worker_policy: global.duplicate_worker_policy,
+                },
             };
 
             kaspa_stratum_bridge::listen_and_serve(
@@ -718,8 +2424,13 @@ async fn main() -> Result<(), anyhow::Error> {
 
     tracing::info!("All {} instance(s) started, waiting for completion...", instance_count);
 
+    spawn_sighup_reload(cli.config.clone(), profile, max_connections_handles);
+    spawn_sigusr1_maintenance(config.global.maintenance_drain);
+
     let bridge_fut = async {
-        let result = try_join_all(instance_handles).await;
+        // Flatten so the first instance that fails (e.g. its stratum port is in use) stops the bridge
+        let instance_results = instance_handles.into_iter().map(|handle| async move { handle.await.map_err(|e| e.to_string())? });
+        let result = try_join_all(instance_results).await;
         match result {
             Ok(_) => {
                 tracing::info!("All instances completed successfully");
@@ -740,6 +2451,13 @@ async fn main() -> Result<(), anyhow::Error> {
             res
         }
         _ = tokio::signal::ctrl_c() => {
+            let closing = kaspa_stratum_bridge::disconnect_all_miners(kaspa_stratum_bridge::DisconnectReason::ServerShutdown);
+            tracing::info!("shutting down, closing {} miner connection(s)", closing);
+            // Let the disconnect handlers log and count them before the process goes
+            let deadline = tokio::time::Instant::now() + SHUTDOWN_DISCONNECT_WAIT;
+            while kaspa_stratum_bridge::connected_clients() > 0 && tokio::time::Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
             if let Some(node) = inprocess_node {
                 shutdown_inprocess(node).await;
             }
@@ -747,3 +2465,365 @@ async fn main() -> Result<(), anyhow::Error> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPO_CONFIG: &str = "kaspad_address: \"127.0.0.1:16110\"\nmin_shares_diff: 4096\n";
+
+    #[test]
+    fn test_tagged_stratum_ports() {
+        let config = BridgeConfig::from_yaml(
+            "min_share_diff: 4096\nprom_port: \":2114\"\nstratum_port:\n  - { port: \":5555\", min_diff: 2048, label: low-diff }\n  - { port: 5557, label: high-diff }\n  - { port: \":5559\" }\n",
+        )
+        .unwrap();
+        let ports: Vec<_> = config.instances.iter().map(|i| (i.stratum_port.as_str(), i.min_share_diff, i.label.as_deref())).collect();
+        assert_eq!(ports, vec![(":5555", 2048, Some("low-diff")), (":5557", 4096, Some("high-diff")), (":5559", 4096, None)]);
+        assert_eq!(config.instances[0].prom_port.as_deref(), Some(":2114"));
+        assert_eq!(config.instances[1].prom_port, None);
+
+        let err = BridgeConfig::from_yaml("stratum_port:\n  - { port: 5555 }\n  - { port: \":5555\" }\n").unwrap_err();
+        assert!(err.to_string().contains("Duplicate stratum_port"), "unexpected error: {}", err);
+        assert!(BridgeConfig::from_yaml("stratum_port:\n  - { min_diff: 2048 }\n").is_err());
+        assert!(BridgeConfig::from_yaml("stratum_port:\n  - { port: 5555, label: \"two words\" }\n").is_err());
+    }
+
+    #[test]
+    fn test_listen_addresses_validated() {
+        let config = BridgeConfig::from_yaml(
+            "health_check_port: \"127.0.0.1:8080\"\nstats_port: \"9090\"\ninstances:\n  - stratum_port: \"5555\"\n    min_share_diff: 2048\n    prom_port: \"10.0.0.2:2114\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.global.health_check_port, "127.0.0.1:8080");
+        assert_eq!(config.global.stats_port, ":9090");
+        assert_eq!(config.instances[0].stratum_port, ":5555");
+        assert_eq!(config.instances[0].prom_port.as_deref(), Some("10.0.0.2:2114"));
+
+        let err = BridgeConfig::from_yaml(
+            "instances:\n  - stratum_port: \":5555\"\n    min_share_diff: 2048\n    prom_port: \"::1:2114\"\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid prom_port"), "unexpected error: {}", err);
+
+        let config = BridgeConfig::from_yaml("stratum_port: \"unix:/run/ks.sock\"\n").unwrap();
+        assert_eq!(config.instances[0].stratum_port, "unix:/run/ks.sock");
+        assert!(BridgeConfig::from_yaml("stats_port: \"unix:/run/stats.sock\"\n").is_err());
+    }
+
+    #[test]
+    fn test_unknown_key_rejected_in_strict_mode() {
+        let strict = format!("strict_config: true\n{}", TYPO_CONFIG);
+        let err = BridgeConfig::from_yaml(&strict).unwrap_err();
+        assert!(err.to_string().contains("min_shares_diff"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_unknown_key_warned_otherwise() {
+        let config = BridgeConfig::from_yaml(TYPO_CONFIG).unwrap();
+        assert_eq!(config.unknown_keys, vec!["min_shares_diff".to_string()]);
+        assert_eq!(config.instances[0].min_share_diff, InstanceConfig::default().min_share_diff);
+
+        let clean = BridgeConfig::from_yaml("strict_config: true\nmin_share_diff: 4096\n").unwrap();
+        assert!(clean.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn test_block_tag_length_validated() {
+        let config = BridgeConfig::from_yaml("block_tag: \"my-pool\"\n").unwrap();
+        assert_eq!(config.global.block_tag, "my-pool");
+
+        let too_long = format!("block_tag: \"{}\"\n", "x".repeat(kaspa_stratum_bridge::MAX_BLOCK_TAG_BYTES + 1));
+        assert!(BridgeConfig::from_yaml(&too_long).unwrap_err().to_string().contains("block_tag"));
+    }
+
+    #[test]
+    fn test_var_diff_retarget_secs_validated() {
+        let config = BridgeConfig::from_yaml("var_diff_retarget_secs: 30\n").unwrap();
+        assert_eq!(config.global.var_diff_retarget, Duration::from_secs(30));
+        assert!(BridgeConfig::from_yaml("var_diff_retarget_secs: 0\n").unwrap_err().to_string().contains("var_diff_retarget_secs"));
+        assert_eq!(BridgeConfig::from_yaml("var_diff_hysteresis: 12.5\n").unwrap().global.var_diff_hysteresis, 12.5);
+        assert!(BridgeConfig::from_yaml("var_diff_hysteresis: 100\n").is_err());
+    }
+
+    #[test]
+    fn test_var_diff_warmup_settings() {
+        let defaults = BridgeConfig::from_yaml("").unwrap();
+        assert_eq!(defaults.global.var_diff_warmup_shares, kaspa_stratum_bridge::DEFAULT_VAR_DIFF_WARMUP_SHARES);
+        let config = BridgeConfig::from_yaml("var_diff_warmup_shares: 0\nvar_diff_warmup_secs: 45\n").unwrap();
+        assert_eq!(config.global.var_diff_warmup_shares, 0);
+        assert_eq!(config.global.var_diff_warmup, Duration::from_secs(45));
+        assert!(BridgeConfig::from_yaml("var_diff_warmup_shares: -1\n").is_err());
+        assert!(BridgeConfig::from_yaml("var_diff_warmup_secs: -1\n").is_err());
+    }
+
+    #[test]
+    fn test_socket_tuning_validated() {
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.socket_tuning, kaspa_stratum_bridge::SocketTuning::default());
+        let config = BridgeConfig::from_yaml("listen_backlog: 4096\nsocket_recv_buffer_bytes: 262144\n").unwrap();
+        assert_eq!(config.global.socket_tuning.listen_backlog, 4096);
+        assert_eq!(config.global.socket_tuning.recv_buffer_size, Some(262144));
+        assert_eq!(config.global.socket_tuning.send_buffer_size, None);
+        assert!(BridgeConfig::from_yaml("listen_backlog: 0\n").unwrap_err().to_string().contains("listen_backlog"));
+        assert!(BridgeConfig::from_yaml("socket_send_buffer_bytes: 100\n")
+            .unwrap_err()
+            .to_string()
+            .contains("socket_send_buffer_bytes"));
+    }
+
+    #[test]
+    fn test_submit_queue_settings() {
+        let config = BridgeConfig::from_yaml("").unwrap();
+        assert_eq!(config.global.submit_queue_size, 0);
+        assert_eq!(config.global.submit_queue_policy, kaspa_stratum_bridge::SubmitQueuePolicy::Wait);
+        let config = BridgeConfig::from_yaml("submit_queue_size: 16\nsubmit_queue_policy: bypass\n").unwrap();
+        assert_eq!(config.global.submit_queue_size, 16);
+        assert_eq!(config.global.submit_queue_policy, kaspa_stratum_bridge::SubmitQueuePolicy::Bypass);
+        assert!(BridgeConfig::from_yaml("submit_queue_size: -1\n").unwrap_err().to_string().contains("submit_queue_size"));
+        assert!(BridgeConfig::from_yaml("submit_queue_policy: drop\n").is_err());
+    }
+
+    #[test]
+    fn test_kaspad_connect_retry_settings() {
+        let config = BridgeConfig::from_yaml("kaspad_connect_retries: 0\nkaspad_connect_timeout_secs: 3\n").unwrap();
+        assert_eq!(config.global.kaspad_connect_retries, 0);
+        assert_eq!(config.global.kaspad_connect_timeout, Duration::from_secs(3));
+        assert!(BridgeConfig::from_yaml("kaspad_connect_timeout_secs: 0\n").is_err());
+        assert!(BridgeConfig::from_yaml("kaspad_connect_retries: -1\n").is_err());
+
+        let config = BridgeConfig::from_yaml("offline_policy: disconnect\noffline_grace_secs: 120\n").unwrap();
+        assert_eq!(config.global.offline_policy, kaspa_stratum_bridge::OfflinePolicy::Disconnect);
+        assert_eq!(config.global.offline_grace, Duration::from_secs(120));
+        assert!(BridgeConfig::from_yaml("offline_policy: failover\n").is_err());
+
+        assert_eq!(kaspad_retry_delay(1, KASPAD_RETRY_DELAY, MAX_KASPAD_RETRY_DELAY), Duration::from_secs(1));
+        assert_eq!(kaspad_retry_delay(3, KASPAD_RETRY_DELAY, MAX_KASPAD_RETRY_DELAY), Duration::from_secs(4));
+        assert_eq!(kaspad_retry_delay(10, KASPAD_RETRY_DELAY, MAX_KASPAD_RETRY_DELAY), MAX_KASPAD_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_extranonce_detection_settings() {
+        let config = BridgeConfig::from_yaml(
+            "extranonce_preferred_size: 3\nextranonce_min_size: 1\nextranonce_max_size: 3\nextranonce_honor_hints: true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.global.extranonce_detection,
+            kaspa_stratum_bridge::ExtranonceDetection { preferred: Some(3), min: 1, max: 3, honor_hints: true }
+        );
+        assert!(BridgeConfig::from_yaml("extranonce_max_size: 4\n").is_err());
+        assert!(BridgeConfig::from_yaml("extranonce_min_size: 2\nextranonce_max_size: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_log_retention_setting() {
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.log_retention, 0);
+        let config = BridgeConfig::from_yaml("log_compress: zstd\nlog_retention: 20\n").unwrap();
+        assert_eq!((config.global.log_compress, config.global.log_retention), (kaspa_stratum_bridge::LogCompression::Zstd, 20));
+        assert_eq!(config.global.log_rotation, kaspa_stratum_bridge::LogRotation::Daily);
+        let config = BridgeConfig::from_yaml("log_rotation: hourly\n").unwrap();
+        assert_eq!(config.global.log_rotation, kaspa_stratum_bridge::LogRotation::Hourly);
+        assert!(BridgeConfig::from_yaml("log_rotation: weekly\n").is_err());
+        assert!(BridgeConfig::from_yaml("log_retention: -1\n").is_err());
+    }
+
+    #[test]
+    fn test_prom_auth_pair() {
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.prom_auth, None);
+        let config = BridgeConfig::from_yaml("prom_auth_user: prometheus\nprom_auth_pass: s3cret\n").unwrap();
+        assert_eq!(config.global.prom_auth, Some(("prometheus".to_string(), "s3cret".to_string())));
+        assert!(BridgeConfig::from_yaml("prom_auth_user: prometheus\n").is_err(), "a user without a password");
+    }
+
+    #[test]
+    fn test_template_refresh_setting() {
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.template_refresh, Duration::ZERO);
+        assert_eq!(BridgeConfig::from_yaml("template_refresh_secs: 15\n").unwrap().global.template_refresh, Duration::from_secs(15));
+        assert!(BridgeConfig::from_yaml("template_refresh_secs: -5\n").is_err());
+    }
+
+    #[test]
+    fn test_reject_warmup_settings() {
+        let config = BridgeConfig::from_yaml("reject_warmup_secs: 30\nreject_warmup_after_job_ms: 250\n").unwrap();
+        assert_eq!(config.global.reject_warmup, Duration::from_secs(30));
+        assert_eq!(config.global.reject_warmup_after_job, Duration::from_millis(250));
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.reject_warmup, Duration::ZERO);
+        assert!(BridgeConfig::from_yaml("reject_warmup_secs: -1\n").is_err());
+    }
+
+    #[test]
+    fn test_runtime_threads_validated() {
+        let defaults = BridgeConfig::from_yaml("").unwrap();
+        assert_eq!(defaults.global.worker_threads, 0);
+        assert_eq!(defaults.global.max_blocking_threads, DEFAULT_MAX_BLOCKING_THREADS);
+        let config = BridgeConfig::from_yaml("worker_threads: 16\nmax_blocking_threads: 64\n").unwrap();
+        assert_eq!((config.global.worker_threads, config.global.max_blocking_threads), (16, 64));
+        assert!(BridgeConfig::from_yaml("worker_threads: -1\n").unwrap_err().to_string().contains("worker_threads"));
+        assert!(BridgeConfig::from_yaml("worker_threads: 100000\n").is_err());
+        assert!(BridgeConfig::from_yaml("max_blocking_threads: 0\n").unwrap_err().to_string().contains("max_blocking_threads"));
+
+        let runtime = build_runtime(&config.global).unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 16);
+    }
+
+    #[test]
+    fn test_duplicate_worker_policy_parsed() {
+        let defaults = BridgeConfig::from_yaml("").unwrap();
+        assert_eq!(defaults.global.duplicate_worker_policy, kaspa_stratum_bridge::DuplicateWorkerPolicy::Allow);
+        let config = BridgeConfig::from_yaml("duplicate_worker_policy: kick_old\n").unwrap();
+        assert_eq!(config.global.duplicate_worker_policy, kaspa_stratum_bridge::DuplicateWorkerPolicy::KickOld);
+        assert!(BridgeConfig::from_yaml("duplicate_worker_policy: rename\n").is_err());
+    }
+
+    #[test]
+    fn test_stale_warn_pct_validated() {
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.stale_warn_pct, 0.0);
+        assert_eq!(BridgeConfig::from_yaml("stale_warn_pct: 5\n").unwrap().global.stale_warn_pct, 5.0);
+        assert_eq!(BridgeConfig::from_yaml("stale_warn_pct: 2.5\n").unwrap().global.stale_warn_pct, 2.5);
+        assert!(BridgeConfig::from_yaml("stale_warn_pct: 120\n").is_err());
+    }
+
+    #[test]
+    fn test_near_miss_ratio_validated() {
+        assert_eq!(BridgeConfig::from_yaml("").unwrap().global.near_miss_ratio, 0.1);
+        assert_eq!(BridgeConfig::from_yaml("near_miss_ratio: 0.5\n").unwrap().global.near_miss_ratio, 0.5);
+        assert_eq!(BridgeConfig::from_yaml("near_miss_ratio: 0\n").unwrap().global.near_miss_ratio, 0.0);
+        assert!(BridgeConfig::from_yaml("near_miss_ratio: 1\n").unwrap_err().to_string().contains("near_miss_ratio"));
+    }
+
+    #[test]
+    fn test_worker_overrides_parsed() {
+        let yaml = "worker_overrides:\n  \"s21-*\":\n    min_share_diff: 65536\n    var_diff: false\n  \"kaspa:qzfarm*\":\n    start_diff: 4096\n";
+        let config = BridgeConfig::from_yaml(yaml).unwrap();
+        assert_eq!(
+            config.global.worker_overrides,
+            vec![
+                kaspa_stratum_bridge::WorkerOverride {
+                    pattern: "s21-*".into(),
+                    min_share_diff: Some(65536.0),
+                    start_diff: None,
+                    var_diff: Some(false),
+                },
+                kaspa_stratum_bridge::WorkerOverride {
+                    pattern: "kaspa:qzfarm*".into(),
+                    min_share_diff: None,
+                    start_diff: Some(4096.0),
+                    var_diff: None,
+                },
+            ]
+        );
+
+        let typo = "worker_overrides:\n  rig01:\n    min_diff: 1024\n";
+        assert!(BridgeConfig::from_yaml(typo).unwrap_err().to_string().contains("unknown setting 'min_diff'"));
+        assert!(BridgeConfig::from_yaml("worker_overrides:\n  rig01:\n    start_diff: 0\n").is_err());
+    }
+
+    #[test]
+    fn test_profile_selects_document() {
+        let yaml =
+            "kaspad_address: \"10.0.0.1:16110\"\nprint_stats: false\n---\nprofile: testnet\nkaspad_address: \"10.0.0.2:16210\"\n\
+                    network: testnet-11\n---\nprofile: local\nkaspad_address: \"127.0.0.1:16110\"\n";
+        let first = BridgeConfig::from_yaml(yaml).unwrap();
+        assert_eq!(first.global.kaspad_address, "10.0.0.1:16110");
+        assert!(first.global.profile.is_empty());
+
+        let testnet = BridgeConfig::from_yaml_profile(yaml, Some("testnet")).unwrap();
+        assert_eq!(testnet.global.profile, "testnet");
+        assert_eq!(testnet.global.kaspad_address, "10.0.0.2:16210");
+        assert_eq!(testnet.global.network, Some(kaspa_stratum_bridge::KaspaNetwork::Testnet11));
+        assert!(!testnet.global.print_stats, "untagged settings are shared by every profile");
+
+        let err = BridgeConfig::from_yaml_profile(yaml, Some("mainnet")).unwrap_err().to_string();
+        assert!(err.contains("available: testnet, local"), "{}", err);
+    }
+
+    #[test]
+    fn test_network_parsed() {
+        let config = BridgeConfig::from_yaml("network: testnet-11\n").unwrap();
+        assert_eq!(config.global.network, Some(kaspa_stratum_bridge::KaspaNetwork::Testnet11));
+        assert_eq!(BridgeConfig::from_yaml("kaspad_address: \"127.0.0.1:16110\"\n").unwrap().global.network, None);
+        assert!(BridgeConfig::from_yaml("network: testnet-12\n").is_err());
+
+        let mainnet_wallet = format!("network: testnet-11\nfixed_wallet: \"kaspa:{}\"\n", "q".repeat(61));
+        assert!(BridgeConfig::from_yaml(&mainnet_wallet).unwrap_err().to_string().contains("not a testnet-11 address"));
+    }
+
+    #[test]
+    fn test_solo_fallback_wallet_validated() {
+        let wallet = format!("kaspa:{}", "q".repeat(61));
+        let config = BridgeConfig::from_yaml(&format!("solo_fallback: true\nsolo_fallback_wallet: \"{}\"\n", wallet)).unwrap();
+        assert!(config.global.solo_fallback);
+        assert_eq!(config.global.solo_fallback_wallet, wallet);
+
+        let typo = BridgeConfig::from_yaml("solo_fallback: true\nsolo_fallback_wallet: \"kaspa:qz0typo\"\n").unwrap_err();
+        assert!(typo.to_string().contains("invalid solo_fallback_wallet"), "{}", typo);
+        assert!(BridgeConfig::from_yaml("solo_fallback: true\n").is_err(), "needs a wallet");
+    }
+
+    #[test]
+    fn test_fixed_wallet_takes_the_network_prefix() {
+        let bare = "q".repeat(61);
+        let config = BridgeConfig::from_yaml(&format!("network: testnet-10\nfixed_wallet: \"{}\"\n", bare)).unwrap();
+        assert_eq!(config.global.fixed_wallet, format!("kaspatest:{}", bare));
+        let config = BridgeConfig::from_yaml(&format!("fixed_wallet: \"{}\"\n", bare)).unwrap();
+        assert_eq!(config.global.fixed_wallet, format!("kaspa:{}", bare));
+        let mainnet = BridgeConfig::from_yaml(&format!("network: testnet-10\nfixed_wallet: \"kaspa:{}\"\n", bare)).unwrap_err();
+        assert!(mainnet.to_string().contains("invalid fixed_wallet"), "{}", mainnet);
+    }
+
+    #[test]
+    fn test_kaspad_pool_parsed() {
+        let yaml =
+            "kaspad_address: \"10.0.0.1:16110\"\nkaspad_weight: 3\nkaspad_pool:\n  - address: \"10.0.0.2:16110\"\n    weight: 2\n\
+                    \x20 - address: \"10.0.0.3:16110\"\n    health_check_secs: 0\n";
+        let config = BridgeConfig::from_yaml(yaml).unwrap();
+        assert_eq!(config.global.kaspad_weight, 3);
+        assert_eq!(
+            config.global.kaspad_pool,
+            vec![
+                kaspa_stratum_bridge::PoolNodeConfig {
+                    address: "10.0.0.2:16110".into(),
+                    weight: 2,
+                    health_check: Duration::from_secs(kaspa_stratum_bridge::DEFAULT_POOL_HEALTH_CHECK_SECS),
+                },
+                kaspa_stratum_bridge::PoolNodeConfig { address: "10.0.0.3:16110".into(), weight: 1, health_check: Duration::ZERO },
+            ]
+        );
+
+        let duplicate = "kaspad_address: \"10.0.0.1:16110\"\nkaspad_pool:\n  - address: \"10.0.0.1:16110\"\n";
+        assert!(BridgeConfig::from_yaml(duplicate).unwrap_err().to_string().contains("more than once"));
+
+        assert!(!config.global.broadcast_blocks);
+        let broadcast = BridgeConfig::from_yaml(&format!("{}broadcast_blocks: true\n", yaml)).unwrap();
+        assert!(broadcast.global.broadcast_blocks);
+        assert!(BridgeConfig::from_yaml("broadcast_blocks: true\n").unwrap_err().to_string().contains("needs kaspad_pool"));
+    }
+
+    #[test]
+    fn test_toml_config_matches_yaml() {
+        let yaml = "kaspad_address: \"127.0.0.1:16110\"\nblock_wait_time: 500\nvar_diff: true\newma_alpha: 0.2\nvar_diff_mode: ewma\n\
+                    instances:\n  - stratum_port: \":5555\"\n    min_share_diff: 2048\n    prom_port: \":2114\"\n";
+        let toml = "kaspad_address = \"127.0.0.1:16110\"\nblock_wait_time = 500\nvar_diff = true\newma_alpha = 0.2\nvar_diff_mode = \"ewma\"\n\n\
+                    [[instances]]\nstratum_port = \":5555\"\nmin_share_diff = 2048\nprom_port = \":2114\"\n";
+
+        let from_yaml = BridgeConfig::from_yaml(yaml).unwrap();
+        let from_toml = BridgeConfig::from_toml(toml).unwrap();
+        assert_eq!(format!("{:?}", from_yaml), format!("{:?}", from_toml));
+        assert_eq!(from_toml.global.block_wait_time, Duration::from_millis(500));
+        assert_eq!(from_toml.instances[0].min_share_diff, 2048);
+        assert!(from_toml.unknown_keys.is_empty());
+
+        assert_eq!(ConfigFormat::from_path(Path::new("bridge.toml")), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path(Path::new("config.yml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("config.yaml")), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn test_malformed_config_reports_line() {
+        let err = BridgeConfig::from_toml("var_diff = true\nshares_per_min = \n").unwrap_err().to_string();
+        assert!(err.contains("line 2"), "unexpected error: {}", err);
+
+        let err = BridgeConfig::from_yaml("var_diff: true\ninstances: [\n").unwrap_err().to_string();
+        assert!(err.contains("line"), "unexpected error: {}", err);
+    }
+}
@@ -1,15 +1,172 @@
+use crate::default_client::WorkerOverride;
 use crate::hasher::KaspaDiff;
 use kaspa_consensus_core::block::Block;
 use kaspa_hashes::Hash;
 use num_bigint::BigUint;
 use num_traits::Zero;
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 use tracing;
 
-const MAX_JOBS: u64 = 300;
+/// Jobs kept per connection for share validation unless job_history says otherwise
+pub const DEFAULT_JOB_HISTORY: u16 = 300;
+
+static JOB_HISTORY: AtomicU16 = AtomicU16::new(DEFAULT_JOB_HISTORY);
+
+/// Number of recent jobs each new connection keeps (process-wide, at least 1)
+pub fn set_job_history(depth: u16) {
+    JOB_HISTORY.store(depth.max(1), Ordering::Relaxed);
+}
+
+/// Only every Nth job is timed for notify->submit round-trip logging
+const NOTIFY_RTT_SAMPLE_INTERVAL: u64 = 10;
+/// Cap on remembered nonces per job so a misbehaving miner can't grow the dedup cache without bound
+const MAX_SEEN_NONCES_PER_JOB: usize = 4096;
+
+/// Window over which max_shares_per_sec is averaged, so the burst after a new job is tolerated
+pub const SHARE_RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Share submissions counted over consecutive SHARE_RATE_WINDOW windows
+#[derive(Debug, Default)]
+pub struct SubmitRateWindow {
+    started: Option<Instant>,
+    count: u64,
+    flagged: bool,
+}
+
+impl SubmitRateWindow {
+    /// Count one submission, returning the rate (per second) of the window it closed, if any
+    pub fn record(&mut self, now: Instant) -> Option<f64> {
+        match self.started {
+            Some(started) if now.saturating_duration_since(started) < SHARE_RATE_WINDOW => {
+                self.count += 1;
+                None
+            }
+            previous => {
+                let closed = previous.map(|_| self.count as f64 / SHARE_RATE_WINDOW.as_secs_f64());
+                *self = SubmitRateWindow { started: Some(now), count: 1, flagged: false };
+                closed
+            }
+        }
+    }
+
+    /// Whether the current window averages more than `max_per_sec` (0 = no limit)
+    pub fn exceeds(&self, max_per_sec: u32) -> bool {
+        max_per_sec > 0 && self.count as f64 > max_per_sec as f64 * SHARE_RATE_WINDOW.as_secs_f64()
+    }
+
+    /// True only the first time it is called in a window, to report a flood once per window
+    pub fn flag(&mut self) -> bool {
+        !std::mem::replace(&mut self.flagged, true)
+    }
+}
+
+/// Window the rolling stale rate (ks_stale_share_pct, stale_warn_pct) is measured over
+pub const STALE_RATE_WINDOW: Duration = Duration::from_secs(600);
+/// Shares are counted in buckets of this long, so the bridge-wide window stays small under any share rate
+const STALE_RATE_BUCKET: Duration = Duration::from_secs(10);
+/// Shares a window needs before it has a stale rate, so one early stale share doesn't read as 100%
+pub const STALE_RATE_MIN_SHARES: u64 = 20;
+/// A connection over stale_warn_pct is warned about at most this often
+const STALE_WARN_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Shares and stale shares over the last STALE_RATE_WINDOW, as counted by the reject counters
+#[derive(Debug, Default)]
+pub struct StaleRateWindow {
+    buckets: VecDeque<(Instant, u64, u64)>, // (bucket start, shares, stale shares)
+    warned_at: Option<Instant>,
+}
+
+impl StaleRateWindow {
+    /// Count one share at `now`, forgetting buckets that have left the window
+    pub fn record(&mut self, now: Instant, stale: bool) {
+        while self.buckets.front().is_some_and(|(start, _, _)| now.saturating_duration_since(*start) >= STALE_RATE_WINDOW) {
+            self.buckets.pop_front();
+        }
+        match self.buckets.back_mut() {
+            Some((start, shares, stale_shares)) if now.saturating_duration_since(*start) < STALE_RATE_BUCKET => {
+                *shares += 1;
+                *stale_shares += stale as u64;
+            }
+            _ => self.buckets.push_back((now, 1, stale as u64)),
+        }
+    }
+
+    /// Stale shares as a percentage of the window, None until it holds STALE_RATE_MIN_SHARES
+    pub fn pct(&self) -> Option<f64> {
+        let (shares, stale) = self.buckets.iter().fold((0, 0), |(shares, stale), (_, s, st)| (shares + s, stale + st));
+        (shares >= STALE_RATE_MIN_SHARES).then(|| stale as f64 * 100.0 / shares as f64)
+    }
+
+    /// True when a warning about this window is due at `now`, at most once per STALE_WARN_INTERVAL
+    pub fn warning_due(&mut self, now: Instant) -> bool {
+        if self.warned_at.is_some_and(|at| now.saturating_duration_since(at) < STALE_WARN_INTERVAL) {
+            return false;
+        }
+        self.warned_at = Some(now);
+        true
+    }
+}
+
+/// Buckets in a SharedStaleRate, one STALE_RATE_BUCKET each
+const STALE_RATE_BUCKETS: usize = (STALE_RATE_WINDOW.as_secs() / STALE_RATE_BUCKET.as_secs()) as usize;
+
+/// One STALE_RATE_BUCKET of a SharedStaleRate; `index` is the bucket number it counts plus one, 0 = never used
+#[derive(Debug, Default)]
+struct StaleRateBucket {
+    index: AtomicU64,
+    shares: AtomicU64,
+    stale: AtomicU64,
+}
+
+/// StaleRateWindow over every connection, lock-free for the share path: a ring of atomic buckets a
+/// share counts into, reset as the window moves past them. A share racing a bucket's reset can go
+/// uncounted, which the rate doesn't notice
+#[derive(Debug)]
+pub struct SharedStaleRate {
+    start: Instant,
+    buckets: [StaleRateBucket; STALE_RATE_BUCKETS],
+}
+
+impl SharedStaleRate {
+    pub fn new(start: Instant) -> Self {
+        Self { start, buckets: std::array::from_fn(|_| StaleRateBucket::default()) }
+    }
+
+    fn bucket_index(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs() / STALE_RATE_BUCKET.as_secs() + 1
+    }
+
+    /// Count one share at `now`
+    pub fn record(&self, now: Instant, stale: bool) {
+        let index = self.bucket_index(now);
+        let bucket = &self.buckets[index as usize % STALE_RATE_BUCKETS];
+        let seen = bucket.index.load(Ordering::Acquire);
+        if seen < index && bucket.index.compare_exchange(seen, index, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            bucket.shares.store(0, Ordering::Relaxed);
+            bucket.stale.store(0, Ordering::Relaxed);
+        }
+        bucket.shares.fetch_add(1, Ordering::Relaxed);
+        bucket.stale.fetch_add(stale as u64, Ordering::Relaxed);
+    }
+
+    /// Stale shares as a percentage of the window ending at `now`, None until it holds STALE_RATE_MIN_SHARES
+    pub fn pct(&self, now: Instant) -> Option<f64> {
+        let index = self.bucket_index(now);
+        let oldest = index.saturating_sub(STALE_RATE_BUCKETS as u64 - 1).max(1);
+        let (shares, stale) = self
+            .buckets
+            .iter()
+            .filter(|bucket| (oldest..=index).contains(&bucket.index.load(Ordering::Acquire)))
+            .fold((0, 0), |(shares, stale), bucket| {
+                (shares + bucket.shares.load(Ordering::Relaxed), stale + bucket.stale.load(Ordering::Relaxed))
+            });
+        (shares >= STALE_RATE_MIN_SHARES).then(|| stale as f64 * 100.0 / shares as f64)
+    }
+}
 
 /// Job structure that holds both the block and the pre-PoW hash
 /// The pre-PoW hash is what we send to the ASIC for mining
@@ -19,6 +176,20 @@ pub struct Job {
     pub pre_pow_hash: Hash,
 }
 
+impl Job {
+    /// Job for a connection on timestamp lane `lane` (partition_nonce): the template with its header time
+    /// moved `lane` ms ahead. The pre-PoW hash leaves the timestamp out and stays the same, but every
+    /// nonce hashes differently, so the lane never repeats work another connection is doing
+    pub fn in_nonce_lane(block: &Block, pre_pow_hash: Hash, lane: u64) -> Self {
+        if lane == 0 {
+            return Self { block: block.clone(), pre_pow_hash };
+        }
+        let mut header = (*block.header).clone();
+        header.timestamp += lane;
+        Self { block: Block::from_arcs(Arc::new(header), Arc::clone(&block.transactions)), pre_pow_hash }
+    }
+}
+
 /// Mining state for a client connection
 #[derive(Debug)]
 pub struct MiningState {
@@ -32,10 +203,52 @@ pub struct MiningState {
     stratum_diff: Arc<Mutex<Option<KaspaDiff>>>,
     max_jobs: u16,
     last_header: Arc<Mutex<Option<kaspa_consensus_core::header::Header>>>, // Track previous header for change logging
+    notify_sent_times: Arc<Mutex<HashMap<u64, Instant>>>,                  // Sampled job ID -> time mining.notify was sent
+    seen_nonces: Arc<Mutex<HashMap<u64, HashSet<u64>>>>,                   // Job ID -> nonces already submitted (duplicate detection)
+    suggested_diff: Arc<Mutex<Option<f64>>>,                               // Accepted mining.suggest_difficulty value
+    submit_rate: Arc<Mutex<SubmitRateWindow>>,                             // Recent mining.submit rate (max_shares_per_sec)
+    first_share: Arc<Mutex<FirstShare>>,                                   // Authorize -> first accepted share timing
+    worker_override: Arc<Mutex<Option<WorkerOverride>>>,                   // worker_overrides entry matched on authorize
+    subscribed: Arc<Mutex<bool>>,                                          // mining.subscribe answered
+    static_diff: Arc<Mutex<Option<f64>>>,                                  // d= worker suffix: fixed difficulty, no vardiff
+    last_submit: Arc<Mutex<Option<Instant>>>,                              // Most recent mining.submit, accepted or not
+    job_resend: Arc<Mutex<JobResend>>,                                     // Current job resent after a submit for an unknown job
+    extranonce_source: Arc<Mutex<Option<&'static str>>>,                   // What the extranonce size was picked from
+    last_job_at: Arc<Mutex<Option<Instant>>>,                              // When the newest job was added
+    nonce_lane: Arc<Mutex<Option<u64>>>,                                   // partition_nonce timestamp lane (no extranonce)
+    stale_rate: Arc<Mutex<StaleRateWindow>>,                               // Rolling stale share percentage
+}
+
+/// The one resend of the current job a connection gets after submitting against a job it was never sent
+#[derive(Debug, Default, Clone, Copy)]
+struct JobResend {
+    requested: bool,
+    pending: bool,
+}
+
+/// Where a connection is in the subscribe -> authorize -> submitting lifecycle
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ConnectionStates {
+    pub subscribed: bool,
+    pub authorized: bool,
+    /// Submitted a share within the activity window
+    pub active: bool,
+}
+
+/// When a connection authorized and whether it has had a share accepted since
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FirstShare {
+    pub authorized_at: Option<Instant>,
+    pub accepted: bool,
 }
 
 impl MiningState {
     pub fn new() -> Self {
+        Self::with_job_history(JOB_HISTORY.load(Ordering::Relaxed))
+    }
+
+    /// State that keeps the `depth` most recent jobs; shares for older ones are stale
+    pub fn with_job_history(depth: u16) -> Self {
         Self {
             jobs: Arc::new(Mutex::new(HashMap::new())),
             job_ids: Arc::new(Mutex::new(HashMap::new())),
@@ -45,8 +258,22 @@ impl MiningState {
             use_big_job: Arc::new(Mutex::new(false)),
             connect_time: SystemTime::now(),
             stratum_diff: Arc::new(Mutex::new(None)),
-            max_jobs: MAX_JOBS as u16,
+            max_jobs: depth.max(1),
             last_header: Arc::new(Mutex::new(None)),
+            notify_sent_times: Arc::new(Mutex::new(HashMap::new())),
+            seen_nonces: Arc::new(Mutex::new(HashMap::new())),
+            suggested_diff: Arc::new(Mutex::new(None)),
+            submit_rate: Arc::new(Mutex::new(SubmitRateWindow::default())),
+            first_share: Arc::new(Mutex::new(FirstShare::default())),
+            worker_override: Arc::new(Mutex::new(None)),
+            subscribed: Arc::new(Mutex::new(false)),
+            static_diff: Arc::new(Mutex::new(None)),
+            last_submit: Arc::new(Mutex::new(None)),
+            job_resend: Arc::new(Mutex::new(JobResend::default())),
+            extranonce_source: Arc::new(Mutex::new(None)),
+            last_job_at: Arc::new(Mutex::new(None)),
+            nonce_lane: Arc::new(Mutex::new(None)),
+            stale_rate: Arc::new(Mutex::new(StaleRateWindow::default())),
         }
     }
 
@@ -55,7 +282,7 @@ impl MiningState {
         let mut counter = self.job_counter.lock();
         *counter += 1;
         let idx = *counter;
-        let slot = idx % MAX_JOBS;
+        let slot = idx % self.max_jobs as u64;
 
         let mut jobs = self.jobs.lock();
         let mut job_ids = self.job_ids.lock();
@@ -63,22 +290,56 @@ impl MiningState {
         // Log if we're overwriting an old job
         if let Some(old_id) = job_ids.get(&slot) {
             tracing::debug!("Overwriting job at slot {}: old_id={}, new_id={}", slot, old_id, idx);
+            // The evicted job can no longer be submitted against, so its nonces can go too
+            self.seen_nonces.lock().remove(old_id);
         }
 
         jobs.insert(slot, job);
         job_ids.insert(slot, idx);
+        *self.last_job_at.lock() = Some(Instant::now());
 
         tracing::debug!("[JOB STORAGE] Added job ID {} at slot {} (counter now: {})", idx, slot, idx);
         idx
     }
 
+    /// Whether `id` was never issued to this connection: 0 or ahead of the job counter, e.g. a submit
+    /// racing the first mining.notify
+    pub fn is_job_unknown(&self, id: u64) -> bool {
+        id == 0 || id > self.current_job_counter()
+    }
+
+    /// Whether a reject at `now` falls within `after_authorize` of authorizing or `after_job` of the newest
+    /// job, when rejects are expected while the miner catches up (a zero window never matches)
+    pub fn in_reject_warmup(&self, now: Instant, after_authorize: Duration, after_job: Duration) -> bool {
+        let within = |at: Option<Instant>, window: Duration| {
+            !window.is_zero() && at.is_some_and(|at| now.saturating_duration_since(at) < window)
+        };
+        within(self.first_share.lock().authorized_at, after_authorize) || within(*self.last_job_at.lock(), after_job)
+    }
+
+    /// Ask for the current job to be resent; only the first call per connection does
+    pub fn request_job_resend(&self) -> bool {
+        let mut resend = self.job_resend.lock();
+        if resend.requested {
+            return false;
+        }
+        resend.requested = true;
+        resend.pending = true;
+        true
+    }
+
+    /// Whether a requested job resend is still to be sent; clears it
+    pub fn take_job_resend(&self) -> bool {
+        std::mem::take(&mut self.job_resend.lock().pending)
+    }
+
     /// Get a job by ID
     /// Return job at slot (id % maxJobs) without verifying ID matches
     ///          return job, exists
     /// Does NOT verify that the stored job ID matches - it just returns whatever is at that slot
     pub fn get_job(&self, id: u64) -> Option<Job> {
         let jobs = self.jobs.lock();
-        let slot = id % MAX_JOBS;
+        let slot = id % self.max_jobs as u64;
 
         // Return job at slot, don't verify ID matches
         jobs.get(&slot).cloned()
@@ -87,7 +348,13 @@ impl MiningState {
     /// Get job ID at a specific slot (for debugging/stale job workaround)
     pub fn get_job_id_at_slot(&self, slot: u64) -> Option<u64> {
         let job_ids = self.job_ids.lock();
-        job_ids.get(&(slot % MAX_JOBS)).copied()
+        job_ids.get(&(slot % self.max_jobs as u64)).copied()
+    }
+
+    /// Whether a job was issued but has since been pushed out of the job history. `id` comes straight
+    /// from a miner's submit, so it may be anything up to u64::MAX
+    pub fn is_job_expired(&self, id: u64) -> bool {
+        id.saturating_add(self.max_jobs as u64) <= self.current_job_counter()
     }
 
     /// Set the big difficulty (network target)
@@ -160,6 +427,157 @@ impl MiningState {
     pub fn set_last_header(&self, header: kaspa_consensus_core::header::Header) {
         *self.last_header.lock() = Some(header);
     }
+
+    /// Remember a nonce submitted for a job
+    /// Returns false if the same nonce was already submitted for that job (duplicate share)
+    pub fn record_nonce(&self, job_id: u64, nonce: u64) -> bool {
+        let mut seen = self.seen_nonces.lock();
+        let nonces = seen.entry(job_id).or_default();
+        if nonces.contains(&nonce) {
+            return false;
+        }
+        if nonces.len() < MAX_SEEN_NONCES_PER_JOB {
+            nonces.insert(nonce);
+        }
+        true
+    }
+
+    /// Submission rate window of this connection
+    pub fn submit_rate(&self) -> parking_lot::MutexGuard<'_, SubmitRateWindow> {
+        self.submit_rate.lock()
+    }
+
+    /// Start the time-to-first-share clock; re-authorizing doesn't restart it
+    pub fn mark_authorized(&self, now: Instant) {
+        self.first_share.lock().authorized_at.get_or_insert(now);
+    }
+
+    /// Note an accepted share; returns the time since authorize only for the connection's first one
+    pub fn record_accepted_share(&self, now: Instant) -> Option<Duration> {
+        let mut first = self.first_share.lock();
+        if first.accepted {
+            return None;
+        }
+        first.accepted = true;
+        first.authorized_at.map(|at| now.saturating_duration_since(at))
+    }
+
+    pub fn first_share(&self) -> FirstShare {
+        *self.first_share.lock()
+    }
+
+    pub fn mark_subscribed(&self) {
+        *self.subscribed.lock() = true;
+    }
+
+    /// Note a mining.submit; counts towards being active whatever the share's outcome
+    pub fn mark_submitted(&self, now: Instant) {
+        *self.last_submit.lock() = Some(now);
+    }
+
+    /// Lifecycle flags of this connection, `active` meaning a submit within `active_window` of `now`
+    pub fn connection_states(&self, now: Instant, active_window: Duration) -> ConnectionStates {
+        ConnectionStates {
+            subscribed: *self.subscribed.lock(),
+            authorized: self.first_share.lock().authorized_at.is_some(),
+            active: self.last_submit.lock().is_some_and(|at| now.saturating_duration_since(at) <= active_window),
+        }
+    }
+
+    /// What the extranonce detection based this connection's size on ("hint", "agent" or "fallback")
+    pub fn extranonce_source(&self) -> Option<&'static str> {
+        *self.extranonce_source.lock()
+    }
+
+    pub fn set_extranonce_source(&self, source: &'static str) {
+        *self.extranonce_source.lock() = Some(source);
+    }
+
+    /// Timestamp lane (ms ahead of the template) this connection's jobs are moved to, 0 without one
+    pub fn nonce_lane(&self) -> u64 {
+        self.nonce_lane.lock().unwrap_or(0)
+    }
+
+    pub fn set_nonce_lane(&self, lane: u64) {
+        *self.nonce_lane.lock() = Some(lane);
+    }
+
+    /// Count a share in the connection's rolling stale rate and return the rate, if the window has one yet
+    pub fn record_share_outcome(&self, now: Instant, stale: bool) -> Option<f64> {
+        let mut window = self.stale_rate.lock();
+        window.record(now, stale);
+        window.pct()
+    }
+
+    /// Whether the connection's stale rate warning is due (throttled per connection)
+    pub fn stale_warning_due(&self, now: Instant) -> bool {
+        self.stale_rate.lock().warning_due(now)
+    }
+
+    /// Give up the connection's lane, returning it so it can be handed out again
+    pub fn take_nonce_lane(&self) -> Option<u64> {
+        self.nonce_lane.lock().take()
+    }
+
+    /// Get the starting difficulty suggested by the miner, if one was accepted
+    pub fn suggested_diff(&self) -> Option<f64> {
+        *self.suggested_diff.lock()
+    }
+
+    /// Set the starting difficulty suggested by the miner
+    pub fn set_suggested_diff(&self, diff: f64) {
+        *self.suggested_diff.lock() = Some(diff);
+    }
+
+    pub fn worker_override(&self) -> Option<WorkerOverride> {
+        self.worker_override.lock().clone()
+    }
+
+    pub fn set_worker_override(&self, worker_override: WorkerOverride) {
+        *self.worker_override.lock() = Some(worker_override);
+    }
+
+    /// Difficulty pinned with a `d=<diff>` worker suffix; vardiff leaves such connections alone
+    pub fn static_diff(&self) -> Option<f64> {
+        *self.static_diff.lock()
+    }
+
+    pub fn set_static_diff(&self, diff: f64) {
+        *self.static_diff.lock() = Some(diff);
+    }
+
+    /// Difficulty the first job goes out at: a pinned static difficulty as is, else the miner's suggestion,
+    /// else the override's start_diff, else `default`, raised to the override's min_share_diff
+    pub fn starting_diff(&self, default: f64) -> f64 {
+        if let Some(diff) = self.static_diff() {
+            return diff;
+        }
+        let worker_override = self.worker_override();
+        let diff = self.suggested_diff().or(worker_override.as_ref().and_then(|o| o.start_diff)).unwrap_or(default);
+        match worker_override.and_then(|o| o.min_share_diff) {
+            Some(floor) => diff.max(floor),
+            None => diff,
+        }
+    }
+
+    /// Record the time mining.notify was sent for a job, if the job is sampled
+    /// Returns true if the job was sampled
+    pub fn record_notify_sent(&self, job_id: u64) -> bool {
+        if job_id == 0 || (job_id - 1) % NOTIFY_RTT_SAMPLE_INTERVAL != 0 {
+            return false;
+        }
+        let mut times = self.notify_sent_times.lock();
+        // Drop samples for jobs that have already been overwritten in the job ring
+        times.retain(|id, _| id.saturating_add(self.max_jobs as u64) > job_id);
+        times.insert(job_id, Instant::now());
+        true
+    }
+
+    /// Take the notify->submit round-trip time for a sampled job
+    /// Only the first submit for a job yields a value
+    pub fn take_notify_rtt(&self, job_id: u64) -> Option<Duration> {
+        self.notify_sent_times.lock().remove(&job_id).map(|sent| sent.elapsed())
+    }
 }
 
 impl Default for MiningState {
@@ -174,3 +592,297 @@ pub fn GetMiningState(ctx: &crate::stratum_context::StratumContext) -> Arc<Minin
     // State is now stored directly as Arc<MiningState>, so we can just clone it
     Arc::clone(&ctx.state)
 }
+
+/// Whether `new` moved the header timestamp at least `refresh` past `old`'s, so a template_refresh_secs
+/// refresh gets through even when template_changed says it is the same work (zero = never)
+pub fn timestamp_refresh_due(
+    old: &kaspa_consensus_core::header::Header,
+    new: &kaspa_consensus_core::header::Header,
+    refresh: Duration,
+) -> bool {
+    !refresh.is_zero() && new.timestamp.saturating_sub(old.timestamp) >= refresh.as_millis() as u64
+}
+
+/// Whether a new template carries different work than the previous one. Timestamp and nonce are
+/// ignored: a refresh that only moved the clock gives miners nothing new to hash
+pub fn template_changed(old: &kaspa_consensus_core::header::Header, new: &kaspa_consensus_core::header::Header) -> bool {
+    old.daa_score != new.daa_score
+        || old.blue_score != new.blue_score
+        || old.bits != new.bits
+        || old.version != new.version
+        || old.hash_merkle_root != new.hash_merkle_root
+        || old.accepted_id_merkle_root != new.accepted_id_merkle_root
+        || old.utxo_commitment != new.utxo_commitment
+        || old.blue_work != new.blue_work
+        || old.pruning_point != new.pruning_point
+        || old.parents_by_level != new.parents_by_level
+}
+
+/// Whether a new template builds on a different block than the previous one, as opposed to a
+/// refresh of the same block (new transactions or timestamp)
+pub fn is_new_block(old: &kaspa_consensus_core::header::Header, new: &kaspa_consensus_core::header::Header) -> bool {
+    old.daa_score != new.daa_score || old.parents_by_level != new.parents_by_level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_rtt_sampling() {
+        let state = MiningState::new();
+
+        // Job 1 is sampled, job 2 is not
+        assert!(state.record_notify_sent(1));
+        assert!(!state.record_notify_sent(2));
+        assert!(state.take_notify_rtt(2).is_none());
+
+        std::thread::sleep(Duration::from_millis(20));
+        let rtt = state.take_notify_rtt(1).expect("sampled job should have an rtt");
+        assert!(rtt >= Duration::from_millis(20));
+        assert!(rtt < Duration::from_secs(5));
+
+        // Subsequent submits for the same job are not timed again
+        assert!(state.take_notify_rtt(1).is_none());
+    }
+
+    #[test]
+    fn test_duplicate_nonce_rejected() {
+        let state = MiningState::new();
+        let job_id = state.add_job(test_job());
+
+        assert!(state.record_nonce(job_id, 0xdead_beef));
+        assert!(!state.record_nonce(job_id, 0xdead_beef), "second submit of the same nonce is a duplicate");
+        assert!(state.record_nonce(job_id, 0xdead_bef0));
+
+        // Same nonce on a different job is fine
+        let next_job_id = state.add_job(test_job());
+        assert!(state.record_nonce(next_job_id, 0xdead_beef));
+    }
+
+    #[test]
+    fn test_seen_nonces_dropped_on_job_eviction() {
+        let state = MiningState::new();
+        let first = state.add_job(test_job());
+        assert!(state.record_nonce(first, 1));
+
+        // Wrap the job ring so the first job's slot is overwritten
+        for _ in 0..state.max_jobs() {
+            state.add_job(test_job());
+        }
+        assert!(!state.seen_nonces.lock().contains_key(&first));
+        assert!(state.seen_nonces.lock().len() <= 1);
+    }
+
+    #[test]
+    fn test_time_to_first_share() {
+        let state = MiningState::new();
+        let start = Instant::now();
+        assert_eq!(state.record_accepted_share(start), None, "shares before authorize have no clock");
+
+        let state = MiningState::new();
+        state.mark_authorized(start);
+        state.mark_authorized(start + Duration::from_secs(5));
+        assert!(!state.first_share().accepted);
+        assert_eq!(state.record_accepted_share(start + Duration::from_secs(42)), Some(Duration::from_secs(42)));
+        assert_eq!(state.record_accepted_share(start + Duration::from_secs(50)), None);
+        assert!(state.first_share().accepted);
+    }
+
+    #[test]
+    fn test_connection_states() {
+        let state = MiningState::new();
+        let start = Instant::now();
+        let window = Duration::from_secs(60);
+        assert_eq!(state.connection_states(start, window), ConnectionStates::default());
+
+        state.mark_subscribed();
+        state.mark_authorized(start);
+        state.mark_submitted(start);
+        let states = state.connection_states(start + Duration::from_secs(30), window);
+        assert!(states.subscribed && states.authorized && states.active);
+        assert!(!state.connection_states(start + Duration::from_secs(61), window).active, "idle past the window");
+    }
+
+    #[test]
+    fn test_starting_diff_with_override() {
+        let state = MiningState::new();
+        assert_eq!(state.starting_diff(4096.0), 4096.0);
+
+        state.set_worker_override(WorkerOverride {
+            pattern: "s21-*".into(),
+            min_share_diff: Some(16384.0),
+            start_diff: Some(32768.0),
+            var_diff: None,
+        });
+        assert_eq!(state.starting_diff(4096.0), 32768.0);
+        // A miner's suggestion still wins, but never below the override's floor
+        state.set_suggested_diff(8192.0);
+        assert_eq!(state.starting_diff(4096.0), 16384.0);
+        // A d= static difficulty is taken as pinned
+        state.set_static_diff(65536.0);
+        assert_eq!(state.starting_diff(4096.0), 65536.0);
+    }
+
+    #[test]
+    fn test_submit_rate_window() {
+        let start = Instant::now();
+        let mut window = SubmitRateWindow::default();
+        assert_eq!(window.record(start), None);
+        // 10/s allows 50 in a 5s window
+        for _ in 1..50 {
+            window.record(start + Duration::from_secs(1));
+        }
+        assert!(!window.exceeds(10));
+        window.record(start + Duration::from_secs(2));
+        assert!(window.exceeds(10));
+        assert!(!window.exceeds(0), "0 = no limit");
+        assert!(window.flag());
+        assert!(!window.flag(), "reported once per window");
+
+        // The next window starts over and reports the closed one's rate
+        assert_eq!(window.record(start + SHARE_RATE_WINDOW), Some(51.0 / 5.0));
+        assert!(!window.exceeds(10));
+        assert!(window.flag());
+    }
+
+    #[test]
+    fn test_jobs_beyond_history_expire() {
+        let state = MiningState::with_job_history(4);
+        let first = state.add_job(test_job());
+        for _ in 0..3 {
+            state.add_job(test_job());
+        }
+        assert!(!state.is_job_expired(first), "still within the last 4 jobs");
+
+        state.add_job(test_job());
+        assert!(state.is_job_expired(first));
+        assert!(!state.is_job_expired(first + 1));
+        assert!(!state.is_job_expired(state.current_job_counter() + 1), "a job never issued is not stale");
+        assert!(!state.is_job_expired(u64::MAX), "a bogus job id does not overflow");
+    }
+
+    #[test]
+    fn test_unknown_job_resent_once() {
+        let state = MiningState::with_job_history(4);
+        assert!(state.is_job_unknown(1), "no job sent yet");
+        let first = state.add_job(test_job());
+        assert!(!state.is_job_unknown(first));
+        assert!(state.is_job_unknown(0));
+        assert!(state.is_job_unknown(first + 1));
+
+        assert!(!state.take_job_resend());
+        assert!(state.request_job_resend());
+        assert!(!state.request_job_resend(), "one resend per connection");
+        assert!(state.take_job_resend());
+        assert!(!state.take_job_resend());
+    }
+
+    #[test]
+    fn test_reject_warmup_windows() {
+        let state = MiningState::with_job_history(4);
+        let start = Instant::now();
+        assert!(!state.in_reject_warmup(start, Duration::from_secs(30), Duration::from_millis(500)), "not authorized, no job");
+
+        state.mark_authorized(start);
+        assert!(state.in_reject_warmup(start + Duration::from_secs(29), Duration::from_secs(30), Duration::ZERO));
+        assert!(!state.in_reject_warmup(start + Duration::from_secs(30), Duration::from_secs(30), Duration::ZERO));
+        assert!(!state.in_reject_warmup(start, Duration::ZERO, Duration::ZERO), "zero = off");
+
+        state.add_job(test_job());
+        let later = Instant::now() + Duration::from_secs(60);
+        assert!(!state.in_reject_warmup(later, Duration::from_secs(30), Duration::from_millis(500)));
+        assert!(state.in_reject_warmup(Instant::now(), Duration::ZERO, Duration::from_secs(5)), "right after a new job");
+    }
+
+    #[test]
+    fn test_template_changed_ignores_timestamp() {
+        use kaspa_consensus_core::header::Header;
+        let old = Header::from_precomputed_hash(Hash::default(), vec![]);
+
+        let mut refreshed = old.clone();
+        refreshed.timestamp += 1000;
+        refreshed.nonce = 42;
+        assert!(!template_changed(&old, &refreshed), "timestamp-only refresh is the same work");
+
+        let mut next_daa = old.clone();
+        next_daa.daa_score += 1;
+        assert!(template_changed(&old, &next_daa));
+
+        let mut new_txs = old.clone();
+        new_txs.hash_merkle_root = Hash::from_bytes([1; 32]);
+        assert!(template_changed(&old, &new_txs), "a different transaction set must be pushed");
+
+        assert!(timestamp_refresh_due(&old, &refreshed, Duration::from_secs(1)));
+        assert!(!timestamp_refresh_due(&old, &refreshed, Duration::from_secs(2)));
+        assert!(!timestamp_refresh_due(&old, &refreshed, Duration::ZERO), "zero = refreshes are deduplicated too");
+    }
+
+    #[test]
+    fn test_nonce_lane_moves_only_the_timestamp() {
+        let job = test_job();
+        let laned = Job::in_nonce_lane(&job.block, job.pre_pow_hash, 7);
+        assert_eq!(laned.block.header.timestamp, job.block.header.timestamp + 7);
+        assert_eq!(laned.pre_pow_hash, job.pre_pow_hash);
+        assert!(!template_changed(&job.block.header, &laned.block.header), "same work, different nonce space");
+        assert_eq!(Job::in_nonce_lane(&job.block, job.pre_pow_hash, 0).block.header.timestamp, job.block.header.timestamp);
+
+        let state = MiningState::new();
+        assert_eq!(state.nonce_lane(), 0);
+        state.set_nonce_lane(3);
+        assert_eq!(state.nonce_lane(), 3);
+        assert_eq!(state.take_nonce_lane(), Some(3));
+        assert_eq!(state.take_nonce_lane(), None);
+    }
+
+    #[test]
+    fn test_stale_rate_window() {
+        let now = Instant::now();
+        let mut window = StaleRateWindow::default();
+        for i in 0..STALE_RATE_MIN_SHARES - 1 {
+            window.record(now + Duration::from_secs(i), i % 4 == 0);
+        }
+        assert_eq!(window.pct(), None, "too few shares for a rate");
+        window.record(now + Duration::from_secs(19), false);
+        assert_eq!(window.pct(), Some(25.0));
+
+        // Every bucket of the first batch has left the window
+        let later = now + STALE_RATE_WINDOW + Duration::from_secs(30);
+        for _ in 0..STALE_RATE_MIN_SHARES {
+            window.record(later, false);
+        }
+        assert_eq!(window.pct(), Some(0.0));
+
+        assert!(window.warning_due(later));
+        assert!(!window.warning_due(later + Duration::from_secs(60)), "throttled");
+        assert!(window.warning_due(later + Duration::from_secs(301)));
+    }
+
+    #[test]
+    fn test_shared_stale_rate_window() {
+        let now = Instant::now();
+        let rate = SharedStaleRate::new(now);
+        for i in 0..STALE_RATE_MIN_SHARES - 1 {
+            rate.record(now + Duration::from_secs(i), i % 4 == 0);
+        }
+        assert_eq!(rate.pct(now + Duration::from_secs(18)), None, "too few shares for a rate");
+        rate.record(now + Duration::from_secs(19), false);
+        assert_eq!(rate.pct(now + Duration::from_secs(19)), Some(25.0));
+
+        // Shares stop: the rate runs out with the window instead of holding its last value
+        assert_eq!(rate.pct(now + STALE_RATE_WINDOW + Duration::from_secs(30)), None);
+
+        // New shares reuse the ring's buckets without the old counts
+        let later = now + STALE_RATE_WINDOW + Duration::from_secs(30);
+        for _ in 0..STALE_RATE_MIN_SHARES {
+            rate.record(later, false);
+        }
+        assert_eq!(rate.pct(later), Some(0.0));
+    }
+
+    fn test_job() -> Job {
+        use kaspa_consensus_core::header::Header;
+        let header = Header::from_precomputed_hash(Hash::default(), vec![]);
+        Job { block: Block::new(header, vec![]), pre_pow_hash: Hash::default() }
+    }
+}
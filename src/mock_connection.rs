@@ -79,3 +79,38 @@ impl std::fmt::Display for MockAddr {
         write!(f, "{}", self.id)
     }
 }
+
+/// A bridge-side StratumContext accepted on `listener` and the miner's end of the socket, for tests that
+/// drive a connection the way a miner would
+pub async fn tcp_connection(
+    listener: &tokio::net::TcpListener,
+) -> (Arc<crate::stratum_context::StratumContext>, tokio::net::TcpStream) {
+    let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    let (server, peer) = listener.accept().await.unwrap();
+    let (disconnect_tx, _) = mpsc::unbounded_channel();
+    let ctx = crate::stratum_context::StratumContext::new(
+        peer.ip().to_string(),
+        peer.port(),
+        String::new(),
+        server,
+        Arc::new(crate::mining_state::MiningState::new()),
+        disconnect_tx,
+    );
+    (ctx, client)
+}
+
+/// Same over a Unix socket pair, with the peer name a Unix listener on `path` gives its `id`th connection
+#[cfg(unix)]
+pub fn unix_connection(path: &str, id: u16) -> (Arc<crate::stratum_context::StratumContext>, tokio::net::UnixStream) {
+    let (client, server) = tokio::net::UnixStream::pair().unwrap();
+    let (disconnect_tx, _) = mpsc::unbounded_channel();
+    let ctx = crate::stratum_context::StratumContext::new(
+        format!("unix:{}", path),
+        id,
+        String::new(),
+        server,
+        Arc::new(crate::mining_state::MiningState::new()),
+        disconnect_tx,
+    );
+    (ctx, client)
+}
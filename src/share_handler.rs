@@ -1,9 +1,11 @@
 use crate::{
+    block_submit_queue::BlockSubmitQueue,
     errors::*,
-    jsonrpc_event::{JsonRpcEvent, JsonRpcResponse},
+    hasher::{assemble_nonce, nonce_to_big_endian, NonceByteOrder},
+    jsonrpc_event::{JsonRpcEvent, JsonRpcResponse, ShareReplyFormat},
     kaspaapi::NODE_STATUS,
     log_colors::LogColors,
-    mining_state::GetMiningState,
+    mining_state::{GetMiningState, MiningState, SharedStaleRate, SubmitRateWindow, SHARE_RATE_WINDOW, STALE_RATE_WINDOW},
     prom::*,
     stratum_context::StratumContext,
 };
@@ -16,7 +18,7 @@ use parking_lot::Mutex;
 use serde_json::Value;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{error, info, warn};
@@ -26,7 +28,7 @@ const VAR_DIFF_THREAD_SLEEP: u64 = 10;
 #[allow(dead_code)]
 const WORK_WINDOW: u64 = 80;
 const STATS_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
-const STATS_PRINT_INTERVAL: Duration = Duration::from_secs(10);
+pub const DEFAULT_STATS_PRINT_INTERVAL: Duration = Duration::from_secs(60);
 
 // VarDiff tunables
 const VARDIFF_MIN_ELAPSED_SECS: f64 = 30.0;
@@ -36,6 +38,335 @@ const VARDIFF_LOWER_RATIO: f64 = 0.75; // below this => decrease diff
 const VARDIFF_UPPER_RATIO: f64 = 1.25; // above this => increase diff
 const VARDIFF_MAX_STEP_UP: f64 = 2.0; // max 2x per adjustment tick
 const VARDIFF_MAX_STEP_DOWN: f64 = 0.5; // max -50% per adjustment tick
+pub const DEFAULT_EWMA_ALPHA: f64 = 0.1;
+
+/// Default var_diff_retarget_secs: how often each worker's share rate is evaluated
+pub const DEFAULT_VAR_DIFF_RETARGET_SECS: u64 = VAR_DIFF_THREAD_SLEEP;
+
+static VAR_DIFF_RETARGET_SECS: AtomicU64 = AtomicU64::new(DEFAULT_VAR_DIFF_RETARGET_SECS);
+
+/// Set the vardiff evaluation interval (process-wide); running vardiff threads pick it up after their current wait
+pub fn set_var_diff_retarget(interval: Duration) {
+    VAR_DIFF_RETARGET_SECS.store(interval.as_secs().max(1), Ordering::Relaxed);
+}
+
+pub fn var_diff_retarget() -> Duration {
+    Duration::from_secs(VAR_DIFF_RETARGET_SECS.load(Ordering::Relaxed))
+}
+
+/// Percent as f64 bits; 0 = every retarget is sent
+static VAR_DIFF_HYSTERESIS_PCT: AtomicU64 = AtomicU64::new(0);
+
+/// Only retarget when the new difficulty is more than `pct` percent from the current one (process-wide, reloadable)
+pub fn set_var_diff_hysteresis(pct: f64) {
+    VAR_DIFF_HYSTERESIS_PCT.store(pct.max(0.0).to_bits(), Ordering::Relaxed);
+}
+
+pub fn var_diff_hysteresis() -> f64 {
+    f64::from_bits(VAR_DIFF_HYSTERESIS_PCT.load(Ordering::Relaxed))
+}
+
+/// Default var_diff_warmup_shares
+pub const DEFAULT_VAR_DIFF_WARMUP_SHARES: u32 = 5;
+
+/// Default var_diff_warmup_secs: as long as a worker without any shares waits for its first step down
+pub const DEFAULT_VAR_DIFF_WARMUP_SECS: u64 = VARDIFF_MAX_ELAPSED_SECS_NO_SHARES as u64;
+
+static VAR_DIFF_WARMUP_SHARES: AtomicU32 = AtomicU32::new(DEFAULT_VAR_DIFF_WARMUP_SHARES);
+static VAR_DIFF_WARMUP_SECS: AtomicU64 = AtomicU64::new(DEFAULT_VAR_DIFF_WARMUP_SECS);
+
+/// Leave a newly authorized connection's difficulty alone until it has had `shares` accepted or `time`
+/// has passed (process-wide, reloadable; zero shares = retarget from the first check)
+pub fn set_var_diff_warmup(shares: u32, time: Duration) {
+    VAR_DIFF_WARMUP_SHARES.store(shares, Ordering::Relaxed);
+    VAR_DIFF_WARMUP_SECS.store(time.as_secs(), Ordering::Relaxed);
+}
+
+pub fn var_diff_warmup() -> (u32, Duration) {
+    (VAR_DIFF_WARMUP_SHARES.load(Ordering::Relaxed), Duration::from_secs(VAR_DIFF_WARMUP_SECS.load(Ordering::Relaxed)))
+}
+
+/// Fraction of network difficulty as f64 bits; 0 = near misses are not counted
+static NEAR_MISS_RATIO: AtomicU64 = AtomicU64::new(0);
+
+/// Count shares reaching `ratio` of the network difficulty as near misses (process-wide)
+pub fn set_near_miss_ratio(ratio: f64) {
+    NEAR_MISS_RATIO.store(ratio.max(0.0).to_bits(), Ordering::Relaxed);
+}
+
+fn near_miss_ratio() -> f64 {
+    f64::from_bits(NEAR_MISS_RATIO.load(Ordering::Relaxed))
+}
+
+/// Whether a share that achieved difficulty `achieved` got at least `ratio` of the way to `network`
+/// without reaching it (that would be a block)
+fn is_near_miss(achieved: f64, network: f64, ratio: f64) -> bool {
+    ratio > 0.0 && network > 0.0 && achieved < network && achieved >= network * ratio
+}
+
+/// Achieved over assigned difficulty of a weak share, or None without an assigned difficulty
+fn weak_share_ratio(achieved: f64, assigned: f64) -> Option<f64> {
+    (assigned > 0.0).then(|| (achieved / assigned).min(1.0))
+}
+
+/// Whether moving from `current` to `next` is too small a change to send under a hysteresis of `pct` percent
+fn within_hysteresis(current: f64, next: f64, pct: f64) -> bool {
+    pct > 0.0 && current > 0.0 && (next - current).abs() / current * 100.0 <= pct
+}
+
+static REJECT_WARMUP_MS: AtomicU64 = AtomicU64::new(0);
+static REJECT_WARMUP_AFTER_JOB_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Count stale and unknown-job rejects within `after_authorize` of a connection authorizing, or within
+/// `after_job` of its newest job, in ks_warmup_reject_counter instead of their own type (process-wide, zero = off)
+pub fn set_reject_warmup(after_authorize: Duration, after_job: Duration) {
+    REJECT_WARMUP_MS.store(after_authorize.as_millis() as u64, Ordering::Relaxed);
+    REJECT_WARMUP_AFTER_JOB_MS.store(after_job.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// What a rejected share was rejected for, as far as the reject warmup and the stale rate care
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RejectKind {
+    Stale,
+    UnknownJob,
+    /// Weak, duplicate or bad-timestamp shares: broken hardware or firmware, never warmup
+    Invalid,
+}
+
+/// Record a rejected share with `record`, or as a warmup reject when it is stale or for an unknown job while
+/// the connection is warming up. Rejects outside the warmup count towards the stale rate. Returns whether
+/// the reject was counted as its own type, so the caller's stale/invalid share stats skip warmup rejects
+fn record_reject(
+    state: &MiningState,
+    record: fn(&crate::prom::WorkerContext),
+    worker: &crate::prom::WorkerContext,
+    kind: RejectKind,
+) -> bool {
+    let after_authorize = Duration::from_millis(REJECT_WARMUP_MS.load(Ordering::Relaxed));
+    let after_job = Duration::from_millis(REJECT_WARMUP_AFTER_JOB_MS.load(Ordering::Relaxed));
+    record_reject_at(state, record, worker, kind, Instant::now(), after_authorize, after_job)
+}
+
+fn record_reject_at(
+    state: &MiningState,
+    record: fn(&crate::prom::WorkerContext),
+    worker: &crate::prom::WorkerContext,
+    kind: RejectKind,
+    now: Instant,
+    after_authorize: Duration,
+    after_job: Duration,
+) -> bool {
+    if kind != RejectKind::Invalid && state.in_reject_warmup(now, after_authorize, after_job) {
+        record_warmup_reject(worker);
+        return false;
+    }
+    record(worker);
+    record_stale_rate(state, worker, now, kind == RejectKind::Stale);
+    true
+}
+
+/// Percent as f64 bits; 0 = no stale rate warnings
+static STALE_WARN_PCT: AtomicU64 = AtomicU64::new(0);
+
+/// Warn about connections whose rolling stale rate is above `pct` percent (process-wide, reloadable, 0 = off)
+pub fn set_stale_warn_pct(pct: f64) {
+    STALE_WARN_PCT.store(pct.max(0.0).to_bits(), Ordering::Relaxed);
+}
+
+pub fn stale_warn_pct() -> f64 {
+    f64::from_bits(STALE_WARN_PCT.load(Ordering::Relaxed))
+}
+
+/// Stale rate over every connection
+static OVERALL_STALE_RATE: Lazy<SharedStaleRate> = Lazy::new(|| SharedStaleRate::new(Instant::now()));
+
+/// Bring ks_stale_share_pct up to date at `now`, so it runs down to 0 once shares stop coming in
+fn refresh_overall_stale_rate(now: Instant) {
+    record_overall_stale_share_pct(OVERALL_STALE_RATE.pct(now).unwrap_or(0.0));
+}
+
+/// Count a share the share or reject counters just recorded in the connection's and the bridge-wide
+/// stale rate, and warn (throttled per connection) when the connection is over stale_warn_pct
+fn record_stale_rate(state: &MiningState, worker: &crate::prom::WorkerContext, now: Instant, stale: bool) {
+    let worker_pct = state.record_share_outcome(now, stale);
+    OVERALL_STALE_RATE.record(now, stale);
+    record_stale_share_pct(&worker.worker_name, &worker.wallet, worker_pct, OVERALL_STALE_RATE.pct(now));
+
+    let warn_pct = stale_warn_pct();
+    let Some(pct) = worker_pct.filter(|pct| warn_pct > 0.0 && *pct > warn_pct) else { return };
+    if state.stale_warning_due(now) {
+        warn!(
+            "{} ({}) stale rate {:.1}% over the last {}m is above stale_warn_pct {}%, check its latency and clean_jobs",
+            worker.worker_name,
+            worker.ip,
+            pct,
+            STALE_RATE_WINDOW.as_secs() / 60,
+            warn_pct
+        );
+    }
+}
+
+/// 0 = timestamps sent with mining.submit are ignored and shares hashed with the template's
+static TIMESTAMP_TOLERANCE_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Honor a header timestamp sent as the 4th mining.submit param when it is within `tolerance`
+/// of the job's template timestamp, and reject the share when it is not (process-wide, zero = off)
+pub fn set_timestamp_tolerance(tolerance: Duration) {
+    TIMESTAMP_TOLERANCE_SECS.store(tolerance.as_secs(), Ordering::Relaxed);
+}
+
+/// Millisecond header timestamp from a submit param, as a number or a hex string (0x optional)
+pub fn parse_submit_timestamp(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => u64::from_str_radix(s.trim().trim_start_matches("0x"), 16).ok(),
+        _ => None,
+    }
+}
+
+/// How far a submitted timestamp is from the template's, or None when it is within `tolerance`
+fn timestamp_skew(submitted_ms: u64, template_ms: u64, tolerance: Duration) -> Option<Duration> {
+    let skew = Duration::from_millis(submitted_ms.abs_diff(template_ms));
+    (skew > tolerance).then_some(skew)
+}
+
+static NONCE_BYTE_ORDER: AtomicU8 = AtomicU8::new(NonceByteOrder::Be as u8);
+
+/// Select the byte order submitted nonces are read in (process-wide)
+pub fn set_nonce_byte_order(order: NonceByteOrder) {
+    NONCE_BYTE_ORDER.store(order as u8, Ordering::Relaxed);
+}
+
+fn nonce_byte_order() -> NonceByteOrder {
+    match NONCE_BYTE_ORDER.load(Ordering::Relaxed) {
+        o if o == NonceByteOrder::Le as u8 => NonceByteOrder::Le,
+        _ => NonceByteOrder::Be,
+    }
+}
+
+/// Default max_shares_per_sec: far above any real miner at a sane difficulty
+pub const DEFAULT_MAX_SHARES_PER_SEC: u32 = 100;
+
+static MAX_SHARES_PER_SEC: AtomicU32 = AtomicU32::new(DEFAULT_MAX_SHARES_PER_SEC);
+static SHARE_FLOOD_ACTION: AtomicU8 = AtomicU8::new(ShareFloodAction::Throttle as u8);
+static GLOBAL_SUBMIT_RATE: Lazy<Mutex<SubmitRateWindow>> = Lazy::new(|| Mutex::new(SubmitRateWindow::default()));
+
+/// What happens to a connection submitting faster than max_shares_per_sec
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShareFloodAction {
+    /// Reject its shares unvalidated until its rate drops (default)
+    Throttle,
+    /// Close the connection
+    Disconnect,
+}
+
+impl std::str::FromStr for ShareFloodAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "throttle" => Ok(ShareFloodAction::Throttle),
+            "disconnect" => Ok(ShareFloodAction::Disconnect),
+            other => Err(format!("unknown share_flood_action '{}', expected throttle or disconnect", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ShareFloodAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShareFloodAction::Throttle => write!(f, "throttle"),
+            ShareFloodAction::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+/// Limit each connection to `max_per_sec` submits, averaged over SHARE_RATE_WINDOW (0 = no limit; process-wide)
+pub fn set_share_rate_limit(max_per_sec: u32, action: ShareFloodAction) {
+    MAX_SHARES_PER_SEC.store(max_per_sec, Ordering::Relaxed);
+    SHARE_FLOOD_ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+fn share_flood_action() -> ShareFloodAction {
+    match SHARE_FLOOD_ACTION.load(Ordering::Relaxed) {
+        a if a == ShareFloodAction::Disconnect as u8 => ShareFloodAction::Disconnect,
+        _ => ShareFloodAction::Throttle,
+    }
+}
+
+static SHARE_REPLY_FORMAT: AtomicU8 = AtomicU8::new(ShareReplyFormat::Bool as u8);
+
+/// Select the result shape sent for accepted shares (process-wide)
+pub fn set_share_reply_format(format: ShareReplyFormat) {
+    SHARE_REPLY_FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+fn share_reply_format() -> ShareReplyFormat {
+    match SHARE_REPLY_FORMAT.load(Ordering::Relaxed) {
+        f if f == ShareReplyFormat::Object as u8 => ShareReplyFormat::Object,
+        _ => ShareReplyFormat::Bool,
+    }
+}
+
+/// How the vardiff thread estimates a worker's share rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VarDiffMode {
+    /// Shares counted since the last retarget (default)
+    Window,
+    /// Exponentially weighted moving average of inter-share times; alpha is the weight of the newest interval
+    Ewma { alpha: f64 },
+}
+
+impl std::str::FromStr for VarDiffMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "window" => Ok(VarDiffMode::Window),
+            "ewma" => Ok(VarDiffMode::Ewma { alpha: DEFAULT_EWMA_ALPHA }),
+            other => Err(format!("unknown var_diff_mode '{}', expected ewma or window", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for VarDiffMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VarDiffMode::Window => write!(f, "window"),
+            VarDiffMode::Ewma { alpha } => write!(f, "ewma (alpha={})", alpha),
+        }
+    }
+}
+
+/// Smoothed inter-share time of one worker, used by VarDiffMode::Ewma
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShareIntervalEwma {
+    pub mean_secs: Option<f64>,
+    pub samples: u64,
+    last_share: Option<Instant>,
+}
+
+impl ShareIntervalEwma {
+    /// Fold the interval since the previous share into the average
+    pub fn record_share(&mut self, at: Instant, alpha: f64) {
+        if let Some(last) = self.last_share {
+            let interval = at.saturating_duration_since(last).as_secs_f64();
+            self.mean_secs = Some(match self.mean_secs {
+                Some(mean) => alpha * interval + (1.0 - alpha) * mean,
+                None => interval,
+            });
+            self.samples += 1;
+        }
+        self.last_share = Some(at);
+    }
+
+    /// Scale the average after a difficulty change so it predicts intervals at the new difficulty
+    pub fn rescale(&mut self, factor: f64) {
+        if let Some(mean) = self.mean_secs.as_mut() {
+            *mean *= factor;
+        }
+    }
+}
 
 fn vardiff_pow2_clamp_towards(current: f64, next: f64) -> f64 {
     if !next.is_finite() || next <= 0.0 {
@@ -103,8 +434,318 @@ fn vardiff_compute_next_diff(current: f64, shares: f64, elapsed_secs: f64, expec
     }
 }
 
+/// Intervals an EWMA of weight `alpha` needs before its mean is trusted: about one time constant (1/alpha),
+/// so the first interval no longer dominates it, and never fewer than window mode's minimum
+fn ewma_min_samples(alpha: f64) -> f64 {
+    if alpha > 0.0 {
+        VARDIFF_MIN_SHARES.max((1.0 / alpha).ceil())
+    } else {
+        VARDIFF_MIN_SHARES
+    }
+}
+
+/// EWMA counterpart of vardiff_compute_next_diff: same deadband, step limits and clamping,
+/// but the share rate comes from the smoothed inter-share time (of weight `alpha`) instead of the window count
+fn vardiff_compute_next_diff_ewma(
+    current: f64,
+    ewma: &ShareIntervalEwma,
+    alpha: f64,
+    shares: f64,
+    elapsed_secs: f64,
+    expected_spm: f64,
+    clamp_pow2: bool,
+) -> Option<f64> {
+    // A worker that has gone quiet gets the same step-down as in window mode
+    if shares == 0.0 {
+        return vardiff_compute_next_diff(current, shares, elapsed_secs, expected_spm, clamp_pow2);
+    }
+    if !elapsed_secs.is_finite() || elapsed_secs < VARDIFF_MIN_ELAPSED_SECS || (ewma.samples as f64) < ewma_min_samples(alpha) {
+        return None;
+    }
+    let mean_secs = ewma.mean_secs.filter(|m| m.is_finite() && *m > 0.0)?;
+
+    // Express the smoothed rate as shares over one minute so the window-mode thresholds apply unchanged
+    vardiff_compute_next_diff(current, 60.0 / mean_secs, 60.0, expected_spm, clamp_pow2)
+}
+
+/// Share rate the vardiff thread measured this tick, or None if there is not enough data yet
+fn vardiff_observed_spm(mode: VarDiffMode, ewma: &ShareIntervalEwma, shares: f64, elapsed_secs: f64) -> Option<f64> {
+    if !elapsed_secs.is_finite() || elapsed_secs <= 0.0 {
+        return None;
+    }
+    if shares == 0.0 {
+        return (elapsed_secs >= VARDIFF_MAX_ELAPSED_SECS_NO_SHARES).then_some(0.0);
+    }
+    if elapsed_secs < VARDIFF_MIN_ELAPSED_SECS {
+        return None;
+    }
+    match mode {
+        VarDiffMode::Window => (shares >= VARDIFF_MIN_SHARES).then(|| (shares / elapsed_secs) * 60.0),
+        VarDiffMode::Ewma { alpha } => {
+            if (ewma.samples as f64) < ewma_min_samples(alpha) {
+                return None;
+            }
+            ewma.mean_secs.filter(|m| m.is_finite() && *m > 0.0).map(|m| 60.0 / m)
+        }
+    }
+}
+
+/// Mean measured-over-target share rate of the workers measured in one retarget cycle
+#[derive(Debug, Default)]
+struct VarDiffRateError {
+    sum: f64,
+    workers: usize,
+}
+
+impl VarDiffRateError {
+    /// Add a worker's measured rate; returns its ratio to `expected_spm`
+    fn observe(&mut self, observed_spm: f64, expected_spm: f64) -> f64 {
+        let ratio = observed_spm / expected_spm.max(1.0);
+        self.sum += ratio;
+        self.workers += 1;
+        ratio
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.workers > 0).then(|| self.sum / self.workers as f64)
+    }
+}
+
+/// When to stop retargeting a worker whose share rate has settled near the target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VarDiffFreezePolicy {
+    pub tolerance_pct: f64, // 0 = never freeze
+    pub after_checks: u32,
+}
+
+impl VarDiffFreezePolicy {
+    pub fn enabled(&self) -> bool {
+        self.tolerance_pct > 0.0 && self.after_checks > 0
+    }
+
+    /// Feed one vardiff check into a worker's freeze state; true means hold the current difficulty
+    pub fn observe(&self, state: &mut VarDiffFreezeState, observed_spm: Option<f64>, expected_spm: f64) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        // Not enough data to judge, keep whatever state we were in
+        let Some(observed_spm) = observed_spm else { return state.frozen };
+
+        let deviation_pct = (observed_spm - expected_spm).abs() / expected_spm.max(1.0) * 100.0;
+        if deviation_pct <= self.tolerance_pct {
+            state.stable_checks = state.stable_checks.saturating_add(1);
+            if state.stable_checks >= self.after_checks {
+                state.frozen = true;
+            }
+        } else {
+            *state = VarDiffFreezeState::default();
+        }
+        state.frozen
+    }
+}
+
+/// Per-worker progress towards (or in) a vardiff freeze
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VarDiffFreezeState {
+    pub stable_checks: u32,
+    pub frozen: bool,
+}
+
+/// Shares accepted since a connection authorized, while var_diff_warmup holds its difficulty
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VarDiffWarmup {
+    started: Option<Instant>,
+    shares: u32,
+}
+
+impl VarDiffWarmup {
+    pub fn start(&mut self, now: Instant) {
+        *self = Self { started: Some(now), shares: 0 };
+    }
+
+    pub fn record_share(&mut self) {
+        self.shares = self.shares.saturating_add(1);
+    }
+
+    /// Whether vardiff still has to wait at `now`: fewer than `min_shares` accepted and less than
+    /// `max_time` since the warmup started
+    pub fn holds(&self, now: Instant, min_shares: u32, max_time: Duration) -> bool {
+        self.started.is_some_and(|started| self.shares < min_shares && now.saturating_duration_since(started) < max_time)
+    }
+}
+
+/// Bounds and rounding applied to per-connection starting difficulties
+/// (the configured start_diff, mining.suggest_difficulty hints and d= static difficulties)
+#[derive(Debug, Clone, Copy)]
+pub struct SuggestDifficultyPolicy {
+    pub var_diff_min: f64, // 0 = no lower bound
+    pub var_diff_max: f64, // 0 = no upper bound
+    pub pow2_clamp: bool,
+}
+
+impl SuggestDifficultyPolicy {
+    /// Difficulty new connections start at: start_diff (0 = min_diff) within var_diff_min/var_diff_max,
+    /// never below min_diff
+    pub fn start_diff(&self, start_diff: f64, min_diff: f64) -> f64 {
+        if !start_diff.is_finite() || start_diff <= 0.0 {
+            return min_diff;
+        }
+        let mut diff = start_diff;
+        if self.var_diff_max > 0.0 {
+            diff = diff.min(self.var_diff_max);
+        }
+        if self.var_diff_min > 0.0 {
+            diff = diff.max(self.var_diff_min);
+        }
+        if self.pow2_clamp {
+            diff = 2_f64.powi(diff.log2().floor() as i32);
+        }
+        diff.max(min_diff)
+    }
+
+    /// Starting difficulty to use for a suggestion, or None if it is outside var_diff_min/var_diff_max
+    pub fn resolve(&self, suggested: f64) -> Option<f64> {
+        if !suggested.is_finite() || suggested < 1.0 {
+            return None;
+        }
+        if self.var_diff_min > 0.0 && suggested < self.var_diff_min {
+            return None;
+        }
+        if self.var_diff_max > 0.0 && suggested > self.var_diff_max {
+            return None;
+        }
+        if !self.pow2_clamp {
+            return Some(suggested);
+        }
+
+        // Same rounding as min_share_diff, but never below the configured floor
+        let floor = 2_f64.powi(suggested.log2().floor() as i32);
+        if floor < self.var_diff_min {
+            Some(2_f64.powi(suggested.log2().ceil() as i32))
+        } else {
+            Some(floor)
+        }
+    }
+
+    /// Keep a vardiff retarget within var_diff_min/var_diff_max. Under pow2_clamp the bounds are rounded
+    /// inwards so the result stays a power of two
+    pub fn bound_retarget(&self, next: f64) -> f64 {
+        let mut diff = next;
+        if self.var_diff_max > 0.0 {
+            let max = if self.pow2_clamp { 2_f64.powi(self.var_diff_max.log2().floor() as i32) } else { self.var_diff_max };
+            diff = diff.min(max);
+        }
+        if self.var_diff_min > 0.0 {
+            let min = if self.pow2_clamp { 2_f64.powi(self.var_diff_min.log2().ceil() as i32) } else { self.var_diff_min };
+            diff = diff.max(min);
+        }
+        diff
+    }
+
+    /// Lowest and highest difficulty a retarget can land on, as bound_retarget applies var_diff_min and
+    /// var_diff_max; vardiff itself never goes below 1. No highest without var_diff_max
+    pub fn retarget_range(&self) -> (f64, Option<f64>) {
+        let floor = self.bound_retarget(1.0);
+        (floor, (self.var_diff_max > 0.0).then(|| self.bound_retarget(f64::MAX)))
+    }
+
+    /// The difficulty ladder vardiff moves on, from the retarget floor (var_diff_min, else 1) up to
+    /// var_diff_max: every power of two in between under pow2_clamp, at most `max_rungs` of them.
+    /// None without pow2_clamp, where vardiff can settle on any value
+    pub fn ladder(&self, max_rungs: usize) -> Option<Vec<f64>> {
+        if !self.pow2_clamp {
+            return None;
+        }
+        let (floor, ceiling) = self.retarget_range();
+        let mut rungs = Vec::new();
+        let mut diff = floor;
+        while rungs.len() < max_rungs && (ceiling.is_none_or(|ceiling| diff <= ceiling) || rungs.is_empty()) {
+            rungs.push(diff);
+            diff *= 2.0;
+        }
+        Some(rungs)
+    }
+}
+
+/// Key of a connection's WorkStats: its worker name, or its address while it has none
+fn stats_key(ctx: &StratumContext) -> String {
+    let worker_name = ctx.worker_name.lock();
+    if worker_name.is_empty() {
+        ctx.remote_endpoint()
+    } else {
+        worker_name.clone()
+    }
+}
+
+/// min_share_diff as an instance applies it: floored to a power of two under pow2_clamp, 4 when unset
+pub fn clamped_min_share_diff(min_share_diff: u32, pow2_clamp: bool) -> f64 {
+    let mut min_diff = min_share_diff as f64;
+    if pow2_clamp && min_diff > 0.0 {
+        min_diff = 2_f64.powi((min_diff.log2().floor()) as i32);
+    }
+    if min_diff == 0.0 {
+        min_diff = 4.0;
+    }
+    min_diff
+}
+
+/// Share totals of all workers paying to one wallet
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletSummary {
+    pub wallet: String,
+    pub workers: usize,
+    pub accepted: i64,
+    pub stale: i64,
+    pub invalid: i64,
+    pub hashrate_ghs: f64,
+}
+
+impl WalletSummary {
+    pub fn reject_rate(&self) -> f64 {
+        let total = self.accepted + self.stale + self.invalid;
+        if total > 0 {
+            (self.stale + self.invalid) as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Roll worker stats up by wallet; every share is counted on exactly one worker, so nothing is counted twice
+pub fn aggregate_by_wallet<'a>(workers: impl IntoIterator<Item = &'a WorkStats>) -> Vec<WalletSummary> {
+    let mut by_wallet: HashMap<String, WalletSummary> = HashMap::new();
+    for v in workers {
+        let wallet = v.wallet.lock().clone();
+        let elapsed = v.start_time.elapsed().as_secs_f64();
+        let entry = by_wallet.entry(wallet.clone()).or_insert_with(|| WalletSummary {
+            wallet,
+            workers: 0,
+            accepted: 0,
+            stale: 0,
+            invalid: 0,
+            hashrate_ghs: 0.0,
+        });
+        entry.workers += 1;
+        entry.accepted += *v.shares_found.lock();
+        entry.stale += *v.stale_shares.lock();
+        entry.invalid += *v.invalid_shares.lock();
+        if elapsed > 0.0 {
+            entry.hashrate_ghs += *v.shares_diff.lock() / elapsed;
+        }
+    }
+    let mut wallets: Vec<WalletSummary> = by_wallet.into_values().collect();
+    wallets.sort_by(|a, b| b.hashrate_ghs.total_cmp(&a.hashrate_ghs).then_with(|| a.wallet.cmp(&b.wallet)));
+    wallets
+}
+
+/// Reports an instance's open stratum connections to the stats printer
+pub type ConnectionCounter = Arc<dyn Fn() -> usize + Send + Sync>;
+
 struct StatsPrinterEntry {
     instance_id: String,
+    per_miner_detail: bool,
+    show_hashrate_estimate: bool,
+    connections: ConnectionCounter,
     inst_short: String,
     target_spm: f64,
     start: Instant,
@@ -123,12 +764,18 @@ pub struct WorkStats {
     pub stale_shares: Arc<Mutex<i64>>,
     pub invalid_shares: Arc<Mutex<i64>>,
     pub worker_name: Arc<Mutex<String>>,
+    pub wallet: Arc<Mutex<String>>,
     pub start_time: Instant,
     pub last_share: Arc<Mutex<Instant>>,
     pub var_diff_start_time: Arc<Mutex<Option<Instant>>>,
     pub var_diff_shares_found: Arc<Mutex<i64>>,
     pub var_diff_window: Arc<Mutex<usize>>,
+    pub var_diff_ewma: Arc<Mutex<ShareIntervalEwma>>,
+    pub var_diff_freeze: Arc<Mutex<VarDiffFreezeState>>,
+    pub var_diff_warmup: Arc<Mutex<VarDiffWarmup>>,
     pub min_diff: Arc<Mutex<f64>>,
+    pub diff_floor: Arc<Mutex<f64>>, // Vardiff never goes below this (worker_overrides min_share_diff, 0 = none)
+    pub var_diff_enabled: Arc<Mutex<Option<bool>>>, // worker_overrides var_diff; None = the instance's var_diff
 }
 
 impl WorkStats {
@@ -140,12 +787,18 @@ impl WorkStats {
             stale_shares: Arc::new(Mutex::new(0)),
             invalid_shares: Arc::new(Mutex::new(0)),
             worker_name: Arc::new(Mutex::new(worker_name)),
+            wallet: Arc::new(Mutex::new(String::new())),
             start_time: Instant::now(),
             last_share: Arc::new(Mutex::new(Instant::now())),
             var_diff_start_time: Arc::new(Mutex::new(None)),
             var_diff_shares_found: Arc::new(Mutex::new(0)),
             var_diff_window: Arc::new(Mutex::new(0)),
+            var_diff_ewma: Arc::new(Mutex::new(ShareIntervalEwma::default())),
+            var_diff_freeze: Arc::new(Mutex::new(VarDiffFreezeState::default())),
+            var_diff_warmup: Arc::new(Mutex::new(VarDiffWarmup::default())),
             min_diff: Arc::new(Mutex::new(0.0)),
+            diff_floor: Arc::new(Mutex::new(0.0)),
+            var_diff_enabled: Arc::new(Mutex::new(None)),
         }
     }
 }
@@ -156,39 +809,80 @@ pub struct ShareHandler {
     stats: Arc<Mutex<HashMap<String, WorkStats>>>,
     overall: Arc<WorkStats>,
     instance_id: String, // Instance identifier for logging
+    paused: AtomicBool,
+    account_shares_while_paused: bool,
+    var_diff_mode: VarDiffMode,
+    target_spm: f64,                             // Share rate difficulties are translated into hashrate against
+    submit_queue: Option<Arc<BlockSubmitQueue>>, // Found blocks go through it when set (submit_queue_size)
 }
 
 impl ShareHandler {
-    pub fn new(instance_id: String) -> Self {
+    pub fn new(
+        instance_id: String,
+        account_shares_while_paused: bool,
+        var_diff_mode: VarDiffMode,
+        shares_per_min: u32,
+        submit_queue: Option<Arc<BlockSubmitQueue>>,
+    ) -> Self {
         Self {
             tip_blue_score: Arc::new(Mutex::new(0)),
             stats: Arc::new(Mutex::new(HashMap::new())),
             overall: Arc::new(WorkStats::new("overall".to_string())),
             instance_id,
+            paused: AtomicBool::new(false),
+            account_shares_while_paused,
+            var_diff_mode,
+            target_spm: shares_per_min.max(1) as f64,
+            submit_queue,
         }
     }
 
+    /// Hashrate a miner needs at `diff` to hit the instance's shares_per_min, for logs
+    pub fn hashrate_estimate(&self, diff: f64) -> String {
+        format!("~{} for {:.0} shares/min", format_hashrate(required_hashrate_ghs(diff, self.target_spm)), self.target_spm)
+    }
+
     fn log_prefix(&self) -> String {
         format!("[{}]", self.instance_id)
     }
 
+    /// Pause or resume share accounting for this instance
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Release);
+    }
+
+    /// Check if this instance is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Late shares that arrive while paused are only accounted if account_shares_while_paused is set
+    fn should_account_share(&self) -> bool {
+        !self.is_paused() || self.account_shares_while_paused
+    }
+
+    /// A worker's stats if it has any, without creating them (read-only inspection)
+    pub fn peek_stats(&self, ctx: &StratumContext) -> Option<WorkStats> {
+        self.stats.lock().get(&stats_key(ctx)).cloned()
+    }
+
     pub fn get_create_stats(&self, ctx: &StratumContext) -> WorkStats {
         let mut stats_map = self.stats.lock();
 
-        let worker_id = {
-            let worker_name = ctx.worker_name.lock();
-            if !worker_name.is_empty() {
-                worker_name.clone()
-            } else {
-                format!("{}:{}", ctx.remote_addr(), ctx.remote_port())
-            }
-        };
+        let worker_id = stats_key(ctx);
 
         if let Some(stats) = stats_map.get(&worker_id) {
+            // Stats can be created before authorize, pick up the wallet once it is known
+            let mut wallet = stats.wallet.lock();
+            if wallet.is_empty() {
+                *wallet = ctx.wallet_addr.lock().clone();
+            }
+            drop(wallet);
             return stats.clone();
         }
 
         let stats = WorkStats::new(worker_id.clone());
+        *stats.wallet.lock() = ctx.wallet_addr.lock().clone();
         stats_map.insert(worker_id.clone(), stats.clone());
         drop(stats_map);
 
@@ -199,7 +893,8 @@ impl ShareHandler {
             worker_name: worker_name.clone(),
             miner: String::new(),
             wallet: wallet_addr.clone(),
-            ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
+            ip: ctx.remote_endpoint(),
+            port: ctx.metrics_port.clone(),
         });
 
         stats
@@ -219,6 +914,43 @@ impl ShareHandler {
 
         // Get per-client mining state from context
         let state = GetMiningState(&ctx);
+
+        // Flood protection, before any parsing or PoW work
+        let now = Instant::now();
+        state.mark_submitted(now);
+        if let Some(rate) = GLOBAL_SUBMIT_RATE.lock().record(now) {
+            record_share_submit_rate(rate);
+        }
+        let max_per_sec = MAX_SHARES_PER_SEC.load(Ordering::Relaxed);
+        let (flooding, first_in_window) = {
+            let mut window = state.submit_rate();
+            window.record(now);
+            let flooding = window.exceeds(max_per_sec);
+            (flooding, flooding && window.flag())
+        };
+        if flooding {
+            let action = share_flood_action();
+            if first_in_window {
+                warn!(
+                    "{} [SUBMIT] {} ({}) is submitting over {} shares/s (averaged over {:?}), action: {}",
+                    prefix,
+                    ctx.remote_endpoint(),
+                    ctx.worker_name.lock(),
+                    max_per_sec,
+                    SHARE_RATE_WINDOW,
+                    action
+                );
+                record_share_flood(&action.to_string());
+            }
+            match action {
+                ShareFloodAction::Throttle => {
+                    ctx.reply(JsonRpcResponse::error(event.id.clone(), 20, "Too many shares, slow down", None)).await?;
+                }
+                ShareFloodAction::Disconnect => ctx.disconnect_for(crate::stratum_context::DisconnectReason::ShareFlood),
+            }
+            return Ok(());
+        }
+
         let _max_jobs = state.max_jobs() as u64;
         let current_counter = state.current_job_counter();
         let stored_ids = state.get_stored_job_ids();
@@ -282,6 +1014,12 @@ impl ShareHandler {
 
         tracing::debug!("[SUBMIT] Parsed job_id: {}", job_id);
 
+        // Round-trip timing is only recorded for sampled jobs when log_notify_submit_rtt is enabled
+        if let Some(rtt) = state.take_notify_rtt(job_id) {
+            let prefix = self.log_prefix();
+            info!("{}", notify_submit_rtt_line(&prefix, &ctx.remote_endpoint(), job_id, rtt));
+        }
+
         // Get current job counter for debugging
         let current_job_counter = state.current_job_counter();
         tracing::debug!(
@@ -295,6 +1033,71 @@ impl ShareHandler {
             }
         );
 
+        // Racy firmware submits before its first mining.notify arrived, or with job id 0. Not an error on our
+        // side: reject it plainly and resend the current job once so the miner recovers
+        if state.is_job_unknown(job_id) {
+            let wallet_addr = ctx.wallet_addr.lock().clone();
+            let worker_name = ctx.worker_name.lock().clone();
+            tracing::debug!(
+                "{} [SUBMIT] {} ({}) submitted for job {}, which was never sent (latest job {})",
+                self.log_prefix(),
+                ctx.remote_endpoint(),
+                worker_name,
+                job_id,
+                current_job_counter
+            );
+            record_reject(
+                &state,
+                record_unknown_job_share,
+                &crate::prom::WorkerContext {
+                    worker_name,
+                    miner: String::new(),
+                    wallet: wallet_addr,
+                    ip: ctx.remote_endpoint(),
+                    port: ctx.metrics_port.clone(),
+                },
+                RejectKind::UnknownJob,
+            );
+            if state.request_job_resend() {
+                tracing::debug!("{} [SUBMIT] resending the current job to {}", self.log_prefix(), ctx.remote_endpoint());
+            }
+            ctx.reply_unknown_job(event.id.clone()).await?;
+            return Ok(());
+        }
+
+        // Jobs pushed out of the job history can't be validated any more
+        if state.is_job_expired(job_id) {
+            let wallet_addr = ctx.wallet_addr.lock().clone();
+            let worker_name = ctx.worker_name.lock().clone();
+            tracing::debug!(
+                "{} [SUBMIT] stale share for job {} from {}, older than the last {} jobs",
+                self.log_prefix(),
+                job_id,
+                worker_name,
+                state.max_jobs()
+            );
+
+            let counted = record_reject(
+                &state,
+                record_stale_share,
+                &crate::prom::WorkerContext {
+                    worker_name,
+                    miner: String::new(),
+                    wallet: wallet_addr,
+                    ip: ctx.remote_endpoint(),
+                    port: ctx.metrics_port.clone(),
+                },
+                RejectKind::Stale,
+            );
+            if counted {
+                let stats = self.get_create_stats(&ctx);
+                *stats.stale_shares.lock() += 1;
+                *self.overall.stale_shares.lock() += 1;
+            }
+            ctx.reply_stale_share(event.id.clone()).await?;
+            return Ok(());
+        }
+
         // Fail immediately if job doesn't exist
         //          if !exists { return nil, fmt.Errorf("job does not exist. stale?") }
         // GetJob returns job at slot (id % maxJobs) without verifying ID matches
@@ -312,7 +1115,7 @@ impl ShareHandler {
                 tracing::warn!(
                     "[SUBMIT] Job ID {} not found at slot {} (current counter: {}, stored IDs: {:?})",
                     job_id,
-                    job_id % 300,
+                    job_id % state.max_jobs() as u64,
                     current_counter,
                     stored_job_ids
                 );
@@ -329,41 +1132,97 @@ impl ShareHandler {
         let nonce_str = nonce_str.replace("0x", "");
         tracing::debug!("[SUBMIT] Nonce after removing 0x: '{}' (length: {} hex chars)", nonce_str, nonce_str.len());
 
-        // Add extranonce if enabled
-        let mut final_nonce_str = nonce_str.clone();
-        {
-            let extranonce = ctx.extranonce.lock();
-            if !extranonce.is_empty() {
-                let extranonce_val = extranonce.clone();
-                let extranonce2_len = 16 - extranonce_val.len();
-
-                // Only prepend extranonce if nonce is shorter than expected
-                if nonce_str.len() <= extranonce2_len {
-                    // Format with zero-padding on the right
-                    final_nonce_str = format!("{}{:0>width$}", extranonce_val, nonce_str, width = extranonce2_len);
-                    tracing::debug!(
-                        "[SUBMIT] Extranonce prepended: '{}' = '{}' + '{:0>width$}'",
-                        final_nonce_str,
-                        extranonce_val,
-                        nonce_str,
-                        width = extranonce2_len
-                    );
-                }
-            }
-        } // extranonce guard is dropped here
-
-        tracing::debug!("[SUBMIT] Final nonce string: '{}'", final_nonce_str);
+        // Put the bytes in order (nonce_byte_order), then place the extranonce above them
         let nonce_val = {
-            let prefix = self.log_prefix();
-            u64::from_str_radix(&final_nonce_str, 16).map_err(|e| {
-                tracing::error!("{} [SUBMIT] ERROR: Failed to parse nonce '{}' as hex: {}", prefix, final_nonce_str, e);
-                format!("failed parsing noncestr: {}", e)
+            let extranonce = ctx.extranonce.lock().clone();
+            let byte_order = nonce_byte_order();
+            let nonce_str = nonce_to_big_endian(byte_order, &nonce_str).map_err(|e| {
+                tracing::error!("{} [SUBMIT] ERROR: {} (nonce_byte_order {})", self.log_prefix(), e, byte_order);
+                e
+            })?;
+            assemble_nonce(&extranonce, &nonce_str).map_err(|e| {
+                tracing::error!("{} [SUBMIT] ERROR: {} (extranonce '{}')", self.log_prefix(), e, extranonce);
+                e
             })?
         };
 
         tracing::debug!("[SUBMIT] Parsed nonce value (u64): {}", nonce_val);
         tracing::debug!("[SUBMIT] Nonce hex: {:016x}", nonce_val);
 
+        // Miners that roll the header time send it after the nonce; mild clock skew is honored, the rest rejected
+        let timestamp_tolerance = Duration::from_secs(TIMESTAMP_TOLERANCE_SECS.load(Ordering::Relaxed));
+        let submitted_timestamp =
+            if timestamp_tolerance.is_zero() { None } else { event.params.get(3).and_then(parse_submit_timestamp) };
+        if let Some(submitted) = submitted_timestamp {
+            let template_timestamp = job.block.header.timestamp;
+            if let Some(skew) = timestamp_skew(submitted, template_timestamp, timestamp_tolerance) {
+                let wallet_addr = ctx.wallet_addr.lock().clone();
+                let worker_name = ctx.worker_name.lock().clone();
+                warn!(
+                    "{} [SUBMIT] rejected share from {} for its timestamp: {} is {:.1}s from template {} (timestamp_tolerance_secs {})",
+                    self.log_prefix(),
+                    worker_name,
+                    submitted,
+                    skew.as_secs_f64(),
+                    template_timestamp,
+                    timestamp_tolerance.as_secs()
+                );
+
+                let counted = record_reject(
+                    &state,
+                    record_timestamp_share,
+                    &crate::prom::WorkerContext {
+                        worker_name,
+                        miner: String::new(),
+                        wallet: wallet_addr,
+                        ip: ctx.remote_endpoint(),
+                        port: ctx.metrics_port.clone(),
+                    },
+                    RejectKind::Invalid,
+                );
+                if counted {
+                    let stats = self.get_create_stats(&ctx);
+                    *stats.invalid_shares.lock() += 1;
+                    *self.overall.invalid_shares.lock() += 1;
+                }
+                ctx.reply(JsonRpcResponse::error(event.id.clone(), 20, "Timestamp out of range", None)).await?;
+                return Ok(());
+            }
+        }
+
+        // Reject replays of a nonce already submitted for this job
+        if !state.record_nonce(job_id, nonce_val) {
+            let wallet_addr = ctx.wallet_addr.lock().clone();
+            let worker_name = ctx.worker_name.lock().clone();
+            tracing::debug!(
+                "{} [SUBMIT] duplicate nonce {:016x} for job {} from {}",
+                self.log_prefix(),
+                nonce_val,
+                job_id,
+                worker_name
+            );
+
+            let counted = record_reject(
+                &state,
+                record_dupe_share,
+                &crate::prom::WorkerContext {
+                    worker_name,
+                    miner: String::new(),
+                    wallet: wallet_addr,
+                    ip: ctx.remote_endpoint(),
+                    port: ctx.metrics_port.clone(),
+                },
+                RejectKind::Invalid,
+            );
+            if counted {
+                let stats = self.get_create_stats(&ctx);
+                *stats.invalid_shares.lock() += 1;
+                *self.overall.invalid_shares.lock() += 1;
+            }
+            ctx.reply_dupe_share(event.id.clone()).await?;
+            return Ok(());
+        }
+
         // PoW validation with job ID workaround
         // Go validates the submitted job first, then tries previous jobs if share doesn't meet pool difficulty
         // This workaround handles IceRiver/Bitmain ASICs that submit jobs with incorrect IDs
@@ -381,6 +1240,9 @@ impl ShareHandler {
             static DIAGNOSTIC_RUN: std::sync::Once = std::sync::Once::new();
             let header = &current_job.block.header;
             let mut header_clone = (**header).clone();
+            if let Some(submitted) = submitted_timestamp {
+                header_clone.timestamp = submitted;
+            }
 
             DIAGNOSTIC_RUN.call_once(|| {
                 tracing::debug!("{}", LogColors::block("===== RUNNING POW DIAGNOSTIC ====="));
@@ -651,17 +1513,17 @@ impl ShareHandler {
                 );
                 tracing::debug!("{} {} {}", LogColors::block("[BLOCK]"), LogColors::label("Job ID:"), current_job_id);
                 tracing::debug!("{} {} {}", LogColors::block("[BLOCK]"), LogColors::label("Wallet:"), wallet_addr);
-                tracing::debug!(
-                    "{} {} {}",
-                    LogColors::block("[BLOCK]"),
-                    LogColors::label("Client:"),
-                    format!("{}:{}", ctx.remote_addr(), ctx.remote_port())
-                );
+                tracing::debug!("{} {} {}", LogColors::block("[BLOCK]"), LogColors::label("Client:"), ctx.remote_endpoint());
                 tracing::debug!("{} {} {}", LogColors::block("[BLOCK]"), LogColors::label("Block Hash:"), block_hash);
                 tracing::debug!("{} {}", LogColors::block("[BLOCK]"), "Calling kaspa_api.submit_block()...");
 
                 // Submit block to node
-                let block_submit_result = kaspa_api.submit_block(block.clone()).await;
+                let block_submit_result = crate::block_submit_queue::submit_block_queued(
+                    self.submit_queue.as_deref(),
+                    Arc::clone(&kaspa_api),
+                    block.clone(),
+                )
+                .await;
 
                 match block_submit_result {
                     Ok(_response) => {
@@ -698,12 +1560,18 @@ impl ShareHandler {
                                 worker_name: worker_name.clone(),
                                 miner: String::new(),
                                 wallet: wallet_addr.clone(),
-                                ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
+                                ip: ctx.remote_endpoint(),
+                                port: ctx.metrics_port.clone(),
                             },
                             nonce_val,
                             blue_score,
                             block_hash.clone(),
                         );
+                        crate::webhook::emit_lifecycle_event(
+                            "block_found",
+                            &ctx,
+                            serde_json::json!({ "hash": block_hash, "nonce": format!("{:x}", nonce_val), "blue_score": blue_score }),
+                        );
 
                         // Return allows HandleSubmit to record share (blocks are shares too!)
                         // After successful block submission, continue to record share at end of function
@@ -740,7 +1608,8 @@ impl ShareHandler {
                                 worker_name: worker_name.clone(),
                                 miner: String::new(),
                                 wallet: wallet_addr.clone(),
-                                ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
+                                ip: ctx.remote_endpoint(),
+                                port: ctx.metrics_port.clone(),
                             });
                             ctx.reply_stale_share(event.id.clone()).await?;
                             return Ok(());
@@ -769,7 +1638,8 @@ impl ShareHandler {
                                 worker_name: worker_name.clone(),
                                 miner: String::new(),
                                 wallet: wallet_addr.clone(),
-                                ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
+                                ip: ctx.remote_endpoint(),
+                                port: ctx.metrics_port.clone(),
                             });
                             ctx.reply_bad_share(event.id.clone()).await?;
                             return Ok(());
@@ -839,7 +1709,11 @@ impl ShareHandler {
                 } else {
                     // Try previous job ID
                     let prev_job_id = current_job_id - 1;
-                    if let Some(prev_job) = state.get_job(prev_job_id) {
+                    if state.is_job_expired(prev_job_id) {
+                        // Its slot now holds a newer job
+                        tracing::debug!("Previous job ID {} is beyond the job history, exiting loop", prev_job_id);
+                        break;
+                    } else if let Some(prev_job) = state.get_job(prev_job_id) {
                         current_job_id = prev_job_id;
                         current_job = prev_job;
                         tracing::debug!("Trying previous job ID: {} (submitted as {})", current_job_id, job_id);
@@ -874,19 +1748,43 @@ impl ShareHandler {
 
         let stats = self.get_create_stats(&ctx);
 
+        let account_share = self.should_account_share();
+        if !account_share {
+            tracing::debug!("{} [SUBMIT] bridge paused, share from {} not accounted", self.log_prefix(), ctx.remote_addr);
+        }
+
+        if account_share {
+            record_share_achieved_difficulty(!invalid_share, crate::hasher::pow_to_diff(&pow_value));
+        }
+
         if invalid_share {
             tracing::debug!("low diff share confirmed");
-            *stats.invalid_shares.lock() += 1;
-            *self.overall.invalid_shares.lock() += 1;
-
-            let wallet_addr = ctx.wallet_addr.lock().clone();
-            let worker_name = ctx.worker_name.lock().clone();
-            record_weak_share(&crate::prom::WorkerContext {
-                worker_name: worker_name.clone(),
-                miner: String::new(),
-                wallet: wallet_addr.clone(),
-                ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
-            });
+            if account_share {
+                let wallet_addr = ctx.wallet_addr.lock().clone();
+                let worker_name = ctx.worker_name.lock().clone();
+                let achieved = crate::hasher::pow_to_diff(&pow_value);
+                let assigned = state.stratum_diff().map(|d| d.diff_value).unwrap_or(0.0);
+                if let Some(ratio) = weak_share_ratio(achieved, assigned) {
+                    tracing::debug!("weak share from {}: achieved diff {:.2} of assigned {:.2}", worker_name, achieved, assigned);
+                    record_weak_share_difficulty_ratio(ratio);
+                }
+                let counted = record_reject(
+                    &state,
+                    record_weak_share,
+                    &crate::prom::WorkerContext {
+                        worker_name: worker_name.clone(),
+                        miner: String::new(),
+                        wallet: wallet_addr.clone(),
+                        ip: ctx.remote_endpoint(),
+                        port: ctx.metrics_port.clone(),
+                    },
+                    RejectKind::Invalid,
+                );
+                if counted {
+                    *stats.invalid_shares.lock() += 1;
+                    *self.overall.invalid_shares.lock() += 1;
+                }
+            }
 
             if let Some(id) = &event.id {
                 let _ = ctx.reply_low_diff_share(id).await;
@@ -901,33 +1799,63 @@ impl ShareHandler {
         //   stats.LastShare = time.Now()
         //   sh.overall.SharesFound.Add(1)
         //   RecordShareFound(ctx, state.stratumDiff.hashValue)
-        let stats = self.get_create_stats(&ctx);
-        *stats.shares_found.lock() += 1;
-        *stats.var_diff_shares_found.lock() += 1;
+        if account_share {
+            let stats = self.get_create_stats(&ctx);
+            *stats.shares_found.lock() += 1;
+            *stats.var_diff_shares_found.lock() += 1;
+            stats.var_diff_warmup.lock().record_share();
+            if let VarDiffMode::Ewma { alpha } = self.var_diff_mode {
+                stats.var_diff_ewma.lock().record_share(Instant::now(), alpha);
+            }
 
-        // Get hashValue from stratum_diff
-        let hash_value = state.stratum_diff().map(|d| d.hash_value).unwrap_or(0.0);
+            // Get hashValue from stratum_diff
+            let hash_value = state.stratum_diff().map(|d| d.hash_value).unwrap_or(0.0);
 
-        // Accumulate hashValue for hashrate calculation
-        *stats.shares_diff.lock() += hash_value;
-        *stats.last_share.lock() = Instant::now();
-        *self.overall.shares_found.lock() += 1;
+            // Accumulate hashValue for hashrate calculation
+            *stats.shares_diff.lock() += hash_value;
+            *stats.last_share.lock() = Instant::now();
+            *self.overall.shares_found.lock() += 1;
 
-        let wallet_addr = ctx.wallet_addr.lock().clone();
-        let worker_name = ctx.worker_name.lock().clone();
-        record_share_found(
-            &crate::prom::WorkerContext {
+            let wallet_addr = ctx.wallet_addr.lock().clone();
+            let worker_name = ctx.worker_name.lock().clone();
+            let worker = crate::prom::WorkerContext {
                 worker_name: worker_name.clone(),
                 miner: String::new(),
                 wallet: wallet_addr.clone(),
-                ip: format!("{}:{}", ctx.remote_addr(), ctx.remote_port()),
-            },
-            hash_value,
-        );
+                ip: ctx.remote_endpoint(),
+                port: ctx.metrics_port.clone(),
+            };
+            record_share_found(&worker, hash_value);
+            record_stale_rate(&state, &worker, Instant::now(), false);
+            if let Some(split) = ctx.payout_split.lock().as_ref() {
+                record_payout_split_share(&wallet_addr, &split.address, hash_value * split.percent / 100.0);
+            }
+            crate::share_log::log_share(
+                &wallet_addr,
+                &worker_name,
+                state.stratum_diff().map(|d| d.diff_value).unwrap_or(0.0),
+                current_job_id,
+            );
+
+            // How close this share came to the block target of the job it was validated against
+            let network_diff = crate::difficulty::difficulty_params().network_difficulty(current_job.block.header.bits);
+            if is_near_miss(crate::hasher::pow_to_diff(&pow_value), network_diff, near_miss_ratio()) {
+                record_near_miss_share(&crate::prom::WorkerContext {
+                    worker_name,
+                    miner: String::new(),
+                    wallet: wallet_addr,
+                    ip: ctx.remote_endpoint(),
+                    port: ctx.metrics_port.clone(),
+                });
+            }
+        }
+
+        if let Some(elapsed) = state.record_accepted_share(Instant::now()) {
+            tracing::debug!("{} [SUBMIT] first accepted share from {} {:?} after authorize", prefix, ctx.worker_name.lock(), elapsed);
+            record_time_to_first_share(elapsed);
+        }
 
-        ctx.reply(JsonRpcResponse { id: event.id.clone(), result: Some(serde_json::Value::Bool(true)), error: None })
-            .await
-            .map_err(|e| format!("failed to reply: {}", e))?;
+        ctx.reply(share_reply_format().accepted(event.id.clone())).await.map_err(|e| format!("failed to reply: {}", e))?;
         Ok(())
     }
 
@@ -948,13 +1876,27 @@ impl ShareHandler {
     pub fn set_client_vardiff(&self, ctx: &StratumContext, min_diff: f64) -> f64 {
         let stats = self.get_create_stats(ctx);
         let previous = *stats.min_diff.lock();
+        if previous > 0.0 {
+            stats.var_diff_ewma.lock().rescale(min_diff / previous);
+        }
         *stats.min_diff.lock() = min_diff;
+        if let Some(worker_override) = ctx.state.worker_override() {
+            *stats.diff_floor.lock() = worker_override.min_share_diff.unwrap_or(0.0);
+            *stats.var_diff_enabled.lock() = worker_override.var_diff;
+        }
         *stats.var_diff_start_time.lock() = Some(Instant::now());
         *stats.var_diff_shares_found.lock() = 0;
         *stats.var_diff_window.lock() = 0;
+        // New connection or manual difficulty: the worker has to settle again before freezing
+        *stats.var_diff_freeze.lock() = VarDiffFreezeState::default();
         previous
     }
 
+    /// Start a newly authorized connection's vardiff warmup (var_diff_warmup_shares / var_diff_warmup_secs)
+    pub fn start_client_vardiff_warmup(&self, ctx: &StratumContext) {
+        self.get_create_stats(ctx).var_diff_warmup.lock().start(Instant::now());
+    }
+
     pub fn get_client_vardiff(&self, ctx: &StratumContext) -> f64 {
         let stats = self.get_create_stats(ctx);
         let min_diff = *stats.min_diff.lock();
@@ -983,12 +1925,24 @@ impl ShareHandler {
                     (shares > 0 || now.duration_since(v.start_time) < Duration::from_secs(180))
                         && now.duration_since(last_share) < Duration::from_secs(600)
                 });
+                drop(stats_map);
+                refresh_overall_stale_rate(now);
                 // Note: Pruning is silent, no logs needed
             }
         });
     }
 
-    pub fn start_print_stats_thread(&self, target_spm: u32) {
+    /// Register this instance with the shared stats report and, with `print`, the printer; the first
+    /// printing caller's interval wins. Instances with per_miner_detail (var_diff_stats) also get a vardiff line per worker
+    pub fn start_print_stats_thread(
+        &self,
+        target_spm: u32,
+        interval: Duration,
+        per_miner_detail: bool,
+        show_hashrate_estimate: bool,
+        connections: ConnectionCounter,
+        print: bool,
+    ) {
         let target_spm = if target_spm == 0 { 20.0 } else { target_spm as f64 };
         let instance_id = self.instance_id.clone();
         let inst_short = {
@@ -1005,6 +1959,9 @@ impl ShareHandler {
             if !registry.iter().any(|e| e.instance_id == instance_id) {
                 registry.push(StatsPrinterEntry {
                     instance_id,
+                    per_miner_detail,
+                    show_hashrate_estimate,
+                    connections,
                     inst_short,
                     target_spm,
                     start: Instant::now(),
@@ -1014,262 +1971,147 @@ impl ShareHandler {
             }
         }
 
-        if STATS_PRINTER_STARTED.swap(true, Ordering::AcqRel) {
+        if !print || STATS_PRINTER_STARTED.swap(true, Ordering::AcqRel) {
             return;
         }
 
         tokio::spawn(async move {
-            fn trunc<'a>(s: &'a str, max: usize) -> Cow<'a, str> {
-                if s.len() <= max {
-                    Cow::Borrowed(s)
-                } else {
-                    Cow::Owned(s.chars().take(max).collect())
-                }
-            }
-
-            const WORKER_W: usize = 16;
-            const INST_W: usize = 5;
-            const HASH_W: usize = 11;
-            const DIFF_W: usize = 6;
-            const SPM_W: usize = 11;
-            const TRND_W: usize = 4;
-            const ACC_W: usize = 12;
-            const BLK_W: usize = 6;
-            const TIME_W: usize = 7;
-
-            fn border() -> String {
-                format!(
-                    "+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+",
-                    "-".repeat(WORKER_W),
-                    "-".repeat(INST_W),
-                    "-".repeat(HASH_W),
-                    "-".repeat(DIFF_W),
-                    "-".repeat(SPM_W),
-                    "-".repeat(TRND_W),
-                    "-".repeat(ACC_W),
-                    "-".repeat(BLK_W),
-                    "-".repeat(TIME_W)
-                )
-            }
-
-            fn header() -> String {
-                format!(
-                    "| {:<WORKER_W$} | {:<INST_W$} | {:>HASH_W$} | {:>DIFF_W$} | {:>SPM_W$} | {:<TRND_W$} | {:>ACC_W$} | {:>BLK_W$} | {:>TIME_W$} |",
-                    "Worker",
-                    "Inst",
-                    "Hash",
-                    "Diff",
-                    "SPM/tgt",
-                    "Trnd",
-                    "Acc/Stl/Inv",
-                    "Blocks",
-                    "Time",
-                )
-            }
-
-            let mut interval = tokio::time::interval(STATS_PRINT_INTERVAL);
+            let mut interval = tokio::time::interval(interval.max(Duration::from_secs(1)));
             loop {
                 interval.tick().await;
-
-                let node_status = {
-                    let s = NODE_STATUS.lock();
-                    s.clone()
-                };
-
-                let entries = {
-                    let registry = STATS_PRINTER_REGISTRY.lock();
-                    registry
-                        .iter()
-                        .map(|e| (e.inst_short.clone(), e.target_spm, e.start, Arc::clone(&e.stats), Arc::clone(&e.overall)))
-                        .collect::<Vec<_>>()
-                };
-
-                if entries.is_empty() {
-                    continue;
-                }
-
-                let mut rows: Vec<(String, String)> = Vec::new();
-                let mut total_rate = 0.0;
-                let mut total_shares: i64 = 0;
-                let mut total_stales: i64 = 0;
-                let mut total_invalids: i64 = 0;
-                let mut total_blocks: i64 = 0;
-
-                let now = Instant::now();
-                let start = entries.iter().map(|(_, _, start, _, _)| *start).max_by_key(|t| t.elapsed()).unwrap_or_else(Instant::now);
-                let total_uptime_mins = now.duration_since(start).as_secs_f64() / 60.0;
-
-                let mut total_target: Option<f64> = Some(entries[0].1);
-                for (inst_short, target_spm, _, stats, overall) in entries.iter() {
-                    if let Some(t) = total_target {
-                        if (t - *target_spm).abs() > 0.0001 {
-                            total_target = None;
-                        }
-                    }
-
-                    total_shares += *overall.shares_found.lock();
-                    total_stales += *overall.stale_shares.lock();
-                    total_invalids += *overall.invalid_shares.lock();
-                    total_blocks += *overall.blocks_found.lock();
-
-                    let stats_map = stats.lock();
-                    for (_, v) in stats_map.iter() {
-                        let elapsed = v.start_time.elapsed().as_secs_f64();
-                        let rate = if elapsed > 0.0 {
-                            let total_hash_value = *v.shares_diff.lock();
-                            total_hash_value / elapsed
-                        } else {
-                            0.0
-                        };
-                        total_rate += rate;
-
-                        let shares = *v.shares_found.lock();
-                        let stales = *v.stale_shares.lock();
-                        let invalids = *v.invalid_shares.lock();
-                        let blocks = *v.blocks_found.lock();
-                        let min_diff = *v.min_diff.lock();
-
-                        let spm = if elapsed > 0.0 { (shares as f64) / (elapsed / 60.0) } else { 0.0 };
-                        let trend = if spm > *target_spm * 1.2 {
-                            "up"
-                        } else if spm < *target_spm * 0.8 {
-                            "down"
-                        } else {
-                            "flat"
-                        };
-
-                        let uptime_mins = v.start_time.elapsed().as_secs_f64() / 60.0;
-                        let worker = v.worker_name.lock().clone();
-
-                        let spm_tgt = format!("{:>4.1}/{:<4.1}", spm, *target_spm);
-
-                        let line = format!(
-                            "| {:<WORKER_W$} | {:<INST_W$} | {:>HASH_W$} | {:>DIFF_W$} | {:>SPM_W$} | {:<TRND_W$} | {:>ACC_W$} | {:>BLK_W$} | {:>TIME_W$} |",
-                            trunc(&worker, WORKER_W),
-                            inst_short,
-                            format_hashrate(rate),
-                            min_diff.round() as u64,
-                            spm_tgt,
-                            trend,
-                            format!("{}/{}/{}", shares, stales, invalids),
-                            blocks,
-                            format!("{:.1}m", uptime_mins)
-                        );
-                        let sort_key = format!("{}:{}", inst_short, worker);
-                        rows.push((sort_key, line));
-                    }
-                }
-
-                rows.sort_by(|a, b| a.0.cmp(&b.0));
-
-                let top = border();
-                let sep = border();
-                let hdr = header();
-
-                let mut out = Vec::new();
-
-                let sync_str = match node_status.is_synced {
-                    Some(true) => "synced".to_string(),
-                    Some(false) => "syncing".to_string(),
-                    None => "unknown".to_string(),
-                };
-                let conn_str = if node_status.is_connected { "connected" } else { "disconnected" };
-
-                let net = node_status.network_id.as_deref().unwrap_or("-");
-                let ver = node_status.server_version.as_deref().unwrap_or("-");
-                let peers = node_status.peers.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string());
-                let vdaa = node_status.virtual_daa_score.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
-                let blocks = node_status.block_count.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
-                let headers = node_status.header_count.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
-                let diff = node_status.difficulty.map(|d| format!("{:.2}", d)).unwrap_or_else(|| "-".to_string());
-                let tip = node_status.tip_hash.as_deref().unwrap_or("-");
-                let mempool = node_status.mempool_size.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
-
-                out.push(format!(
-                    "[NODE] {} / {} | net={} | ver={} | peers={} | vdaa={} | blocks={}/{} | diff={} | mempool={} | tip={}",
-                    conn_str, sync_str, net, ver, peers, vdaa, blocks, headers, diff, mempool, tip
-                ));
-
-                out.push(top.clone());
-                out.push(hdr);
-                out.push(sep.clone());
-
-                for (_, line) in rows.iter() {
-                    out.push(line.clone());
+                if let Some(report) = stats_report() {
+                    info!("{}", report);
                 }
-
-                out.push(sep.clone());
-
-                let overall_spm = if total_uptime_mins > 0.0 { (total_shares as f64) / total_uptime_mins } else { 0.0 };
-                let total_spm_tgt = match total_target {
-                    Some(t) => format!("{:>4.1}/{:<4.1}", overall_spm, t),
-                    None => format!("{:>4.1}/-", overall_spm),
-                };
-
-                out.push(format!(
-                    "| {:<WORKER_W$} | {:<INST_W$} | {:>HASH_W$} | {:>DIFF_W$} | {:>SPM_W$} | {:<TRND_W$} | {:>ACC_W$} | {:>BLK_W$} | {:>TIME_W$} |",
-                    "TOTAL",
-                    "ALL",
-                    format_hashrate(total_rate),
-                    "-",
-                    total_spm_tgt,
-                    "-",
-                    format!("{}/{}/{}", total_shares, total_stales, total_invalids),
-                    total_blocks,
-                    format!("{:.1}m", total_uptime_mins)
-                ));
-
-                out.push(top);
-                info!("{}", out.join("\n"));
             }
         });
     }
 
-    pub fn start_vardiff_thread(&self, _expected_share_rate: u32, _log_stats: bool, _clamp: bool) {
+    /// Retarget workers every var_diff_retarget. `enabled` is the instance's var_diff; worker_overrides
+    /// entries can switch individual workers on or off against it
+    pub fn start_vardiff_thread(
+        &self,
+        _expected_share_rate: u32,
+        _log_stats: bool,
+        _clamp: bool,
+        freeze: VarDiffFreezePolicy,
+        enabled: bool,
+        bounds: SuggestDifficultyPolicy,
+    ) {
         let stats = Arc::clone(&self.stats);
         let prefix = self.log_prefix();
+        let instance_id = self.instance_id.clone();
         let expected_share_rate = _expected_share_rate;
         let log_stats = _log_stats;
         let clamp = _clamp;
+        let mode = self.var_diff_mode;
 
         tokio::spawn(async move {
             let expected_spm = expected_share_rate.max(1) as f64;
-            let mut interval = tokio::time::interval(Duration::from_secs(VAR_DIFF_THREAD_SLEEP));
 
             if log_stats {
                 tracing::info!(
-                    "{} VarDiff enabled (target={} shares/min, tick={}s, pow2_clamp={})",
+                    "{} VarDiff enabled (target={} shares/min, tick={}s, pow2_clamp={}, mode={})",
                     prefix,
                     expected_spm,
-                    VAR_DIFF_THREAD_SLEEP,
-                    clamp
+                    var_diff_retarget().as_secs(),
+                    clamp,
+                    mode
                 );
             } else {
                 tracing::debug!(
-                    "{} VarDiff thread started (target={} shares/min, tick={}s, pow2_clamp={})",
+                    "{} VarDiff thread started (target={} shares/min, tick={}s, pow2_clamp={}, mode={})",
                     prefix,
                     expected_spm,
-                    VAR_DIFF_THREAD_SLEEP,
-                    clamp
+                    var_diff_retarget().as_secs(),
+                    clamp,
+                    mode
                 );
             }
 
             loop {
-                interval.tick().await;
+                // Re-read every pass so a SIGHUP reload changes the pace without a restart
+                tokio::time::sleep(var_diff_retarget()).await;
 
                 let mut stats_map = stats.lock();
                 let now = Instant::now();
+                let mut rate_error = VarDiffRateError::default();
+                let (warmup_shares, warmup_time) = var_diff_warmup();
 
                 for (_worker_id, v) in stats_map.iter_mut() {
+                    if !v.var_diff_enabled.lock().unwrap_or(enabled) {
+                        continue;
+                    }
                     let start_opt = *v.var_diff_start_time.lock();
                     let Some(start) = start_opt else { continue };
+                    // The first shares of a new connection say little about its rate; its window keeps
+                    // filling so the first retarget after the warmup sees all of them
+                    if v.var_diff_warmup.lock().holds(now, warmup_shares, warmup_time) {
+                        continue;
+                    }
 
                     let elapsed = now.duration_since(start).as_secs_f64().max(0.0);
                     let shares = *v.var_diff_shares_found.lock() as f64;
                     let current = *v.min_diff.lock();
-                    let next_opt = vardiff_compute_next_diff(current, shares, elapsed, expected_spm, clamp);
-                    let Some(next) = next_opt else { continue };
 
+                    let observed = vardiff_observed_spm(mode, &v.var_diff_ewma.lock(), shares, elapsed);
+                    if let Some(observed_spm) = observed {
+                        record_vardiff_rate_ratio(&instance_id, rate_error.observe(observed_spm, expected_spm));
+                    }
+                    let was_frozen = v.var_diff_freeze.lock().frozen;
+                    let frozen = freeze.observe(&mut v.var_diff_freeze.lock(), observed, expected_spm);
+                    if frozen != was_frozen && log_stats {
+                        let worker = v.worker_name.lock().clone();
+                        if frozen {
+                            tracing::info!("{} VarDiff: {} settled at diff {:.0}, freezing", prefix, worker, current);
+                        } else {
+                            tracing::info!("{} VarDiff: {} drifted outside tolerance, unfreezing", prefix, worker);
+                        }
+                    }
+                    if frozen {
+                        // Measure the next check over a fresh window so drift shows up quickly
+                        if observed.is_some() {
+                            *v.var_diff_start_time.lock() = Some(now);
+                            *v.var_diff_shares_found.lock() = 0;
+                        }
+                        continue;
+                    }
+
+                    let next_opt = match mode {
+                        VarDiffMode::Window => vardiff_compute_next_diff(current, shares, elapsed, expected_spm, clamp),
+                        VarDiffMode::Ewma { alpha } => vardiff_compute_next_diff_ewma(
+                            current,
+                            &v.var_diff_ewma.lock(),
+                            alpha,
+                            shares,
+                            elapsed,
+                            expected_spm,
+                            clamp,
+                        ),
+                    };
+                    // var_diff_min/var_diff_max bound every retarget; a worker's own floor still wins
+                    let floor = *v.diff_floor.lock();
+                    let Some(next) = next_opt
+                        .map(|next| bounds.bound_retarget(next).max(floor))
+                        .filter(|next| (next - current).abs() > f64::EPSILON)
+                    else {
+                        continue;
+                    };
+                    // Small corrections are not worth a set_difficulty; the window keeps growing so a real
+                    // trend still clears the hysteresis later. Getting up to a worker's floor is never held back
+                    if current >= floor && within_hysteresis(current, next, var_diff_hysteresis()) {
+                        tracing::debug!(
+                            "{} VarDiff: {} diff {:.0} -> {:.0} within {}% hysteresis, not sent",
+                            prefix,
+                            v.worker_name.lock(),
+                            current,
+                            next,
+                            var_diff_hysteresis()
+                        );
+                        record_vardiff_suppressed();
+                        continue;
+                    }
+
+                    v.var_diff_ewma.lock().rescale(next / current);
                     *v.min_diff.lock() = next;
                     *v.var_diff_start_time.lock() = Some(now);
                     *v.var_diff_shares_found.lock() = 0;
@@ -1288,12 +2130,336 @@ impl ShareHandler {
                             next
                         );
                     }
+                    tracing::debug!(
+                        "{} VarDiff: {} diff {:.0} needs ~{} for {:.0} shares/min",
+                        prefix,
+                        v.worker_name.lock(),
+                        next,
+                        format_hashrate(required_hashrate_ghs(next, expected_spm)),
+                        expected_spm
+                    );
+                }
+                if let Some(mean) = rate_error.mean() {
+                    record_vardiff_rate_ratio_mean(&instance_id, mean);
                 }
             }
         });
     }
 }
 
+/// Node line, session summary, per-wallet lines and worker table across every registered instance,
+/// as printed by the stats thread and served on stats_port. None until an instance registers
+pub fn stats_report() -> Option<String> {
+    fn trunc<'a>(s: &'a str, max: usize) -> Cow<'a, str> {
+        if s.len() <= max {
+            Cow::Borrowed(s)
+        } else {
+            Cow::Owned(s.chars().take(max).collect())
+        }
+    }
+
+    const WORKER_W: usize = 16;
+    const INST_W: usize = 5;
+    const HASH_W: usize = 11;
+    const DIFF_W: usize = 6;
+    const SPM_W: usize = 11;
+    const TRND_W: usize = 4;
+    const ACC_W: usize = 12;
+    const BLK_W: usize = 6;
+    const TIME_W: usize = 7;
+
+    fn border() -> String {
+        format!(
+            "+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+-{}-+",
+            "-".repeat(WORKER_W),
+            "-".repeat(INST_W),
+            "-".repeat(HASH_W),
+            "-".repeat(DIFF_W),
+            "-".repeat(SPM_W),
+            "-".repeat(TRND_W),
+            "-".repeat(ACC_W),
+            "-".repeat(BLK_W),
+            "-".repeat(TIME_W)
+        )
+    }
+
+    fn header() -> String {
+        format!(
+            "| {:<WORKER_W$} | {:<INST_W$} | {:>HASH_W$} | {:>DIFF_W$} | {:>SPM_W$} | {:<TRND_W$} | {:>ACC_W$} | {:>BLK_W$} | {:>TIME_W$} |",
+            "Worker",
+            "Inst",
+            "Hash",
+            "Diff",
+            "SPM/tgt",
+            "Trnd",
+            "Acc/Stl/Inv",
+            "Blocks",
+            "Time",
+        )
+    }
+
+    let node_status = {
+        let s = NODE_STATUS.lock();
+        s.clone()
+    };
+
+    let entries = {
+        let registry = STATS_PRINTER_REGISTRY.lock();
+        registry
+            .iter()
+            .map(|e| {
+                (
+                    e.inst_short.clone(),
+                    e.target_spm,
+                    e.start,
+                    Arc::clone(&e.stats),
+                    Arc::clone(&e.overall),
+                    e.per_miner_detail,
+                    e.show_hashrate_estimate,
+                    (e.connections)(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if entries.is_empty() {
+        return None;
+    }
+
+    let mut rows: Vec<(String, String)> = Vec::new();
+    let mut estimates: Vec<(String, String)> = Vec::new();
+    let mut vardiff_details: Vec<(String, String)> = Vec::new();
+    let mut total_rate = 0.0;
+    let mut total_shares: i64 = 0;
+    let mut total_stales: i64 = 0;
+    let mut total_invalids: i64 = 0;
+    let mut total_blocks: i64 = 0;
+    let mut total_connections: usize = 0;
+    let mut all_workers: Vec<WorkStats> = Vec::new();
+
+    let now = Instant::now();
+    let start = entries.iter().map(|(_, _, start, ..)| *start).max_by_key(|t| t.elapsed()).unwrap_or_else(Instant::now);
+    let total_uptime_mins = now.duration_since(start).as_secs_f64() / 60.0;
+
+    let mut total_target: Option<f64> = Some(entries[0].1);
+    for (inst_short, target_spm, _, stats, overall, per_miner_detail, show_hashrate_estimate, connections) in entries.iter() {
+        if let Some(t) = total_target {
+            if (t - *target_spm).abs() > 0.0001 {
+                total_target = None;
+            }
+        }
+
+        total_shares += *overall.shares_found.lock();
+        total_stales += *overall.stale_shares.lock();
+        total_invalids += *overall.invalid_shares.lock();
+        total_blocks += *overall.blocks_found.lock();
+        total_connections += *connections;
+
+        let stats_map = stats.lock();
+        all_workers.extend(stats_map.values().cloned());
+        for (_, v) in stats_map.iter() {
+            let elapsed = v.start_time.elapsed().as_secs_f64();
+            let rate = if elapsed > 0.0 {
+                let total_hash_value = *v.shares_diff.lock();
+                total_hash_value / elapsed
+            } else {
+                0.0
+            };
+            total_rate += rate;
+            if *show_hashrate_estimate {
+                let worker = v.worker_name.lock().clone();
+                let line = hashrate_estimate_line(inst_short, &worker, *v.min_diff.lock(), *target_spm, rate);
+                estimates.push((format!("{}:{}", inst_short, worker), line));
+            }
+            let shares = *v.shares_found.lock();
+            let stales = *v.stale_shares.lock();
+            let invalids = *v.invalid_shares.lock();
+            let blocks = *v.blocks_found.lock();
+            let min_diff = *v.min_diff.lock();
+
+            let spm = if elapsed > 0.0 { (shares as f64) / (elapsed / 60.0) } else { 0.0 };
+            let trend = if spm > *target_spm * 1.2 {
+                "up"
+            } else if spm < *target_spm * 0.8 {
+                "down"
+            } else {
+                "flat"
+            };
+
+            let uptime_mins = v.start_time.elapsed().as_secs_f64() / 60.0;
+            let worker = v.worker_name.lock().clone();
+
+            let spm_tgt = format!("{:>4.1}/{:<4.1}", spm, *target_spm);
+
+            let line = format!(
+                "| {:<WORKER_W$} | {:<INST_W$} | {:>HASH_W$} | {:>DIFF_W$} | {:>SPM_W$} | {:<TRND_W$} | {:>ACC_W$} | {:>BLK_W$} | {:>TIME_W$} |",
+                trunc(&worker, WORKER_W),
+                inst_short,
+                format_hashrate(rate),
+                min_diff.round() as u64,
+                spm_tgt,
+                trend,
+                format!("{}/{}/{}", shares, stales, invalids),
+                blocks,
+                format!("{:.1}m", uptime_mins)
+            );
+            let sort_key = format!("{}:{}", inst_short, worker);
+            rows.push((sort_key.clone(), line));
+
+            if *per_miner_detail {
+                let window = v.var_diff_start_time.lock().map(|t| now.saturating_duration_since(t).as_secs_f64()).unwrap_or(0.0);
+                let line = format!(
+                    "[VARDIFF] {} {} | diff={} | {} shares in {:.0}s since retarget{}",
+                    inst_short,
+                    worker,
+                    min_diff.round() as u64,
+                    *v.var_diff_shares_found.lock(),
+                    window,
+                    if v.var_diff_freeze.lock().frozen { " | frozen" } else { "" }
+                );
+                vardiff_details.push((sort_key, line));
+            }
+        }
+    }
+
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    estimates.sort_by(|a, b| a.0.cmp(&b.0));
+    vardiff_details.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let top = border();
+    let sep = border();
+    let hdr = header();
+
+    let mut out = Vec::new();
+
+    let sync_str = match node_status.is_synced {
+        Some(true) => "synced".to_string(),
+        Some(false) => "syncing".to_string(),
+        None => "unknown".to_string(),
+    };
+    let conn_str = if node_status.is_connected { "connected" } else { "disconnected" };
+
+    let net = node_status.network_id.as_deref().unwrap_or("-");
+    let ver = node_status.server_version.as_deref().unwrap_or("-");
+    let peers = node_status.peers.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string());
+    let vdaa = node_status.virtual_daa_score.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+    let blocks = node_status.block_count.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+    let headers = node_status.header_count.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+    let diff = node_status.difficulty.map(|d| format!("{:.2}", d)).unwrap_or_else(|| "-".to_string());
+    let tip = node_status.tip_hash.as_deref().unwrap_or("-");
+    let mempool = node_status.mempool_size.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+
+    out.push(format!(
+        "[NODE] {} / {} | net={} | ver={} | peers={} | vdaa={} | blocks={}/{} | diff={} | mempool={} | tip={}",
+        conn_str, sync_str, net, ver, peers, vdaa, blocks, headers, diff, mempool, tip
+    ));
+    out.push(stats_summary_line(
+        total_shares,
+        total_stales,
+        total_invalids,
+        total_connections,
+        total_rate,
+        total_blocks,
+        node_status.last_template.map(|t| t.elapsed()),
+    ));
+    for wallet in aggregate_by_wallet(&all_workers) {
+        out.push(format!(
+            "[WALLET] {} | workers={} | accepted={} | reject={:.2}% | hashrate={}",
+            if wallet.wallet.is_empty() { "(unauthorized)" } else { &wallet.wallet },
+            wallet.workers,
+            wallet.accepted,
+            wallet.reject_rate(),
+            format_hashrate(wallet.hashrate_ghs)
+        ));
+    }
+
+    out.push(top.clone());
+    out.push(hdr);
+    out.push(sep.clone());
+
+    if !rows.is_empty() {
+        for (_, line) in rows.iter() {
+            out.push(line.clone());
+        }
+        out.push(sep.clone());
+    }
+
+    let overall_spm = if total_uptime_mins > 0.0 { (total_shares as f64) / total_uptime_mins } else { 0.0 };
+    let total_spm_tgt = match total_target {
+        Some(t) => format!("{:>4.1}/{:<4.1}", overall_spm, t),
+        None => format!("{:>4.1}/-", overall_spm),
+    };
+
+    out.push(format!(
+        "| {:<WORKER_W$} | {:<INST_W$} | {:>HASH_W$} | {:>DIFF_W$} | {:>SPM_W$} | {:<TRND_W$} | {:>ACC_W$} | {:>BLK_W$} | {:>TIME_W$} |",
+        "TOTAL",
+        "ALL",
+        format_hashrate(total_rate),
+        "-",
+        total_spm_tgt,
+        "-",
+        format!("{}/{}/{}", total_shares, total_stales, total_invalids),
+        total_blocks,
+        format!("{:.1}m", total_uptime_mins)
+    ));
+
+    out.push(top);
+    out.extend(estimates.into_iter().map(|(_, line)| line));
+    out.extend(vardiff_details.into_iter().map(|(_, line)| line));
+    Some(out.join("\n"))
+}
+
+/// One-line session summary printed above the stats table
+fn stats_summary_line(
+    accepted: i64,
+    stale: i64,
+    invalid: i64,
+    connections: usize,
+    hashrate_ghs: f64,
+    blocks: i64,
+    template_age: Option<Duration>,
+) -> String {
+    let rejected = stale + invalid;
+    let total = accepted + rejected;
+    let reject_rate = if total > 0 { rejected as f64 * 100.0 / total as f64 } else { 0.0 };
+    let template_age = template_age.map(|a| format!("{:.0}s", a.as_secs_f64())).unwrap_or_else(|| "-".to_string());
+    format!(
+        "[SUMMARY] shares={} | accepted={} | rejected={} ({} stale, {} invalid) | reject={:.2}% | conns={} | hashrate={} | blocks={} | template age={}",
+        total,
+        accepted,
+        rejected,
+        stale,
+        invalid,
+        reject_rate,
+        connections,
+        format_hashrate(hashrate_ghs),
+        blocks,
+        template_age
+    )
+}
+
+fn notify_submit_rtt_line(prefix: &str, client: &str, job_id: u64, rtt: Duration) -> String {
+    format!("{} [RTT] {} job {} notify->submit: {:.1}ms", prefix, client, job_id, rtt.as_secs_f64() * 1000.0)
+}
+
+/// Hashrate (GH/s, same units as the measured rates) needed to find `shares_per_min` shares a minute at `diff`
+pub fn required_hashrate_ghs(diff: f64, shares_per_min: f64) -> f64 {
+    crate::hasher::diff_to_hash(diff) * shares_per_min / 60.0
+}
+
+/// Stats block line comparing the hashrate a worker's difficulty implies with what it actually delivers
+fn hashrate_estimate_line(inst_short: &str, worker: &str, diff: f64, target_spm: f64, measured_ghs: f64) -> String {
+    format!(
+        "[DIFF] {} {} | diff={:.0} | needs ~{} for {:.1} shares/min | measured {}",
+        inst_short,
+        worker,
+        diff,
+        format_hashrate(required_hashrate_ghs(diff, target_spm)),
+        target_spm,
+        format_hashrate(measured_ghs)
+    )
+}
+
 fn format_hashrate(ghs: f64) -> String {
     if ghs < 1.0 {
         format!("{:.2}MH/s", ghs * 1000.0)
@@ -1331,3 +2497,415 @@ pub struct WorkerContext<'a> {
     pub worker_name: &'a str,
     pub wallet_addr: &'a str,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TEST_REJECTS_RECORDED: AtomicU64 = AtomicU64::new(0);
+
+    fn count_test_reject(_: &crate::prom::WorkerContext) {
+        TEST_REJECTS_RECORDED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_only_stale_rejects_are_warmup() {
+        let state = MiningState::new();
+        let now = Instant::now();
+        state.mark_authorized(now);
+        let worker = crate::prom::WorkerContext {
+            worker_name: "rig-warmup".to_string(),
+            miner: String::new(),
+            wallet: "kaspa:qz0000".to_string(),
+            ip: "10.0.0.5:4000".to_string(),
+            port: String::new(),
+        };
+        let warmup = Duration::from_secs(30);
+        let reject = |kind| record_reject_at(&state, count_test_reject, &worker, kind, now, warmup, Duration::ZERO);
+
+        // A weak share right after authorizing is broken hardware, not a miner catching up
+        assert!(reject(RejectKind::Invalid), "a weak share during warmup still counts as invalid");
+        assert_eq!(TEST_REJECTS_RECORDED.load(Ordering::Relaxed), 1);
+        assert!(!reject(RejectKind::Stale));
+        assert!(!reject(RejectKind::UnknownJob));
+        assert_eq!(TEST_REJECTS_RECORDED.load(Ordering::Relaxed), 1, "warmup rejects skip their own type");
+
+        let later = now + warmup;
+        assert!(record_reject_at(&state, count_test_reject, &worker, RejectKind::Stale, later, warmup, Duration::ZERO));
+        assert_eq!(TEST_REJECTS_RECORDED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_notify_submit_rtt_line_for_sampled_job() {
+        let state = MiningState::new();
+        assert!(state.record_notify_sent(1));
+        std::thread::sleep(Duration::from_millis(15));
+
+        let rtt = state.take_notify_rtt(1).expect("sampled job should be timed");
+        let line = notify_submit_rtt_line("[Instance 1]", "10.0.0.5:4000", 1, rtt);
+        assert!(line.starts_with("[Instance 1] [RTT] 10.0.0.5:4000 job 1 notify->submit: "));
+
+        let ms: f64 = line.rsplit(": ").next().unwrap().trim_end_matches("ms").parse().unwrap();
+        assert!((15.0..5000.0).contains(&ms), "implausible rtt {}ms", ms);
+    }
+
+    #[test]
+    fn test_aggregate_by_wallet() {
+        let worker = |name: &str, wallet: &str, accepted: i64, stale: i64, invalid: i64| {
+            let stats = WorkStats::new(name.to_string());
+            *stats.wallet.lock() = wallet.to_string();
+            *stats.shares_found.lock() = accepted;
+            *stats.stale_shares.lock() = stale;
+            *stats.invalid_shares.lock() = invalid;
+            stats
+        };
+        let workers =
+            [worker("rig1", "kaspa:farm", 90, 5, 5), worker("rig2", "kaspa:farm", 110, 0, 0), worker("solo", "kaspa:solo", 10, 0, 10)];
+
+        let wallets = aggregate_by_wallet(&workers);
+        assert_eq!(wallets.len(), 2);
+        let farm = wallets.iter().find(|w| w.wallet == "kaspa:farm").unwrap();
+        assert_eq!((farm.workers, farm.accepted, farm.stale, farm.invalid), (2, 200, 5, 5));
+        assert!((farm.reject_rate() - 10.0 * 100.0 / 210.0).abs() < 1e-9);
+        let solo = wallets.iter().find(|w| w.wallet == "kaspa:solo").unwrap();
+        assert_eq!(solo.reject_rate(), 50.0);
+
+        // Worker totals and wallet totals agree
+        let worker_total: i64 = workers.iter().map(|w| *w.shares_found.lock()).sum();
+        assert_eq!(wallets.iter().map(|w| w.accepted).sum::<i64>(), worker_total);
+    }
+
+    #[test]
+    fn test_submit_timestamp_tolerance() {
+        assert_eq!(parse_submit_timestamp(&serde_json::json!(1_700_000_000_000u64)), Some(1_700_000_000_000));
+        assert_eq!(parse_submit_timestamp(&serde_json::json!("0x18bcfe56800")), Some(1_700_000_000_000));
+        assert_eq!(parse_submit_timestamp(&serde_json::json!("18bcfe56800")), Some(1_700_000_000_000));
+        assert_eq!(parse_submit_timestamp(&serde_json::json!("soon")), None);
+        assert_eq!(parse_submit_timestamp(&serde_json::json!(-5)), None);
+
+        let template = 1_700_000_000_000;
+        let tolerance = Duration::from_secs(2);
+        assert_eq!(timestamp_skew(template + 1_500, template, tolerance), None);
+        assert_eq!(timestamp_skew(template - 2_000, template, tolerance), None);
+        assert_eq!(timestamp_skew(template - 2_001, template, tolerance), Some(Duration::from_millis(2_001)));
+        assert_eq!(timestamp_skew(template + 3_600_000, template, tolerance), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_stats_summary_line() {
+        let line = stats_summary_line(950, 30, 20, 4, 1500.0, 2, Some(Duration::from_secs(3)));
+        assert!(line.contains("shares=1000"), "{}", line);
+        assert!(line.contains("accepted=950"), "{}", line);
+        assert!(line.contains("rejected=50 (30 stale, 20 invalid)"), "{}", line);
+        assert!(line.contains("reject=5.00%"), "{}", line);
+        assert!(line.contains("conns=4"), "{}", line);
+        assert!(line.contains("blocks=2"), "{}", line);
+        assert!(line.contains("template age=3s"), "{}", line);
+
+        let idle = stats_summary_line(0, 0, 0, 0, 0.0, 0, None);
+        assert!(idle.contains("reject=0.00%") && idle.contains("template age=-"), "{}", idle);
+    }
+
+    #[test]
+    fn test_required_hashrate_estimate() {
+        // One share a second at diff 1 is 2^32 hashes a second
+        assert!((required_hashrate_ghs(1.0, 60.0) - 4.294967296).abs() < 1e-9);
+        // Doubling the difficulty or the share rate doubles the hashrate needed
+        assert!((required_hashrate_ghs(2048.0, 20.0) * 2.0 - required_hashrate_ghs(4096.0, 20.0)).abs() < 1e-6);
+        assert!((required_hashrate_ghs(4096.0, 10.0) * 2.0 - required_hashrate_ghs(4096.0, 20.0)).abs() < 1e-6);
+
+        let line = hashrate_estimate_line("Ins01", "rig01", 4096.0, 20.0, 5000.0);
+        assert!(line.contains("diff=4096"), "{}", line);
+        assert!(line.contains("needs ~5.86TH/s for 20.0 shares/min"), "{}", line);
+        assert!(line.contains("measured 5.00TH/s"), "{}", line);
+    }
+
+    struct NoKaspad;
+
+    #[async_trait::async_trait]
+    impl KaspaApiTrait for NoKaspad {
+        async fn get_block_template(&self, _: &str, _: &str, _: &str) -> Result<Block, Box<dyn std::error::Error + Send + Sync>> {
+            Err("no kaspad in tests".into())
+        }
+
+        async fn submit_block(
+            &self,
+            _: Block,
+        ) -> Result<kaspa_rpc_core::SubmitBlockResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Err("no kaspad in tests".into())
+        }
+
+        async fn get_balances_by_addresses(
+            &self,
+            _: &[String],
+        ) -> Result<Vec<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Submit one share far below its assigned difficulty and return the rejects `handler` accounted for it
+    async fn submit_low_diff_share(handler: &ShareHandler) -> i64 {
+        use kaspa_consensus_core::header::Header;
+        use kaspa_hashes::Hash;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, _client) = crate::mock_connection::tcp_connection(&listener).await;
+        *ctx.worker_name.lock() = "rig01".to_string();
+        let mut diff = crate::hasher::KaspaDiff::new();
+        diff.set_diff_value(1e15);
+        ctx.state.set_stratum_diff(diff);
+        let header = Header::from_precomputed_hash(Hash::default(), vec![]);
+        let job_id = ctx.state.add_job(crate::mining_state::Job { block: Block::new(header, vec![]), pre_pow_hash: Hash::default() });
+
+        let params = vec![Value::from("kaspa:qz.rig01"), Value::from(job_id.to_string()), Value::from("0000000000000001")];
+        let event = JsonRpcEvent::new(Some("4".to_string()), "mining.submit", params);
+        handler.handle_submit(Arc::clone(&ctx), event, Arc::new(NoKaspad)).await.unwrap();
+        let rejects = *handler.get_create_stats(&ctx).invalid_shares.lock();
+        rejects
+    }
+
+    #[tokio::test]
+    async fn test_shares_dropped_while_paused() {
+        let handler = ShareHandler::new("Instance 1".to_string(), false, VarDiffMode::Window, 20, None);
+        assert_eq!(submit_low_diff_share(&handler).await, 1, "running: accounted");
+
+        handler.set_paused(true);
+        assert_eq!(submit_low_diff_share(&handler).await, 1, "paused: dropped from accounting");
+        assert_eq!(*handler.overall.invalid_shares.lock(), 1);
+
+        handler.set_paused(false);
+        assert_eq!(submit_low_diff_share(&handler).await, 2);
+
+        let accounting = ShareHandler::new("Instance 1".to_string(), true, VarDiffMode::Window, 20, None);
+        accounting.set_paused(true);
+        assert_eq!(submit_low_diff_share(&accounting).await, 1, "account_shares_while_paused: accounted");
+    }
+
+    #[test]
+    fn test_suggest_difficulty_policy() {
+        let policy = SuggestDifficultyPolicy { var_diff_min: 1024.0, var_diff_max: 65536.0, pow2_clamp: false };
+        assert_eq!(policy.resolve(5000.0), Some(5000.0));
+        assert_eq!(policy.resolve(512.0), None);
+        assert_eq!(policy.resolve(100000.0), None);
+        assert_eq!(policy.resolve(f64::NAN), None);
+
+        let pow2 = SuggestDifficultyPolicy { pow2_clamp: true, ..policy };
+        assert_eq!(pow2.resolve(5000.0), Some(4096.0));
+        assert_eq!(pow2.resolve(1500.0), Some(1024.0));
+        // Rounding down would fall below var_diff_min, so round up instead
+        let pow2_odd_min = SuggestDifficultyPolicy { var_diff_min: 3000.0, ..pow2 };
+        assert_eq!(pow2_odd_min.resolve(3500.0), Some(4096.0));
+
+        let unbounded = SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false };
+        assert_eq!(unbounded.resolve(1e9), Some(1e9));
+        assert_eq!(unbounded.resolve(0.5), None);
+    }
+
+    #[test]
+    fn test_difficulty_ladder() {
+        assert_eq!(clamped_min_share_diff(3000, true), 2048.0);
+        assert_eq!(clamped_min_share_diff(3000, false), 3000.0);
+        assert_eq!(clamped_min_share_diff(0, true), 4.0);
+
+        let policy = SuggestDifficultyPolicy { var_diff_min: 3000.0, var_diff_max: 20000.0, pow2_clamp: true };
+        assert_eq!(policy.ladder(16), Some(vec![4096.0, 8192.0, 16384.0]), "bounds rounded inwards");
+        assert_eq!(policy.retarget_range(), (4096.0, Some(16384.0)));
+        // var_diff_max below var_diff_min leaves var_diff_min as the only rung
+        assert_eq!(SuggestDifficultyPolicy { var_diff_min: 32768.0, ..policy }.ladder(16), Some(vec![32768.0]));
+        // Without var_diff_min vardiff can go all the way down to 1
+        assert_eq!(SuggestDifficultyPolicy { var_diff_min: 0.0, ..policy }.ladder(3), Some(vec![1.0, 2.0, 4.0]));
+
+        let unbounded = SuggestDifficultyPolicy { var_diff_max: 0.0, ..policy };
+        assert_eq!(unbounded.ladder(3), Some(vec![4096.0, 8192.0, 16384.0]));
+        assert_eq!(unbounded.retarget_range(), (4096.0, None));
+        let any = SuggestDifficultyPolicy { pow2_clamp: false, ..policy };
+        assert_eq!(any.ladder(16), None);
+        assert_eq!(any.retarget_range(), (3000.0, Some(20000.0)));
+    }
+
+    #[test]
+    fn test_retarget_bounds() {
+        let bounds = SuggestDifficultyPolicy { var_diff_min: 1024.0, var_diff_max: 65536.0, pow2_clamp: false };
+        assert_eq!(bounds.bound_retarget(131072.0), 65536.0);
+        assert_eq!(bounds.bound_retarget(512.0), 1024.0);
+        assert_eq!(bounds.bound_retarget(5000.0), 5000.0);
+
+        let pow2 = SuggestDifficultyPolicy { var_diff_min: 3000.0, var_diff_max: 50000.0, pow2_clamp: true };
+        assert_eq!(pow2.bound_retarget(65536.0), 32768.0, "max rounded down to stay a power of two");
+        assert_eq!(pow2.bound_retarget(2048.0), 4096.0, "min rounded up");
+
+        let unbounded = SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: true };
+        assert_eq!(unbounded.bound_retarget(1e12), 1e12);
+
+        // A climbing worker stops at var_diff_max however fast it submits
+        let mut diff = 4096.0;
+        for _ in 0..10 {
+            diff = bounds.bound_retarget(vardiff_compute_next_diff(diff, 400.0, 60.0, 20.0, false).unwrap());
+        }
+        assert_eq!(diff, 65536.0);
+    }
+
+    #[test]
+    fn test_start_diff_policy() {
+        let policy = SuggestDifficultyPolicy { var_diff_min: 0.0, var_diff_max: 0.0, pow2_clamp: false };
+        // 0 keeps the old behaviour of starting at min_share_diff
+        assert_eq!(policy.start_diff(0.0, 4096.0), 4096.0);
+        assert_eq!(policy.start_diff(20000.0, 4096.0), 20000.0);
+        assert_eq!(policy.start_diff(1000.0, 4096.0), 4096.0);
+
+        let bounded = SuggestDifficultyPolicy { var_diff_min: 8192.0, var_diff_max: 16384.0, pow2_clamp: false };
+        assert_eq!(bounded.start_diff(20000.0, 4096.0), 16384.0);
+        assert_eq!(bounded.start_diff(5000.0, 4096.0), 8192.0);
+
+        let pow2 = SuggestDifficultyPolicy { pow2_clamp: true, ..policy };
+        assert_eq!(pow2.start_diff(20000.0, 4096.0), 16384.0);
+    }
+
+    #[test]
+    fn test_vardiff_freeze_policy() {
+        let policy = VarDiffFreezePolicy { tolerance_pct: 10.0, after_checks: 3 };
+        let mut state = VarDiffFreezeState::default();
+
+        assert!(!policy.observe(&mut state, Some(21.0), 20.0));
+        assert!(!policy.observe(&mut state, Some(19.0), 20.0));
+        // Missing measurements neither count nor break the streak
+        assert!(!policy.observe(&mut state, None, 20.0));
+        assert!(policy.observe(&mut state, Some(20.5), 20.0));
+        assert!(policy.observe(&mut state, None, 20.0));
+
+        // Drifting out of tolerance unfreezes and restarts the count
+        assert!(!policy.observe(&mut state, Some(25.0), 20.0));
+        assert_eq!(state.stable_checks, 0);
+        assert!(!policy.observe(&mut state, Some(20.0), 20.0));
+
+        let disabled = VarDiffFreezePolicy { tolerance_pct: 0.0, after_checks: 3 };
+        let mut state = VarDiffFreezeState::default();
+        for _ in 0..10 {
+            assert!(!disabled.observe(&mut state, Some(20.0), 20.0));
+        }
+    }
+
+    #[test]
+    fn test_within_hysteresis() {
+        assert!(within_hysteresis(4096.0, 4300.0, 10.0));
+        assert!(within_hysteresis(4096.0, 3800.0, 10.0));
+        assert!(!within_hysteresis(4096.0, 8192.0, 10.0));
+        assert!(!within_hysteresis(4096.0, 3000.0, 10.0));
+        assert!(!within_hysteresis(4096.0, 4100.0, 0.0), "0 = every retarget is sent");
+    }
+
+    #[test]
+    fn test_vardiff_warmup() {
+        let now = Instant::now();
+        let mut warmup = VarDiffWarmup::default();
+        assert!(!warmup.holds(now, 5, Duration::from_secs(90)), "connections that never started warming up retarget");
+
+        warmup.start(now);
+        for _ in 0..4 {
+            warmup.record_share();
+        }
+        assert!(warmup.holds(now + Duration::from_secs(10), 5, Duration::from_secs(90)));
+        assert!(!warmup.holds(now + Duration::from_secs(90), 5, Duration::from_secs(90)), "the warmup time caps it");
+        warmup.record_share();
+        assert!(!warmup.holds(now + Duration::from_secs(10), 5, Duration::from_secs(90)));
+        let mut unlimited = VarDiffWarmup::default();
+        unlimited.start(now);
+        assert!(!unlimited.holds(now, 0, Duration::from_secs(90)), "0 shares = no warmup");
+
+        warmup.start(now);
+        assert!(warmup.holds(now, 5, Duration::from_secs(90)), "a new connection starts over");
+    }
+
+    #[test]
+    fn test_vardiff_rate_error_mean() {
+        let mut rate_error = VarDiffRateError::default();
+        assert_eq!(rate_error.mean(), None);
+        assert_eq!(rate_error.observe(30.0, 20.0), 1.5);
+        assert_eq!(rate_error.observe(10.0, 20.0), 0.5);
+        assert_eq!(rate_error.mean(), Some(1.0));
+    }
+
+    #[test]
+    fn test_is_near_miss() {
+        assert!(is_near_miss(2000.0, 10000.0, 0.1));
+        assert!(is_near_miss(1000.0, 10000.0, 0.1));
+        assert!(!is_near_miss(999.0, 10000.0, 0.1));
+        assert!(!is_near_miss(10000.0, 10000.0, 0.1), "a block is not a near miss");
+        assert!(!is_near_miss(5000.0, 10000.0, 0.0), "0 = off");
+    }
+
+    #[test]
+    fn test_weak_share_ratio() {
+        assert_eq!(weak_share_ratio(3072.0, 4096.0), Some(0.75));
+        assert_eq!(weak_share_ratio(4096.0, 4096.0), Some(1.0));
+        assert_eq!(weak_share_ratio(5000.0, 4096.0), Some(1.0), "capped when the target rounds the other way");
+        assert_eq!(weak_share_ratio(3072.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_var_diff_mode_parse() {
+        assert_eq!("window".parse::<VarDiffMode>().unwrap(), VarDiffMode::Window);
+        assert_eq!("EWMA".parse::<VarDiffMode>().unwrap(), VarDiffMode::Ewma { alpha: DEFAULT_EWMA_ALPHA });
+        assert!("sliding".parse::<VarDiffMode>().is_err());
+    }
+
+    /// Drive the EWMA retarget with a miner of fixed hashrate and return the difficulty after `secs`
+    fn simulate_ewma_vardiff(hashrate: f64, start_diff: f64, secs: f64) -> (f64, usize) {
+        let base = Instant::now();
+        let at = |t: f64| base + Duration::from_secs_f64(t);
+        let mut ewma = ShareIntervalEwma::default();
+        let mut diff = start_diff;
+        let mut retargets = 0;
+        let mut window_start = 0.0;
+        let mut window_shares = 0.0;
+        let mut next_share = 0.0;
+        let mut next_tick = VAR_DIFF_THREAD_SLEEP as f64;
+
+        while next_tick <= secs {
+            if next_share <= next_tick {
+                ewma.record_share(at(next_share), DEFAULT_EWMA_ALPHA);
+                window_shares += 1.0;
+                next_share += diff / hashrate;
+                continue;
+            }
+
+            if let Some(next) =
+                vardiff_compute_next_diff_ewma(diff, &ewma, DEFAULT_EWMA_ALPHA, window_shares, next_tick - window_start, 20.0, false)
+            {
+                ewma.rescale(next / diff);
+                diff = next;
+                retargets += 1;
+                window_start = next_tick;
+                window_shares = 0.0;
+            }
+            next_tick += VAR_DIFF_THREAD_SLEEP as f64;
+        }
+        (diff, retargets)
+    }
+
+    #[test]
+    fn test_ewma_vardiff_converges() {
+        // 1000 diff/s at 20 shares/min settles around diff 3000
+        let (diff, retargets) = simulate_ewma_vardiff(1000.0, 64.0, 1800.0);
+        let spm = 60.0 * 1000.0 / diff;
+        assert!(spm > 20.0 * VARDIFF_LOWER_RATIO && spm < 20.0 * VARDIFF_UPPER_RATIO, "settled at diff {} ({} spm)", diff, spm);
+
+        // Once settled it stays put: running longer doesn't add retargets
+        let (_, more_retargets) = simulate_ewma_vardiff(1000.0, 64.0, 3600.0);
+        assert_eq!(retargets, more_retargets);
+    }
+
+    #[test]
+    fn test_ewma_alpha_sets_samples_needed() {
+        // Six shares a second apart: three times the 20 spm target
+        let base = Instant::now();
+        let mut ewma = ShareIntervalEwma::default();
+        for i in 0..6 {
+            ewma.record_share(base + Duration::from_secs(i), 0.5);
+        }
+        assert_eq!(ewma.samples, 5);
+        assert!(vardiff_compute_next_diff_ewma(1024.0, &ewma, 0.5, 6.0, 120.0, 20.0, false).is_some());
+        assert_eq!(vardiff_compute_next_diff_ewma(1024.0, &ewma, 0.1, 6.0, 120.0, 20.0, false), None, "alpha 0.1 wants 10 intervals");
+        assert_eq!(ewma_min_samples(1.0), VARDIFF_MIN_SHARES);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::log_compress::{spawn_rolled_over_compression, LogCompression};
+use crate::prom::record_share_log_dropped;
+use serde_json::json;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// Accepted shares waiting for the writer; beyond this new shares are dropped from the log, never queued
+const SHARE_LOG_QUEUE_LEN: usize = 16384;
+
+const CSV_HEADER: &str = "timestamp,wallet,worker,difficulty,job_id";
+
+static SHARE_LOG_QUEUE: OnceLock<mpsc::Sender<ShareRecord>> = OnceLock::new();
+
+/// Line format of the share log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShareLogFormat {
+    /// One JSON object per line (default)
+    Jsonl,
+    /// Comma-separated with a header row
+    Csv,
+}
+
+impl ShareLogFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ShareLogFormat::Jsonl => "jsonl",
+            ShareLogFormat::Csv => "csv",
+        }
+    }
+}
+
+impl std::str::FromStr for ShareLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "jsonl" | "json" => Ok(ShareLogFormat::Jsonl),
+            "csv" => Ok(ShareLogFormat::Csv),
+            other => Err(format!("unknown share_log_format '{}', expected jsonl or csv", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ShareLogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// One accepted share
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareRecord {
+    pub timestamp_ms: u64,
+    pub wallet: String,
+    pub worker: String,
+    pub difficulty: f64,
+    pub job_id: u64,
+}
+
+impl ShareRecord {
+    /// The record as one line, without the newline
+    pub fn to_line(&self, format: ShareLogFormat) -> String {
+        match format {
+            ShareLogFormat::Jsonl => json!({
+                "timestamp": self.timestamp_ms,
+                "wallet": self.wallet,
+                "worker": self.worker,
+                "difficulty": self.difficulty,
+                "job_id": self.job_id,
+            })
+            .to_string(),
+            ShareLogFormat::Csv => format!(
+                "{},{},{},{},{}",
+                self.timestamp_ms,
+                csv_field(&self.wallet),
+                csv_field(&self.worker),
+                self.difficulty,
+                self.job_id
+            ),
+        }
+    }
+}
+
+/// Quote a field that would otherwise break the row (worker names are miner-chosen)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The file this run writes for share_log_path: like the main log, a new `<stem>_<unix secs>.<ext>`
+/// per start, so "shares.csv" becomes "shares_1700000000.csv"
+pub fn share_log_file(configured: &Path, format: ShareLogFormat, start_secs: u64) -> PathBuf {
+    let stem = configured.file_stem().and_then(|s| s.to_str()).unwrap_or("shares");
+    let extension = configured.extension().and_then(|e| e.to_str()).unwrap_or(format.extension());
+    configured.with_file_name(format!("{}_{}.{}", stem, start_secs, extension))
+}
+
+/// Open this run's share log and start the writer thread. Share logs of earlier runs are compressed in
+/// the background like rolled-over logs, skipping any another bridge is still writing to (this run's
+/// file is locked for the same reason). Returns the file being written
+pub fn start_share_log(configured: &Path, format: ShareLogFormat, compression: LogCompression) -> io::Result<PathBuf> {
+    let start_secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = share_log_file(configured, format, start_secs);
+    let mut writer = BufWriter::new(open_locked(&path)?);
+    if format == ShareLogFormat::Csv {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        writer.flush()?;
+    }
+
+    let (tx, mut rx) = mpsc::channel::<ShareRecord>(SHARE_LOG_QUEUE_LEN);
+    if SHARE_LOG_QUEUE.set(tx).is_err() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "share log already started"));
+    }
+    let active = path.clone();
+    std::thread::Builder::new().name("share-log".to_string()).spawn(move || {
+        // Flush whenever the queue runs dry, so the file is current without a write per share
+        while let Some(record) = rx.blocking_recv() {
+            let mut result = writeln!(writer, "{}", record.to_line(format));
+            while let Ok(record) = rx.try_recv() {
+                result = result.and_then(|_| writeln!(writer, "{}", record.to_line(format)));
+            }
+            if let Err(e) = result.and_then(|_| writer.flush()) {
+                tracing::warn!("share log: could not write {}: {}", active.display(), e);
+            }
+        }
+    })?;
+
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+    let stem = configured.file_stem().and_then(|s| s.to_str()).unwrap_or("shares");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or(format.extension());
+    spawn_rolled_over_compression(dir, path.clone(), format!("{}_", stem), extension.to_string(), compression);
+    Ok(path)
+}
+
+/// Create `path` and lock it for as long as the writer holds it, so another bridge starting in the same
+/// directory sees it in use. A file already locked is another bridge's and is refused, not truncated
+fn open_locked(path: &Path) -> io::Result<File> {
+    let file = File::options().write(true).create(true).truncate(false).open(path)?;
+    #[cfg(unix)]
+    file.try_lock().map_err(|e| match e {
+        std::fs::TryLockError::WouldBlock => {
+            io::Error::new(io::ErrorKind::AddrInUse, format!("{} is being written by another bridge", path.display()))
+        }
+        std::fs::TryLockError::Error(e) => e,
+    })?;
+    file.set_len(0)?;
+    Ok(file)
+}
+
+/// Queue an accepted share for the share log. Never waits: with the queue full the share is left out of
+/// the log (and counted in ks_share_log_dropped_counter)
+pub fn log_share(wallet: &str, worker: &str, difficulty: f64, job_id: u64) {
+    let Some(queue) = SHARE_LOG_QUEUE.get() else {
+        return;
+    };
+    let record = ShareRecord {
+        timestamp_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+        wallet: wallet.to_string(),
+        worker: worker.to_string(),
+        difficulty,
+        job_id,
+    };
+    if queue.try_send(record).is_err() {
+        record_share_log_dropped();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_record_lines() {
+        let record = ShareRecord {
+            timestamp_ms: 1700000000123,
+            wallet: "kaspa:qz".to_string(),
+            worker: "rig,1".to_string(),
+            difficulty: 4096.0,
+            job_id: 7,
+        };
+        assert_eq!(record.to_line(ShareLogFormat::Csv), "1700000000123,kaspa:qz,\"rig,1\",4096,7");
+        let json: serde_json::Value = serde_json::from_str(&record.to_line(ShareLogFormat::Jsonl)).unwrap();
+        assert_eq!(json["worker"], "rig,1");
+        assert_eq!(json["difficulty"], 4096.0);
+        assert_eq!(json["job_id"], 7);
+    }
+
+    #[test]
+    fn test_share_log_file_per_start() {
+        assert_eq!(share_log_file(Path::new("logs/shares.csv"), ShareLogFormat::Csv, 100), PathBuf::from("logs/shares_100.csv"));
+        assert_eq!(share_log_file(Path::new("shares"), ShareLogFormat::Jsonl, 100), PathBuf::from("shares_100.jsonl"));
+        assert!("xml".parse::<ShareLogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_share_log_series() {
+        let dir = std::env::temp_dir().join(format!("ks_share_log_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let active = dir.join("shares_300.jsonl");
+        for name in ["shares_100.jsonl", "shares_200.jsonl", "shares_backup.jsonl", "shares_2024_old.jsonl", "shares_.jsonl"] {
+            std::fs::write(dir.join(name), "{}\n").unwrap();
+        }
+
+        let held = open_locked(&active).unwrap();
+        let series = crate::log_compress::rolled_over_files(&dir, &active, "shares_", "jsonl").unwrap();
+        assert_eq!(series, vec![dir.join("shares_100.jsonl"), dir.join("shares_200.jsonl")], "only <stem>_<secs>.<ext>");
+        #[cfg(unix)]
+        {
+            assert_eq!(open_locked(&active).unwrap_err().kind(), io::ErrorKind::AddrInUse, "another bridge's file");
+            assert!(crate::log_compress::is_in_use(&active, std::time::Duration::ZERO));
+        }
+        drop(held);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{client_handler::connected_clients, prom::record_solo_fallback_active, share_handler::KaspaApiTrait};
+use kaspa_consensus_core::block::Block;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Nonces hashed between pauses; small enough that a batch never holds a runtime thread for long
+const NONCES_PER_BATCH: u64 = 1024;
+
+/// Pause after each batch, keeps the fallback at a trickle of CPU
+const BATCH_PAUSE: Duration = Duration::from_millis(50);
+
+/// How often an idle fallback checks whether miners are gone
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Remote app reported to kaspad for fallback templates
+const SOLO_FALLBACK_APP: &str = "solo-fallback";
+
+/// Mine to `wallet` on one task at low effort whenever no stratum miner is connected to any instance,
+/// purely to keep the node exercised. Work stops within one batch once a miner connects
+pub async fn run_solo_fallback<T: KaspaApiTrait + Send + Sync + 'static>(
+    kaspa_api: Arc<T>,
+    wallet: String,
+    template_refresh: Duration,
+) {
+    solo_fallback_loop(kaspa_api, wallet, template_refresh, connected_clients).await
+}
+
+/// run_solo_fallback with the stratum miner count taken from `miners`
+async fn solo_fallback_loop<T: KaspaApiTrait + Send + Sync + 'static>(
+    kaspa_api: Arc<T>,
+    wallet: String,
+    template_refresh: Duration,
+    miners: impl Fn() -> usize,
+) {
+    let mut nonce = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+    let mut active = false;
+    loop {
+        if miners() > 0 {
+            if active {
+                tracing::info!("[SOLO] miner connected, solo fallback stopped");
+                active = false;
+                record_solo_fallback_active(false);
+            }
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            continue;
+        }
+
+        let block = match kaspa_api.get_block_template(&wallet, SOLO_FALLBACK_APP, "").await {
+            Ok(block) => block,
+            Err(e) => {
+                tracing::warn!("[SOLO] failed fetching block template: {}", e);
+                tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+                continue;
+            }
+        };
+        if !active {
+            tracing::info!("[SOLO] no miners connected, solo fallback mining to {}", wallet);
+            active = true;
+            record_solo_fallback_active(true);
+        }
+
+        if let Some(found) = mine_template(&block, &mut nonce, template_refresh, &miners).await {
+            tracing::info!("[SOLO] solo fallback found a block (nonce {:x}), submitting", found.header.nonce);
+            if let Err(e) = kaspa_api.submit_block(found).await {
+                tracing::warn!("[SOLO] block submit failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Hash `block` in small batches until a nonce meets the network target, the template is older than
+/// `template_refresh`, or a stratum miner connects
+async fn mine_template(block: &Block, nonce: &mut u64, template_refresh: Duration, miners: impl Fn() -> usize) -> Option<Block> {
+    let pow_state = kaspa_pow::State::new(&block.header);
+    let started = Instant::now();
+    while started.elapsed() < template_refresh && miners() == 0 {
+        for _ in 0..NONCES_PER_BATCH {
+            *nonce = nonce.wrapping_add(1);
+            let (passed, _) = pow_state.check_pow(*nonce);
+            if passed {
+                let mut header = (*block.header).clone();
+                header.nonce = *nonce;
+                return Some(Block::from_arcs(Arc::new(header), Arc::clone(&block.transactions)));
+            }
+        }
+        tokio::time::sleep(BATCH_PAUSE).await;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kaspa_consensus_core::header::Header;
+    use kaspa_hashes::Hash;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Hands out templates no nonce can solve (zero target) and counts them
+    #[derive(Default)]
+    struct TemplateCounter {
+        fetched: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl KaspaApiTrait for TemplateCounter {
+        async fn get_block_template(&self, _: &str, _: &str, _: &str) -> Result<Block, Box<dyn std::error::Error + Send + Sync>> {
+            self.fetched.fetch_add(1, Ordering::Relaxed);
+            Ok(Block::new(Header::from_precomputed_hash(Hash::default(), vec![]), vec![]))
+        }
+
+        async fn submit_block(
+            &self,
+            _: Block,
+        ) -> Result<kaspa_rpc_core::SubmitBlockResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Err("no block can be found in tests".into())
+        }
+
+        async fn get_balances_by_addresses(
+            &self,
+            _: &[String],
+        ) -> Result<Vec<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_mines_only_while_no_miners_are_connected() {
+        let api = Arc::new(TemplateCounter::default());
+        let miners = Arc::new(AtomicUsize::new(1));
+        let fallback = tokio::spawn(solo_fallback_loop(Arc::clone(&api), "kaspa:qz".to_string(), Duration::from_millis(50), {
+            let miners = Arc::clone(&miners);
+            move || miners.load(Ordering::Relaxed)
+        }));
+        let fetched = || api.fetched.load(Ordering::Relaxed);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(fetched(), 0, "idle while a miner is connected");
+
+        // Failover: the last miner leaves, templates are fetched and mined within one idle check
+        miners.store(0, Ordering::Relaxed);
+        tokio::time::sleep(IDLE_CHECK_INTERVAL + Duration::from_millis(500)).await;
+        assert!(fetched() >= 2, "mining fresh templates, fetched {}", fetched());
+
+        // Failback: a miner connects, mining stops within one batch and no more templates are fetched
+        miners.store(1, Ordering::Relaxed);
+        tokio::time::sleep(BATCH_PAUSE * 4).await;
+        let stopped_at = fetched();
+        tokio::time::sleep(IDLE_CHECK_INTERVAL + Duration::from_millis(500)).await;
+        assert_eq!(fetched(), stopped_at, "back to the pool");
+
+        fallback.abort();
+    }
+}
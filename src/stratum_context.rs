@@ -1,19 +1,93 @@
 use crate::jsonrpc_event::{JsonRpcEvent, JsonRpcResponse};
 use crate::log_colors::LogColors;
+use crate::log_sampler::{log_sampled, log_sampled_method, LogCategory};
 use hex;
 use parking_lot::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc;
 
+/// A miner connection: a TCP stream, or a Unix socket stream for a `unix:` stratum_port
+pub trait ClientStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> ClientStream for T {}
+
+pub type ClientReadHalf = tokio::io::ReadHalf<Box<dyn ClientStream>>;
+type ClientWriteHalf = tokio::io::WriteHalf<Box<dyn ClientStream>>;
+
 /// Error for disconnected clients
 #[derive(Debug, thiserror::Error)]
 #[error("disconnecting")]
 pub struct ErrorDisconnected;
 
+/// Why a connection was closed, logged and counted in ks_disconnect_reason_counter when it goes away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The miner closed or reset the connection
+    ClientClosed,
+    /// No answer to keepalive pings
+    IdleTimeout,
+    /// No wallet within authorize_timeout_secs (or a job was due without one)
+    AuthTimeout,
+    /// The wallet kaspad was asked to mine to could not be decoded
+    BadWallet,
+    /// Disconnected through the admin socket
+    Kicked,
+    /// Sent something that is not stratum, or a line over max_line_bytes
+    ProtocolError,
+    /// Over max_shares_per_sec with share_flood_action: disconnect
+    ShareFlood,
+    /// No free extranonce prefix for it
+    NoExtranonce,
+    /// Sent client.reconnect to apply a new difficulty
+    Reconnect,
+    /// Refused at accept: max_connections reached
+    MaxConnections,
+    /// Refused at accept or drained in maintenance mode
+    Maintenance,
+    /// The bridge is shutting down
+    ServerShutdown,
+    /// Dropped or refused under offline_policy: disconnect while kaspad is unreachable
+    NodeOffline,
+    /// Its wallet.worker authorized again elsewhere (duplicate_worker_policy reject or kick_old)
+    DuplicateWorker,
+    /// Reading from or writing to the socket failed
+    SocketError,
+    /// Closed without a recorded reason
+    Other,
+}
+
+impl DisconnectReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DisconnectReason::ClientClosed => "client_closed",
+            DisconnectReason::IdleTimeout => "idle_timeout",
+            DisconnectReason::AuthTimeout => "auth_timeout",
+            DisconnectReason::BadWallet => "bad_wallet",
+            DisconnectReason::Kicked => "kicked",
+            DisconnectReason::ProtocolError => "protocol_error",
+            DisconnectReason::ShareFlood => "share_flood",
+            DisconnectReason::NoExtranonce => "no_extranonce",
+            DisconnectReason::Reconnect => "reconnect",
+            DisconnectReason::MaxConnections => "max_connections",
+            DisconnectReason::Maintenance => "maintenance",
+            DisconnectReason::ServerShutdown => "server_shutdown",
+            DisconnectReason::NodeOffline => "node_offline",
+            DisconnectReason::DuplicateWorker => "duplicate_worker",
+            DisconnectReason::SocketError => "socket_error",
+            DisconnectReason::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// Context summary for logging
 #[derive(Debug, Clone)]
 pub struct ContextSummary {
@@ -28,17 +102,23 @@ pub struct ContextSummary {
 pub struct StratumContext {
     pub remote_addr: String,
     pub remote_port: u16,
+    pub metrics_port: String, // "port" label for worker metrics (listener label, else empty unless label_metrics_by_port)
     pub wallet_addr: Arc<Mutex<String>>,
     pub worker_name: Arc<Mutex<String>>,
     pub canxium_addr: Arc<Mutex<String>>,
     pub remote_app: Arc<Mutex<String>>,
     pub id: Arc<Mutex<i32>>,
     pub extranonce: Arc<Mutex<String>>,
+    pub payout_split: Arc<Mutex<Option<crate::default_client::PayoutSplit>>>, // Requested via worker-name extension
+    pub extensions: Arc<Mutex<serde_json::Map<String, serde_json::Value>>>,   // Negotiated via mining.configure
+    extranonce_updates: Arc<AtomicBool>, // Miner opted in to in-band mining.set_extranonce changes
+    last_activity: Arc<Mutex<Instant>>,  // Last time anything was read from the miner
     pub state: Arc<crate::mining_state::MiningState>,
     disconnecting: Arc<AtomicBool>,
+    disconnect_reason: Arc<Mutex<Option<DisconnectReason>>>, // First reason the connection was closed for
     write_lock: Arc<AtomicBool>,
-    read_half: Arc<Mutex<Option<tokio::io::ReadHalf<TcpStream>>>>,
-    write_half: Arc<Mutex<Option<tokio::io::WriteHalf<TcpStream>>>>,
+    read_half: Arc<Mutex<Option<ClientReadHalf>>>,
+    write_half: Arc<Mutex<Option<ClientWriteHalf>>>,
     on_disconnect: mpsc::UnboundedSender<Arc<StratumContext>>,
 }
 
@@ -46,22 +126,29 @@ impl StratumContext {
     pub fn new(
         remote_addr: String,
         remote_port: u16,
-        stream: TcpStream,
+        metrics_port: String,
+        stream: impl ClientStream + 'static,
         state: Arc<crate::mining_state::MiningState>,
         on_disconnect: mpsc::UnboundedSender<Arc<StratumContext>>,
     ) -> Arc<Self> {
-        let (read_half, write_half) = tokio::io::split(stream);
+        let (read_half, write_half) = tokio::io::split(Box::new(stream) as Box<dyn ClientStream>);
         Arc::new(Self {
             remote_addr,
             remote_port,
+            metrics_port,
             wallet_addr: Arc::new(Mutex::new(String::new())),
             worker_name: Arc::new(Mutex::new(String::new())),
             canxium_addr: Arc::new(Mutex::new(String::new())),
             remote_app: Arc::new(Mutex::new(String::new())),
             id: Arc::new(Mutex::new(0)),
             extranonce: Arc::new(Mutex::new(String::new())),
+            payout_split: Arc::new(Mutex::new(None)),
+            extensions: Arc::new(Mutex::new(serde_json::Map::new())),
+            extranonce_updates: Arc::new(AtomicBool::new(false)),
+            last_activity: Arc::new(Mutex::new(Instant::now())),
             state,
             disconnecting: Arc::new(AtomicBool::new(false)),
+            disconnect_reason: Arc::new(Mutex::new(None)),
             write_lock: Arc::new(AtomicBool::new(false)),
             read_half: Arc::new(Mutex::new(Some(read_half))),
             write_half: Arc::new(Mutex::new(Some(write_half))),
@@ -74,6 +161,26 @@ impl StratumContext {
         !self.disconnecting.load(Ordering::Acquire)
     }
 
+    /// Whether the miner sent mining.extranonce.subscribe (or negotiated subscribe-extranonce),
+    /// i.e. its extranonce can be changed without a reconnect
+    pub fn supports_extranonce_updates(&self) -> bool {
+        self.extranonce_updates.load(Ordering::Acquire)
+    }
+
+    pub fn set_extranonce_updates(&self, enabled: bool) {
+        self.extranonce_updates.store(enabled, Ordering::Release);
+    }
+
+    /// Note that the miner sent something, for the keepalive
+    pub fn mark_activity(&self) {
+        *self.last_activity.lock() = Instant::now();
+    }
+
+    /// Time since the miner last sent anything
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.lock().elapsed()
+    }
+
     /// Get client ID
     pub fn id(&self) -> Option<i32> {
         let id = *self.id.lock();
@@ -110,6 +217,11 @@ impl StratumContext {
         self.remote_port
     }
 
+    /// Remote "ip:port", bracketing IPv6 addresses
+    pub fn remote_endpoint(&self) -> String {
+        crate::stratum_listener::format_peer_addr(&self.remote_addr, self.remote_port)
+    }
+
     /// Send a JSON-RPC response
     pub async fn reply(&self, response: JsonRpcResponse) -> Result<(), ErrorDisconnected> {
         if self.disconnecting.load(Ordering::Acquire) {
@@ -119,94 +231,98 @@ impl StratumContext {
         let json = serde_json::to_string(&response).map_err(|_| ErrorDisconnected)?;
         let data = format!("{}\n", json);
 
-        // Get client context for detailed logging
-        let wallet_addr = self.wallet_addr.lock().clone();
-        let worker_name = self.worker_name.lock().clone();
-        let remote_app = self.remote_app.lock().clone();
-
-        // Log outgoing response at DEBUG level (detailed logs moved to debug)
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
-        tracing::debug!("{}", LogColors::bridge_to_asic("===== SENDING RESPONSE TO ASIC ===== "));
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Client Information:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - IP Address:"),
-            format!("{}:{}", self.remote_addr, self.remote_port)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Wallet Address:"),
-            format!("'{}'", wallet_addr)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Worker Name:"),
-            format!("'{}'", worker_name)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Miner Application:"),
-            format!("'{}'", remote_app)
-        );
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Response Details:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Response ID:"),
-            format!("{:?}", response.id)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Response Type:"),
-            "JSON-RPC Response"
-        );
-        if let Some(ref result) = response.result {
-            let result_str = serde_json::to_string(result).unwrap_or_else(|_| "N/A".to_string());
-            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Result:"), result_str);
-            tracing::debug!(
-                "{} {} {}",
-                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-                LogColors::label("  - Result Length:"),
-                format!("{} characters", result_str.len())
+        // Sampled per log_sample_rate, checked before any of the log arguments are built; error replies (rejects)
+        // are always logged
+        if response.error.is_some() || log_sampled(LogCategory::Reply) {
+            // Get client context for detailed logging
+            let wallet_addr = self.wallet_addr.lock().clone();
+            let worker_name = self.worker_name.lock().clone();
+            let remote_app = self.remote_app.lock().clone();
+
+            // Log outgoing response at DEBUG level (detailed logs moved to debug)
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+            tracing::debug!("{}", LogColors::bridge_to_asic("===== SENDING RESPONSE TO ASIC ===== "));
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Client Information:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - IP Address:"),
+                format!("{}:{}", self.remote_addr, self.remote_port)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Wallet Address:"),
+                format!("'{}'", wallet_addr)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Worker Name:"),
+                format!("'{}'", worker_name)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Miner Application:"),
+                format!("'{}'", remote_app)
+            );
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Response Details:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Response ID:"),
+                format!("{:?}", response.id)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Response Type:"),
+                "JSON-RPC Response"
+            );
+            if let Some(ref result) = response.result {
+                let result_str = serde_json::to_string(result).unwrap_or_else(|_| "N/A".to_string());
+                tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Result:"), result_str);
+                tracing::debug!(
+                    "{} {} {}",
+                    LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                    LogColors::label("  - Result Length:"),
+                    format!("{} characters", result_str.len())
+                );
+            }
+            if let Some(ref error) = response.error {
+                let error_str = serde_json::to_string(error).unwrap_or_else(|_| "N/A".to_string());
+                tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::error("  - Error:"), error_str);
+                tracing::debug!(
+                    "{} {} {}",
+                    LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                    LogColors::label("  - Error Length:"),
+                    format!("{} characters", error_str.len())
+                );
+            }
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Message Data:"));
+            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Raw JSON:"), json);
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - JSON Length:"),
+                format!("{} characters", json.len())
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Total Bytes (with newline):"),
+                format!("{} bytes", data.len())
             );
-        }
-        if let Some(ref error) = response.error {
-            let error_str = serde_json::to_string(error).unwrap_or_else(|_| "N/A".to_string());
-            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::error("  - Error:"), error_str);
             tracing::debug!(
                 "{} {} {}",
                 LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-                LogColors::label("  - Error Length:"),
-                format!("{} characters", error_str.len())
+                LogColors::label("  - Raw Bytes (hex):"),
+                hex::encode(data.as_bytes())
             );
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
         }
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Message Data:"));
-        tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Raw JSON:"), json);
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - JSON Length:"),
-            format!("{} characters", json.len())
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Total Bytes (with newline):"),
-            format!("{} bytes", data.len())
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Raw Bytes (hex):"),
-            hex::encode(data.as_bytes())
-        );
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
 
         self.write_data(data.as_bytes()).await?;
         Ok(())
@@ -221,124 +337,127 @@ impl StratumContext {
         let json = serde_json::to_string(&event).map_err(|_| ErrorDisconnected)?;
         let data = format!("{}\n", json);
 
-        // Get client context for detailed logging
-        let wallet_addr = self.wallet_addr.lock().clone();
-        let worker_name = self.worker_name.lock().clone();
-        let remote_app = self.remote_app.lock().clone();
-        let params_str = serde_json::to_string(&event.params).unwrap_or_else(|_| "[]".to_string());
-
-        // Log outgoing event at DEBUG level (detailed logs moved to debug)
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
-        tracing::debug!("{}", LogColors::bridge_to_asic("===== SENDING EVENT TO ASIC ===== "));
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Client Information:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - IP Address:"),
-            format!("{}:{}", self.remote_addr, self.remote_port)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Wallet Address:"),
-            format!("'{}'", wallet_addr)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Worker Name:"),
-            format!("'{}'", worker_name)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Miner Application:"),
-            format!("'{}'", remote_app)
-        );
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Event Details:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Method:"),
-            format!("'{}'", event.method)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Event ID:"),
-            format!("{:?}", event.id)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - JSON-RPC Version:"),
-            format!("'{}'", event.jsonrpc)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Format:"),
-            "Standard JSON-RPC (with jsonrpc field)"
-        );
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Parameters:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Params Count:"),
-            event.params.len()
-        );
-        tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Params JSON:"), params_str);
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Params Length:"),
-            format!("{} characters", params_str.len())
-        );
-        // Log each param individually
-        for (idx, param) in event.params.iter().enumerate() {
-            let param_str = serde_json::to_string(param).unwrap_or_else(|_| "N/A".to_string());
-            let param_type = if param.is_string() {
-                "String".to_string()
-            } else if param.is_number() {
-                "Number".to_string()
-            } else if param.is_array() {
-                "Array".to_string()
-            } else if param.is_object() {
-                "Object".to_string()
-            } else if param.is_boolean() {
-                "Boolean".to_string()
-            } else {
-                "Null".to_string()
-            };
+        // Sampled per log_sample_rate; checked before any of the log arguments are built
+        if log_sampled_method(&event.method) {
+            // Get client context for detailed logging
+            let wallet_addr = self.wallet_addr.lock().clone();
+            let worker_name = self.worker_name.lock().clone();
+            let remote_app = self.remote_app.lock().clone();
+            let params_str = serde_json::to_string(&event.params).unwrap_or_else(|_| "[]".to_string());
+
+            // Log outgoing event at DEBUG level (detailed logs moved to debug)
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+            tracing::debug!("{}", LogColors::bridge_to_asic("===== SENDING EVENT TO ASIC ===== "));
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Client Information:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - IP Address:"),
+                format!("{}:{}", self.remote_addr, self.remote_port)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Wallet Address:"),
+                format!("'{}'", wallet_addr)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Worker Name:"),
+                format!("'{}'", worker_name)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Miner Application:"),
+                format!("'{}'", remote_app)
+            );
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Event Details:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Method:"),
+                format!("'{}'", event.method)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Event ID:"),
+                format!("{:?}", event.id)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - JSON-RPC Version:"),
+                format!("'{}'", event.jsonrpc)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Format:"),
+                "Standard JSON-RPC (with jsonrpc field)"
+            );
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Parameters:"));
             tracing::debug!(
                 "{} {} {}",
                 LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-                LogColors::label(&format!("  - Param[{}]:", idx)),
-                format!("{} (type: {})", param_str, param_type)
+                LogColors::label("  - Params Count:"),
+                event.params.len()
             );
+            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Params JSON:"), params_str);
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Params Length:"),
+                format!("{} characters", params_str.len())
+            );
+            // Log each param individually
+            for (idx, param) in event.params.iter().enumerate() {
+                let param_str = serde_json::to_string(param).unwrap_or_else(|_| "N/A".to_string());
+                let param_type = if param.is_string() {
+                    "String".to_string()
+                } else if param.is_number() {
+                    "Number".to_string()
+                } else if param.is_array() {
+                    "Array".to_string()
+                } else if param.is_object() {
+                    "Object".to_string()
+                } else if param.is_boolean() {
+                    "Boolean".to_string()
+                } else {
+                    "Null".to_string()
+                };
+                tracing::debug!(
+                    "{} {} {}",
+                    LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                    LogColors::label(&format!("  - Param[{}]:", idx)),
+                    format!("{} (type: {})", param_str, param_type)
+                );
+            }
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Message Data:"));
+            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Raw JSON:"), json);
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - JSON Length:"),
+                format!("{} characters", json.len())
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Total Bytes (with newline):"),
+                format!("{} bytes", data.len())
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Raw Bytes (hex):"),
+                hex::encode(data.as_bytes())
+            );
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
         }
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Message Data:"));
-        tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Raw JSON:"), json);
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - JSON Length:"),
-            format!("{} characters", json.len())
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Total Bytes (with newline):"),
-            format!("{} bytes", data.len())
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Raw Bytes (hex):"),
-            hex::encode(data.as_bytes())
-        );
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
 
         self.write_data(data.as_bytes()).await?;
         Ok(())
@@ -360,113 +479,149 @@ impl StratumContext {
         let json = serde_json::to_string(&notification).map_err(|_| ErrorDisconnected)?;
         let data = format!("{}\n", json);
 
-        // Get client context for detailed logging
-        let wallet_addr = self.wallet_addr.lock().clone();
-        let worker_name = self.worker_name.lock().clone();
-        let remote_app = self.remote_app.lock().clone();
-        let params_str = serde_json::to_string(&params).unwrap_or_else(|_| "[]".to_string());
-
-        // Log outgoing notification at DEBUG level (detailed logs moved to debug)
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
-        tracing::debug!("{}", LogColors::bridge_to_asic("===== SENDING NOTIFICATION TO ASIC ===== "));
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Client Information:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - IP Address:"),
-            format!("{}:{}", self.remote_addr, self.remote_port)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Wallet Address:"),
-            format!("'{}'", wallet_addr)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Worker Name:"),
-            format!("'{}'", worker_name)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Miner Application:"),
-            format!("'{}'", remote_app)
-        );
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Notification Details:"));
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Method:"),
-            format!("'{}'", method)
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Format:"),
-            "Minimal Stratum (no id/jsonrpc fields)"
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Target:"),
-            "IceRiver/BzMiner compatible"
-        );
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Parameters:"));
-        tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Params Count:"), params.len());
-        tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Params JSON:"), params_str);
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Params Length:"),
-            format!("{} characters", params_str.len())
-        );
-        // Log each param individually
-        for (idx, param) in params.iter().enumerate() {
-            let param_str = serde_json::to_string(param).unwrap_or_else(|_| "N/A".to_string());
-            let param_type = if param.is_string() {
-                format!("String (length: {})", param.as_str().map(|s| s.len()).unwrap_or(0))
-            } else if param.is_number() {
-                "Number".to_string()
-            } else if param.is_array() {
-                format!("Array (length: {})", param.as_array().map(|a| a.len()).unwrap_or(0))
-            } else if param.is_object() {
-                "Object".to_string()
-            } else if param.is_boolean() {
-                "Boolean".to_string()
-            } else {
-                "Null".to_string()
-            };
+        // Sampled per log_sample_rate; checked before any of the log arguments are built
+        if log_sampled_method(method) {
+            // Get client context for detailed logging
+            let wallet_addr = self.wallet_addr.lock().clone();
+            let worker_name = self.worker_name.lock().clone();
+            let remote_app = self.remote_app.lock().clone();
+            let params_str = serde_json::to_string(&params).unwrap_or_else(|_| "[]".to_string());
+
+            // Log outgoing notification at DEBUG level (detailed logs moved to debug)
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+            tracing::debug!("{}", LogColors::bridge_to_asic("===== SENDING NOTIFICATION TO ASIC ===== "));
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Client Information:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - IP Address:"),
+                format!("{}:{}", self.remote_addr, self.remote_port)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Wallet Address:"),
+                format!("'{}'", wallet_addr)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Worker Name:"),
+                format!("'{}'", worker_name)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Miner Application:"),
+                format!("'{}'", remote_app)
+            );
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Notification Details:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Method:"),
+                format!("'{}'", method)
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Format:"),
+                "Minimal Stratum (no id/jsonrpc fields)"
+            );
             tracing::debug!(
                 "{} {} {}",
                 LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-                LogColors::label(&format!("  - Param[{}]:", idx)),
-                format!("{} (type: {})", param_str, param_type)
+                LogColors::label("  - Target:"),
+                "IceRiver/BzMiner compatible"
+            );
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Parameters:"));
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Params Count:"),
+                params.len()
+            );
+            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Params JSON:"), params_str);
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Params Length:"),
+                format!("{} characters", params_str.len())
+            );
+            // Log each param individually
+            for (idx, param) in params.iter().enumerate() {
+                let param_str = serde_json::to_string(param).unwrap_or_else(|_| "N/A".to_string());
+                let param_type = if param.is_string() {
+                    format!("String (length: {})", param.as_str().map(|s| s.len()).unwrap_or(0))
+                } else if param.is_number() {
+                    "Number".to_string()
+                } else if param.is_array() {
+                    format!("Array (length: {})", param.as_array().map(|a| a.len()).unwrap_or(0))
+                } else if param.is_object() {
+                    "Object".to_string()
+                } else if param.is_boolean() {
+                    "Boolean".to_string()
+                } else {
+                    "Null".to_string()
+                };
+                tracing::debug!(
+                    "{} {} {}",
+                    LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                    LogColors::label(&format!("  - Param[{}]:", idx)),
+                    format!("{} (type: {})", param_str, param_type)
+                );
+            }
+            tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Message Data:"));
+            tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Raw JSON:"), json);
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - JSON Length:"),
+                format!("{} characters", json.len())
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Total Bytes (with newline):"),
+                format!("{} bytes", data.len())
+            );
+            tracing::debug!(
+                "{} {} {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label("  - Raw Bytes (hex):"),
+                hex::encode(data.as_bytes())
+            );
+            tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
+        }
+
+        self.write_data(data.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Send an already serialized line (without its trailing newline), e.g. a mining.notify shared by a broadcast
+    pub async fn send_raw(&self, method: &str, line: &str) -> Result<(), ErrorDisconnected> {
+        if self.disconnecting.load(Ordering::Acquire) {
+            return Err(ErrorDisconnected);
+        }
+
+        let data = format!("{}\n", line);
+
+        // Sampled per log_sample_rate; checked before any of the log arguments are built
+        if log_sampled_method(method) {
+            let wallet_addr = self.wallet_addr.lock().clone();
+            let worker_name = self.worker_name.lock().clone();
+            tracing::debug!(
+                "{} {} to {}:{} ('{}' / '{}'): {}",
+                LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
+                LogColors::label(method),
+                self.remote_addr,
+                self.remote_port,
+                wallet_addr,
+                worker_name,
+                line
             );
         }
-        tracing::debug!("{} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("Message Data:"));
-        tracing::debug!("{} {} {}", LogColors::bridge_to_asic("[BRIDGE->ASIC]"), LogColors::label("  - Raw JSON:"), json);
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - JSON Length:"),
-            format!("{} characters", json.len())
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Total Bytes (with newline):"),
-            format!("{} bytes", data.len())
-        );
-        tracing::debug!(
-            "{} {} {}",
-            LogColors::bridge_to_asic("[BRIDGE->ASIC]"),
-            LogColors::label("  - Raw Bytes (hex):"),
-            hex::encode(data.as_bytes())
-        );
-        tracing::debug!("{}", LogColors::bridge_to_asic("========================================"));
 
         self.write_data(data.as_bytes()).await?;
         Ok(())
@@ -540,6 +695,12 @@ impl StratumContext {
         self.reply(JsonRpcResponse::error(id, 21, "Job not found", None)).await
     }
 
+    /// Reply to a submit for a job this connection was never sent
+    pub async fn reply_unknown_job(&self, id: Option<Value>) -> Result<(), ErrorDisconnected> {
+        tracing::debug!("[BRIDGE->ASIC] Preparing UNKNOWN JOB response (Error Code: 21, Job not found)");
+        self.reply(JsonRpcResponse::error(id, 21, "Job not found, no job with this id was sent yet", None)).await
+    }
+
     /// Reply with duplicate share error
     pub async fn reply_dupe_share(&self, id: Option<Value>) -> Result<(), ErrorDisconnected> {
         tracing::debug!("[BRIDGE->ASIC] Preparing DUPLICATE SHARE response (Error Code: 22, Duplicate share submitted)");
@@ -552,6 +713,12 @@ impl StratumContext {
         self.reply(JsonRpcResponse::error(id, 20, "Unknown problem", None)).await
     }
 
+    /// Reply to a line that is not valid JSON-RPC (standard JSON-RPC parse error code)
+    pub async fn reply_parse_error(&self, id: Option<Value>) -> Result<(), ErrorDisconnected> {
+        tracing::debug!("[BRIDGE->ASIC] Preparing PARSE ERROR response (Error Code: -32700, Parse error)");
+        self.reply(JsonRpcResponse::error(Some(id.unwrap_or(Value::Null)), -32700, "Parse error", None)).await
+    }
+
     /// Reply with low difficulty share error
     pub async fn reply_low_diff_share(&self, id: &serde_json::Value) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         tracing::debug!("[BRIDGE->ASIC] Preparing LOW DIFFICULTY SHARE response (Error Code: 23, Invalid difficulty)");
@@ -594,8 +761,20 @@ impl StratumContext {
         }
     }
 
+    /// Disconnect the client for `reason`; a reason recorded earlier is kept
+    pub fn disconnect_for(&self, reason: DisconnectReason) {
+        self.disconnect_reason.lock().get_or_insert(reason);
+        self.disconnect();
+    }
+
+    /// Why the connection was closed, once it has been
+    pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        *self.disconnect_reason.lock()
+    }
+
     fn check_disconnect(&self) {
         if !self.disconnecting.load(Ordering::Acquire) {
+            self.disconnect_reason.lock().get_or_insert(DisconnectReason::SocketError);
             // Spawn async disconnect
             let ctx = self.clone();
             tokio::spawn(async move {
@@ -605,7 +784,7 @@ impl StratumContext {
     }
 
     /// Get a reference to the read half (for reading)
-    pub fn get_read_half(&self) -> parking_lot::MutexGuard<'_, Option<tokio::io::ReadHalf<TcpStream>>> {
+    pub fn get_read_half(&self) -> parking_lot::MutexGuard<'_, Option<ClientReadHalf>> {
         self.read_half.lock()
     }
 }
@@ -615,14 +794,20 @@ impl Clone for StratumContext {
         Self {
             remote_addr: self.remote_addr.clone(),
             remote_port: self.remote_port,
+            metrics_port: self.metrics_port.clone(),
             wallet_addr: self.wallet_addr.clone(),
             worker_name: self.worker_name.clone(),
             canxium_addr: self.canxium_addr.clone(),
             remote_app: self.remote_app.clone(),
             id: self.id.clone(),
             extranonce: self.extranonce.clone(),
+            payout_split: self.payout_split.clone(),
+            extensions: self.extensions.clone(),
+            extranonce_updates: self.extranonce_updates.clone(),
+            last_activity: self.last_activity.clone(),
             state: self.state.clone(),
             disconnecting: self.disconnecting.clone(),
+            disconnect_reason: self.disconnect_reason.clone(),
             write_lock: self.write_lock.clone(),
             read_half: self.read_half.clone(),
             write_half: self.write_half.clone(),
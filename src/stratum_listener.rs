@@ -1,11 +1,14 @@
-use crate::jsonrpc_event::JsonRpcEvent;
+use crate::jsonrpc_event::{JsonRpcEvent, JsonRpcResponse};
 use crate::log_colors::LogColors;
-use crate::stratum_context::StratumContext;
+use crate::log_sampler::log_sampled_line;
+use crate::stratum_context::{ClientStream, DisconnectReason, StratumContext};
 use hex;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpListener;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 
@@ -20,6 +23,158 @@ pub type EventHandler = Arc<
         + Sync,
 >;
 
+/// Default cap on an unterminated line before the connection is dropped
+pub const DEFAULT_MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// Refused connections answered at once; past this a refused connection is closed without an answer, so
+/// a reconnect storm against a full pool can't pile up tasks each holding a socket for the read timeout
+const MAX_PENDING_REJECTS: usize = 64;
+
+/// Addresses to try for a configured port. ":5555" (or "5555") means all interfaces: a dual-stack
+/// IPv6 socket first, IPv4 only where IPv6 is unavailable. Explicit "0.0.0.0:5555" / "[::]:5555" are used as given
+pub fn listen_addresses(port: &str) -> Vec<String> {
+    let port = port.trim();
+    if let Some(bare) = port.strip_prefix(':').or_else(|| port.parse::<u16>().is_ok().then_some(port)) {
+        vec![format!("[::]:{}", bare), format!("0.0.0.0:{}", bare)]
+    } else {
+        vec![port.to_string()]
+    }
+}
+
+/// The socket path of a `unix:/path/to/sock` stratum_port, None for TCP ports
+pub fn unix_socket_path(port: &str) -> Option<&str> {
+    port.trim().strip_prefix("unix:")
+}
+
+/// Check a listen address is ":port", "port" or "host:port" (IPv6 hosts bracketed) before anything binds
+pub fn validate_listen_address(addr: &str) -> Result<(), String> {
+    let addr = addr.trim();
+    if let Some(path) = unix_socket_path(addr) {
+        if path.is_empty() || path.ends_with('/') {
+            return Err(format!("{:?} has no socket path (expected \"unix:/path/to/sock\")", addr));
+        }
+        return Ok(());
+    }
+    let (host, port) = match addr.rsplit_once(':') {
+        Some((host, port)) => (host, port),
+        None => ("", addr),
+    };
+    match port.parse::<u16>() {
+        Ok(0) | Err(_) => return Err(format!("{:?} has no valid port (expected \":PORT\" or \"HOST:PORT\")", addr)),
+        Ok(_) => {}
+    }
+    if let Some(v6) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        return v6.parse::<std::net::Ipv6Addr>().map(|_| ()).map_err(|_| format!("{:?} has an invalid IPv6 address", addr));
+    }
+    if host.contains(':') {
+        return Err(format!("{:?}: IPv6 addresses must be bracketed, e.g. \"[::1]:{}\"", addr, port));
+    }
+    if host.chars().any(|c| c.is_whitespace() || c == '/') {
+        return Err(format!("{:?} has an invalid host", addr));
+    }
+    Ok(())
+}
+
+/// Listen backlog tokio uses for TcpListener::bind, kept as the default
+pub const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
+
+/// Kernel-level queueing for stratum sockets: the pending-connection backlog of the listener and
+/// SO_SNDBUF/SO_RCVBUF for accepted connections (None = OS default)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SocketTuning {
+    pub listen_backlog: u32,
+    pub send_buffer_size: Option<u32>,
+    pub recv_buffer_size: Option<u32>,
+}
+
+impl Default for SocketTuning {
+    fn default() -> Self {
+        Self { listen_backlog: DEFAULT_LISTEN_BACKLOG, send_buffer_size: None, recv_buffer_size: None }
+    }
+}
+
+impl SocketTuning {
+    /// Buffer sizes on an accepted connection; a failure is logged and the connection kept at OS defaults
+    fn apply_to_stream(&self, stream: &TcpStream) {
+        let socket = socket2::SockRef::from(stream);
+        if let Some(size) = self.send_buffer_size {
+            if let Err(e) = socket.set_send_buffer_size(size as usize) {
+                tracing::debug!("could not set send buffer size {}: {}", size, e);
+            }
+        }
+        if let Some(size) = self.recv_buffer_size {
+            if let Err(e) = socket.set_recv_buffer_size(size as usize) {
+                tracing::debug!("could not set receive buffer size {}: {}", size, e);
+            }
+        }
+    }
+}
+
+/// Bind the first of listen_addresses(port) that succeeds
+pub async fn bind_listener(port: &str) -> std::io::Result<TcpListener> {
+    bind_tuned_listener(port, &SocketTuning::default()).await
+}
+
+/// bind_listener with a listen backlog and buffer sizes; buffers set on the listener carry over to
+/// connections the kernel queues before accept
+pub async fn bind_tuned_listener(port: &str, tuning: &SocketTuning) -> std::io::Result<TcpListener> {
+    let mut last_err = None;
+    for addr in listen_addresses(port) {
+        match bind_one(&addr, tuning).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) => {
+                tracing::debug!("could not bind {}: {}", addr, e);
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("no address for {}", port))))
+}
+
+async fn bind_one(addr: &str, tuning: &SocketTuning) -> std::io::Result<TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let mut last_err = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        let domain = if addr.is_ipv4() { Domain::IPV4 } else { Domain::IPV6 };
+        let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+        // Same as TcpListener::bind: a restart must not wait out TIME_WAIT on the port
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        if let Some(size) = tuning.send_buffer_size {
+            socket.set_send_buffer_size(size as usize)?;
+        }
+        if let Some(size) = tuning.recv_buffer_size {
+            socket.set_recv_buffer_size(size as usize)?;
+        }
+        // Windows and the BSDs make IPv6 sockets IPv6-only by default, which would lock IPv4 miners out of
+        // "[::]:PORT". Where dual-stack is unsupported this fails and the 0.0.0.0 address is tried next
+        let v6_only = if addr.is_ipv6() { socket.set_only_v6(false) } else { Ok(()) };
+        let bound = v6_only
+            .and_then(|_| socket.set_nonblocking(true))
+            .and_then(|_| socket.bind(&addr.into()))
+            .and_then(|_| socket.listen(tuning.listen_backlog.min(i32::MAX as u32) as i32))
+            .and_then(|_| TcpListener::from_std(socket.into()));
+        match bound {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("could not resolve {}", addr))))
+}
+
+/// "ip:port" for logs and metrics, with IPv6 addresses bracketed ("[::1]:5555"). Unix socket
+/// connections read "unix:/path#<connection number>"
+pub fn format_peer_addr(ip: &str, port: u16) -> String {
+    if ip.starts_with("unix:") {
+        format!("{}#{}", ip, port)
+    } else if ip.contains(':') {
+        format!("[{}]:{}", ip, port)
+    } else {
+        format!("{}:{}", ip, port)
+    }
+}
+
 /// Client listener trait
 pub trait StratumClientListener: Send + Sync {
     fn on_connect(&self, ctx: Arc<StratumContext>);
@@ -41,6 +196,57 @@ pub struct StratumListenerConfig {
     pub on_connect: Arc<dyn Fn(Arc<StratumContext>) + Send + Sync>,
     pub on_disconnect: Arc<dyn Fn(Arc<StratumContext>) + Send + Sync>,
     pub port: String,
+    /// Maximum concurrent connections (0 = unlimited), shared so it can be changed on reload
+    pub max_connections: Arc<AtomicUsize>,
+    /// Add the listening port as a "port" label on worker metrics
+    pub label_metrics_by_port: bool,
+    /// Tag for connections accepted here (e.g. "high-diff"); replaces the port number in logs and the "port" metric label
+    pub label: Option<String>,
+    /// Longest line a client may send before it is disconnected (bounds the read buffer)
+    pub max_line_bytes: usize,
+    /// Application-level heartbeat for quiet connections (None = off)
+    pub keepalive: Option<KeepaliveConfig>,
+    /// Close connections that have not authorized this long after connecting (None = never)
+    pub authorize_timeout: Option<Duration>,
+    /// Listen backlog and socket buffer sizes
+    pub socket_tuning: SocketTuning,
+}
+
+/// Ping a connection that has been silent for `interval` and close it if nothing arrives within `timeout`
+/// after that. Catches half-open connections that TCP keepalive misses behind some NATs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeepaliveConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+/// A JSON-RPC reply from the miner (to a bridge-initiated request such as mining.ping) rather than a request
+pub fn is_client_response(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|v| v.as_object().map(|o| o.get("method").is_none() && (o.contains_key("result") || o.contains_key("error"))))
+        .unwrap_or(false)
+}
+
+/// Input that cannot be the start of a stratum session: HTTP requests, the HTTP/2 (gRPC) preface,
+/// TLS handshakes and other binary or plain-text noise. Stratum lines are JSON, so anything that doesn't
+/// open like JSON is garbage; malformed JSON still goes to the parser and gets a parse error reply.
+/// Only the first message of a connection is screened, later lines always reach the parser
+pub fn is_non_stratum_input(line: &str) -> bool {
+    let line = line.trim_start_matches('\u{feff}').trim_start();
+    !line.is_empty() && !line.starts_with('{') && !line.starts_with('[')
+}
+
+/// Drop a connection that is not speaking stratum with a single debug line, scanners hit exposed ports all day
+fn close_non_stratum(ctx: &StratumContext, port: &str, line: &str) {
+    let preview: String = line.chars().take(32).map(|c| if c.is_control() { '.' } else { c }).collect();
+    tracing::debug!(
+        "[CONNECTION] Closing {} - not stratum (starts with {:?}); HTTP/gRPC clients belong on the kaspad port",
+        ctx.remote_endpoint(),
+        preview
+    );
+    crate::prom::record_non_stratum_connection(port);
+    ctx.disconnect_for(DisconnectReason::ProtocolError);
 }
 
 /// Stratum TCP listener
@@ -48,6 +254,8 @@ pub struct StratumListener {
     config: StratumListenerConfig,
     stats: Arc<parking_lot::Mutex<StratumStats>>,
     shutting_down: Arc<std::sync::atomic::AtomicBool>,
+    active_connections: Arc<AtomicUsize>,
+    pending_rejects: Arc<tokio::sync::Semaphore>, // One permit per refused connection being answered
 }
 
 impl StratumListener {
@@ -57,120 +265,319 @@ impl StratumListener {
             config,
             stats: Arc::new(parking_lot::Mutex::new(StratumStats::default())),
             shutting_down: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            pending_rejects: Arc::new(tokio::sync::Semaphore::new(MAX_PENDING_REJECTS)),
         }
     }
 
+    /// Number of currently open client connections
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Acquire)
+    }
+
     /// Start listening for connections
     pub async fn listen(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.shutting_down.store(false, std::sync::atomic::Ordering::Release);
 
-        // Parse port - ensure we bind to IPv4 (0.0.0.0) to accept IPv4 connections
-        // If it starts with ':', prepend "0.0.0.0", otherwise format as "0.0.0.0:PORT"
-        let addr_str = if self.config.port.starts_with(':') {
-            format!("0.0.0.0{}", self.config.port)
-        } else {
-            format!("0.0.0.0:{}", self.config.port)
-        };
+        if let Some(path) = unix_socket_path(&self.config.port) {
+            return self.listen_unix(path).await;
+        }
 
-        let listener =
-            TcpListener::bind(&addr_str).await.map_err(|e| format!("failed listening to socket {}: {}", self.config.port, e))?;
+        // ":PORT" listens on all interfaces, IPv4 and IPv6
+        let listener = bind_tuned_listener(&self.config.port, &self.config.socket_tuning)
+            .await
+            .map_err(|e| format!("failed listening to socket {}: {}", self.config.port, e))?;
 
-        tracing::debug!("Stratum listener started on {}", self.config.port);
+        match listener.local_addr() {
+            Ok(addr) => info!("Stratum listener bound to {} ({})", addr, self.config.port),
+            Err(_) => info!("Stratum listener bound to {}", self.config.port),
+        }
+        let disconnect_tx = self.start_serving();
+
+        // Accept connections
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    // IPv4 peers on a dual-stack socket arrive as ::ffff:a.b.c.d, log them as plain IPv4
+                    let addr = std::net::SocketAddr::new(addr.ip().to_canonical(), addr.port());
+                    self.config.socket_tuning.apply_to_stream(&stream);
+                    tracing::debug!("[CONNECTION] Local address: {:?}", stream.local_addr());
+                    self.accept_client(stream, &addr.to_string(), addr.ip().to_string(), addr.port(), &disconnect_tx);
+                }
+                Err(e) => {
+                    if self.accept_failed(&e) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// listen() for a `unix:/path` stratum_port, e.g. behind a proxy on the same host. Connections have no
+    /// peer address, so they are logged as the socket path with a per-listener connection number
+    #[cfg(unix)]
+    async fn listen_unix(&self, path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // A socket left behind by a previous run would make bind fail; anything else at the path is kept
+        crate::admin_socket::remove_stale_socket(std::path::Path::new(path))
+            .map_err(|e| format!("failed removing stale socket {}: {}", path, e))?;
+        let listener = tokio::net::UnixListener::bind(path).map_err(|e| format!("failed listening to socket {}: {}", path, e))?;
+        info!("Stratum listener bound to unix socket {}", path);
+        let disconnect_tx = self.start_serving();
+
+        let remote_addr = format!("unix:{}", path);
+        let mut connection_number: u16 = 0;
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    connection_number = connection_number.wrapping_add(1);
+                    let addr = format_peer_addr(&remote_addr, connection_number);
+                    self.accept_client(stream, &addr, remote_addr.clone(), connection_number, &disconnect_tx);
+                }
+                Err(e) => {
+                    if self.accept_failed(&e) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    async fn listen_unix(&self, path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("cannot listen on unix socket {}: Unix domain sockets are not supported on this platform", path).into())
+    }
+
+    /// Publish the connection metrics of a freshly bound listener and start its disconnect handler
+    fn start_serving(&self) -> mpsc::UnboundedSender<Arc<StratumContext>> {
+        crate::prom::record_max_connections(&self.config.port, self.config.max_connections.load(Ordering::Acquire));
+        crate::prom::record_connection_count(&self.config.port, self.active_connections());
 
         let (disconnect_tx, mut disconnect_rx) = mpsc::unbounded_channel::<Arc<StratumContext>>();
-        let disconnect_tx_clone = disconnect_tx.clone();
         let on_disconnect = Arc::clone(&self.config.on_disconnect);
         let stats = self.stats.clone();
 
         // Spawn disconnect handler
         tokio::spawn(async move {
             while let Some(ctx) = disconnect_rx.recv().await {
-                info!("[CONNECTION] client disconnecting - {}", ctx.remote_addr);
-                tracing::info!("[CONNECTION] Disconnect event for {}:{}", ctx.remote_addr, ctx.remote_port);
+                let reason = ctx.disconnect_reason().unwrap_or(DisconnectReason::Other);
+                info!("[CONNECTION] client disconnecting - {} ({})", ctx.remote_addr, reason);
+                tracing::info!("[CONNECTION] Disconnect event for {}", ctx.remote_endpoint());
+                crate::prom::record_disconnect_reason(reason.as_str());
                 stats.lock().disconnects += 1;
                 on_disconnect(ctx);
             }
         });
+        disconnect_tx
+    }
 
-        // Accept connections
-        loop {
-            tokio::select! {
-                result = listener.accept() => {
-                    match result {
-                        Ok((stream, addr)) => {
-                            let remote_addr = addr.ip().to_string();
-                            let remote_port = addr.port();
-
-                            tracing::debug!("[CONNECTION] new client connecting - {}:{}", remote_addr, remote_port);
-                            tracing::debug!("[CONNECTION] ===== TCP CONNECTION ESTABLISHED =====");
-                            tracing::debug!("[CONNECTION] Remote address: {}:{}", remote_addr, remote_port);
-                            tracing::debug!("[CONNECTION] Local address: {:?}", stream.local_addr());
-                            tracing::debug!("[CONNECTION] Connection accepted successfully");
-
-                            // Create new MiningState for each client
-                            // Each client gets its own isolated state, just like in Go
-                            use crate::mining_state::MiningState;
-                            let state = Arc::new(MiningState::new());
-
-                            // Clone for logging after move
-                            let remote_addr_for_log = remote_addr.clone();
-                            let remote_port_for_log = remote_port;
-
-                            tracing::debug!("[CONNECTION] Creating StratumContext for {}:{}", remote_addr_for_log, remote_port_for_log);
-                            let ctx = StratumContext::new(
-                                remote_addr,
-                                remote_port,
-                                stream,
-                                state,
-                                disconnect_tx_clone.clone(),
-                            );
-                            tracing::debug!("[CONNECTION] StratumContext created successfully");
-
-                            tracing::debug!("[CONNECTION] Calling on_connect handler");
-                            (self.config.on_connect)(ctx.clone());
-                            tracing::debug!("[CONNECTION] on_connect handler completed");
-
-                            // Spawn client handler
-                            tracing::debug!("[CONNECTION] Spawning client listener task for {}:{}", remote_addr_for_log, remote_port_for_log);
-                            let ctx_clone = ctx.clone();
-                            let handler_map = self.config.handler_map.clone();
-                            tokio::spawn(async move {
-                                tracing::debug!("[CONNECTION] Client listener task started for {}:{}", ctx_clone.remote_addr, ctx_clone.remote_port);
-                                Self::spawn_client_listener(ctx_clone, &handler_map).await;
-                                tracing::debug!("[CONNECTION] Client listener task ended");
-                            });
-                            tracing::debug!("[CONNECTION] ===== CONNECTION SETUP COMPLETE FOR {}:{} =====", remote_addr_for_log, remote_port_for_log);
-                        }
-                        Err(e) => {
-                            if self.shutting_down.load(std::sync::atomic::Ordering::Acquire) {
-                                info!("stopping listening due to server shutdown");
-                                break;
-                            }
-                            error!("[CONNECTION] ===== FAILED TO ACCEPT INCOMING CONNECTION =====");
-                            error!("[CONNECTION] Error: {}", e);
-                            error!("[CONNECTION] Error kind: {:?}", e.kind());
-                            tracing::error!("[CONNECTION] Failed to accept connection: {} (kind: {:?})", e, e.kind());
-                        }
-                    }
+    /// Log a failed accept; true when it is the listener being shut down and the accept loop should end
+    fn accept_failed(&self, e: &std::io::Error) -> bool {
+        if self.shutting_down.load(std::sync::atomic::Ordering::Acquire) {
+            info!("stopping listening due to server shutdown");
+            return true;
+        }
+        error!("[CONNECTION] ===== FAILED TO ACCEPT INCOMING CONNECTION =====");
+        error!("[CONNECTION] Error: {}", e);
+        error!("[CONNECTION] Error kind: {:?}", e.kind());
+        tracing::error!("[CONNECTION] Failed to accept connection: {} (kind: {:?})", e, e.kind());
+        false
+    }
+
+    /// Admit (or refuse) one accepted connection and start serving it, for TCP and Unix socket listeners alike
+    fn accept_client(
+        &self,
+        stream: impl ClientStream + 'static,
+        addr: &str,
+        remote_addr: String,
+        remote_port: u16,
+        disconnect_tx: &mpsc::UnboundedSender<Arc<StratumContext>>,
+    ) {
+        // Maintenance refuses everyone new, so miners go to their backup pool
+        if crate::admin_socket::maintenance_mode() {
+            tracing::info!("[CONNECTION] Rejecting {} - maintenance mode", addr);
+            crate::prom::record_disconnect_reason(DisconnectReason::Maintenance.as_str());
+            Self::spawn_reject(&self.pending_rejects, stream, "Pool is in maintenance, use a backup pool");
+            return;
+        }
+
+        // Without kaspad there is no work to hand out; under offline_policy: disconnect miners go elsewhere
+        if crate::kaspaapi::kaspad_offline_refusing() {
+            tracing::info!("[CONNECTION] Rejecting {} - kaspad unreachable (offline_policy disconnect)", addr);
+            crate::prom::record_disconnect_reason(DisconnectReason::NodeOffline.as_str());
+            Self::spawn_reject(&self.pending_rejects, stream, "Pool node is offline, use a backup pool");
+            return;
+        }
+
+        // Connection cap - checked only at accept time, so lowering the cap
+        // never affects miners that are already connected
+        let max_connections = self.config.max_connections.load(Ordering::Acquire);
+        crate::prom::record_max_connections(&self.config.port, max_connections);
+        if max_connections > 0 && self.active_connections.load(Ordering::Acquire) >= max_connections {
+            warn!(
+                "[CONNECTION] Rejecting {} - connection limit reached ({}/{})",
+                addr,
+                self.active_connections.load(Ordering::Acquire),
+                max_connections
+            );
+            crate::prom::record_disconnect_reason(DisconnectReason::MaxConnections.as_str());
+            Self::spawn_reject(&self.pending_rejects, stream, "Pool is full, connection limit reached");
+            return;
+        }
+        let current = self.active_connections.fetch_add(1, Ordering::AcqRel) + 1;
+        crate::prom::record_connection_count(&self.config.port, current);
+
+        match &self.config.label {
+            Some(label) => tracing::debug!("[CONNECTION] new client connecting - {} on {}", addr, label),
+            None => tracing::debug!("[CONNECTION] new client connecting - {}", addr),
+        }
+        tracing::debug!("[CONNECTION] ===== CONNECTION ESTABLISHED =====");
+        tracing::debug!("[CONNECTION] Remote address: {}", addr);
+        tracing::debug!("[CONNECTION] Connection accepted successfully");
+
+        // Create new MiningState for each client
+        // Each client gets its own isolated state, just like in Go
+        use crate::mining_state::MiningState;
+        let state = Arc::new(MiningState::new());
+
+        tracing::debug!("[CONNECTION] Creating StratumContext for {}", addr);
+        let ctx = StratumContext::new(
+            remote_addr,
+            remote_port,
+            self.config.label.clone().unwrap_or_else(|| crate::prom::port_label(&self.config.port, self.config.label_metrics_by_port)),
+            stream,
+            state,
+            disconnect_tx.clone(),
+        );
+        tracing::debug!("[CONNECTION] StratumContext created successfully");
+
+        tracing::debug!("[CONNECTION] Calling on_connect handler");
+        (self.config.on_connect)(ctx.clone());
+        tracing::debug!("[CONNECTION] on_connect handler completed");
+
+        // Spawn client handler
+        tracing::debug!("[CONNECTION] Spawning client listener task for {}", addr);
+        let ctx_clone = ctx.clone();
+        let handler_map = self.config.handler_map.clone();
+        let active_connections = Arc::clone(&self.active_connections);
+        let port = self.config.port.clone();
+        let max_line_bytes = self.config.max_line_bytes;
+        let disconnect_tx = disconnect_tx.clone();
+        if let Some(keepalive) = self.config.keepalive {
+            tokio::spawn(Self::run_keepalive(ctx.clone(), keepalive, port.clone()));
+        }
+        if let Some(timeout) = self.config.authorize_timeout {
+            tokio::spawn(Self::enforce_authorize_timeout(ctx.clone(), timeout, port.clone()));
+        }
+        tokio::spawn(async move {
+            tracing::debug!("[CONNECTION] Client listener task started for {}", ctx_clone.remote_endpoint());
+            Self::spawn_client_listener(Arc::clone(&ctx_clone), &handler_map, &port, max_line_bytes).await;
+            // Hand the context to the disconnect handler so the client is removed
+            // and its extranonce returned to the reuse queue
+            let _ = disconnect_tx.send(ctx_clone);
+            let remaining = active_connections.fetch_sub(1, Ordering::AcqRel).saturating_sub(1);
+            crate::prom::record_connection_count(&port, remaining);
+            tracing::debug!("[CONNECTION] Client listener task ended");
+        });
+        tracing::debug!("[CONNECTION] ===== CONNECTION SETUP COMPLETE FOR {} =====", addr);
+    }
+
+    /// Answer a refused connection in the background while `pending_rejects` has room, otherwise close it
+    /// straight away. Returns whether it gets an answer
+    fn spawn_reject(pending_rejects: &Arc<tokio::sync::Semaphore>, stream: impl ClientStream + 'static, reason: &'static str) -> bool {
+        let Ok(permit) = Arc::clone(pending_rejects).try_acquire_owned() else {
+            tracing::debug!("[CONNECTION] {} refused connections already being answered, closing", MAX_PENDING_REJECTS);
+            return false;
+        };
+        tokio::spawn(async move {
+            Self::reject_connection(stream, reason).await;
+            drop(permit);
+        });
+        true
+    }
+
+    /// Answer the first request of a refused connection (max_connections reached, maintenance)
+    /// with an error carrying `reason`, then close the socket
+    async fn reject_connection(mut stream: impl ClientStream + 'static, reason: &'static str) {
+        let mut buffer = [0u8; 1024];
+        let id = match tokio::time::timeout(std::time::Duration::from_secs(5), stream.read(&mut buffer)).await {
+            Ok(Ok(n)) if n > 0 => String::from_utf8_lossy(&buffer[..n])
+                .lines()
+                .next()
+                .and_then(|line| crate::jsonrpc_event::unmarshal_event(line.trim()).ok())
+                .and_then(|event| event.id),
+            _ => None,
+        };
+
+        let response = JsonRpcResponse::error(id, 20, reason, None);
+        if let Ok(json) = serde_json::to_string(&response) {
+            let _ = stream.write_all(format!("{}\n", json).as_bytes()).await;
+        }
+        let _ = stream.shutdown().await;
+    }
+
+    /// Ping the miner whenever it has been silent for keepalive.interval; drop it once it has been
+    /// silent for interval + timeout. Busy miners never see a ping, their shares count as activity
+    async fn run_keepalive(ctx: Arc<StratumContext>, keepalive: KeepaliveConfig, port: String) {
+        let check_every = (keepalive.interval.min(keepalive.timeout) / 4).max(Duration::from_millis(50));
+        let mut last_ping: Option<std::time::Instant> = None;
+        while ctx.connected() {
+            tokio::time::sleep(check_every).await;
+            let idle = ctx.idle_for();
+            if idle >= keepalive.interval + keepalive.timeout {
+                warn!("[CONNECTION] Closing {} - no response to keepalive for {:.0?}", ctx.remote_endpoint(), idle);
+                crate::prom::record_keepalive_timeout(&port);
+                ctx.disconnect_for(DisconnectReason::IdleTimeout);
+                break;
+            }
+            if idle >= keepalive.interval && last_ping.is_none_or(|at| at.elapsed() >= keepalive.interval) {
+                let ping = JsonRpcEvent::new(Some("ping".to_string()), "mining.ping", vec![]);
+                tracing::debug!("[CONNECTION] {} idle for {:.0?}, sending mining.ping", ctx.remote_endpoint(), idle);
+                if ctx.send(ping).await.is_err() {
+                    break;
                 }
+                last_ping = Some(std::time::Instant::now());
             }
         }
+    }
 
-        Ok(())
+    /// Drop the connection if it still has no wallet `timeout` after connecting. Until it authorizes
+    /// it is never sent a job, so such a connection only holds a slot and an extranonce
+    async fn enforce_authorize_timeout(ctx: Arc<StratumContext>, timeout: Duration, port: String) {
+        tokio::time::sleep(timeout).await;
+        if ctx.connected() && ctx.wallet_addr.lock().is_empty() {
+            warn!(
+                "[CONNECTION] Closing {} ({}) - not authorized within {:?} of connecting",
+                ctx.remote_endpoint(),
+                ctx.remote_app.lock(),
+                timeout
+            );
+            crate::prom::record_authorize_timeout(&port);
+            ctx.disconnect_for(DisconnectReason::AuthTimeout);
+        }
     }
 
     /// Spawn a client listener task
-    async fn spawn_client_listener(ctx: Arc<StratumContext>, handler_map: &Arc<HashMap<String, EventHandler>>) {
-        tracing::debug!("[CLIENT_LISTENER] Starting client listener for {}:{}", ctx.remote_addr, ctx.remote_port);
+    async fn spawn_client_listener(
+        ctx: Arc<StratumContext>,
+        handler_map: &Arc<HashMap<String, EventHandler>>,
+        port: &str,
+        max_line_bytes: usize,
+    ) {
+        tracing::debug!("[CLIENT_LISTENER] Starting client listener for {}", ctx.remote_endpoint());
         let mut buffer = [0u8; 1024];
         let mut line_buffer = String::new();
         let mut first_message = true;
 
-        loop {
+        // Whatever ends the loop is why the connection closes, unless it was closed for a reason already
+        let reason = loop {
             // Check if disconnected
             if !ctx.connected() {
-                tracing::debug!("[CLIENT_LISTENER] Client {}:{} disconnected", ctx.remote_addr, ctx.remote_port);
-                break;
+                tracing::debug!("[CLIENT_LISTENER] Client {} disconnected", ctx.remote_endpoint());
+                break DisconnectReason::Other;
             }
 
             // Get read half for reading (must drop guard before await)
@@ -195,17 +602,18 @@ impl StratumListener {
             } else {
                 // Read half is None, disconnect
                 tracing::warn!("[CONNECTION] Read half is None for {}, disconnecting", ctx.remote_addr);
-                break;
+                break DisconnectReason::Other;
             };
 
             match read_result {
                 Ok(Ok(0)) => {
                     // EOF - client closed connection
                     tracing::debug!("[CONNECTION] Client {} closed connection (EOF)", ctx.remote_addr);
-                    break;
+                    break DisconnectReason::ClientClosed;
                 }
                 Ok(Ok(n)) => {
-                    tracing::debug!("[CLIENT_LISTENER] Read {} bytes from {}:{}", n, ctx.remote_addr, ctx.remote_port);
+                    tracing::debug!("[CLIENT_LISTENER] Read {} bytes from {}", n, ctx.remote_endpoint());
+                    ctx.mark_activity();
 
                     // Remove null bytes and process
                     let data: Vec<u8> = buffer[..n].iter().copied().filter(|&b| b != 0).collect();
@@ -216,69 +624,12 @@ impl StratumListener {
                         let remote_app = ctx.remote_app.lock().clone();
                         let message_str = String::from_utf8_lossy(&data);
 
-                        // Check for HTTP/2/gRPC protocol in first message (before logging)
+                        // Port scanners, HTTP clients, TLS and gRPC: not worth more than a debug line.
+                        // Checked before the first newline arrives since e.g. a TLS ClientHello never sends one
                         let first_line = message_str.lines().next().unwrap_or("").trim();
-                        if first_line.starts_with("PRI * HTTP/2.0")
-                            || first_line.starts_with("PRI * HTTP/2")
-                            || first_line == "SM"
-                            || first_line.starts_with("GET ")
-                            || first_line.starts_with("POST ")
-                            || first_line.starts_with("PUT ")
-                            || first_line.starts_with("DELETE ")
-                            || first_line.starts_with("HEAD ")
-                            || first_line.starts_with("OPTIONS ")
-                        {
-                            error!("{}", LogColors::error("========================================"));
-                            error!("{}", LogColors::error("===== PROTOCOL MISMATCH DETECTED (FIRST MESSAGE) ===== "));
-                            error!("{}", LogColors::error("========================================"));
-                            error!("{} {}", LogColors::error("[ERROR]"), LogColors::label("Client Information:"));
-                            error!(
-                                "{} {} {}",
-                                LogColors::error("[ERROR]"),
-                                LogColors::label("  - IP Address:"),
-                                format!("{}:{}", ctx.remote_addr, ctx.remote_port)
-                            );
-                            error!(
-                                "{} {} {}",
-                                LogColors::error("[ERROR]"),
-                                LogColors::label("  - Protocol Detected:"),
-                                "HTTP/2 or HTTP (gRPC)"
-                            );
-                            error!(
-                                "{} {} {}",
-                                LogColors::error("[ERROR]"),
-                                LogColors::label("  - Expected Protocol:"),
-                                "Plain TCP/JSON-RPC (Stratum)"
-                            );
-                            error!(
-                                "{} {} {}",
-                                LogColors::error("[ERROR]"),
-                                LogColors::label("  - First Message (hex):"),
-                                hex::encode(&data)
-                            );
-                            error!(
-                                "{} {} {}",
-                                LogColors::error("[ERROR]"),
-                                LogColors::label("  - First Message (string):"),
-                                first_line
-                            );
-                            error!("{} {}", LogColors::error("[ERROR]"), LogColors::label("Action:"));
-                            error!(
-                                "{} {}",
-                                LogColors::error("[ERROR]"),
-                                "  * Rejecting connection - Stratum port only accepts JSON-RPC over plain TCP"
-                            );
-                            error!(
-                                "{} {}",
-                                LogColors::error("[ERROR]"),
-                                "  * HTTP/2/gRPC connections should use the Kaspa node port (16110), not the bridge port (5555)"
-                            );
-                            error!("{} {}", LogColors::error("[ERROR]"), "  * Closing connection immediately");
-                            error!("{}", LogColors::error("========================================"));
-
-                            // Close connection
-                            ctx.disconnect();
-                            break;
+                        if is_non_stratum_input(first_line) {
+                            close_non_stratum(&ctx, port, first_line);
+                            break DisconnectReason::ProtocolError;
                         }
 
                         tracing::debug!("{}", LogColors::asic_to_bridge("========================================"));
@@ -293,7 +644,7 @@ impl StratumListener {
                             "{} {} {}",
                             LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
                             LogColors::label("  - IP Address:"),
-                            format!("{}:{}", ctx.remote_addr, ctx.remote_port)
+                            ctx.remote_endpoint()
                         );
                         tracing::debug!(
                             "{} {} {}",
@@ -377,247 +728,205 @@ impl StratumListener {
                             let worker_name = ctx.worker_name.lock().clone();
                             let remote_app = ctx.remote_app.lock().clone();
 
-                            // Detect HTTP/2/gRPC connections early and reject them
-                            // HTTP/2 connection preface starts with "PRI * HTTP/2.0"
-                            if line.starts_with("PRI * HTTP/2.0")
-                                || line.starts_with("PRI * HTTP/2")
-                                || line == "SM"
-                                || line.starts_with("GET ")
-                                || line.starts_with("POST ")
-                                || line.starts_with("PUT ")
-                                || line.starts_with("DELETE ")
-                                || line.starts_with("HEAD ")
-                                || line.starts_with("OPTIONS ")
-                            {
-                                error!("{}", LogColors::error("========================================"));
-                                error!("{}", LogColors::error("===== PROTOCOL MISMATCH DETECTED ===== "));
-                                error!("{}", LogColors::error("========================================"));
-                                error!("{} {}", LogColors::error("[ERROR]"), LogColors::label("Client Information:"));
-                                error!(
+                            // Per-message detail is sampled (log_sample_rate); errors below are always logged
+                            let log_detail = log_sampled_line(&line);
+
+                            if log_detail {
+                                // Log raw incoming message from ASIC at DEBUG level (verbose details)
+                                tracing::debug!("{}", LogColors::asic_to_bridge("========================================"));
+                                tracing::debug!("{}", LogColors::asic_to_bridge("===== RECEIVED MESSAGE FROM ASIC ===== "));
+                                tracing::debug!("{}", LogColors::asic_to_bridge("========================================"));
+                                tracing::debug!(
+                                    "{} {}",
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("Client Information:")
+                                );
+                                tracing::debug!(
                                     "{} {} {}",
-                                    LogColors::error("[ERROR]"),
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
                                     LogColors::label("  - IP Address:"),
-                                    format!("{}:{}", ctx.remote_addr, ctx.remote_port)
+                                    ctx.remote_endpoint()
                                 );
-                                error!(
+                                tracing::debug!(
                                     "{} {} {}",
-                                    LogColors::error("[ERROR]"),
-                                    LogColors::label("  - Protocol Detected:"),
-                                    "HTTP/2 or HTTP (gRPC)"
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Wallet Address:"),
+                                    format!("'{}'", wallet_addr)
                                 );
-                                error!(
+                                tracing::debug!(
                                     "{} {} {}",
-                                    LogColors::error("[ERROR]"),
-                                    LogColors::label("  - Expected Protocol:"),
-                                    "Plain TCP/JSON-RPC (Stratum)"
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Worker Name:"),
+                                    format!("'{}'", worker_name)
                                 );
-                                error!("{} {} {}", LogColors::error("[ERROR]"), LogColors::label("  - Received Message:"), &line);
-                                error!("{} {}", LogColors::error("[ERROR]"), LogColors::label("Action:"));
-                                error!(
-                                    "{} {}",
-                                    LogColors::error("[ERROR]"),
-                                    "  * Rejecting connection - Stratum port only accepts JSON-RPC over plain TCP"
+                                tracing::debug!(
+                                    "{} {} {}",
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Miner Application:"),
+                                    format!("'{}'", remote_app)
                                 );
-                                error!(
+                                tracing::debug!(
                                     "{} {}",
-                                    LogColors::error("[ERROR]"),
-                                    "  * HTTP/2/gRPC connections should use the Kaspa node port (16110), not the bridge port (5555)"
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("Raw Message Data:")
+                                );
+                                tracing::debug!(
+                                    "{} {} {}",
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Raw Message:"),
+                                    line
+                                );
+                                tracing::debug!(
+                                    "{} {} {}",
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Message Length:"),
+                                    format!("{} bytes", line.len())
+                                );
+                                tracing::debug!(
+                                    "{} {} {}",
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Message Length:"),
+                                    format!("{} characters", line.chars().count())
+                                );
+                                tracing::debug!(
+                                    "{} {} {}",
+                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                    LogColors::label("  - Raw Bytes (hex):"),
+                                    hex::encode(line.as_bytes())
                                 );
-                                error!("{} {}", LogColors::error("[ERROR]"), "  * Closing connection immediately");
-                                error!("{}", LogColors::error("========================================"));
-
-                                // Close connection
-                                ctx.disconnect();
-                                break;
                             }
 
-                            // Log raw incoming message from ASIC at DEBUG level (verbose details)
-                            tracing::debug!("{}", LogColors::asic_to_bridge("========================================"));
-                            tracing::debug!("{}", LogColors::asic_to_bridge("===== RECEIVED MESSAGE FROM ASIC ===== "));
-                            tracing::debug!("{}", LogColors::asic_to_bridge("========================================"));
-                            tracing::debug!(
-                                "{} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("Client Information:")
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - IP Address:"),
-                                format!("{}:{}", ctx.remote_addr, ctx.remote_port)
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Wallet Address:"),
-                                format!("'{}'", wallet_addr)
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Worker Name:"),
-                                format!("'{}'", worker_name)
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Miner Application:"),
-                                format!("'{}'", remote_app)
-                            );
-                            tracing::debug!(
-                                "{} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("Raw Message Data:")
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Raw Message:"),
-                                line
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Message Length:"),
-                                format!("{} bytes", line.len())
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Message Length:"),
-                                format!("{} characters", line.chars().count())
-                            );
-                            tracing::debug!(
-                                "{} {} {}",
-                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                LogColors::label("  - Raw Bytes (hex):"),
-                                hex::encode(line.as_bytes())
-                            );
-
                             match crate::jsonrpc_event::unmarshal_event(&line) {
                                 Ok(event) => {
-                                    let params_str = serde_json::to_string(&event.params).unwrap_or_else(|_| "[]".to_string());
+                                    crate::prom::record_message_type(&event.method);
+                                    if log_detail {
+                                        let params_str = serde_json::to_string(&event.params).unwrap_or_else(|_| "[]".to_string());
 
-                                    // Log parsed event details at DEBUG level (detailed logs moved to debug)
-                                    tracing::debug!("{}", LogColors::asic_to_bridge("===== PARSING SUCCESSFUL ===== "));
-                                    tracing::debug!(
-                                        "{} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("Parsed Event Structure:")
-                                    );
-                                    tracing::debug!(
-                                        "{} {} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("  - Method:"),
-                                        format!("'{}'", event.method)
-                                    );
-                                    tracing::debug!(
-                                        "{} {} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("  - Event ID:"),
-                                        format!("{:?}", event.id)
-                                    );
-                                    tracing::debug!(
-                                        "{} {} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("  - JSON-RPC Version:"),
-                                        format!("'{}'", event.jsonrpc)
-                                    );
-                                    tracing::debug!(
-                                        "{} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("Parameters:")
-                                    );
-                                    tracing::debug!(
-                                        "{} {} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("  - Params Count:"),
-                                        event.params.len()
-                                    );
-                                    tracing::debug!(
-                                        "{} {} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("  - Params JSON:"),
-                                        params_str
-                                    );
-                                    tracing::debug!(
-                                        "{} {} {}",
-                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                        LogColors::label("  - Params Length:"),
-                                        format!("{} characters", params_str.len())
-                                    );
-                                    // Log each param individually with type information
-                                    for (idx, param) in event.params.iter().enumerate() {
-                                        let param_str = serde_json::to_string(param).unwrap_or_else(|_| "N/A".to_string());
-                                        let param_type = if param.is_string() {
-                                            let s = param.as_str().unwrap_or("");
-                                            format!("String (length: {}, value: '{}')", s.len(), s)
-                                        } else if param.is_number() {
-                                            format!("Number (value: {})", param)
-                                        } else if param.is_array() {
-                                            let arr = param.as_array().unwrap();
-                                            format!(
-                                                "Array (length: {}, items: {:?})",
-                                                arr.len(),
-                                                arr.iter()
-                                                    .take(5)
-                                                    .map(|v| serde_json::to_string(v).unwrap_or_else(|_| "?".to_string()))
-                                                    .collect::<Vec<_>>()
-                                            )
-                                        } else if param.is_object() {
-                                            "Object".to_string()
-                                        } else if param.is_boolean() {
-                                            format!("Boolean (value: {})", param.as_bool().unwrap_or(false))
-                                        } else {
-                                            "Null".to_string()
-                                        };
+                                        // Log parsed event details at DEBUG level (detailed logs moved to debug)
+                                        tracing::debug!("{}", LogColors::asic_to_bridge("===== PARSING SUCCESSFUL ===== "));
+                                        tracing::debug!(
+                                            "{} {}",
+                                            LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                            LogColors::label("Parsed Event Structure:")
+                                        );
                                         tracing::debug!(
                                             "{} {} {}",
                                             LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                            LogColors::label(&format!("  - Param[{}]:", idx)),
-                                            format!("{} (type: {})", param_str, param_type)
+                                            LogColors::label("  - Method:"),
+                                            format!("'{}'", event.method)
                                         );
-                                    }
-
-                                    if let Some(handler) = handler_map.get(&event.method) {
-                                        tracing::debug!("{}", LogColors::asic_to_bridge("===== PROCESSING MESSAGE ===== "));
                                         tracing::debug!(
                                             "{} {} {}",
                                             LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                            LogColors::label("  - Handler Found:"),
-                                            "YES"
+                                            LogColors::label("  - Event ID:"),
+                                            format!("{:?}", event.id)
                                         );
                                         tracing::debug!(
                                             "{} {} {}",
                                             LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                            LogColors::label("  - Method:"),
-                                            format!("'{}'", event.method)
+                                            LogColors::label("  - JSON-RPC Version:"),
+                                            format!("'{}'", event.jsonrpc)
                                         );
                                         tracing::debug!(
                                             "{} {}",
                                             LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                            "  - Starting handler execution..."
+                                            LogColors::label("Parameters:")
+                                        );
+                                        tracing::debug!(
+                                            "{} {} {}",
+                                            LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                            LogColors::label("  - Params Count:"),
+                                            event.params.len()
                                         );
+                                        tracing::debug!(
+                                            "{} {} {}",
+                                            LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                            LogColors::label("  - Params JSON:"),
+                                            params_str
+                                        );
+                                        tracing::debug!(
+                                            "{} {} {}",
+                                            LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                            LogColors::label("  - Params Length:"),
+                                            format!("{} characters", params_str.len())
+                                        );
+                                        // Log each param individually with type information
+                                        for (idx, param) in event.params.iter().enumerate() {
+                                            let param_str = serde_json::to_string(param).unwrap_or_else(|_| "N/A".to_string());
+                                            let param_type = if param.is_string() {
+                                                let s = param.as_str().unwrap_or("");
+                                                format!("String (length: {}, value: '{}')", s.len(), s)
+                                            } else if param.is_number() {
+                                                format!("Number (value: {})", param)
+                                            } else if param.is_array() {
+                                                let arr = param.as_array().unwrap();
+                                                format!(
+                                                    "Array (length: {}, items: {:?})",
+                                                    arr.len(),
+                                                    arr.iter()
+                                                        .take(5)
+                                                        .map(|v| serde_json::to_string(v).unwrap_or_else(|_| "?".to_string()))
+                                                        .collect::<Vec<_>>()
+                                                )
+                                            } else if param.is_object() {
+                                                "Object".to_string()
+                                            } else if param.is_boolean() {
+                                                format!("Boolean (value: {})", param.as_bool().unwrap_or(false))
+                                            } else {
+                                                "Null".to_string()
+                                            };
+                                            tracing::debug!(
+                                                "{} {} {}",
+                                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                                LogColors::label(&format!("  - Param[{}]:", idx)),
+                                                format!("{} (type: {})", param_str, param_type)
+                                            );
+                                        }
+                                    }
+
+                                    if let Some(handler) = handler_map.get(&event.method) {
+                                        if log_detail {
+                                            tracing::debug!("{}", LogColors::asic_to_bridge("===== PROCESSING MESSAGE ===== "));
+                                            tracing::debug!(
+                                                "{} {} {}",
+                                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                                LogColors::label("  - Handler Found:"),
+                                                "YES"
+                                            );
+                                            tracing::debug!(
+                                                "{} {} {}",
+                                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                                LogColors::label("  - Method:"),
+                                                format!("'{}'", event.method)
+                                            );
+                                            tracing::debug!(
+                                                "{} {}",
+                                                LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                                "  - Starting handler execution..."
+                                            );
+                                        }
                                         if let Err(e) = handler(ctx.clone(), event).await {
                                             let error_msg = e.to_string();
                                             if error_msg.contains("stale") || error_msg.contains("job does not exist") {
                                                 // Log stale job errors as debug (expected behavior, not important)
-                                                tracing::debug!(
-                                                    "{}",
-                                                    LogColors::asic_to_bridge("===== HANDLER EXECUTION RESULT ===== ")
-                                                );
-                                                tracing::debug!(
-                                                    "{} {} {}",
-                                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                                    LogColors::validation("  - Result:"),
-                                                    "STALE JOB (expected - job no longer exists)"
-                                                );
-                                                tracing::debug!(
-                                                    "{} {} {}",
-                                                    LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
-                                                    LogColors::label("  - Error Message:"),
-                                                    error_msg
-                                                );
+                                                if log_detail {
+                                                    tracing::debug!(
+                                                        "{}",
+                                                        LogColors::asic_to_bridge("===== HANDLER EXECUTION RESULT ===== ")
+                                                    );
+                                                    tracing::debug!(
+                                                        "{} {} {}",
+                                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                                        LogColors::validation("  - Result:"),
+                                                        "STALE JOB (expected - job no longer exists)"
+                                                    );
+                                                    tracing::debug!(
+                                                        "{} {} {}",
+                                                        LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
+                                                        LogColors::label("  - Error Message:"),
+                                                        error_msg
+                                                    );
+                                                }
                                             } else if error_msg.contains("job id is not parsable") {
                                                 // Log parsing errors as warnings
                                                 warn!(
@@ -646,7 +955,7 @@ impl StratumListener {
                                                     error_msg
                                                 );
                                             }
-                                        } else {
+                                        } else if log_detail {
                                             tracing::debug!("{}", LogColors::asic_to_bridge("===== HANDLER EXECUTION RESULT ===== "));
                                             tracing::debug!(
                                                 "{} {} {}",
@@ -660,9 +969,18 @@ impl StratumListener {
                                                 "  - Message processed successfully"
                                             );
                                         }
-                                        tracing::debug!("{}", LogColors::asic_to_bridge("========================================"));
+                                        if log_detail {
+                                            tracing::debug!(
+                                                "{}",
+                                                LogColors::asic_to_bridge("========================================")
+                                            );
+                                        }
                                     }
                                 }
+                                Err(_) if is_client_response(&line) => {
+                                    // e.g. the answer to a keepalive mining.ping; receiving it already counted as activity
+                                    tracing::debug!("[ASIC->BRIDGE] {} replied: {}", ctx.remote_endpoint(), line);
+                                }
                                 Err(e) => {
                                     error!("{}", LogColors::asic_to_bridge("========================================"));
                                     error!("{}", LogColors::error("===== ERROR PARSING MESSAGE ===== "));
@@ -676,7 +994,7 @@ impl StratumListener {
                                         "{} {} {}",
                                         LogColors::asic_to_bridge("[ASIC->BRIDGE]"),
                                         LogColors::label("  - IP Address:"),
-                                        format!("{}:{}", ctx.remote_addr, ctx.remote_port)
+                                        ctx.remote_endpoint()
                                     );
                                     error!(
                                         "{} {} {}",
@@ -742,10 +1060,29 @@ impl StratumListener {
                                     error!("{} {}", LogColors::asic_to_bridge("[ASIC->BRIDGE]"), "    * Incomplete message");
                                     error!("{} {}", LogColors::asic_to_bridge("[ASIC->BRIDGE]"), "    * Encoding issue");
                                     error!("{}", LogColors::asic_to_bridge("========================================"));
+
+                                    // Answer with a JSON-RPC parse error, keeping the request id if one can be recovered
+                                    let id = serde_json::from_str::<serde_json::Value>(&line).ok().and_then(|v| v.get("id").cloned());
+                                    if ctx.reply_parse_error(id).await.is_err() {
+                                        break;
+                                    }
                                 }
                             }
                         }
                     }
+
+                    // Whatever is left has no newline yet; don't let it grow without bound
+                    if line_buffer.len() > max_line_bytes {
+                        warn!(
+                            "[CONNECTION] Closing {}:{} - sent {} bytes without a newline (max_line_bytes: {})",
+                            ctx.remote_addr,
+                            ctx.remote_port,
+                            line_buffer.len(),
+                            max_line_bytes
+                        );
+                        crate::prom::record_oversized_message(port);
+                        break DisconnectReason::ProtocolError;
+                    }
                 }
                 Ok(Err(e)) => {
                     // Check if it's a connection closed error (expected when client disconnects)
@@ -757,10 +1094,10 @@ impl StratumListener {
                         || e.kind() == std::io::ErrorKind::BrokenPipe
                     {
                         tracing::debug!("client disconnected: {}", ctx.remote_addr);
-                    } else {
-                        error!("error reading from socket: {}", e);
+                        break DisconnectReason::ClientClosed;
                     }
-                    break;
+                    error!("error reading from socket: {}", e);
+                    break DisconnectReason::SocketError;
                 }
                 Err(_) => {
                     // Timeout - continue
@@ -768,9 +1105,9 @@ impl StratumListener {
                     continue;
                 }
             }
-        }
+        };
 
-        ctx.disconnect();
+        ctx.disconnect_for(reason);
     }
 
     /// Handle an event
@@ -788,3 +1125,325 @@ impl StratumListener {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_connection::tcp_connection;
+    #[cfg(unix)]
+    use crate::mock_connection::unix_connection;
+    use tokio::io::AsyncBufReadExt;
+
+    #[tokio::test]
+    async fn test_tuned_listener_accepts() {
+        let tuning = SocketTuning { listen_backlog: 16, send_buffer_size: Some(65536), recv_buffer_size: Some(65536) };
+        let listener = bind_tuned_listener("127.0.0.1:0", &tuning).await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        tuning.apply_to_stream(&server);
+        assert!(socket2::SockRef::from(&server).recv_buffer_size().unwrap() >= 65536);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_line_drops_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, mut client) = tcp_connection(&listener).await;
+        let handler_map = Arc::new(HashMap::new());
+        let listener_task = tokio::spawn({
+            let ctx = Arc::clone(&ctx);
+            async move { StratumListener::spawn_client_listener(ctx, &handler_map, ":0", DEFAULT_MAX_LINE_BYTES).await }
+        });
+
+        // 1MB with no newline; the write may fail part-way once the bridge hangs up
+        let blob = vec![b'a'; 1024 * 1024];
+        let writer = tokio::spawn(async move {
+            let _ = client.write_all(&blob).await;
+            client
+        });
+
+        tokio::time::timeout(std::time::Duration::from_secs(10), listener_task)
+            .await
+            .expect("listener should drop the connection instead of buffering")
+            .unwrap();
+        assert!(!ctx.connected());
+
+        let mut client = writer.await.unwrap();
+        let mut buf = [0u8; 16];
+        let read = tokio::time::timeout(std::time::Duration::from_secs(5), client.read(&mut buf)).await.unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)), "bridge side of the socket should be closed");
+    }
+
+    #[test]
+    fn test_listen_addresses() {
+        assert_eq!(listen_addresses(":5555"), vec!["[::]:5555".to_string(), "0.0.0.0:5555".to_string()]);
+        assert_eq!(listen_addresses("5555"), listen_addresses(":5555"));
+        assert_eq!(listen_addresses("0.0.0.0:5555"), vec!["0.0.0.0:5555".to_string()]);
+        assert_eq!(listen_addresses("[::]:5555"), vec!["[::]:5555".to_string()]);
+    }
+
+    #[test]
+    fn test_format_peer_addr() {
+        assert_eq!(format_peer_addr("192.168.1.10", 5555), "192.168.1.10:5555");
+        assert_eq!(format_peer_addr("::1", 5555), "[::1]:5555");
+        assert_eq!(format_peer_addr("2001:db8::7", 40000), "[2001:db8::7]:40000");
+        assert_eq!(format_peer_addr("unix:/run/ks.sock", 3), "unix:/run/ks.sock#3");
+    }
+
+    #[tokio::test]
+    async fn test_all_interfaces_accepts_ipv4() {
+        // ":PORT" binds [::] where IPv6 exists; IPv4 miners have to get through it too
+        let listener = bind_listener(":0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept()).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs an IPv6 loopback (::1)"]
+    async fn test_ipv6_client_can_subscribe() {
+        let listener = TcpListener::bind("[::1]:0").await.unwrap();
+        let (ctx, client) = tcp_connection(&listener).await;
+        assert_eq!(ctx.remote_endpoint(), format!("[::1]:{}", ctx.remote_port));
+
+        let handler_map = Arc::new(crate::default_client::default_handlers());
+        tokio::spawn({
+            let ctx = Arc::clone(&ctx);
+            async move { StratumListener::spawn_client_listener(ctx, &handler_map, ":0", DEFAULT_MAX_LINE_BYTES).await }
+        });
+
+        let (read_half, mut write_half) = client.into_split();
+        write_half.write_all(b"{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[\"TestMiner/1.0\"]}\n").await.unwrap();
+
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["id"], 1);
+        assert!(reply["error"].is_null(), "subscribe failed: {}", reply);
+        ctx.disconnect();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_client_can_subscribe() {
+        let (ctx, client) = unix_connection("/tmp/ks.sock", 1);
+        assert_eq!(ctx.remote_endpoint(), "unix:/tmp/ks.sock#1");
+
+        let handler_map = Arc::new(crate::default_client::default_handlers());
+        tokio::spawn({
+            let ctx = Arc::clone(&ctx);
+            async move { StratumListener::spawn_client_listener(ctx, &handler_map, "unix:/tmp/ks.sock", DEFAULT_MAX_LINE_BYTES).await }
+        });
+
+        let (read_half, mut write_half) = client.into_split();
+        write_half.write_all(b"{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[\"TestMiner/1.0\"]}\n").await.unwrap();
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["id"], 1);
+        assert!(reply["error"].is_null(), "subscribe failed: {}", reply);
+        ctx.disconnect();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_disconnect_reason_recorded_at_loop_exit() {
+        let handler_map = Arc::new(crate::default_client::default_handlers());
+        for (input, expected) in
+            [(&b""[..], DisconnectReason::ClientClosed), (&b"GET / HTTP/1.1\r\n\r\n"[..], DisconnectReason::ProtocolError)]
+        {
+            let (ctx, mut client) = unix_connection("/tmp/ks.sock", 1);
+            client.write_all(input).await.unwrap();
+            client.shutdown().await.unwrap();
+            tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                StratumListener::spawn_client_listener(Arc::clone(&ctx), &handler_map, "unix:/tmp/ks.sock", DEFAULT_MAX_LINE_BYTES),
+            )
+            .await
+            .unwrap();
+            assert_eq!(ctx.disconnect_reason(), Some(expected));
+        }
+
+        // A reason given before the loop notices is the one kept
+        let (ctx, _client) = unix_connection("/tmp/ks.sock", 2);
+        ctx.disconnect_for(DisconnectReason::Kicked);
+        StratumListener::spawn_client_listener(Arc::clone(&ctx), &handler_map, "unix:/tmp/ks.sock", DEFAULT_MAX_LINE_BYTES).await;
+        assert_eq!(ctx.disconnect_reason(), Some(DisconnectReason::Kicked));
+    }
+
+    #[tokio::test]
+    async fn test_extranonce_subscribe_marks_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, client) = tcp_connection(&listener).await;
+        assert!(!ctx.supports_extranonce_updates());
+
+        let handler_map = Arc::new(crate::default_client::default_handlers());
+        tokio::spawn({
+            let ctx = Arc::clone(&ctx);
+            async move { StratumListener::spawn_client_listener(ctx, &handler_map, ":0", DEFAULT_MAX_LINE_BYTES).await }
+        });
+
+        let (read_half, mut write_half) = client.into_split();
+        write_half.write_all(b"{\"id\":2,\"method\":\"mining.extranonce.subscribe\",\"params\":[]}\n").await.unwrap();
+
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["id"], 2);
+        assert_eq!(reply["result"], true);
+        assert!(ctx.supports_extranonce_updates());
+        ctx.disconnect();
+    }
+
+    #[test]
+    fn test_validate_listen_address() {
+        for ok in [
+            ":5555",
+            "5555",
+            "0.0.0.0:5555",
+            "127.0.0.1:2114",
+            "10.0.0.2:2114",
+            "[::]:5555",
+            "[::1]:2114",
+            "localhost:2114",
+            "unix:/run/ks.sock",
+        ] {
+            assert!(validate_listen_address(ok).is_ok(), "{} should be accepted", ok);
+        }
+        for bad in
+            ["", ":", ":0", ":99999", "127.0.0.1", "127.0.0.1:http", "::1:2114", "[::g]:2114", "my host:2114", "unix:", "unix:/run/"]
+        {
+            assert!(validate_listen_address(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_is_non_stratum_input() {
+        assert!(is_non_stratum_input("GET / HTTP/1.1"));
+        assert!(is_non_stratum_input("POST /api HTTP/1.1"));
+        assert!(is_non_stratum_input("PRI * HTTP/2.0"));
+        assert!(is_non_stratum_input("\u{16}\u{3}\u{1}\u{2}\u{0}"));
+        assert!(is_non_stratum_input("hello"));
+
+        assert!(!is_non_stratum_input("{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[]}"));
+        assert!(!is_non_stratum_input("  {\"id\":1,"));
+        assert!(!is_non_stratum_input("\u{feff}{\"id\":1}"));
+        assert!(!is_non_stratum_input(""));
+    }
+
+    #[tokio::test]
+    async fn test_http_request_closed_quietly_and_bad_json_answered() {
+        async fn connect() -> (Arc<StratumContext>, TcpStream) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let (ctx, client) = tcp_connection(&listener).await;
+            let handler_map = Arc::new(crate::default_client::default_handlers());
+            tokio::spawn({
+                let ctx = Arc::clone(&ctx);
+                async move { StratumListener::spawn_client_listener(ctx, &handler_map, ":0", DEFAULT_MAX_LINE_BYTES).await }
+            });
+            (ctx, client)
+        }
+
+        // A port scanner's HTTP request: connection closed, nothing written back
+        let (ctx, mut client) = connect().await;
+        client.write_all(b"GET / HTTP/1.1\r\nHost: bridge\r\n\r\n").await.unwrap();
+        let mut buf = [0u8; 64];
+        let read = tokio::time::timeout(std::time::Duration::from_secs(5), client.read(&mut buf)).await.unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)), "expected a silent close, got {:?}", read);
+        assert!(!ctx.connected());
+
+        // Malformed JSON from a real miner: parse error reply, connection stays open
+        let (ctx, client) = connect().await;
+        let (read_half, mut write_half) = client.into_split();
+        write_half.write_all(b"{\"id\":7,\"method\":\"mining.subscribe\",\"params\":[}\n").await.unwrap();
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["error"][0], -32700);
+        assert!(ctx.connected());
+
+        // Only the first message is screened: a stray non-JSON line later in the session is a parse error, not a close
+        write_half.write_all(b"hello\n").await.unwrap();
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["error"][0], -32700);
+        assert!(ctx.connected());
+        ctx.disconnect();
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_pings_quiet_miner_then_drops_it() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (ctx, client) = tcp_connection(&listener).await;
+        let handler_map = Arc::new(HashMap::new());
+        tokio::spawn({
+            let ctx = Arc::clone(&ctx);
+            async move { StratumListener::spawn_client_listener(ctx, &handler_map, ":0", DEFAULT_MAX_LINE_BYTES).await }
+        });
+        let keepalive = KeepaliveConfig { interval: Duration::from_millis(200), timeout: Duration::from_millis(400) };
+        tokio::spawn(StratumListener::run_keepalive(Arc::clone(&ctx), keepalive, ":0".to_string()));
+
+        let (read_half, mut write_half) = client.into_split();
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+        async fn next_line(lines: &mut tokio::io::Lines<tokio::io::BufReader<tokio::net::tcp::OwnedReadHalf>>) -> Option<String> {
+            tokio::time::timeout(Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap()
+        }
+
+        // Silent miner gets pinged; its answer is activity, not a parse error
+        let ping: serde_json::Value = serde_json::from_str(&next_line(&mut lines).await.unwrap()).unwrap();
+        assert_eq!(ping["method"], "mining.ping");
+        write_half.write_all(b"{\"id\":\"ping\",\"result\":\"pong\",\"error\":null}\n").await.unwrap();
+        let ping: serde_json::Value = serde_json::from_str(&next_line(&mut lines).await.unwrap()).unwrap();
+        assert_eq!(ping["method"], "mining.ping");
+        assert!(ctx.connected());
+
+        // No answer this time: dropped after interval + timeout
+        assert_eq!(next_line(&mut lines).await, None);
+        assert!(!ctx.connected());
+    }
+
+    #[tokio::test]
+    async fn test_authorize_timeout_drops_only_unauthorized() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut contexts = Vec::new();
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            let (ctx, client) = tcp_connection(&listener).await;
+            contexts.push(ctx);
+            clients.push(client);
+        }
+        *contexts[1].wallet_addr.lock() = "kaspa:qz".to_string();
+        for ctx in &contexts {
+            tokio::spawn(StratumListener::enforce_authorize_timeout(Arc::clone(ctx), Duration::from_millis(100), ":0".to_string()));
+        }
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!contexts[0].connected(), "never authorized");
+        assert!(contexts[1].connected());
+        contexts[1].disconnect();
+    }
+
+    #[tokio::test]
+    async fn test_refused_connections_past_the_cap_are_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let pending_rejects = Arc::new(tokio::sync::Semaphore::new(1));
+        let mut answered = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(StratumListener::spawn_reject(&pending_rejects, stream, "Pool is full, connection limit reached"));
+        let mut closed = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(!StratumListener::spawn_reject(&pending_rejects, stream, "Pool is full, connection limit reached"));
+
+        // Past the cap: closed without waiting for a request
+        let mut buf = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(1), closed.read_to_end(&mut buf)).await;
+        assert_eq!(read.unwrap().unwrap(), 0);
+
+        // Within it: the request is answered with the reason, and the permit comes back
+        answered.write_all(b"{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[]}\n").await.unwrap();
+        let mut reply = String::new();
+        tokio::io::BufReader::new(&mut answered).read_line(&mut reply).await.unwrap();
+        assert!(reply.contains("connection limit reached"), "{}", reply);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pending_rejects.available_permits(), 1);
+    }
+}
@@ -1,31 +1,59 @@
 use crate::{
+    block_submit_queue::BlockSubmitQueue,
     client_handler::ClientHandler,
     default_client::*,
+    hasher::DifficultyFormat,
     jsonrpc_event::JsonRpcEvent,
     kaspaapi::KaspaApi,
-    share_handler::{KaspaApiTrait, ShareHandler},
+    mining_state::GetMiningState,
+    share_handler::{clamped_min_share_diff, KaspaApiTrait, ShareHandler, SuggestDifficultyPolicy, VarDiffFreezePolicy, VarDiffMode},
     stratum_context::StratumContext,
     stratum_listener::{StratumListener, StratumListenerConfig},
 };
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::warn;
 
+/// How often the difficulty distribution metric is re-sampled from the connected miners
+const DIFFICULTY_SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
 pub struct BridgeConfig {
     pub instance_id: String, // Instance identifier for logging (e.g., "Instance 1", "Instance 2")
     pub stratum_port: String,
     pub kaspad_address: String,
     pub prom_port: String,
     pub print_stats: bool,
+    pub stats_interval: Duration,
+    pub show_hashrate_estimate: bool, // List the hashrate each worker's difficulty implies in the stats block
     pub log_to_file: bool,
     pub health_check_port: String,
     pub block_wait_time: Duration,
     pub min_share_diff: u32,
+    pub start_diff: u32, // 0 = start at min_share_diff
     pub var_diff: bool,
     pub shares_per_min: u32,
     pub var_diff_stats: bool,
     pub extranonce_size: u8,
     pub pow2_clamp: bool,
+    pub log_notify_submit_rtt: bool,
+    pub account_shares_while_paused: bool,
+    pub difficulty_format: DifficultyFormat,
+    pub max_connections: Arc<AtomicUsize>, // 0 = unlimited, shared so SIGHUP reload can change it
+    pub label_metrics_by_port: bool,
+    pub listener_label: Option<String>, // Tags this listener's connections in logs and the "port" metric label
+    pub extranonce_reuse_delay: Duration,
+    pub honor_suggest_difficulty: bool,
+    pub var_diff_min: u32, // 0 = no lower bound for suggested difficulties
+    pub var_diff_max: u32, // 0 = no upper bound for suggested difficulties
+    pub max_line_bytes: usize,
+    pub keepalive: Option<crate::stratum_listener::KeepaliveConfig>, // None = no application-level heartbeat
+    pub authorize_timeout: Option<Duration>,                         // None = unauthorized connections are kept
+    pub socket_tuning: crate::stratum_listener::SocketTuning,
+    pub var_diff_mode: VarDiffMode,
+    pub var_diff_freeze: VarDiffFreezePolicy,
+    pub submit_queue: Option<Arc<BlockSubmitQueue>>, // None = blocks are submitted straight from the share handler
+    pub authorize: AuthorizeConfig,
 }
 
 /// Start block template listener with concrete KaspaApi
@@ -59,32 +87,48 @@ pub async fn listen_and_serve<T: KaspaApiTrait + Send + Sync + 'static>(
     concrete_kaspa_api: Option<Arc<KaspaApi>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Calculate min diff with pow2 clamp if needed
-    let mut min_diff = config.min_share_diff as f64;
-    if config.pow2_clamp && min_diff > 0.0 {
-        min_diff = 2_f64.powi((min_diff.log2().floor()) as i32);
-    }
-    if min_diff == 0.0 {
-        min_diff = 4.0;
-    }
+    let min_diff = clamped_min_share_diff(config.min_share_diff, config.pow2_clamp);
 
-    // Extranonce size is now auto-detected per client based on miner type
-    // We still need to pass a value to ClientHandler::new() for backward compatibility,
-    // but it will be ignored as extranonce is assigned per-client in handle_subscribe
-    // Default to 2 (for IceRiver/BzMiner/Goldshell) as that's the most common case
-    let extranonce_size = if config.extranonce_size > 0 {
-        config.extranonce_size.min(3) as i8
-    } else {
-        2 // Default to 2, will be auto-detected per client anyway
+    // Extranonce size is picked per client in handle_subscribe; extranonce_size is what miners the
+    // detection has nothing better for get. Default to 2 (for IceRiver/BzMiner/Goldshell) as that's
+    // the most common case
+    let extranonce_size = if config.extranonce_size > 0 { config.extranonce_size.min(3) as i8 } else { 2 };
+
+    // Starting difficulties (configured start_diff and miner hints) share the same bounds
+    let suggest_policy = SuggestDifficultyPolicy {
+        var_diff_min: config.var_diff_min as f64,
+        var_diff_max: config.var_diff_max as f64,
+        pow2_clamp: config.pow2_clamp,
     };
+    let start_diff = suggest_policy.start_diff(config.start_diff as f64, min_diff);
 
     // Create share handler with instance identifier
     let instance_id = config.instance_id.clone();
-    let share_handler = Arc::new(ShareHandler::new(instance_id.clone()));
+    let shares_per_min = if config.shares_per_min > 0 { config.shares_per_min } else { 20 };
+    let share_handler = Arc::new(ShareHandler::new(
+        instance_id.clone(),
+        config.account_shares_while_paused,
+        config.var_diff_mode,
+        shares_per_min,
+        config.submit_queue.clone(),
+    ));
 
     // Create client handler
-    // Note: extranonce_size parameter is now only used for backward compatibility
-    // Actual extranonce assignment happens per-client in handle_subscribe based on detected miner type
-    let client_handler = Arc::new(ClientHandler::new(Arc::clone(&share_handler), min_diff, extranonce_size, instance_id.clone()));
+    let client_handler = Arc::new(ClientHandler::new(
+        Arc::clone(&share_handler),
+        start_diff,
+        extranonce_size,
+        instance_id.clone(),
+        config.log_notify_submit_rtt,
+        config.difficulty_format,
+        config.extranonce_reuse_delay,
+        suggest_policy,
+        min_diff,
+        config.authorize.clone(),
+    ));
+
+    // Make this instance's miners reachable from the admin socket
+    crate::admin_socket::register_admin_client_handler(Arc::clone(&client_handler));
 
     // Setup default handlers
     let mut handlers = default_handlers();
@@ -128,22 +172,46 @@ pub async fn listen_and_serve<T: KaspaApiTrait + Send + Sync + 'static>(
     // Override submit handler
     let submit_handler = {
         let share_handler = Arc::clone(&share_handler);
+        let client_handler = Arc::clone(&client_handler);
         let kaspa_api = Arc::clone(&kaspa_api);
         Arc::new(move |ctx: Arc<StratumContext>, event: JsonRpcEvent| {
             let share_handler = Arc::clone(&share_handler);
+            let client_handler = Arc::clone(&client_handler);
             let kaspa_api = Arc::clone(&kaspa_api);
             let ctx_clone = Arc::clone(&ctx);
             Box::pin(async move {
-                share_handler
-                    .handle_submit(ctx_clone, event, kaspa_api)
+                let result = share_handler
+                    .handle_submit(Arc::clone(&ctx_clone), event, Arc::clone(&kaspa_api))
                     .await
-                    .map_err(|e| Box::new(std::io::Error::other(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)
+                    .map_err(|e| Box::new(std::io::Error::other(e.to_string())) as Box<dyn std::error::Error + Send + Sync>);
+                // A submit for a job the miner was never sent gets the current job once
+                if GetMiningState(&ctx_clone).take_job_resend() {
+                    client_handler.send_immediate_job_to_client(ctx_clone, kaspa_api).await;
+                }
+                result
             })
                 as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send>>
         }) as crate::stratum_listener::EventHandler
     };
     handlers.insert("mining.submit".to_string(), submit_handler);
 
+    // Let miners hint their starting difficulty
+    let suggest_handler = {
+        let share_handler = Arc::clone(&share_handler);
+        Arc::new(move |ctx: Arc<StratumContext>, event: JsonRpcEvent| {
+            let share_handler = Arc::clone(&share_handler);
+            Box::pin(async move {
+                crate::default_client::handle_suggest_difficulty(ctx, event, share_handler, suggest_policy)
+                    .await
+                    .map_err(|e| Box::new(std::io::Error::other(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)
+            })
+                as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send>>
+        }) as crate::stratum_listener::EventHandler
+    };
+    if config.honor_suggest_difficulty {
+        handlers.insert("mining.suggest_difficulty".to_string(), suggest_handler);
+    }
+
     // Setup listener config
     // Each client will get its own MiningState (created in stratum_listener)
     // Each client gets its own isolated state
@@ -162,23 +230,62 @@ pub async fn listen_and_serve<T: KaspaApiTrait + Send + Sync + 'static>(
                 client_handler.on_disconnect(&ctx);
             }
         }),
+        max_connections: Arc::clone(&config.max_connections),
+        label_metrics_by_port: config.label_metrics_by_port,
+        label: config.listener_label.clone(),
+        max_line_bytes: config.max_line_bytes,
+        keepalive: config.keepalive,
+        authorize_timeout: config.authorize_timeout,
+        socket_tuning: config.socket_tuning,
     };
 
-    // Start vardiff thread if enabled
-    if config.var_diff {
-        let shares_per_min = if config.shares_per_min > 0 { config.shares_per_min } else { 20 };
-        share_handler.start_vardiff_thread(shares_per_min, config.var_diff_stats, config.pow2_clamp);
+    // Start vardiff thread if enabled, here or for workers a worker_overrides entry turns it on for
+    if config.var_diff || config.authorize.overrides_enable_var_diff() {
+        share_handler.start_vardiff_thread(
+            shares_per_min,
+            config.var_diff_stats,
+            config.pow2_clamp,
+            config.var_diff_freeze,
+            config.var_diff,
+            suggest_policy,
+        );
     }
 
-    // Start stats printing thread if enabled
-    if config.print_stats {
-        let shares_per_min = if config.shares_per_min > 0 { config.shares_per_min } else { 20 };
-        share_handler.start_print_stats_thread(shares_per_min);
+    // Register for the stats report (stats_port) and start the printing thread if enabled
+    {
+        let connections = {
+            let client_handler = Arc::clone(&client_handler);
+            Arc::new(move || client_handler.client_count())
+        };
+        share_handler.start_print_stats_thread(
+            shares_per_min,
+            config.stats_interval,
+            config.var_diff_stats,
+            config.show_hashrate_estimate,
+            connections,
+            config.print_stats,
+        );
     }
 
     // Start stats pruning thread
     share_handler.start_prune_stats_thread();
 
+    // Sample assigned difficulties and extranonce use on a timer rather than per share; stopped with the listener
+    let sampler = {
+        let lowest = if config.var_diff_min > 0 { min_diff.min(config.var_diff_min as f64) } else { min_diff };
+        crate::prom::init_difficulty_distribution(crate::prom::difficulty_buckets(lowest, config.var_diff_max as f64));
+        let client_handler = Arc::clone(&client_handler);
+        let port = config.stratum_port.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DIFFICULTY_SAMPLE_INTERVAL);
+            loop {
+                interval.tick().await;
+                crate::prom::record_difficulty_distribution(&port, &client_handler.current_difficulties());
+                crate::prom::record_extranonce_utilization(&port, client_handler.extranonce_utilization());
+            }
+        })
+    };
+
     // Start block template listener with notifications + ticker fallback
     // This provides immediate notifications when new blocks are available, with polling as fallback
 
@@ -226,6 +333,13 @@ pub async fn listen_and_serve<T: KaspaApiTrait + Send + Sync + 'static>(
 
     // Start listener
     let listener = StratumListener::new(listener_config);
-    tracing::info!("{} Starting stratum listener on {}", instance_id, config.stratum_port);
-    listener.listen().await
+    match &config.listener_label {
+        Some(label) => tracing::info!("{} Starting stratum listener on {} ({})", instance_id, config.stratum_port, label),
+        None => tracing::info!("{} Starting stratum listener on {}", instance_id, config.stratum_port),
+    }
+    tracing::info!("{} Sending difficulty to miners as {}", instance_id, config.difficulty_format);
+    tracing::info!("{} New connections start at difficulty {} (min_share_diff {})", instance_id, start_diff, min_diff);
+    let result = listener.listen().await;
+    sampler.abort();
+    result
 }
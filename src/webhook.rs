@@ -0,0 +1,197 @@
+use crate::{prom::record_webhook_failure, stratum_context::StratumContext};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+/// Events waiting for delivery; beyond this new events are dropped, never queued
+const WEBHOOK_QUEUE_LEN: usize = 1024;
+
+/// Delivery attempts per event before it is dropped
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Pause between attempts, doubled after each failure
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Limit on connecting, sending and reading the status line of one attempt
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+static WEBHOOK_QUEUE: OnceLock<mpsc::Sender<String>> = OnceLock::new();
+
+/// Where lifecycle events are POSTed. Only plain http:// is supported
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookUrl {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl std::str::FromStr for WebhookUrl {
+    type Err = String;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let rest = url
+            .trim()
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("webhook_url '{}' must start with http:// (https is not supported)", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        // A colon inside [brackets] belongs to an IPv6 address, not a port
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+                (host, port.parse::<u16>().map_err(|_| format!("webhook_url '{}' has an invalid port", url))?)
+            }
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("webhook_url '{}' has no host", url));
+        }
+        Ok(WebhookUrl { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+impl std::fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// Start the delivery task; events emitted before this (or without a webhook) are ignored
+pub fn start_webhook(url: WebhookUrl) {
+    let (tx, mut rx) = mpsc::channel::<String>(WEBHOOK_QUEUE_LEN);
+    if WEBHOOK_QUEUE.set(tx).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        while let Some(body) = rx.recv().await {
+            let mut delay = WEBHOOK_RETRY_DELAY;
+            for attempt in 1..=WEBHOOK_ATTEMPTS {
+                match tokio::time::timeout(WEBHOOK_TIMEOUT, post_json(&url, &body)).await {
+                    Ok(Ok(())) => break,
+                    Ok(Err(e)) => tracing::debug!("webhook delivery to {} failed (attempt {}): {}", url, attempt, e),
+                    Err(_) => tracing::debug!("webhook delivery to {} timed out (attempt {})", url, attempt),
+                }
+                if attempt == WEBHOOK_ATTEMPTS {
+                    tracing::warn!("dropping webhook event after {} failed attempts to {}", WEBHOOK_ATTEMPTS, url);
+                    record_webhook_failure("delivery");
+                } else {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    });
+}
+
+/// Queue a lifecycle event for a connection. Never waits: with the queue full the event is dropped
+pub fn emit_lifecycle_event(event: &str, ctx: &StratumContext, fields: Value) {
+    let Some(queue) = WEBHOOK_QUEUE.get() else {
+        return;
+    };
+    let body = lifecycle_payload(
+        event,
+        &ctx.worker_name.lock(),
+        &ctx.wallet_addr.lock(),
+        &ctx.remote_endpoint(),
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+        fields,
+    );
+    if queue.try_send(body.to_string()).is_err() {
+        record_webhook_failure("queue_full");
+    }
+}
+
+/// JSON body of a lifecycle event; event-specific `fields` are merged in at the top level
+pub fn lifecycle_payload(event: &str, worker: &str, wallet: &str, address: &str, timestamp_ms: u64, fields: Value) -> Value {
+    let mut payload = json!({
+        "event": event,
+        "worker": worker,
+        "wallet": wallet,
+        "address": address,
+        "timestamp": timestamp_ms,
+    });
+    if let (Some(payload), Value::Object(fields)) = (payload.as_object_mut(), fields) {
+        payload.extend(fields);
+    }
+    payload
+}
+
+/// POST `body` and succeed on any 2xx status
+async fn post_json(url: &WebhookUrl, body: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut stream = TcpStream::connect((url.host.trim_start_matches('[').trim_end_matches(']'), url.port)).await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        url.host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut buffer = [0; 64];
+    let n = stream.read(&mut buffer).await?;
+    let status_line = String::from_utf8_lossy(&buffer[..n]);
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        Some(code) => Err(format!("endpoint answered {}", code).into()),
+        None => Err("endpoint closed without a response".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_webhook_url() {
+        let url: WebhookUrl = "http://hooks.local:8080/kaspa/events".parse().unwrap();
+        assert_eq!(url, WebhookUrl { host: "hooks.local".into(), port: 8080, path: "/kaspa/events".into() });
+
+        let url: WebhookUrl = "http://10.0.0.5".parse().unwrap();
+        assert_eq!((url.port, url.path.as_str()), (80, "/"));
+
+        assert!("https://hooks.local/x".parse::<WebhookUrl>().is_err());
+        assert!("http://hooks.local:notaport/".parse::<WebhookUrl>().is_err());
+
+        let url: WebhookUrl = "http://[::1]:9000/".parse().unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("[::1]", 9000));
+    }
+
+    #[test]
+    fn test_lifecycle_payload_merges_event_fields() {
+        let payload = lifecycle_payload("block_found", "rig01", "kaspa:qz", "10.0.0.9:4411", 1_700_000_000_000, json!({"hash": "ab"}));
+        assert_eq!(payload["event"], "block_found");
+        assert_eq!(payload["worker"], "rig01");
+        assert_eq!(payload["address"], "10.0.0.9:4411");
+        assert_eq!(payload["timestamp"], 1_700_000_000_000u64);
+        assert_eq!(payload["hash"], "ab");
+    }
+
+    #[tokio::test]
+    async fn test_post_json_delivers_body() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: WebhookUrl = format!("http://{}/events", listener.local_addr().unwrap()).parse().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut chunk = [0; 1024];
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut chunk).await.unwrap();
+                assert!(n > 0, "client closed before sending the body");
+                request.extend_from_slice(&chunk[..n]);
+            }
+            stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        post_json(&url, r#"{"event":"connect"}"#).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /events HTTP/1.1\r\n"));
+        assert!(request.ends_with(r#"{"event":"connect"}"#));
+    }
+}